@@ -0,0 +1,182 @@
+//! `#[derive(FromResolvedConfig)]` for `precedence_config` (enable its `derive` feature).
+//!
+//! Every named field is read from the resolved params under the field's name:
+//!
+//! ```ignore
+//! #[derive(FromResolvedConfig)]
+//! struct PricingParams {
+//!     discount_pct: Decimal,                 // required
+//!     #[param(rename = "max_orders_day")]
+//!     max_orders: i64,
+//!     notes: Option<String>,                 // absent or null -> None
+//!     #[param(default)]
+//!     active: bool,                          // absent or null -> Default::default()
+//!     #[param(default = 30)]
+//!     ttl_minutes: i64,                      // absent or null -> 30
+//!     #[param(default = Some(5))]
+//!     retries: Option<i64>,                  // the default is an Option too
+//! }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, LitStr, Type};
+
+#[proc_macro_derive(FromResolvedConfig, attributes(param))]
+pub fn derive_from_resolved_config(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+enum FieldDefault {
+    /// required field
+    None,
+    Trait,
+    Expr(Expr),
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(input, "FromResolvedConfig can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(input, "FromResolvedConfig needs a struct with named fields"));
+    };
+
+    let mut inits = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let mut key = ident.to_string();
+        let mut default = FieldDefault::None;
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("param")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    key = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    default = if meta.input.peek(syn::Token![=]) {
+                        FieldDefault::Expr(meta.value()?.parse()?)
+                    } else {
+                        FieldDefault::Trait
+                    };
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename = \"...\"` or `default`"))
+                }
+            })?;
+        }
+
+        let value = match (option_inner(&field.ty), default) {
+            (Some(_), FieldDefault::None | FieldDefault::Trait) => quote! { params.get_opt(#key)? },
+            (Some(_), FieldDefault::Expr(expr)) => quote! { params.get_opt(#key)?.or_else(|| #expr) },
+            (_, FieldDefault::None) => quote! { params.get(#key)? },
+            (_, FieldDefault::Trait) => quote! { params.get_opt(#key)?.unwrap_or_default() },
+            (_, FieldDefault::Expr(expr)) => quote! { params.get_opt(#key)?.unwrap_or_else(|| #expr) },
+        };
+        inits.push(quote! { #ident: #value });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let from_resolved = quote!(::precedence_config::config_params::FromResolvedConfig);
+    Ok(quote! {
+        impl #impl_generics #from_resolved for #name #ty_generics #where_clause {
+            fn from_resolved_params(
+                params: &::precedence_config::config_params::ResolvedParams,
+            ) -> ::precedence_config::config_error::Result<Self> {
+                Ok(Self { #(#inits),* })
+            }
+        }
+    })
+}
+
+/// `T` for an `Option<T>` field type.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    /// The expansion with whitespace removed, for matching generated code.
+    fn expanded(input: DeriveInput) -> String {
+        expand(&input).unwrap().to_string().split_whitespace().collect()
+    }
+
+    #[test]
+    fn fields_read_their_params_by_kind() {
+        let code = expanded(parse_quote! {
+            struct PricingParams {
+                discount_pct: f64,
+                #[param(rename = "max_orders_day")]
+                max_orders: i64,
+                notes: Option<String>,
+                #[param(default)]
+                active: bool,
+                #[param(default = 30)]
+                ttl_minutes: i64,
+                #[param(default = Some(5))]
+                retries: Option<i64>,
+            }
+        });
+        for init in [
+            r#"discount_pct:params.get("discount_pct")?"#,
+            r#"max_orders:params.get("max_orders_day")?"#,
+            r#"notes:params.get_opt("notes")?"#,
+            r#"active:params.get_opt("active")?.unwrap_or_default()"#,
+            r#"ttl_minutes:params.get_opt("ttl_minutes")?.unwrap_or_else(||30)"#,
+            r#"retries:params.get_opt("retries")?.or_else(||Some(5))"#,
+        ] {
+            assert!(code.contains(init), "{init} not in {code}");
+        }
+        assert!(code.contains("::precedence_config::config_params::FromResolvedConfigforPricingParams"), "{code}");
+    }
+
+    #[test]
+    fn generics_carry_over() {
+        let code = expanded(parse_quote! {
+            struct Wrapped<T: Clone> where T: Default { value: T }
+        });
+        assert!(code.contains("impl<T:Clone>"), "{code}");
+        assert!(code.contains("forWrapped<T>whereT:Default"), "{code}");
+    }
+
+    #[test]
+    fn only_structs_with_named_fields_derive() {
+        let errors = [
+            expand(&parse_quote! { enum Mode { On, Off } }),
+            expand(&parse_quote! { struct Pair(i64, i64); }),
+            expand(&parse_quote! { struct Bad { #[param(rename = 1)] value: i64 } }),
+            expand(&parse_quote! { struct Bad { #[param(flatten)] value: i64 } }),
+        ];
+        let messages: Vec<String> = errors.into_iter().map(|result| result.unwrap_err().to_string()).collect();
+        assert_eq!(messages[0], "FromResolvedConfig can only be derived for structs");
+        assert_eq!(messages[1], "FromResolvedConfig needs a struct with named fields");
+        assert_eq!(messages[3], "expected `rename = \"...\"` or `default`");
+    }
+
+    #[test]
+    fn option_inner_sees_through_paths() {
+        let ty: Type = parse_quote!(std::option::Option<u8>);
+        assert!(option_inner(&ty).is_some());
+        assert!(option_inner(&parse_quote!(Vec<u8>)).is_none());
+        assert!(option_inner(&parse_quote!(&str)).is_none());
+    }
+}
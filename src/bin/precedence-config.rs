@@ -0,0 +1,545 @@
+//! Command-line front-end: validate, convert, diff and resolve config files.
+//!
+//! Every command that needs to map attribute names to ids takes `--attrs`, a
+//! JSON array of CONFIG_ATTR rows (`AttrMeta`). Results go to stdout as JSON;
+//! problems go to stderr and exit with status 1.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use precedence_config::config_attr::AttrRegistry;
+use precedence_config::config_deprecation::{rule_deprecations, DeprecationWarning};
+use precedence_config::config_diff::{diff_envelopes, diff_precedence, render_diff, ReportFormat};
+use precedence_config::config_envelope::{validate_envelope, validate_envelope_with_options, EnvelopeOptions};
+use precedence_config::config_error::{PrecedenceConfigError, Result};
+use precedence_config::config_findings::{envelope_findings, matrix_findings, Severity};
+use precedence_config::config_formats::{render_matrix, TableFormat};
+use precedence_config::config_json_schema::{envelope_json_schema, matrix_json_schema, params_json_schema};
+use precedence_config::config_lint::{lint_precedence, validate_row_conflicts_report};
+use precedence_config::config_migration::{migrate_envelope_with_options, MigrationOptions};
+use precedence_config::config_precidence_rules::{
+    matrix_json_to_tall, matrix_json_to_tall_report, renumber_ranks, tall_to_matrix_rows_ordered, validate_ranks,
+    verify_round_trip, ConfigPrecedenceRule, ConversionOptions, MatrixRow, RankScheme,
+};
+use precedence_config::config_resolver::{Context, Resolver};
+use precedence_config::config_simulation::simulate;
+use precedence_config::config_sql::{export_sql, export_values_sql, SqlDialect};
+use precedence_config::config_types::ConfigEnvelope;
+use precedence_config::config_value::{parse_json_value, AttrMeta, AttrRole};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "precedence-config", version, about = "Validate, convert, diff and resolve precedence configs")]
+struct Cli {
+    /// Accept the camelCase and alternate field names of older envelope exports
+    /// (`configVersion`, `versionName`, `matchAttrs`, ..).
+    #[arg(long, global = true)]
+    lenient: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check an envelope, or with --matrix a precedence matrix, and list every problem found.
+    Validate {
+        #[arg(long)]
+        attrs: PathBuf,
+        /// Treat FILE as a precedence matrix instead of an envelope.
+        #[arg(long)]
+        matrix: bool,
+        /// Rank layout the matrix must follow.
+        #[arg(long, value_enum, default_value_t = Scheme::Triangular)]
+        scheme: Scheme,
+        /// ConfigSchema JSON (`{ "required": [..], "optional": [..] }`) the envelope's params must follow.
+        #[arg(long)]
+        schema: Option<PathBuf>,
+        /// Print every error, warning and note as JSON findings; matrix columns missing from --attrs
+        /// are warnings here.
+        #[arg(long)]
+        findings: bool,
+        /// With --findings, the least severity that fails validation.
+        #[arg(long, value_enum, default_value_t = FailOn::Error, requires = "findings")]
+        fail_on: FailOn,
+        /// With --findings, comma-separated codes to leave out (`PC0251,PC0050`).
+        #[arg(long, value_delimiter = ',', requires = "findings")]
+        allow: Vec<String>,
+        file: PathBuf,
+    },
+    /// List duplicate, shadowed and misplaced catch-all ranks in a JSON precedence matrix.
+    Lint {
+        #[arg(long)]
+        attrs: PathBuf,
+        file: PathBuf,
+    },
+    /// Convert a JSON precedence matrix into tall rules.
+    MatrixToTall {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long, default_value_t = 1)]
+        config_version_id: i32,
+        /// Error on matrix columns missing from --attrs instead of skipping them.
+        #[arg(long)]
+        strict: bool,
+        /// Compact the ranks to 1..=N, keeping their order; changed ranks are listed on stderr.
+        #[arg(long)]
+        renumber: bool,
+        file: PathBuf,
+    },
+    /// List what a JSON precedence matrix loses converted to tall rules and back; fails if it loses anything.
+    RoundTrip {
+        #[arg(long)]
+        attrs: PathBuf,
+        file: PathBuf,
+    },
+    /// Convert tall rules into a JSON precedence matrix.
+    TallToMatrix {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long)]
+        strict: bool,
+        /// Comma-separated column order; unlisted attributes follow by name.
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+        file: PathBuf,
+    },
+    /// Print a JSON precedence matrix as an aligned table, ASCII or with --markdown Markdown.
+    Render {
+        #[arg(long)]
+        markdown: bool,
+        file: PathBuf,
+    },
+    /// Diff two envelopes, or with --precedence two tall precedence tables.
+    Diff {
+        #[arg(long)]
+        precedence: bool,
+        /// Print the envelope diff as a text or Markdown summary instead of JSON.
+        #[arg(long, value_enum, conflicts_with = "precedence")]
+        report: Option<Report>,
+        old: PathBuf,
+        new: PathBuf,
+    },
+    /// Resolve a context against an envelope and a precedence matrix.
+    Resolve {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long)]
+        matrix: PathBuf,
+        #[arg(long)]
+        envelope: PathBuf,
+        /// JSON object of match attribute name -> value.
+        #[arg(long)]
+        context: PathBuf,
+    },
+    /// Print SQL that loads a JSON precedence matrix, or with --envelope an envelope's values.
+    ExportSql {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long, value_enum, default_value_t = Dialect::Postgres)]
+        dialect: Dialect,
+        #[arg(long, default_value_t = 1)]
+        config_version_id: i32,
+        /// Treat FILE as an envelope instead of a precedence matrix.
+        #[arg(long)]
+        envelope: bool,
+        file: PathBuf,
+    },
+    /// Resolve a JSON array of sample contexts and report rows and ranks hit; fails if any sample matches no row.
+    Simulate {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long)]
+        matrix: PathBuf,
+        #[arg(long)]
+        envelope: PathBuf,
+        #[arg(long)]
+        samples: PathBuf,
+    },
+    /// Upgrade an envelope of an older layout to the current one.
+    Migrate { file: PathBuf },
+    /// Print the JSON Schema of envelopes, precedence matrices or params arrays for these attributes.
+    JsonSchema {
+        #[arg(long)]
+        attrs: PathBuf,
+        #[arg(long, value_enum, default_value_t = SchemaOf::Envelope)]
+        of: SchemaOf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Scheme {
+    Triangular,
+    PowerSet,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FailOn {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SchemaOf {
+    Envelope,
+    Matrix,
+    Params,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Report {
+    Text,
+    Markdown,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Dialect {
+    Postgres,
+    PostgresCopy,
+    SqlServer,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let options = MigrationOptions { lenient_field_names: cli.lenient };
+    match run(cli.command, &options) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error[{}]: {}", err.code(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command, envelopes: &MigrationOptions) -> Result<ExitCode> {
+    match command {
+        Command::Validate { attrs, matrix, scheme, schema, findings: true, fail_on, allow, file } => {
+            let registry = load_registry(&attrs)?;
+            let mut findings = if matrix {
+                let options = ConversionOptions { strict_unknown_attrs: false };
+                matrix_findings(&read(&file)?, &registry, &options, Some(&rank_scheme(scheme)))
+            } else {
+                let options = EnvelopeOptions { schema: schema.as_deref().map(read_json).transpose()? };
+                envelope_findings(&read_envelope(&file, envelopes)?, &registry, &options)
+            };
+            findings.suppress(&allow);
+            print_json(&findings)?;
+            let threshold = match fail_on {
+                FailOn::Info => Severity::Info,
+                FailOn::Warning => Severity::Warning,
+                FailOn::Error => Severity::Error,
+            };
+            Ok(if findings.fails(threshold) { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+        }
+        Command::Validate { attrs, matrix, scheme, schema, file, .. } => {
+            let registry = load_registry(&attrs)?;
+            let outcome = if matrix {
+                validate_matrix(&read(&file)?, &registry, scheme)
+            } else {
+                let envelope = read_envelope(&file, envelopes)?;
+                let options = EnvelopeOptions { schema: schema.as_deref().map(read_json).transpose()? };
+                match validate_envelope_with_options(&envelope, &registry, &options) {
+                    Ok(validated) => {
+                        print_warnings(&validated.warnings);
+                        validate_row_conflicts_report(&validated.values).map_err(|report| report.to_string())
+                    }
+                    Err(err) => Err(err.to_string()),
+                }
+            };
+            match outcome {
+                Ok(()) => {
+                    println!("{}: ok", file.display());
+                    Ok(ExitCode::SUCCESS)
+                }
+                Err(problems) => {
+                    eprintln!("{}: {}", file.display(), problems);
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+        Command::Lint { attrs, file } => {
+            let registry = load_registry(&attrs)?;
+            let tall = matrix_json_to_tall(&read(&file)?, 1, &registry)?;
+            let findings = lint_precedence(&tall);
+            print_json(&findings)?;
+            Ok(if findings.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+        }
+        Command::MatrixToTall { attrs, config_version_id, strict, renumber, file } => {
+            let registry = load_registry(&attrs)?;
+            let options = ConversionOptions { strict_unknown_attrs: strict };
+            match matrix_json_to_tall_report(&read(&file)?, config_version_id, &registry, &options) {
+                Ok(mut tall) => {
+                    if renumber {
+                        for (old, new) in renumber_ranks(&mut tall).into_iter().filter(|(old, new)| old != new) {
+                            eprintln!("rank {} -> {}", old, new);
+                        }
+                    }
+                    print_json(&tall)
+                }
+                Err(report) => {
+                    eprintln!("{}: {}", file.display(), report);
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+        Command::RoundTrip { attrs, file } => {
+            let registry = load_registry(&attrs)?;
+            let report = verify_round_trip(&read(&file)?, &registry)?;
+            print_json(&report.losses)?;
+            Ok(if report.is_lossless() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+        }
+        Command::TallToMatrix { attrs, strict, columns, file } => {
+            let registry = load_registry(&attrs)?;
+            let tall: Vec<ConfigPrecedenceRule> = read_json(&file)?;
+            let options = ConversionOptions { strict_unknown_attrs: strict };
+            let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+            print_json(&tall_to_matrix_rows_ordered(&tall, &registry, &options, &columns)?)
+        }
+        Command::Render { markdown, file } => {
+            let rows: Vec<MatrixRow> = read_json(&file)?;
+            let format = if markdown { TableFormat::Markdown } else { TableFormat::Ascii };
+            write!(std::io::stdout(), "{}", render_matrix(&rows, format)).map_err(PrecedenceConfigError::Io)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Diff { precedence, report, old, new } => {
+            if precedence {
+                let old: Vec<ConfigPrecedenceRule> = read_json(&old)?;
+                let new: Vec<ConfigPrecedenceRule> = read_json(&new)?;
+                print_json(&diff_precedence(&old, &new))
+            } else {
+                let old = read_envelope(&old, envelopes)?;
+                let new = read_envelope(&new, envelopes)?;
+                let diff = diff_envelopes(&old, &new);
+                match report {
+                    Some(report) => {
+                        let format = match report {
+                            Report::Text => ReportFormat::Text,
+                            Report::Markdown => ReportFormat::Markdown,
+                        };
+                        write!(std::io::stdout(), "{}", render_diff(&diff, format)).map_err(PrecedenceConfigError::Io)?;
+                        Ok(ExitCode::SUCCESS)
+                    }
+                    None => print_json(&diff),
+                }
+            }
+        }
+        Command::Resolve { attrs, matrix, envelope, context } => {
+            let registry = load_registry(&attrs)?;
+            let rules = matrix_json_to_tall(&read(&matrix)?, 1, &registry)?;
+            let validated = validate_envelope(&read_envelope(&envelope, envelopes)?, &registry)?;
+            let resolver = Resolver::new(&rules, &validated.values, &registry)?;
+
+            let context = parse_context(&registry, &read_json(&context)?)?;
+            match resolver.resolve(&context) {
+                Some(resolved) => print_json(&resolved),
+                None => {
+                    eprintln!("no row matched the context");
+                    Ok(ExitCode::FAILURE)
+                }
+            }
+        }
+        Command::ExportSql { attrs, dialect, config_version_id, envelope, file } => {
+            let registry = load_registry(&attrs)?;
+            let dialect = match dialect {
+                Dialect::Postgres => SqlDialect::Postgres,
+                Dialect::PostgresCopy => SqlDialect::PostgresCopy,
+                Dialect::SqlServer => SqlDialect::SqlServer,
+            };
+            let sql = if envelope {
+                let validated = validate_envelope(&read_envelope(&file, envelopes)?, &registry)?;
+                export_values_sql(config_version_id, &validated.values, dialect)?
+            } else {
+                export_sql(&matrix_json_to_tall(&read(&file)?, config_version_id, &registry)?, dialect)
+            };
+            write!(std::io::stdout(), "{}", sql).map_err(PrecedenceConfigError::Io)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Simulate { attrs, matrix, envelope, samples } => {
+            let registry = load_registry(&attrs)?;
+            let rules = matrix_json_to_tall(&read(&matrix)?, 1, &registry)?;
+            let validated = validate_envelope(&read_envelope(&envelope, envelopes)?, &registry)?;
+            let raw: Vec<serde_json::Map<String, serde_json::Value>> = read_json(&samples)?;
+            let samples = raw.iter().map(|raw| parse_context(&registry, raw)).collect::<Result<Vec<_>>>()?;
+
+            let report = simulate(&rules, &validated.values, &registry, &samples)?;
+            print_json(&report)?;
+            Ok(if report.unmatched.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+        }
+        Command::Migrate { file } => print_json(&read_envelope(&file, envelopes)?),
+        Command::JsonSchema { attrs, of } => {
+            let registry = load_registry(&attrs)?;
+            print_json(&match of {
+                SchemaOf::Envelope => envelope_json_schema(&registry)?,
+                SchemaOf::Matrix => matrix_json_schema(&registry),
+                SchemaOf::Params => params_json_schema(&registry)?,
+            })
+        }
+    }
+}
+
+fn validate_matrix(json: &str, registry: &AttrRegistry, scheme: Scheme) -> std::result::Result<(), String> {
+    let options = ConversionOptions { strict_unknown_attrs: true };
+    let tall = matrix_json_to_tall_report(json, 1, registry, &options).map_err(|report| report.to_string())?;
+    print_warnings(&rule_deprecations(&tall, registry));
+    let attr_count = registry.with_role(AttrRole::Match).count();
+    validate_ranks(&tall, attr_count, &rank_scheme(scheme)).map_err(|e| e.to_string())
+}
+
+fn rank_scheme(scheme: Scheme) -> RankScheme {
+    match scheme {
+        Scheme::Triangular => RankScheme::Triangular,
+        Scheme::PowerSet => RankScheme::PowerSet,
+    }
+}
+
+fn print_warnings(warnings: &[DeprecationWarning]) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+fn parse_context(registry: &AttrRegistry, raw: &serde_json::Map<String, serde_json::Value>) -> Result<Context> {
+    let mut context = Context::new();
+    for (key, value) in raw {
+        let meta = match_attr(registry, key)?;
+        context.insert(meta.attr_name.to_string(), parse_json_value(meta, value)?);
+    }
+    Ok(context)
+}
+
+fn match_attr<'a>(registry: &'a AttrRegistry, key: &str) -> Result<&'a AttrMeta> {
+    let meta = registry
+        .get(key)
+        .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: key.to_string() })?;
+    if meta.role != AttrRole::Match {
+        return Err(PrecedenceConfigError::WrongRole {
+            key: key.to_string(),
+            expected: AttrRole::Match,
+            found: meta.role,
+        });
+    }
+    Ok(meta)
+}
+
+fn load_registry(path: &Path) -> Result<AttrRegistry> {
+    let metas: Vec<AttrMeta> = read_json(path)?;
+    AttrRegistry::from_metas(metas)
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| PrecedenceConfigError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    serde_json::from_str(&read(path)?).map_err(|e| PrecedenceConfigError::InvalidFormat {
+        format: "JSON",
+        reason: format!("{}: {}", path.display(), e),
+    })
+}
+
+/// An envelope of any layout, upgraded to the current one.
+fn read_envelope(path: &Path, options: &MigrationOptions) -> Result<ConfigEnvelope> {
+    migrate_envelope_with_options(read_json(path)?, options)
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> Result<ExitCode> {
+    let json = serde_json::to_string_pretty(value).map_err(PrecedenceConfigError::InvalidJson)?;
+    writeln!(std::io::stdout(), "{}", json).map_err(PrecedenceConfigError::Io)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use serde_json::json;
+
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.register("limit", AttrRole::Param, "int").unwrap();
+        registry
+    }
+
+    /// Writes `files` (name, contents) to a fresh directory named after `test`.
+    fn fixture(test: &str, files: &[(&str, String)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("precedence-config-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn resolve_files(test: &str, context: serde_json::Value) -> PathBuf {
+        let registry = registry();
+        let metas: Vec<&AttrMeta> = registry.iter().collect();
+        let envelope = json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [{ "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 5 }] }],
+        });
+        fixture(
+            test,
+            &[
+                ("attrs.json", serde_json::to_string(&metas).unwrap()),
+                ("matrix.json", r#"[{ "rank": 1, "region": 1 }]"#.to_string()),
+                ("envelope.json", envelope.to_string()),
+                ("context.json", context.to_string()),
+            ],
+        )
+    }
+
+    fn resolve(dir: &Path) -> Command {
+        let mut args = vec!["precedence-config".to_string(), "resolve".to_string()];
+        for name in ["attrs", "matrix", "envelope", "context"] {
+            args.extend([format!("--{name}"), dir.join(format!("{name}.json")).display().to_string()]);
+        }
+        Cli::try_parse_from(args).unwrap().command
+    }
+
+    #[test]
+    fn the_command_line_definition_is_consistent() {
+        Cli::command().debug_assert();
+        assert!(Cli::try_parse_from(["precedence-config", "resolve", "--attrs", "a.json"]).is_err());
+        let cli = Cli::try_parse_from(["precedence-config", "diff", "--precedence", "old.json", "new.json"]).unwrap();
+        assert!(matches!(cli.command, Command::Diff { precedence: true, .. }));
+        let cli = Cli::try_parse_from(["precedence-config", "--lenient", "migrate", "old.json"]).unwrap();
+        assert!(cli.lenient && matches!(cli.command, Command::Migrate { .. }));
+    }
+
+    // a match prints to stdout directly, past the test harness's capture, so only misses are run here
+    #[test]
+    fn resolve_fails_without_a_matching_row() {
+        let options = MigrationOptions::default();
+        let dir = resolve_files("resolve-miss", json!({ "region": "us" }));
+        assert_eq!(run(resolve(&dir), &options).unwrap(), ExitCode::FAILURE);
+        let dir = resolve_files("resolve-param", json!({ "limit": 5 }));
+        assert!(matches!(run(resolve(&dir), &options), Err(PrecedenceConfigError::WrongRole { .. })));
+        let dir = resolve_files("resolve-unknown", json!({ "planet": "mars" }));
+        assert!(matches!(run(resolve(&dir), &options), Err(PrecedenceConfigError::UnknownAttrKey { .. })));
+    }
+
+    #[test]
+    fn contexts_are_typed_match_attributes() {
+        let registry = registry();
+        let raw = json!({ "region": "eu" });
+        let context = parse_context(&registry, raw.as_object().unwrap()).unwrap();
+        assert_eq!(context.len(), 1);
+        let raw = json!({ "planet": "mars" });
+        assert!(matches!(
+            parse_context(&registry, raw.as_object().unwrap()),
+            Err(PrecedenceConfigError::UnknownAttrKey { .. })
+        ));
+    }
+
+    #[test]
+    fn matrices_are_checked_against_the_scheme() {
+        let registry = registry();
+        assert!(validate_matrix(r#"[{ "rank": 1, "region": 1 }]"#, &registry, Scheme::Triangular).is_ok());
+        let err = validate_matrix(r#"[{ "rank": 2, "region": 1 }]"#, &registry, Scheme::Triangular).unwrap_err();
+        assert!(err.contains("rank"), "{err}");
+        let err = validate_matrix(r#"[{ "rank": 1, "planet": 1 }]"#, &registry, Scheme::PowerSet).unwrap_err();
+        assert!(err.contains("planet"), "{err}");
+    }
+}
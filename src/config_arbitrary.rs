@@ -0,0 +1,365 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_envelope::{config_values_to_envelope, envelope_to_config_values};
+use crate::config_precidence_rules::{
+    verify_round_trip, ConfigPrecedenceRule, MatchType, MatrixRow,
+};
+use crate::config_types::{ConfigEnvelope, ConfigMeta, ConfigRow, MatchPart, Param, ParamType, ENVELOPE_SCHEMA_VERSION};
+use crate::config_value::{AttrMeta, AttrRole, ConfigValue, TypedValue};
+use arbitrary::{Arbitrary, Unstructured};
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+use std::collections::{BTreeSet, HashMap};
+
+/// Column names of generated [`MatrixRow`]s, in name order.
+pub const MATRIX_COLUMNS: [&str; 6] = ["attr_1", "attr_2", "attr_3", "attr_4", "attr_5", "attr_6"];
+
+impl<'a> Arbitrary<'a> for MatchType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(if u.arbitrary()? { MatchType::Exact } else { MatchType::Ignore })
+    }
+}
+
+/// Small ids, so generated rules collide on rank and attribute.
+impl<'a> Arbitrary<'a> for ConfigPrecedenceRule {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ConfigPrecedenceRule {
+            config_version_id: u.int_in_range(1..=4)?,
+            rank: u.int_in_range(1..=16)?,
+            attr_id: u.int_in_range(1..=MATRIX_COLUMNS.len() as i32)?,
+            match_type: u.arbitrary()?,
+        })
+    }
+}
+
+/// A rank from 1 to 16 over a subset of [`MATRIX_COLUMNS`], in column order.
+impl<'a> Arbitrary<'a> for MatrixRow {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut attrs = Vec::new();
+        for name in MATRIX_COLUMNS {
+            if u.arbitrary()? {
+                attrs.push((name.into(), u.int_in_range(0..=1)?));
+            }
+        }
+        Ok(MatrixRow { rank: u.int_in_range(1..=16)?, attrs })
+    }
+}
+
+/// A plain value: a scalar of any type, a list of scalars of one type, or
+/// `null`. Match-only values (ranges, alternatives, patterns, regexes) are not
+/// generated; decimals are finite and strings never the `"ALL"` wildcard.
+impl<'a> Arbitrary<'a> for TypedValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let kinds = scalar_kinds();
+        match u.int_in_range(0..=kinds.len() + 1)? {
+            i if i < kinds.len() => scalar(u, &kinds[i]),
+            i if i == kinds.len() => {
+                let kind = u.choose(&kinds)?;
+                let len = u.int_in_range(0..=4)?;
+                (0..len).map(|_| scalar(u, kind)).collect::<arbitrary::Result<_>>().map(TypedValue::List)
+            }
+            _ => Ok(TypedValue::Null),
+        }
+    }
+}
+
+/// The envelope of an [`ArbitraryConfig`], valid against its catalog.
+impl<'a> Arbitrary<'a> for ConfigEnvelope {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ArbitraryConfig::arbitrary(u)?.envelope)
+    }
+}
+
+/// A catalog, a precedence matrix over its match attributes and an envelope
+/// that validates against it, for property tests and fuzz targets that
+/// exercise whole pipelines.
+///
+/// Match attributes are `m1`, `m2`, .. (`int`, `str`, `bool` or `enum` with
+/// values `a`, `b`, `c`) and params `p1`, `p2`, .. (`int`, `dec`, `str`, `bool`,
+/// `date` or `list<int>`). Values come from small pools so rows overlap; no two
+/// rows share a match tuple, and every row sets `p1`. Every rank of the matrix
+/// lists every match attribute, in name order, so [`verify_round_trip`] finds
+/// nothing lost; see [`ArbitraryConfig::check_round_trips`].
+#[derive(Debug, Clone)]
+pub struct ArbitraryConfig {
+    pub registry: AttrRegistry,
+    /// Ranks 1, 2, ..
+    pub matrix: Vec<MatrixRow>,
+    pub envelope: ConfigEnvelope,
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryConfig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut metas = Vec::new();
+        let match_count = u.int_in_range(1..=4)?;
+        for i in 1..=match_count {
+            let data_type = *u.choose(&["int", "str", "bool", "enum"])?;
+            metas.push(attr_meta(metas.len() as i32 + 1, format!("m{}", i), AttrRole::Match, data_type));
+        }
+        for i in 1..=u.int_in_range(1..=3)? {
+            let data_type = *u.choose(&["int", "dec", "str", "bool", "date", "list<int>"])?;
+            metas.push(attr_meta(metas.len() as i32 + 1, format!("p{}", i), AttrRole::Param, data_type));
+        }
+
+        let mut matrix = Vec::new();
+        for rank in 1..=u.int_in_range(1..=4)? {
+            let attrs = (1..=match_count)
+                .map(|i| Ok((format!("m{}", i).into(), u.int_in_range(0..=1)?)))
+                .collect::<arbitrary::Result<_>>()?;
+            matrix.push(MatrixRow { rank, attrs });
+        }
+
+        let mut rows = Vec::new();
+        let mut tuples = BTreeSet::new();
+        for _ in 0..u.int_in_range(0..=6)? {
+            let mut attrs = HashMap::new();
+            for meta in metas.iter().filter(|m| m.role == AttrRole::Match) {
+                if u.arbitrary()? {
+                    attrs.insert(meta.attr_name.clone(), pooled(u, &meta.data_type)?.to_json_value());
+                }
+            }
+            let mut tuple: Vec<String> = attrs.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            tuple.sort();
+            if !tuples.insert(tuple) {
+                continue;
+            }
+            let mut params = Vec::new();
+            // `p1` always, so no row is empty and match_ids survive the round trip
+            for (i, meta) in metas.iter().filter(|m| m.role == AttrRole::Param).enumerate() {
+                if i == 0 || u.ratio(3, 4)? {
+                    let ty: ParamType = meta.data_type.parse().expect("generated types parse");
+                    let value = pooled(u, &meta.data_type)?.to_json_value();
+                    params.push(Param { key: meta.attr_name.clone(), ty, value });
+                }
+            }
+            rows.push(ConfigRow { match_part: MatchPart { attrs }, params, audit: None });
+        }
+
+        let version = u.int_in_range(1..=9)?;
+        let config = ConfigMeta {
+            name: "arbitrary".to_string(),
+            version,
+            version_name: format!("v{}", version),
+            status: Default::default(),
+            valid_from: None,
+            valid_to: None,
+            audit: None,
+        };
+        let registry = AttrRegistry::from_metas(metas).expect("generated names and ids are distinct");
+        let envelope = ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config, rows };
+        Ok(ArbitraryConfig { registry, matrix, envelope })
+    }
+}
+
+impl ArbitraryConfig {
+    /// The invariants the crate keeps for every generated config: the matrix
+    /// survives JSON -> tall -> matrix unchanged, and the envelope survives
+    /// values -> envelope -> values with the same typed values. The error says
+    /// which one broke.
+    pub fn check_round_trips(&self) -> std::result::Result<(), String> {
+        let json = serde_json::to_string(&self.matrix).map_err(|e| e.to_string())?;
+        let report = verify_round_trip(&json, &self.registry).map_err(|e| format!("verify_round_trip: {}", e))?;
+        if !report.is_lossless() {
+            return Err(format!("matrix round trip lost {:?}", report.losses));
+        }
+
+        let values = envelope_to_config_values(&self.envelope, &self.registry)
+            .map_err(|e| format!("envelope_to_config_values: {}", e))?;
+        let rebuilt = config_values_to_envelope(&values, &self.envelope.config, &self.registry);
+        let again = envelope_to_config_values(&rebuilt, &self.registry)
+            .map_err(|e| format!("rebuilt envelope_to_config_values: {}", e))?;
+        if comparable(&values) != comparable(&again) {
+            return Err(format!("envelope round trip changed {:?} into {:?}", values, again));
+        }
+        Ok(())
+    }
+}
+
+fn attr_meta(attr_id: i32, attr_name: String, role: AttrRole, data_type: &str) -> AttrMeta {
+    AttrMeta {
+        attr_id,
+        attr_name: attr_name.into(),
+        data_type: data_type.to_string(),
+        role,
+        default: None,
+        enum_values: if data_type == "enum" { ["a", "b", "c"].map(String::from).to_vec() } else { Vec::new() },
+        constraints: None,
+        patterns: false,
+        parents: Default::default(),
+        deprecated: None,
+        aliases: Vec::new(),
+    }
+}
+
+/// A value of `data_type` from a small pool.
+fn pooled(u: &mut Unstructured, data_type: &str) -> arbitrary::Result<TypedValue> {
+    Ok(match data_type {
+        "int" => TypedValue::Int(u.int_in_range(-3..=3)?),
+        "dec" => TypedValue::Dec(u.int_in_range(-400..=400)? as f64 / 4.0),
+        "str" => TypedValue::Str(u.choose(&["us", "eu", "ca", "jp"])?.to_string()),
+        "bool" => TypedValue::Bool(u.arbitrary()?),
+        "enum" => TypedValue::Enum(u.choose(&["a", "b", "c"])?.to_string()),
+        "date" => {
+            let first = NaiveDate::from_ymd_opt(2026, 1, 1).expect("valid");
+            TypedValue::Date(first + chrono::Days::new(u.int_in_range(0..=30)?))
+        }
+        "list<int>" => {
+            let len = u.int_in_range(0..=3)?;
+            let items = (0..len).map(|_| Ok(TypedValue::Int(u.int_in_range(0..=9)?)));
+            TypedValue::List(items.collect::<arbitrary::Result<_>>()?)
+        }
+        other => unreachable!("no pool for {}", other),
+    })
+}
+
+/// The scalar variants [`TypedValue`]'s `Arbitrary` picks from.
+#[derive(Clone)]
+enum ScalarKind {
+    Int,
+    Dec,
+    Str,
+    Enum,
+    Bool,
+    Dt,
+    DtTz,
+    Date,
+    Time,
+    Duration,
+    #[cfg(feature = "uuid")]
+    Uuid,
+}
+
+fn scalar_kinds() -> Vec<ScalarKind> {
+    use ScalarKind::*;
+
+    vec![
+        Int,
+        Dec,
+        Str,
+        Enum,
+        Bool,
+        Dt,
+        DtTz,
+        Date,
+        Time,
+        Duration,
+        #[cfg(feature = "uuid")]
+        Uuid,
+    ]
+}
+
+fn scalar(u: &mut Unstructured, kind: &ScalarKind) -> arbitrary::Result<TypedValue> {
+    // whole seconds within 1970..2100, which every datetime format round-trips
+    let instant = |u: &mut Unstructured| -> arbitrary::Result<_> {
+        let secs = u.int_in_range(0..=4_102_444_800i64)?;
+        Ok(Utc.timestamp_opt(secs, 0).single().expect("in range"))
+    };
+    Ok(match kind {
+        ScalarKind::Int => TypedValue::Int(u.arbitrary()?),
+        ScalarKind::Dec => TypedValue::Dec(u.int_in_range(-1_000_000_000i64..=1_000_000_000)? as f64 / 1000.0),
+        ScalarKind::Str => TypedValue::Str(text(u)?),
+        ScalarKind::Enum => TypedValue::Enum(text(u)?),
+        ScalarKind::Bool => TypedValue::Bool(u.arbitrary()?),
+        ScalarKind::Dt => TypedValue::Dt(instant(u)?.naive_utc()),
+        ScalarKind::DtTz => {
+            let offset = chrono::FixedOffset::east_opt(u.int_in_range(-12..=14)? * 3600).expect("whole hours");
+            TypedValue::DtTz(instant(u)?.with_timezone(&offset))
+        }
+        ScalarKind::Date => TypedValue::Date(instant(u)?.date_naive()),
+        ScalarKind::Time => {
+            let secs = u.int_in_range(0..=86_399)?;
+            TypedValue::Time(NaiveTime::from_num_seconds_from_midnight_opt(secs, 0).expect("in range"))
+        }
+        ScalarKind::Duration => TypedValue::Duration(std::time::Duration::from_secs(u.int_in_range(0..=1_000_000)?)),
+        #[cfg(feature = "uuid")]
+        ScalarKind::Uuid => TypedValue::Uuid(uuid::Uuid::from_u128(u.arbitrary()?)),
+    })
+}
+
+/// 1 to 8 characters from `[a-z0-9_-]`.
+fn text(u: &mut Unstructured) -> arbitrary::Result<String> {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_-";
+    let len = u.int_in_range(1..=8)?;
+    (0..len).map(|_| Ok(*u.choose(CHARS)? as char)).collect()
+}
+
+fn comparable(values: &[ConfigValue]) -> Vec<(i32, i32, AttrRole, &TypedValue)> {
+    let mut rows: Vec<_> = values.iter().map(|v| (v.match_id, v.attr_id, v.role, &v.value)).collect();
+    rows.sort_by_key(|(match_id, attr_id, ..)| (*match_id, *attr_id));
+    rows
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::tall_to_matrix_rows;
+
+    /// `len` bytes of xorshift noise from `seed`, so failures reproduce.
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn generate<T: for<'a> Arbitrary<'a>>(seed: u64) -> T {
+        T::arbitrary(&mut Unstructured::new(&noise(seed, 4096))).expect("4 KiB is plenty")
+    }
+
+    #[test]
+    fn generated_configs_round_trip() {
+        let mut rows = 0;
+        for seed in 0..500 {
+            let config: ArbitraryConfig = generate(seed);
+            config.check_round_trips().unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            let values = envelope_to_config_values(&config.envelope, &config.registry).unwrap();
+            let rebuilt = config_values_to_envelope(&values, &config.envelope.config, &config.registry);
+            assert_eq!(rebuilt.rows.len(), config.envelope.rows.len(), "seed {}", seed);
+            rows += rebuilt.rows.len();
+        }
+        assert!(rows > 500, "only {} rows generated", rows);
+    }
+
+    #[test]
+    fn generated_rules_convert_back_to_the_same_cells() {
+        let mut registry = AttrRegistry::new();
+        for name in MATRIX_COLUMNS {
+            registry.register(name, AttrRole::Match, "str").unwrap();
+        }
+        for seed in 0..200 {
+            let rules: Vec<ConfigPrecedenceRule> = generate(seed);
+            let mut seen = BTreeSet::new();
+            let unique: Vec<_> = rules.into_iter().filter(|r| seen.insert((r.rank, r.attr_id))).collect();
+            if unique.is_empty() {
+                continue;
+            }
+            let matrix = tall_to_matrix_rows(&unique, &registry).unwrap();
+            let cells: usize = matrix.iter().map(|row| row.attrs.len()).sum();
+            assert_eq!(cells, unique.len(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn generated_matrices_round_trip_once_canonical() {
+        let mut registry = AttrRegistry::new();
+        for name in MATRIX_COLUMNS {
+            registry.register(name, AttrRole::Match, "str").unwrap();
+        }
+        let mut converted = 0;
+        for seed in 0..500 {
+            let matrix: Vec<MatrixRow> = generate(seed);
+            let json = serde_json::to_string(&matrix).unwrap();
+            // repeated (rank, attr) cells are rejected; everything else converts
+            let Ok(report) = verify_round_trip(&json, &registry) else { continue };
+            converted += 1;
+            let canonical = serde_json::to_string(&report.matrix).unwrap();
+            let again = verify_round_trip(&canonical, &registry).unwrap();
+            assert!(again.is_lossless(), "seed {}: {:?}", seed, again.losses);
+            assert_eq!(again.matrix, report.matrix, "seed {}", seed);
+        }
+        assert!(converted > 50, "only {} matrices converted", converted);
+    }
+}
@@ -0,0 +1,293 @@
+//! Arrow record batches and Parquet files (`arrow` feature) of tall precedence
+//! rules and config values, for data-lake ingestion without re-parsing JSON.
+
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::ConfigPrecedenceRule;
+use crate::config_value::{ConfigValue, TypedValue};
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, DurationMicrosecondArray, Float64Array, Int32Array, Int64Array, RecordBatch,
+    StringArray, Time64MicrosecondArray, TimestampMicrosecondArray, UInt8Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{NaiveDate, Timelike};
+use parquet::arrow::ArrowWriter;
+use std::io;
+use std::sync::{Arc, LazyLock};
+
+static RULES_SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("config_version_id", DataType::Int32, false),
+        Field::new("rank", DataType::Int32, false),
+        Field::new("attr_id", DataType::Int32, false),
+        Field::new("match_type", DataType::UInt8, false),
+    ]))
+});
+
+static VALUES_SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("match_id", DataType::Int32, false),
+        Field::new("attr_id", DataType::Int32, false),
+        Field::new("role", DataType::Utf8, false),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("int_value", DataType::Int64, true),
+        Field::new("dec_value", DataType::Float64, true),
+        Field::new("str_value", DataType::Utf8, true),
+        Field::new("bool_value", DataType::Boolean, true),
+        Field::new("dt_value", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), true),
+        Field::new("date_value", DataType::Date32, true),
+        Field::new("time_value", DataType::Time64(TimeUnit::Microsecond), true),
+        Field::new("duration_value", DataType::Duration(TimeUnit::Microsecond), true),
+        Field::new("json_value", DataType::Utf8, true),
+    ]))
+});
+
+/// `config_version_id`, `rank`, `attr_id` (int32) and `match_type` (uint8, 0/1).
+pub fn rules_schema() -> SchemaRef {
+    RULES_SCHEMA.clone()
+}
+
+/// `match_id`, `attr_id`, `role` and `type` (the `TypedValue::type_name`), then
+/// one nullable column per kind of value, of which each row sets at most one:
+///
+/// | column           | Arrow type            | holds                                  |
+/// |------------------|-----------------------|----------------------------------------|
+/// | `int_value`      | int64                 | `int`                                  |
+/// | `dec_value`      | float64               | `dec` (without `rust_decimal`)         |
+/// | `str_value`      | utf8                  | `str`, `enum`                          |
+/// | `bool_value`     | bool                  | `bool`                                 |
+/// | `dt_value`       | timestamp(us, UTC)    | `dt`, with or without an offset        |
+/// | `date_value`     | date32                | `date`                                 |
+/// | `time_value`     | time64(us)            | `time`                                 |
+/// | `duration_value` | duration(us)          | `duration`                             |
+/// | `json_value`     | utf8                  | anything else, as its JSON text        |
+///
+/// "Anything else" is exact decimals, uuids, lists and match-only values; `null`
+/// sets no column.
+pub fn values_schema() -> SchemaRef {
+    VALUES_SCHEMA.clone()
+}
+
+/// Tall rules as one batch of [`rules_schema`].
+pub fn rules_to_record_batch(rules: &[ConfigPrecedenceRule]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(rules.iter().map(|r| r.config_version_id))),
+        Arc::new(Int32Array::from_iter_values(rules.iter().map(|r| r.rank))),
+        Arc::new(Int32Array::from_iter_values(rules.iter().map(|r| r.attr_id))),
+        Arc::new(UInt8Array::from_iter_values(rules.iter().map(|r| u8::from(r.match_type)))),
+    ];
+    RecordBatch::try_new(rules_schema(), columns).map_err(arrow_error)
+}
+
+/// Config values as one batch of [`values_schema`].
+pub fn values_to_record_batch(values: &[ConfigValue]) -> Result<RecordBatch> {
+    let mut cells = ValueColumns::with_capacity(values.len());
+    for value in values {
+        cells.push(&value.value);
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(values.iter().map(|v| v.match_id))),
+        Arc::new(Int32Array::from_iter_values(values.iter().map(|v| v.attr_id))),
+        Arc::new(StringArray::from_iter_values(values.iter().map(|v| v.role.to_string()))),
+        Arc::new(StringArray::from_iter_values(values.iter().map(|v| v.value.type_name()))),
+        Arc::new(Int64Array::from(cells.int)),
+        Arc::new(Float64Array::from(cells.dec)),
+        Arc::new(StringArray::from(cells.str)),
+        Arc::new(BooleanArray::from(cells.bool)),
+        Arc::new(TimestampMicrosecondArray::from(cells.dt).with_timezone("UTC")),
+        Arc::new(Date32Array::from(cells.date)),
+        Arc::new(Time64MicrosecondArray::from(cells.time)),
+        Arc::new(DurationMicrosecondArray::from(cells.duration)),
+        Arc::new(StringArray::from(cells.json)),
+    ];
+    RecordBatch::try_new(values_schema(), columns).map_err(arrow_error)
+}
+
+/// Writes tall rules to `writer` as a Snappy-compressed Parquet file.
+pub fn write_rules_parquet<W: io::Write + Send>(rules: &[ConfigPrecedenceRule], writer: W) -> Result<()> {
+    write_parquet(rules_to_record_batch(rules)?, writer)
+}
+
+/// Writes config values to `writer` as a Snappy-compressed Parquet file.
+pub fn write_values_parquet<W: io::Write + Send>(values: &[ConfigValue], writer: W) -> Result<()> {
+    write_parquet(values_to_record_batch(values)?, writer)
+}
+
+fn write_parquet<W: io::Write + Send>(batch: RecordBatch, writer: W) -> Result<()> {
+    let properties = parquet::file::properties::WriterProperties::builder()
+        .set_compression(parquet::basic::Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(properties)).map_err(parquet_error)?;
+    writer.write(&batch).map_err(parquet_error)?;
+    writer.close().map_err(parquet_error)?;
+    Ok(())
+}
+
+/// The typed value columns of [`values_schema`], one entry per config value.
+struct ValueColumns {
+    int: Vec<Option<i64>>,
+    dec: Vec<Option<f64>>,
+    str: Vec<Option<String>>,
+    bool: Vec<Option<bool>>,
+    dt: Vec<Option<i64>>,
+    date: Vec<Option<i32>>,
+    time: Vec<Option<i64>>,
+    duration: Vec<Option<i64>>,
+    json: Vec<Option<String>>,
+}
+
+impl ValueColumns {
+    fn with_capacity(len: usize) -> Self {
+        Self {
+            int: Vec::with_capacity(len),
+            dec: Vec::with_capacity(len),
+            str: Vec::with_capacity(len),
+            bool: Vec::with_capacity(len),
+            dt: Vec::with_capacity(len),
+            date: Vec::with_capacity(len),
+            time: Vec::with_capacity(len),
+            duration: Vec::with_capacity(len),
+            json: Vec::with_capacity(len),
+        }
+    }
+
+    /// Appends a row of nulls, then sets the column `value` belongs in.
+    fn push(&mut self, value: &TypedValue) {
+        self.int.push(None);
+        self.dec.push(None);
+        self.str.push(None);
+        self.bool.push(None);
+        self.dt.push(None);
+        self.date.push(None);
+        self.time.push(None);
+        self.duration.push(None);
+        self.json.push(None);
+
+        match value {
+            TypedValue::Int(v) => *last(&mut self.int) = Some(*v),
+            TypedValue::Dec(v) => *last(&mut self.dec) = Some(*v),
+            TypedValue::Str(v) | TypedValue::Enum(v) => *last(&mut self.str) = Some(v.clone()),
+            TypedValue::Bool(v) => *last(&mut self.bool) = Some(*v),
+            TypedValue::Dt(v) => *last(&mut self.dt) = Some(v.and_utc().timestamp_micros()),
+            TypedValue::DtTz(v) => *last(&mut self.dt) = Some(v.timestamp_micros()),
+            TypedValue::Date(v) => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid");
+                *last(&mut self.date) = Some(v.signed_duration_since(epoch).num_days() as i32);
+            }
+            TypedValue::Time(v) => {
+                let micros = v.num_seconds_from_midnight() as i64 * 1_000_000 + v.nanosecond() as i64 / 1_000;
+                *last(&mut self.time) = Some(micros);
+            }
+            TypedValue::Duration(v) => {
+                *last(&mut self.duration) = Some(i64::try_from(v.as_micros()).unwrap_or(i64::MAX));
+            }
+            TypedValue::Null => {}
+            other => *last(&mut self.json) = Some(other.to_json_value().to_string()),
+        }
+    }
+}
+
+fn last<T>(column: &mut [Option<T>]) -> &mut Option<T> {
+    column.last_mut().expect("a row was just pushed")
+}
+
+fn arrow_error(e: arrow_schema::ArrowError) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "Arrow", reason: e.to_string() }
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "Parquet", reason: e.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::MatchType;
+    use crate::config_value::AttrRole;
+    use arrow_array::Array;
+    use chrono::{NaiveTime, TimeZone, Utc};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::time::Duration;
+
+    fn rules() -> Vec<ConfigPrecedenceRule> {
+        vec![
+            ConfigPrecedenceRule { config_version_id: 7, rank: 1, attr_id: 1, match_type: MatchType::Exact },
+            ConfigPrecedenceRule { config_version_id: 7, rank: 2, attr_id: 1, match_type: MatchType::Ignore },
+        ]
+    }
+
+    fn value(attr_id: i32, value: TypedValue) -> ConfigValue {
+        ConfigValue { match_id: 1, attr_id, role: AttrRole::Param, value }
+    }
+
+    /// The name of the one value column `row` sets, if any.
+    fn set_column(batch: &RecordBatch, row: usize) -> Option<&str> {
+        let schema = batch.schema_ref();
+        (4..batch.num_columns()).find(|&i| batch.column(i).is_valid(row)).map(|i| schema.field(i).name().as_str())
+    }
+
+    #[test]
+    fn rules_become_one_column_per_field() {
+        let batch = rules_to_record_batch(&rules()).unwrap();
+        assert_eq!(batch.schema(), rules_schema());
+        assert_eq!(batch.num_rows(), 2);
+        let ranks = batch.column(1).as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(ranks.values(), &[1, 2]);
+        let match_types = batch.column(3).as_any().downcast_ref::<UInt8Array>().unwrap();
+        assert_eq!(match_types.values(), &[1, 0]);
+    }
+
+    #[test]
+    fn each_value_sets_the_column_of_its_kind() {
+        let at = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let values = vec![
+            value(1, TypedValue::Int(5)),
+            value(2, TypedValue::Dec(2.5)),
+            value(3, TypedValue::Enum("gold".to_string())),
+            value(4, TypedValue::Bool(true)),
+            value(5, TypedValue::Dt(at.naive_utc())),
+            value(6, TypedValue::DtTz(at.fixed_offset())),
+            value(7, TypedValue::Date(NaiveDate::from_ymd_opt(1970, 1, 11).unwrap())),
+            value(8, TypedValue::Time(NaiveTime::from_hms_micro_opt(0, 0, 1, 5).unwrap())),
+            value(9, TypedValue::Duration(Duration::from_secs(60))),
+            value(10, TypedValue::List(vec![TypedValue::Int(1), TypedValue::Int(2)])),
+            value(11, TypedValue::Null),
+        ];
+        let batch = values_to_record_batch(&values).unwrap();
+        assert_eq!(batch.schema(), values_schema());
+        let columns: Vec<Option<&str>> = (0..values.len()).map(|row| set_column(&batch, row)).collect();
+        assert_eq!(
+            columns,
+            [
+                Some("int_value"),
+                Some("dec_value"),
+                Some("str_value"),
+                Some("bool_value"),
+                Some("dt_value"),
+                Some("dt_value"),
+                Some("date_value"),
+                Some("time_value"),
+                Some("duration_value"),
+                Some("json_value"),
+                None,
+            ]
+        );
+        let dates = batch.column(9).as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(dates.value(6), 10);
+        let times = batch.column(10).as_any().downcast_ref::<Time64MicrosecondArray>().unwrap();
+        assert_eq!(times.value(7), 1_000_005);
+        let json = batch.column(12).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(json.value(9), "[1,2]");
+        let types = batch.column(3).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((types.value(2), types.value(10)), ("enum", "null"));
+    }
+
+    #[test]
+    fn parquet_files_read_back_as_the_batch() {
+        let path = std::env::temp_dir().join(format!("precedence-config-arrow-{}.parquet", std::process::id()));
+        write_rules_parquet(&rules(), std::fs::File::create(&path).unwrap()).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+        let batches: Vec<RecordBatch> = reader.build().unwrap().map(|batch| batch.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batches, [rules_to_record_batch(&rules()).unwrap()]);
+    }
+}
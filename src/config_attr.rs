@@ -0,0 +1,563 @@
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_value::{AttrMeta, AttrRole, Deprecation, TypedValue};
+use serde::{de, Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// CONFIG_ATTR catalog: owns the attr_name <-> attr_id mapping and each attribute's `AttrMeta`.
+/// Lookups by name also find attributes by their aliases.
+#[derive(Debug, Clone)]
+pub struct AttrRegistry {
+    by_id: BTreeMap<i32, AttrMeta>,
+    /// attr_name and aliases -> attr_id
+    by_name: HashMap<AttrName, i32>,
+    next_id: i32,
+}
+
+impl Default for AttrRegistry {
+    fn default() -> Self {
+        Self { by_id: BTreeMap::new(), by_name: HashMap::new(), next_id: 1 }
+    }
+}
+
+impl AttrRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a registry from existing catalog rows (e.g. loaded from CONFIG_ATTR).
+    pub fn from_metas(metas: impl IntoIterator<Item = AttrMeta>) -> Result<Self> {
+        let mut registry = Self::new();
+        for meta in metas {
+            registry.insert(meta)?;
+        }
+        Ok(registry)
+    }
+
+    /// Adds a new attribute, allocating the next free attr_id (like `IDENTITY(1,1)`).
+    pub fn register(&mut self, attr_name: &str, role: AttrRole, data_type: &str) -> Result<i32> {
+        let attr_id = self.next_id;
+        self.insert(AttrMeta {
+            attr_id,
+            attr_name: attr_name.into(),
+            data_type: data_type.to_string(),
+            role,
+            default: None,
+            enum_values: Vec::new(),
+            constraints: None,
+            patterns: false,
+            parents: Default::default(),
+            deprecated: None,
+            aliases: Vec::new(),
+        })?;
+        Ok(attr_id)
+    }
+
+    /// Adds an attribute with a caller-chosen attr_id. Neither its name nor its
+    /// aliases may be taken, by a name or an alias.
+    pub fn insert(&mut self, meta: AttrMeta) -> Result<()> {
+        let mut names: HashSet<&str> = HashSet::new();
+        for name in std::iter::once(meta.attr_name.as_str()).chain(meta.aliases.iter().map(String::as_str)) {
+            if self.by_name.contains_key(name) || !names.insert(name) {
+                return Err(PrecedenceConfigError::DuplicateAttrName { attr_name: name.to_string() });
+            }
+        }
+        if self.by_id.contains_key(&meta.attr_id) {
+            return Err(PrecedenceConfigError::DuplicateAttrId { attr_id: meta.attr_id });
+        }
+        meta.check_parents()?;
+        let after = meta.attr_id.checked_add(1).ok_or(PrecedenceConfigError::AttrIdOverflow { attr_id: meta.attr_id })?;
+
+        self.next_id = self.next_id.max(after);
+        self.by_name.insert(meta.attr_name.clone(), meta.attr_id);
+        for alias in &meta.aliases {
+            self.by_name.insert(AttrName::new(alias), meta.attr_id);
+        }
+        self.by_id.insert(meta.attr_id, meta);
+        Ok(())
+    }
+
+    /// Sets (or with `None` clears) the value rows fall back to when they omit `attr_name`.
+    pub fn set_default(&mut self, attr_name: &str, default: Option<TypedValue>) -> Result<()> {
+        self.meta_mut(attr_name)?.default = default;
+        Ok(())
+    }
+
+    /// Lets (or stops) `attr_name` take `*` patterns as match values, see `AttrMeta::patterns`.
+    pub fn set_patterns(&mut self, attr_name: &str, patterns: bool) -> Result<()> {
+        self.meta_mut(attr_name)?.patterns = patterns;
+        Ok(())
+    }
+
+    /// Makes `attr_name` hierarchical: a context value also matches rows keyed at its
+    /// ancestors by `parents` (child -> parent, in text form), the nearest winning.
+    /// Replaces any earlier table, and an empty one makes the attribute flat again.
+    /// Fails on a cycle, leaving the attribute unchanged.
+    pub fn set_parents(&mut self, attr_name: &str, parents: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+        let meta = self.meta_mut(attr_name)?;
+        let previous = std::mem::replace(&mut meta.parents, parents.into_iter().collect());
+        meta.check_parents().inspect_err(|_| meta.parents = previous)
+    }
+
+    /// [`AttrRegistry::set_parents`] with the table built by following `parent_of`
+    /// up from each of `values`, until it returns `None`.
+    pub fn set_parents_with(
+        &mut self,
+        attr_name: &str,
+        values: impl IntoIterator<Item = String>,
+        parent_of: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        let mut parents = BTreeMap::new();
+        for value in values {
+            let mut child = value;
+            while !parents.contains_key(&child)
+                && let Some(parent) = parent_of(&child)
+            {
+                parents.insert(child, parent.clone());
+                child = parent;
+            }
+        }
+        self.set_parents(attr_name, parents)
+    }
+
+    /// Replaces the aliases of `attr_name`, see `AttrMeta::aliases`; repeats and the
+    /// attribute's own name are dropped. Fails, leaving the aliases unchanged, if one
+    /// is taken by another attribute's name or alias.
+    pub fn set_aliases(&mut self, attr_name: &str, aliases: impl IntoIterator<Item = String>) -> Result<()> {
+        let meta = self
+            .get(attr_name)
+            .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: attr_name.to_string() })?;
+        let attr_id = meta.attr_id;
+        let mut kept: Vec<String> = Vec::new();
+        for alias in aliases {
+            if alias == *meta.attr_name || kept.contains(&alias) {
+                continue;
+            }
+            if self.id_of(&alias).is_some_and(|id| id != attr_id) {
+                return Err(PrecedenceConfigError::DuplicateAttrName { attr_name: alias });
+            }
+            kept.push(alias);
+        }
+
+        let meta = self.by_id.get_mut(&attr_id).expect("by_name and by_id agree");
+        for old in &meta.aliases {
+            self.by_name.remove(old.as_str());
+        }
+        for alias in &kept {
+            self.by_name.insert(AttrName::new(alias), attr_id);
+        }
+        meta.aliases = kept;
+        Ok(())
+    }
+
+    /// Marks `attr_name` deprecated (or with `None` no longer), see `AttrMeta::deprecated`.
+    /// A replacement must be another registered attribute of the same role, and
+    /// following replacements on from it must not lead back to `attr_name`. On error
+    /// the attribute is left unchanged.
+    pub fn set_deprecated(&mut self, attr_name: &str, deprecated: Option<Deprecation>) -> Result<()> {
+        let (attr_id, role) = self.meta_mut(attr_name).map(|meta| (meta.attr_id, meta.role))?;
+        if let Some(replacement) = deprecated.as_ref().and_then(|d| d.replacement.as_deref()) {
+            let invalid = |reason: String| PrecedenceConfigError::InvalidAttributeEdit {
+                op: "deprecate",
+                attr_name: attr_name.to_string(),
+                reason,
+            };
+            let meta = self
+                .get(replacement)
+                .ok_or_else(|| invalid(format!("replacement '{}' is not registered", replacement)))?;
+            if meta.role != role {
+                return Err(invalid(format!("replacement '{}' is a {} attribute", replacement, meta.role)));
+            }
+            let mut current = replacement;
+            for _ in 0..self.by_id.len() {
+                if self.id_of(current) == Some(attr_id) {
+                    return Err(invalid(format!("following replacements from '{}' leads back to it", replacement)));
+                }
+                match self.get(current).and_then(|m| m.deprecated.as_ref()).and_then(|d| d.replacement.as_deref()) {
+                    Some(next) => current = next,
+                    None => break,
+                }
+            }
+        }
+        self.meta_mut(attr_name)?.deprecated = deprecated;
+        Ok(())
+    }
+
+    /// The attribute a deprecated `attr_name` migrates to: its replacement, followed
+    /// on through replacements that are deprecated in turn. `None` if it has no
+    /// replacement, or the replacements lead nowhere (an unknown name, a cycle).
+    pub fn replacement_of(&self, attr_name: &str) -> Option<&AttrMeta> {
+        let mut current = self.get(attr_name)?;
+        for _ in 0..self.by_id.len() {
+            match current.deprecated.as_ref().and_then(|d| d.replacement.as_deref()) {
+                Some(next) => current = self.get(next)?,
+                None => return (self.id_of(attr_name) != Some(current.attr_id)).then_some(current),
+            }
+        }
+        None
+    }
+
+    /// Renames attribute `old` to `new`, keeping its attr_id, aliases and metadata.
+    /// Fails if `old` is unknown or an alias, or `new` is taken by another attribute.
+    /// Deprecations naming `old` as their replacement follow it to `new`.
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<()> {
+        let meta = self.get(old).ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: old.to_string() })?;
+        if *meta.attr_name != *old {
+            return Err(PrecedenceConfigError::InvalidAttributeEdit {
+                op: "rename",
+                attr_name: old.to_string(),
+                reason: format!("it is an alias of '{}'", meta.attr_name),
+            });
+        }
+        let attr_id = meta.attr_id;
+        if old == new {
+            return Ok(());
+        }
+        if self.by_name.contains_key(new) {
+            return Err(PrecedenceConfigError::DuplicateAttrName { attr_name: new.to_string() });
+        }
+        let new = AttrName::new(new);
+        self.by_name.remove(old);
+        self.by_name.insert(new.clone(), attr_id);
+        self.by_id.get_mut(&attr_id).expect("by_name and by_id agree").attr_name = new.clone();
+        for deprecation in self.by_id.values_mut().filter_map(|m| m.deprecated.as_mut()) {
+            if deprecation.replacement.as_deref() == Some(old) {
+                deprecation.replacement = Some(new.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes an attribute by name or alias, returning its metadata.
+    pub fn remove(&mut self, attr_name: &str) -> Option<AttrMeta> {
+        let attr_id = self.id_of(attr_name)?;
+        let meta = self.by_id.remove(&attr_id)?;
+        self.by_name.remove(&meta.attr_name);
+        for alias in &meta.aliases {
+            self.by_name.remove(alias.as_str());
+        }
+        Some(meta)
+    }
+
+    pub fn id_of(&self, attr_name: &str) -> Option<i32> {
+        self.by_name.get(attr_name).copied()
+    }
+
+    pub fn name_of(&self, attr_id: i32) -> Option<&str> {
+        self.by_id.get(&attr_id).map(|m| m.attr_name.as_str())
+    }
+
+    pub fn get(&self, attr_name: &str) -> Option<&AttrMeta> {
+        self.by_name.get(attr_name).and_then(|id| self.by_id.get(id))
+    }
+
+    pub fn get_by_id(&self, attr_id: i32) -> Option<&AttrMeta> {
+        self.by_id.get(&attr_id)
+    }
+
+    fn meta_mut(&mut self, attr_name: &str) -> Result<&mut AttrMeta> {
+        self.by_name
+            .get(attr_name)
+            .and_then(|id| self.by_id.get_mut(id))
+            .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: attr_name.to_string() })
+    }
+
+    /// Attributes in attr_id order.
+    pub fn iter(&self) -> impl Iterator<Item = &AttrMeta> {
+        self.by_id.values()
+    }
+
+    /// Attributes with the given role, in attr_id order.
+    pub fn with_role(&self, role: AttrRole) -> impl Iterator<Item = &AttrMeta> {
+        self.iter().filter(move |m| m.role == role)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+}
+
+/// An attribute name, interned: every `AttrName` spelled the same shares one
+/// allocation, so the names repeated on every row of a wide matrix or envelope
+/// are stored once. Derefs to `str`, compares, hashes and orders like it, and
+/// (de)serializes as a string; deserializing a name seen before allocates nothing.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AttrName(Arc<str>);
+
+/// Distinct names interned process-wide; past it new names are allocated but not
+/// kept, so keys of untrusted payloads can't grow the table without bound.
+const MAX_INTERNED: usize = 1 << 16;
+
+static INTERNED: LazyLock<RwLock<HashSet<Arc<str>>>> = LazyLock::new(Default::default);
+
+impl AttrName {
+    pub fn new(name: &str) -> Self {
+        if let Some(interned) = INTERNED.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(name) {
+            return AttrName(interned.clone());
+        }
+        let mut interned = INTERNED.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(name) = interned.get(name) {
+            return AttrName(name.clone());
+        }
+        let name: Arc<str> = Arc::from(name);
+        if interned.len() < MAX_INTERNED {
+            interned.insert(name.clone());
+        }
+        AttrName(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for AttrName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for AttrName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for AttrName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for AttrName {
+    fn from(name: &str) -> Self {
+        AttrName::new(name)
+    }
+}
+
+impl From<String> for AttrName {
+    fn from(name: String) -> Self {
+        AttrName::new(&name)
+    }
+}
+
+impl From<&String> for AttrName {
+    fn from(name: &String) -> Self {
+        AttrName::new(name)
+    }
+}
+
+impl From<AttrName> for String {
+    fn from(name: AttrName) -> Self {
+        name.0.to_string()
+    }
+}
+
+impl PartialEq<str> for AttrName {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for AttrName {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for AttrName {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<AttrName> for str {
+    fn eq(&self, other: &AttrName) -> bool {
+        self == &*other.0
+    }
+}
+
+impl PartialEq<AttrName> for &str {
+    fn eq(&self, other: &AttrName) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<AttrName> for String {
+    fn eq(&self, other: &AttrName) -> bool {
+        **self == *other.0
+    }
+}
+
+impl fmt::Debug for AttrName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for AttrName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for AttrName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttrName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl de::Visitor<'_> for NameVisitor {
+            type Value = AttrName;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an attribute name")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<AttrName, E> {
+                Ok(AttrName::new(v))
+            }
+        }
+
+        deserializer.deserialize_str(NameVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_allocates_ids_and_maps_both_ways() {
+        let mut registry = AttrRegistry::new();
+        assert!(registry.is_empty());
+        assert_eq!(registry.register("region", AttrRole::Match, "str").unwrap(), 1);
+        assert_eq!(registry.register("limit", AttrRole::Param, "int").unwrap(), 2);
+        assert_eq!(registry.id_of("limit"), Some(2));
+        assert_eq!(registry.name_of(1), Some("region"));
+        assert_eq!(registry.get_by_id(2).unwrap().data_type, "int");
+        assert_eq!(registry.with_role(AttrRole::Match).map(|m| m.attr_id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn names_and_ids_are_unique() {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        assert!(matches!(
+            registry.register("region", AttrRole::Param, "int"),
+            Err(PrecedenceConfigError::DuplicateAttrName { ref attr_name }) if attr_name == "region"
+        ));
+        let mut meta = registry.get("region").unwrap().clone();
+        meta.attr_name = "channel".into();
+        assert!(matches!(registry.insert(meta), Err(PrecedenceConfigError::DuplicateAttrId { attr_id: 1 })));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn insert_moves_allocation_past_the_highest_id() {
+        let mut scratch = AttrRegistry::new();
+        scratch.register("region", AttrRole::Match, "str").unwrap();
+        let mut meta = scratch.remove("region").unwrap();
+        meta.attr_id = 10;
+        let mut registry = AttrRegistry::from_metas([meta]).unwrap();
+        assert_eq!(registry.id_of("region"), Some(10));
+        assert_eq!(registry.register("channel", AttrRole::Match, "str").unwrap(), 11);
+        assert_eq!(registry.remove("region").map(|m| m.attr_id), Some(10));
+        assert_eq!(registry.id_of("region"), None);
+        // removed ids are not handed out again
+        assert_eq!(registry.register("region", AttrRole::Match, "str").unwrap(), 12);
+    }
+
+    #[test]
+    fn the_last_attr_id_is_rejected_and_leaves_the_registry_unchanged() {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        let mut meta = registry.get("region").unwrap().clone();
+        meta.attr_id = i32::MAX;
+        meta.attr_name = "channel".into();
+        let err = registry.insert(meta).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::AttrIdOverflow { attr_id: i32::MAX }));
+        assert!(registry.get("channel").is_none());
+        assert_eq!(registry.register("channel", AttrRole::Match, "str").unwrap(), 2);
+    }
+
+    #[test]
+    fn a_parent_cycle_leaves_the_table_unchanged() {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.set_parents("region", [("berlin".to_string(), "de".to_string())]).unwrap();
+        let cycle = [("de", "eu"), ("eu", "de")].map(|(c, p)| (c.to_string(), p.to_string()));
+        assert!(matches!(registry.set_parents("region", cycle), Err(PrecedenceConfigError::HierarchyCycle { .. })));
+        assert_eq!(registry.get("region").unwrap().parents.get("berlin").map(String::as_str), Some("de"));
+        assert_eq!(registry.get("region").unwrap().parents.len(), 1);
+    }
+
+    #[test]
+    fn parents_with_follows_the_chain_up() {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        let parent_of = |value: &str| match value {
+            "berlin" => Some("de".to_string()),
+            "de" => Some("eu".to_string()),
+            _ => None,
+        };
+        registry.set_parents_with("region", ["berlin".to_string()], parent_of).unwrap();
+        let parents = &registry.get("region").unwrap().parents;
+        assert_eq!(parents.len(), 2);
+        assert_eq!(parents.get("de").map(String::as_str), Some("eu"));
+    }
+
+    #[test]
+    fn attr_names_spelled_the_same_share_one_allocation() {
+        let name = AttrName::new("interned_region");
+        let parsed: AttrName = serde_json::from_str("\"interned_region\"").unwrap();
+        assert!(Arc::ptr_eq(&name.0, &parsed.0));
+        assert!(Arc::ptr_eq(&name.0, &AttrName::from(String::from("interned_region")).0));
+        assert!(!Arc::ptr_eq(&name.0, &AttrName::new("interned_channel").0));
+
+        assert_eq!(name, "interned_region");
+        assert_eq!("interned_region".to_string(), name);
+        assert_eq!((name.len(), name.to_string()), (15, "interned_region".to_string()));
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"interned_region\"");
+        let mut names = [AttrName::new("b"), AttrName::new("a")];
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn aliases_look_up_their_attribute() {
+        let mut registry = AttrRegistry::new();
+        registry.register("country", AttrRole::Match, "str").unwrap();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        let aliases = ["ctry", "country", "ctry", "cc"].map(str::to_string);
+        registry.set_aliases("country", aliases).unwrap();
+        assert_eq!(registry.get("country").unwrap().aliases, ["ctry", "cc"]);
+        assert_eq!((registry.id_of("ctry"), registry.get("cc").unwrap().attr_name.as_str()), (Some(1), "country"));
+
+        let err = registry.set_aliases("region", ["cc".to_string()]).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateAttrName { attr_name } if attr_name == "cc"));
+        assert!(registry.get("region").unwrap().aliases.is_empty());
+        let err = registry.register("ctry", AttrRole::Match, "str").unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateAttrName { .. }));
+        assert!(matches!(registry.rename("ctry", "c"), Err(PrecedenceConfigError::InvalidAttributeEdit { .. })));
+
+        registry.set_aliases("ctry", ["c".to_string()]).unwrap();
+        assert_eq!((registry.id_of("cc"), registry.id_of("c")), (None, Some(1)));
+        assert_eq!(registry.remove("c").unwrap().attr_name, "country");
+        assert!(registry.get("c").is_none() && registry.get("country").is_none());
+    }
+}
@@ -0,0 +1,304 @@
+//! Versioned binary form (`binary` feature) of a compiled config, see
+//! [`CompiledConfig::to_bytes`](crate::config_resolver::CompiledConfig::to_bytes).
+//!
+//! A file is [`MAGIC`], [`BINARY_FORMAT_VERSION`] as a little-endian `u16`, then
+//! a postcard payload. The payload's layout only changes with the format version,
+//! whatever the crate features: values needing a feature (exact decimals, uuids,
+//! regexes) are always encodable and fail to load without it.
+
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_resolver::ValueKey;
+use crate::config_value::{RangeBound, StrPattern, TypedValue, ValueRange};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, Timelike};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// First bytes of every binary config.
+pub const MAGIC: [u8; 4] = *b"PCFG";
+
+/// Layout of the payload; files of any other version are refused.
+pub const BINARY_FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// The format version `bytes` were written with, read from their header.
+pub fn binary_format_version(bytes: &[u8]) -> Result<u16> {
+    if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a binary config (bad header)".to_string()));
+    }
+    Ok(u16::from_le_bytes([bytes[MAGIC.len()], bytes[MAGIC.len() + 1]]))
+}
+
+/// `payload` behind a header of the current format version.
+pub(crate) fn encode<T: Serialize>(payload: &T) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&BINARY_FORMAT_VERSION.to_le_bytes());
+    postcard::to_extend(payload, bytes).map_err(|e| invalid(e.to_string()))
+}
+
+/// The payload of `bytes`, which must be of the current format version and hold
+/// nothing after the payload.
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let found = binary_format_version(bytes)?;
+    if found != BINARY_FORMAT_VERSION {
+        return Err(PrecedenceConfigError::BinaryFormatVersion { found, expected: BINARY_FORMAT_VERSION });
+    }
+    let (payload, rest) = postcard::take_from_bytes(&bytes[HEADER_LEN..]).map_err(|e| invalid(e.to_string()))?;
+    if !rest.is_empty() {
+        return Err(invalid(format!("{} trailing byte(s) after the payload", rest.len())));
+    }
+    Ok(payload)
+}
+
+/// A `TypedValue` (or `ValueKey`) as postcard can read it back: no self-describing
+/// parts, and the same variants whatever the crate features.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum BinaryValue {
+    Int(i64),
+    /// f64 bits
+    Dec(u64),
+    /// `rust_decimal::Decimal::serialize`
+    Decimal([u8; 16]),
+    Str(String),
+    Enum(String),
+    Bool(bool),
+    /// UTC seconds and nanoseconds
+    Dt(i64, u32),
+    /// UTC seconds, nanoseconds and the offset in seconds east of UTC
+    DtTz(i64, u32, i32),
+    /// days since 0001-01-01, which is day 1
+    Date(i32),
+    /// seconds since midnight and nanoseconds
+    Time(u32, u32),
+    Duration(u64, u32),
+    Uuid([u8; 16]),
+    List(Vec<BinaryValue>),
+    OneOf(Vec<BinaryValue>),
+    /// the pattern as written
+    Pattern(String),
+    /// the regex source
+    Regex(String),
+    /// (bound, inclusive) of the lower and upper end
+    Range(Option<(Box<BinaryValue>, bool)>, Option<(Box<BinaryValue>, bool)>),
+    Null,
+}
+
+impl From<&ValueKey> for BinaryValue {
+    fn from(key: &ValueKey) -> Self {
+        let bound = |bound: &Option<(Box<ValueKey>, bool)>| {
+            bound.as_ref().map(|(key, inclusive)| (Box::new(BinaryValue::from(key.as_ref())), *inclusive))
+        };
+        match key {
+            ValueKey::Int(v) => BinaryValue::Int(*v),
+            ValueKey::Dec(bits) => BinaryValue::Dec(*bits),
+            #[cfg(feature = "rust_decimal")]
+            ValueKey::Decimal(v) => BinaryValue::Decimal(v.serialize()),
+            ValueKey::Str(v) => BinaryValue::Str(v.clone()),
+            ValueKey::Enum(v) => BinaryValue::Enum(v.clone()),
+            ValueKey::Bool(v) => BinaryValue::Bool(*v),
+            ValueKey::Dt(v) => BinaryValue::Dt(v.and_utc().timestamp(), v.and_utc().timestamp_subsec_nanos()),
+            ValueKey::Date(v) => BinaryValue::Date(v.num_days_from_ce()),
+            ValueKey::Time(v) => BinaryValue::Time(v.num_seconds_from_midnight(), v.nanosecond()),
+            ValueKey::Duration(v) => BinaryValue::Duration(v.as_secs(), v.subsec_nanos()),
+            #[cfg(feature = "uuid")]
+            ValueKey::Uuid(v) => BinaryValue::Uuid(*v.as_bytes()),
+            ValueKey::List(items) => BinaryValue::List(items.iter().map(BinaryValue::from).collect()),
+            ValueKey::OneOf(items) => BinaryValue::OneOf(items.iter().map(BinaryValue::from).collect()),
+            ValueKey::Pattern(pattern) => BinaryValue::Pattern(pattern.to_string()),
+            #[cfg(feature = "regex")]
+            ValueKey::Regex(source) => BinaryValue::Regex(source.clone()),
+            ValueKey::Range(lower, upper) => BinaryValue::Range(bound(lower), bound(upper)),
+            ValueKey::Null => BinaryValue::Null,
+        }
+    }
+}
+
+/// Keys fold a `DtTz` into its UTC instant; values keep the offset.
+impl From<&TypedValue> for BinaryValue {
+    fn from(value: &TypedValue) -> Self {
+        let bound = |bound: Option<&RangeBound>| bound.map(|b| (Box::new(BinaryValue::from(&b.value)), b.inclusive));
+        match value {
+            TypedValue::DtTz(v) => {
+                BinaryValue::DtTz(v.timestamp(), v.timestamp_subsec_nanos(), v.offset().local_minus_utc())
+            }
+            TypedValue::List(items) => BinaryValue::List(items.iter().map(BinaryValue::from).collect()),
+            TypedValue::OneOf(items) => BinaryValue::OneOf(items.iter().map(BinaryValue::from).collect()),
+            TypedValue::Range(range) => BinaryValue::Range(bound(range.lower()), bound(range.upper())),
+            value => BinaryValue::from(&ValueKey::from(value)),
+        }
+    }
+}
+
+impl BinaryValue {
+    pub(crate) fn into_value(self) -> Result<TypedValue> {
+        let out_of_range = |what: &str| invalid(format!("{} out of range", what));
+        let bound = |bound: Option<(Box<BinaryValue>, bool)>| -> Result<Option<RangeBound>> {
+            bound.map(|(value, inclusive)| Ok(RangeBound { value: value.into_value()?, inclusive })).transpose()
+        };
+        Ok(match self {
+            BinaryValue::Int(v) => TypedValue::Int(v),
+            BinaryValue::Dec(bits) => TypedValue::Dec(f64::from_bits(bits)),
+            #[cfg(feature = "rust_decimal")]
+            BinaryValue::Decimal(bytes) => TypedValue::Decimal(rust_decimal::Decimal::deserialize(bytes)),
+            #[cfg(not(feature = "rust_decimal"))]
+            BinaryValue::Decimal(_) => return Err(needs_feature("an exact decimal", "rust_decimal")),
+            BinaryValue::Str(v) => TypedValue::Str(v),
+            BinaryValue::Enum(v) => TypedValue::Enum(v),
+            BinaryValue::Bool(v) => TypedValue::Bool(v),
+            BinaryValue::Dt(secs, nanos) => {
+                TypedValue::Dt(DateTime::from_timestamp(secs, nanos).ok_or_else(|| out_of_range("dt"))?.naive_utc())
+            }
+            BinaryValue::DtTz(secs, nanos, offset) => {
+                let offset = FixedOffset::east_opt(offset).ok_or_else(|| out_of_range("dt offset"))?;
+                let utc = DateTime::from_timestamp(secs, nanos).ok_or_else(|| out_of_range("dt"))?;
+                TypedValue::DtTz(utc.with_timezone(&offset))
+            }
+            BinaryValue::Date(days) => {
+                TypedValue::Date(NaiveDate::from_num_days_from_ce_opt(days).ok_or_else(|| out_of_range("date"))?)
+            }
+            BinaryValue::Time(secs, nanos) => TypedValue::Time(
+                NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos).ok_or_else(|| out_of_range("time"))?,
+            ),
+            BinaryValue::Duration(secs, nanos) => {
+                if nanos >= 1_000_000_000 {
+                    return Err(out_of_range("duration"));
+                }
+                TypedValue::Duration(std::time::Duration::new(secs, nanos))
+            }
+            #[cfg(feature = "uuid")]
+            BinaryValue::Uuid(bytes) => TypedValue::Uuid(uuid::Uuid::from_bytes(bytes)),
+            #[cfg(not(feature = "uuid"))]
+            BinaryValue::Uuid(_) => return Err(needs_feature("a uuid", "uuid")),
+            BinaryValue::List(items) => TypedValue::List(into_values(items)?),
+            BinaryValue::OneOf(items) => TypedValue::OneOf(into_values(items)?),
+            BinaryValue::Pattern(text) => {
+                TypedValue::Pattern(StrPattern::parse(&text).ok_or_else(|| invalid(format!("bad pattern '{}'", text)))?)
+            }
+            #[cfg(feature = "regex")]
+            BinaryValue::Regex(source) => TypedValue::Regex(
+                crate::config_value::RegexMatch::new(&source).map_err(|e| invalid(format!("bad regex: {}", e)))?,
+            ),
+            #[cfg(not(feature = "regex"))]
+            BinaryValue::Regex(_) => return Err(needs_feature("a regex", "regex")),
+            BinaryValue::Range(lower, upper) => {
+                TypedValue::Range(Box::new(ValueRange::new(bound(lower)?, bound(upper)?)?))
+            }
+            BinaryValue::Null => TypedValue::Null,
+        })
+    }
+
+    pub(crate) fn into_key(self) -> Result<ValueKey> {
+        Ok(ValueKey::from(&self.into_value()?))
+    }
+}
+
+fn into_values(items: Vec<BinaryValue>) -> Result<Vec<TypedValue>> {
+    items.into_iter().map(BinaryValue::into_value).collect()
+}
+
+#[cfg(not(all(feature = "rust_decimal", feature = "uuid", feature = "regex")))]
+fn needs_feature(what: &str, feature: &str) -> PrecedenceConfigError {
+    invalid(format!("{} value needs the {} feature", what, feature))
+}
+
+fn invalid(reason: String) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "binary config", reason }
+}
+
+#[cfg(all(test, feature = "binary"))]
+mod tests {
+    use super::*;
+    use crate::config_attr::AttrRegistry;
+    use crate::config_envelope::envelope_to_config_values;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::config_resolver::{CompiledConfig, Context, ResolvedConfig, Resolver, TieBreakPolicy};
+    use crate::config_value::AttrRole;
+    use serde_json::json;
+
+    fn compiled() -> CompiledConfig {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.register("total", AttrRole::Match, "int").unwrap();
+        registry.register("starts", AttrRole::Param, "dt").unwrap();
+        registry.set_parents("region", [("berlin".to_string(), "de".to_string())]).unwrap();
+        let matrix = r#"[{ "rank": 1, "region": 1, "total": 1 }, { "rank": 2, "region": 1, "total": 0 }]"#;
+        let envelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [
+                {
+                    "match": { "region": "de", "total": { "gte": 100 } },
+                    "params": [{ "key": "starts", "type": "dt", "value": "2025-08-22T02:00:00+02:00" }],
+                },
+                { "match": { "region": ["de", "fr"] }, "params": [] },
+                { "match": {}, "params": [] },
+            ],
+        }))
+        .unwrap();
+        let values = envelope_to_config_values(&envelope, &registry).unwrap();
+        let rules = matrix_json_to_tall(matrix, 1, &registry).unwrap();
+        let mut resolver = Resolver::new(&rules, &values, &registry).unwrap();
+        resolver.set_tie_break(TieBreakPolicy::LatestCreated).unwrap();
+        resolver.compile()
+    }
+
+    #[test]
+    fn a_loaded_config_resolves_as_the_saved_one() {
+        let compiled = compiled();
+        let loaded = CompiledConfig::from_bytes(&compiled.to_bytes().unwrap()).unwrap();
+        assert_eq!(loaded.tie_break(), &TieBreakPolicy::LatestCreated);
+        assert_eq!(loaded.index_len(), compiled.index_len());
+        for region in ["berlin", "de", "fr", "us"] {
+            for total in [None, Some(99), Some(100)] {
+                let mut context: Context = [("region".to_string(), TypedValue::Str(region.to_string()))].into();
+                context.extend(total.map(|total| ("total".to_string(), TypedValue::Int(total))));
+                let (expected, got) = (compiled.try_resolve(&context).unwrap(), loaded.try_resolve(&context).unwrap());
+                let summary = |r: Option<ResolvedConfig>| {
+                    r.map(|r| (r.match_id, r.rank, r.params.into_iter().map(|p| p.value).collect::<Vec<_>>()))
+                };
+                assert_eq!(summary(got), summary(expected), "{:?}", context);
+            }
+        }
+        // params keep their offset
+        let context: Context =
+            [("region".to_string(), TypedValue::Str("de".to_string())), ("total".to_string(), TypedValue::Int(100))]
+                .into();
+        let starts = &loaded.resolve(&context).unwrap().params[0].value;
+        assert_eq!(starts.to_string(), "2025-08-22T02:00:00+02:00");
+    }
+
+    #[test]
+    fn another_format_version_is_refused() {
+        let mut bytes = compiled().to_bytes().unwrap();
+        bytes[MAGIC.len()..HEADER_LEN].copy_from_slice(&(BINARY_FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(binary_format_version(&bytes).unwrap(), BINARY_FORMAT_VERSION + 1);
+        let err = CompiledConfig::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            PrecedenceConfigError::BinaryFormatVersion { found, expected: BINARY_FORMAT_VERSION }
+                if found == BINARY_FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn truncated_and_trailing_input_is_refused() {
+        let bytes = compiled().to_bytes().unwrap();
+        for cut in [HEADER_LEN, HEADER_LEN + 1, bytes.len() / 2, bytes.len() - 1] {
+            let err = CompiledConfig::from_bytes(&bytes[..cut]).unwrap_err();
+            assert!(matches!(err, PrecedenceConfigError::InvalidFormat { format: "binary config", .. }), "{}", cut);
+        }
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[0, 0]);
+        let err = CompiledConfig::from_bytes(&trailing).unwrap_err();
+        assert!(err.to_string().contains("2 trailing byte(s)"), "{}", err);
+    }
+
+    #[test]
+    fn a_bad_header_is_refused() {
+        let mut bytes = compiled().to_bytes().unwrap();
+        bytes[0] = b'X';
+        assert!(matches!(binary_format_version(&bytes), Err(PrecedenceConfigError::InvalidFormat { .. })));
+        assert!(CompiledConfig::from_bytes(&bytes).is_err());
+        assert!(binary_format_version(&MAGIC).is_err());
+        assert!(CompiledConfig::from_bytes(&[]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::config_error::Result;
+use crate::config_metrics::{Metrics, MetricsSink};
+use crate::config_resolver::{normalized_context, Context, ResolvedConfig, ValueKey};
+use crate::config_shared::SharedConfig;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A context with its `null` values dropped, by attribute name.
+type CacheKey = Vec<(String, ValueKey)>;
+
+/// An LRU cache of lookups in front of a [`SharedConfig`].
+///
+/// Contexts are keyed in normalized form: `null` values are dropped (they count
+/// as not supplied) and attributes are sorted by name, so two contexts holding
+/// the same values share an entry. The cache empties itself when the shared
+/// config is swapped (see [`SharedConfig::generation`]); [`ResolverCache::clear`]
+/// empties it by hand. Failed lookups are not cached. It can be shared across
+/// threads; lookups that miss resolve outside the lock.
+#[derive(Debug)]
+pub struct ResolverCache {
+    shared: Arc<SharedConfig>,
+    capacity: usize,
+    state: Mutex<CacheState>,
+    metrics: Metrics,
+}
+
+/// Counters since the cache was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room for newer ones.
+    pub evictions: u64,
+    /// Times the cache was emptied, by a config swap or [`ResolverCache::clear`].
+    pub invalidations: u64,
+}
+
+#[derive(Debug)]
+struct CacheState {
+    /// generation of the shared config the entries were resolved against
+    generation: u64,
+    /// key -> (result, tick of its last use)
+    entries: HashMap<CacheKey, (Option<ResolvedConfig>, u64)>,
+    /// tick of last use -> key, least recently used first
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl ResolverCache {
+    /// A cache of at most `capacity` lookups; a capacity of 0 caches nothing.
+    pub fn new(shared: Arc<SharedConfig>, capacity: usize) -> Self {
+        let state = CacheState {
+            generation: shared.generation(),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        };
+        Self { shared, capacity, state: Mutex::new(state), metrics: Metrics::default() }
+    }
+
+    /// Reports every hit and miss to `sink`; the lookups a miss runs report to
+    /// the sink of the shared config, if it has one.
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Metrics(Some(sink));
+    }
+
+    pub fn shared(&self) -> &Arc<SharedConfig> {
+        &self.shared
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached lookups.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Drops every cached lookup; swaps of the shared config do this on their own.
+    pub fn clear(&self) {
+        self.lock().invalidate();
+    }
+
+    /// [`SharedConfig::resolve`], answered from the cache when it can be.
+    pub fn resolve(&self, context: &Context) -> Option<ResolvedConfig> {
+        self.try_resolve(context).ok().flatten()
+    }
+
+    /// [`CompiledConfig::try_resolve`](crate::config_resolver::CompiledConfig::try_resolve)
+    /// against the current config, answered from the cache when it can be.
+    pub fn try_resolve(&self, context: &Context) -> Result<Option<ResolvedConfig>> {
+        let key = normalized_context(context);
+        // read before loading the config, so the result is at least this recent
+        let generation = self.shared.generation();
+        {
+            let mut state = self.lock();
+            if state.generation != generation {
+                state.invalidate();
+                state.generation = generation;
+            }
+            let hit = state.get(&key);
+            if let Some(sink) = &self.metrics.0 {
+                sink.cache_lookup(hit.is_some());
+            }
+            if let Some(hit) = hit {
+                state.stats.hits += 1;
+                return Ok(hit);
+            }
+            state.stats.misses += 1;
+        }
+
+        let resolved = self.shared.load().try_resolve(context)?;
+        let mut state = self.lock();
+        // a swap in the meantime invalidated what this lookup saw
+        if state.generation == generation && self.capacity > 0 {
+            state.insert(key, resolved.clone(), self.capacity);
+        }
+        Ok(resolved)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        // the state is consistent between statements, so a panic elsewhere leaves it usable
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CacheState {
+    fn get(&mut self, key: &CacheKey) -> Option<Option<ResolvedConfig>> {
+        self.tick += 1;
+        let (resolved, last_used) = self.entries.get_mut(key)?;
+        let key = self.recency.remove(last_used).expect("every entry has a recency tick");
+        *last_used = self.tick;
+        self.recency.insert(self.tick, key);
+        Some(resolved.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, resolved: Option<ResolvedConfig>, capacity: usize) {
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (resolved, self.tick)) {
+            // another thread cached the same context while this one resolved it
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(self.tick, key);
+        while self.entries.len() > capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.stats.invalidations += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::TypedValue;
+    use crate::test_fixtures::{eu_compiled, region_context};
+
+    fn shared(limit: i64) -> SharedConfig {
+        SharedConfig::new(eu_compiled(limit))
+    }
+
+    fn limit(cache: &ResolverCache, context: &Context) -> Option<TypedValue> {
+        cache.resolve(context).map(|r| r.params[0].value.clone())
+    }
+
+    #[test]
+    fn contexts_with_the_same_values_share_an_entry() {
+        let cache = ResolverCache::new(Arc::new(shared(5)), 4);
+        assert_eq!(limit(&cache, &region_context("eu")), Some(TypedValue::Int(5)));
+        let mut with_null = region_context("eu");
+        with_null.insert("channel".to_string(), TypedValue::Null);
+        assert_eq!(limit(&cache, &with_null), Some(TypedValue::Int(5)));
+        // lookups that find nothing are cached too
+        assert_eq!(limit(&cache, &region_context("us")), None);
+        assert_eq!(limit(&cache, &region_context("us")), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2, evictions: 0, invalidations: 0 });
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let cache = ResolverCache::new(Arc::new(shared(5)), 2);
+        cache.resolve(&region_context("eu"));
+        cache.resolve(&region_context("us"));
+        // eu is now more recent than us
+        cache.resolve(&region_context("eu"));
+        cache.resolve(&region_context("jp"));
+        assert_eq!((cache.len(), cache.stats().evictions), (2, 1));
+        cache.resolve(&region_context("eu"));
+        assert_eq!(cache.stats().hits, 2);
+        cache.resolve(&region_context("us"));
+        assert_eq!(cache.stats().misses, 4);
+
+        let uncached = ResolverCache::new(Arc::new(shared(5)), 0);
+        assert_eq!(limit(&uncached, &region_context("eu")), Some(TypedValue::Int(5)));
+        assert!(uncached.is_empty());
+    }
+
+    #[test]
+    fn a_swap_or_clear_empties_the_cache() {
+        let cache = ResolverCache::new(Arc::new(shared(5)), 4);
+        assert_eq!(limit(&cache, &region_context("eu")), Some(TypedValue::Int(5)));
+        cache.shared().store(eu_compiled(6));
+        assert_eq!(limit(&cache, &region_context("eu")), Some(TypedValue::Int(6)));
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2, evictions: 0, invalidations: 2 });
+    }
+}
@@ -0,0 +1,135 @@
+//! Canonical JSON: object keys sorted (by UTF-8 bytes), no insignificant
+//! whitespace, numbers in serde_json's shortest round-trip form. Array order is
+//! kept, so two envelopes hash the same only if their rows and params are in
+//! the same order.
+
+use crate::config_error::{PrecedenceConfigError, Result};
+#[cfg(feature = "hash")]
+use crate::config_types::{ConfigEnvelope, ConfigStatus};
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(feature = "hash")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "hash")]
+use std::fmt::Write;
+
+/// Serializes `value` as canonical JSON.
+pub fn to_canonical_json<T: Serialize>(value: &T) -> Result<String> {
+    let value = serde_json::to_value(value).map_err(PrecedenceConfigError::InvalidJson)?;
+    let mut out = String::new();
+    write_canonical(&value, &mut out);
+    Ok(out)
+}
+
+/// Lowercase hex SHA-256 of the envelope's canonical JSON; equal hashes mean identical config versions.
+/// The lifecycle `status`, the audit fields and the `schema_version` are not content
+/// and are left out, so publishing, re-stamping or migrating a version keeps the hash.
+#[cfg(feature = "hash")]
+pub fn content_hash(envelope: &ConfigEnvelope) -> String {
+    let mut envelope = envelope.clone();
+    envelope.config.status = ConfigStatus::Draft;
+    envelope.config.audit = None;
+    for row in &mut envelope.rows {
+        row.audit = None;
+    }
+    let mut value = serde_json::to_value(&envelope).expect("envelope serializes to JSON");
+    if let Value::Object(fields) = &mut value {
+        fields.remove("schema_version");
+    }
+    let mut json = String::new();
+    write_canonical(&value, &mut json);
+    sha256_hex(json.as_bytes())
+}
+
+#[cfg(feature = "hash")]
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+        // scalars: serde_json's compact form is already canonical
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(feature = "hash")]
+    fn envelope(limit: i64) -> ConfigEnvelope {
+        serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [{
+                "match": { "region": "eu", "channel": "web", "tier": null },
+                "params": [{ "key": "limit", "type": "int", "value": limit }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn keys_are_sorted_at_every_level() {
+        let value = json!({ "b": [{ "z": 1, "a": "x\"y" }], "a": { "d": 1.5, "c": null } });
+        assert_eq!(to_canonical_json(&value).unwrap(), r#"{"a":{"c":null,"d":1.5},"b":[{"a":"x\"y","z":1}]}"#);
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn hashes_are_sha256_hex() {
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(content_hash(&envelope(5)).len(), 64);
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn the_hash_follows_content_only() {
+        let base = content_hash(&envelope(5));
+        // match attrs live in a HashMap; the hash must not depend on its order
+        for _ in 0..8 {
+            assert_eq!(content_hash(&envelope(5)), base);
+        }
+        assert_ne!(content_hash(&envelope(6)), base);
+
+        let mut restamped = envelope(5);
+        restamped.config.status = ConfigStatus::Published;
+        restamped.config.audit = serde_json::from_value(json!({ "created_by": "ops" })).unwrap();
+        restamped.rows[0].audit = restamped.config.audit.clone();
+        restamped.schema_version += 1;
+        assert_eq!(content_hash(&restamped), base);
+
+        let mut renamed = envelope(5);
+        renamed.config.version_name = "v1.1".into();
+        assert_ne!(content_hash(&renamed), base);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_patch::ConfigPatch;
+use crate::config_types::{is_effective_at, AuditInfo, ConfigStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Append-only record of the changes made to the configs of a
+/// [`ConfigStore`](crate::config_store::ConfigStore), oldest first.
+///
+/// The log lives with the store in memory and is not part of its storage; it
+/// serializes as a JSON list of [`ChangeEntry`] for audit reports.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ChangeLog {
+    entries: Vec<ChangeEntry>,
+}
+
+/// One mutation, stamped with the store's actor (see
+/// [`ConfigStore::set_actor`](crate::config_store::ConfigStore::set_actor)).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    /// 1, 2, .. in the order the changes were made
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub name: String,
+    /// `None` for changes to the config as a whole.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+/// What a [`ChangeEntry`] did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    ConfigCreated,
+    ConfigRemoved,
+    VersionCreated,
+    EnvelopeInserted,
+    OverlayInserted { based_on: i32 },
+    /// The version was replaced by its stored copy.
+    VersionReloaded,
+    VersionRemoved,
+    RulesSet,
+    /// Rows by match_id.
+    ValuesSet { added: Vec<i32>, changed: Vec<i32>, removed: Vec<i32> },
+    VersionRenamed { from: String, to: String },
+    ValiditySet { valid_from: Option<DateTime<Utc>>, valid_to: Option<DateTime<Utc>> },
+    StatusChanged { from: ConfigStatus, to: ConfigStatus },
+    PatchApplied { patch: ConfigPatch },
+    /// The version was put back as it was in a snapshot.
+    RolledBack { snapshot_id: u64 },
+}
+
+/// Filter for [`ChangeLog::query`]; every field left `None` matches all entries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangeQuery {
+    pub name: Option<String>,
+    pub version: Option<i32>,
+    /// Entries at or after this instant.
+    pub from: Option<DateTime<Utc>>,
+    /// Entries before this instant.
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ChangeQuery {
+    pub fn matches(&self, entry: &ChangeEntry) -> bool {
+        self.name.as_ref().is_none_or(|name| name == &entry.name)
+            && self.version.is_none_or(|version| entry.version == Some(version))
+            && is_effective_at(self.from, self.to, entry.at)
+    }
+}
+
+impl ChangeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[ChangeEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries matching `query`, oldest first.
+    pub fn query<'a>(&'a self, query: &'a ChangeQuery) -> impl Iterator<Item = &'a ChangeEntry> + 'a {
+        self.entries.iter().filter(move |entry| query.matches(entry))
+    }
+
+    /// The entries matching `query` as a pretty-printed JSON list.
+    pub fn to_json(&self, query: &ChangeQuery) -> Result<String> {
+        serde_json::to_string_pretty(&self.query(query).collect::<Vec<_>>()).map_err(PrecedenceConfigError::InvalidJson)
+    }
+
+    /// Appends an entry stamped with `audit`'s actor and reason, at `audit.created_at`
+    /// or now.
+    pub(crate) fn record(&mut self, name: &str, version: Option<i32>, audit: AuditInfo, change: Change) {
+        self.entries.push(ChangeEntry {
+            seq: self.entries.len() as u64 + 1,
+            at: audit.created_at.unwrap_or_else(Utc::now),
+            actor: audit.created_by,
+            reason: audit.reason,
+            name: name.to_string(),
+            version,
+            change,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_store::ConfigStore;
+    use crate::test_fixtures::region_registry;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 9, 1, hour, 0, 0).unwrap()
+    }
+
+    fn log() -> ChangeLog {
+        let mut log = ChangeLog::new();
+        let audit = |hour, by: &str| AuditInfo {
+            created_by: Some(by.to_string()),
+            created_at: Some(at(hour)),
+            reason: None,
+        };
+        log.record("a", None, audit(1, "alice"), Change::ConfigCreated);
+        log.record("a", Some(1), audit(2, "alice"), Change::VersionCreated);
+        log.record("b", Some(1), audit(3, "bob"), Change::RulesSet);
+        log.record("a", Some(2), audit(4, "bob"), Change::VersionRemoved);
+        log
+    }
+
+    fn seqs(log: &ChangeLog, query: &ChangeQuery) -> Vec<u64> {
+        log.query(query).map(|entry| entry.seq).collect()
+    }
+
+    #[test]
+    fn queries_filter_by_name_version_and_time() {
+        let log = log();
+        assert_eq!(seqs(&log, &ChangeQuery::default()), [1, 2, 3, 4]);
+        assert_eq!(seqs(&log, &ChangeQuery { name: Some("a".to_string()), ..Default::default() }), [1, 2, 4]);
+        assert_eq!(seqs(&log, &ChangeQuery { version: Some(1), ..Default::default() }), [2, 3]);
+        assert_eq!(seqs(&log, &ChangeQuery { from: Some(at(2)), to: Some(at(4)), ..Default::default() }), [2, 3]);
+    }
+
+    #[test]
+    fn entries_serialize_flat_with_their_change() {
+        let log = log();
+        let query = ChangeQuery { name: Some("b".to_string()), ..Default::default() };
+        let json: serde_json::Value = serde_json::from_str(&log.to_json(&query).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "seq": 3,
+                "at": "2025-09-01T03:00:00Z",
+                "actor": "bob",
+                "name": "b",
+                "version": 1,
+                "change": "rules_set",
+            }])
+        );
+        let back: ChangeLog = serde_json::from_value(serde_json::to_value(&log).unwrap()).unwrap();
+        assert_eq!(back, log);
+    }
+
+    #[test]
+    fn the_store_logs_each_mutation() {
+        let mut store = ConfigStore::new(region_registry());
+        store.set_actor(Some("alice"), None);
+        store.create_config("c").unwrap();
+        store.create_version("c", 1, "v1", Vec::new(), Vec::new()).unwrap();
+        store.set_version_name("c", 1, "first").unwrap();
+        assert!(store.set_version_name("c", 2, "missing").is_err());
+        store.submit_for_review("c", 1).unwrap();
+        let changes: Vec<&Change> = store.changelog().entries().iter().map(|entry| &entry.change).collect();
+        assert_eq!(
+            changes,
+            [
+                &Change::ConfigCreated,
+                &Change::VersionCreated,
+                &Change::VersionRenamed { from: "v1".to_string(), to: "first".to_string() },
+                &Change::StatusChanged { from: ConfigStatus::Draft, to: ConfigStatus::Review },
+            ]
+        );
+        assert!(store.changelog().entries().iter().all(|entry| entry.actor.as_deref() == Some("alice")));
+    }
+}
@@ -0,0 +1,332 @@
+//! Warnings for configs that still use deprecated attributes (see
+//! [`AttrMeta::deprecated`](crate::config_value::AttrMeta::deprecated)), and
+//! migrating them onto the replacements.
+
+use crate::config_attr::{AttrName, AttrRegistry};
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatrixRow};
+use crate::config_rename::RenameSummary;
+use crate::config_types::ConfigEnvelope;
+use crate::config_value::AttrMeta;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+/// Where a deprecated attribute is used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeprecatedUse {
+    /// Match key of the envelope rows at these indexes.
+    MatchKey { rows: Vec<usize> },
+    /// Param key of the envelope rows at these indexes.
+    ParamKey { rows: Vec<usize> },
+    /// Precedence cell (exact or ignore) at these ranks.
+    Precedence { ranks: Vec<i32> },
+}
+
+/// A use of a deprecated attribute: a warning, the config still loads.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeprecationWarning {
+    pub attr_id: i32,
+    pub attr_name: String,
+    /// The attribute [`migrate_deprecated`] rewrites it to, see
+    /// [`AttrRegistry::replacement_of`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(flatten)]
+    pub used: DeprecatedUse,
+}
+
+impl fmt::Display for DeprecationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Attribute '{}' is deprecated", self.attr_name)?;
+        if let Some(replacement) = &self.replacement {
+            write!(f, ", use '{}' instead", replacement)?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " ({})", note)?;
+        }
+        match &self.used {
+            DeprecatedUse::MatchKey { rows } => write!(f, ": match key of rows {:?}", rows),
+            DeprecatedUse::ParamKey { rows } => write!(f, ": param key of rows {:?}", rows),
+            DeprecatedUse::Precedence { ranks } => write!(f, ": precedence cell at ranks {:?}", ranks),
+        }
+    }
+}
+
+/// Deprecated match and param keys of `envelope`'s rows, by attr_id with match
+/// keys first. Keys `registry` doesn't know are left to validation.
+pub fn envelope_deprecations(envelope: &ConfigEnvelope, registry: &AttrRegistry) -> Vec<DeprecationWarning> {
+    // (attr_id, is a param) -> row indexes
+    let mut uses: BTreeMap<(i32, bool), BTreeSet<usize>> = BTreeMap::new();
+    for (row_index, row) in envelope.rows.iter().enumerate() {
+        let keys = row.match_part.attrs.keys().map(|k| (k, false)).chain(row.params.iter().map(|p| (&p.key, true)));
+        for (key, is_param) in keys {
+            if let Some(meta) = deprecated(registry, key) {
+                uses.entry((meta.attr_id, is_param)).or_default().insert(row_index);
+            }
+        }
+    }
+    uses.into_iter()
+        .map(|((attr_id, is_param), rows)| {
+            let rows = rows.into_iter().collect();
+            let used = if is_param { DeprecatedUse::ParamKey { rows } } else { DeprecatedUse::MatchKey { rows } };
+            warning(registry, attr_id, used)
+        })
+        .collect()
+}
+
+/// Deprecated attributes with a cell in tall precedence `rules`, by attr_id.
+pub fn rule_deprecations(rules: &[ConfigPrecedenceRule], registry: &AttrRegistry) -> Vec<DeprecationWarning> {
+    let mut ranks: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for rule in rules {
+        if registry.get_by_id(rule.attr_id).is_some_and(|meta| meta.deprecated.is_some()) {
+            ranks.entry(rule.attr_id).or_default().insert(rule.rank);
+        }
+    }
+    precedence_warnings(registry, ranks)
+}
+
+/// Deprecated columns of a precedence matrix, by attr_id.
+pub fn matrix_deprecations(matrix: &[MatrixRow], registry: &AttrRegistry) -> Vec<DeprecationWarning> {
+    let mut ranks: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for row in matrix {
+        for (column, _) in &row.attrs {
+            if let Some(meta) = deprecated(registry, column) {
+                ranks.entry(meta.attr_id).or_default().insert(row.rank);
+            }
+        }
+    }
+    precedence_warnings(registry, ranks)
+}
+
+/// Rewrites every deprecated attribute with a replacement to that replacement
+/// (see [`AttrRegistry::replacement_of`]) in the columns of `matrix` and the match
+/// and param keys of the rows of `envelopes`, returning what was rewritten per old
+/// name. The registry is left as it is: the deprecated attributes stay registered,
+/// so configs not migrated yet still load. Tall rules and config values refer to
+/// the attr_id and are best rebuilt from the migrated matrix and envelopes.
+///
+/// Everything is checked before anything changes, so on error all of it is left
+/// as it was. Fails if a replacement leads to no registered attribute, or if a
+/// matrix or envelope row would end up with the same column or key twice.
+pub fn migrate_deprecated(
+    registry: &AttrRegistry,
+    matrix: &mut [MatrixRow],
+    envelopes: &mut [ConfigEnvelope],
+) -> Result<BTreeMap<String, RenameSummary>> {
+    let mut renames: HashMap<&str, AttrName> = HashMap::new();
+    for meta in registry.iter().filter(|m| m.deprecated.as_ref().is_some_and(|d| d.replacement.is_some())) {
+        let broken = || PrecedenceConfigError::InvalidAttributeEdit {
+            op: "migrate",
+            attr_name: meta.attr_name.to_string(),
+            reason: "its replacements lead to no registered attribute".to_string(),
+        };
+        let target = registry.replacement_of(&meta.attr_name).ok_or_else(broken)?;
+        renames.insert(&meta.attr_name, target.attr_name.clone());
+    }
+    let twice = |name: &str, row: String| PrecedenceConfigError::InvalidAttributeEdit {
+        op: "migrate",
+        attr_name: name.to_string(),
+        reason: format!("{} would have '{}' twice", row, renamed(&renames, name)),
+    };
+    for row in matrix.iter() {
+        if let Some(name) = first_repeat(row.attrs.iter().map(|(column, _)| column.as_str()), &renames) {
+            return Err(twice(name, format!("matrix rank {}", row.rank)));
+        }
+    }
+    for envelope in envelopes.iter() {
+        for (row_index, row) in envelope.rows.iter().enumerate() {
+            let repeat = first_repeat(row.match_part.attrs.keys().map(|k| k.as_str()), &renames)
+                .or_else(|| first_repeat(row.params.iter().map(|p| p.key.as_str()), &renames));
+            if let Some(name) = repeat {
+                return Err(twice(name, format!("row {} of '{}'", row_index, envelope.config.name)));
+            }
+        }
+    }
+
+    let mut summaries: BTreeMap<String, RenameSummary> = BTreeMap::new();
+    for (column, _) in matrix.iter_mut().flat_map(|row| &mut row.attrs) {
+        if let Some(target) = renames.get(column.as_str()) {
+            summaries.entry(column.to_string()).or_default().matrix_cells += 1;
+            *column = target.clone();
+        }
+    }
+    for row in envelopes.iter_mut().flat_map(|envelope| &mut envelope.rows) {
+        let keys = row.match_part.attrs.keys();
+        let old: Vec<AttrName> = keys.filter(|k| renames.contains_key(k.as_str())).cloned().collect();
+        for key in old {
+            let value = row.match_part.attrs.remove(&key).expect("key was just listed");
+            row.match_part.attrs.insert(renames[key.as_str()].clone(), value);
+            summaries.entry(key.to_string()).or_default().match_keys += 1;
+        }
+        for param in &mut row.params {
+            if let Some(target) = renames.get(param.key.as_str()) {
+                summaries.entry(param.key.to_string()).or_default().param_keys += 1;
+                param.key = target.clone();
+            }
+        }
+    }
+    Ok(summaries)
+}
+
+fn deprecated<'a>(registry: &'a AttrRegistry, attr_name: &str) -> Option<&'a AttrMeta> {
+    registry.get(attr_name).filter(|meta| meta.deprecated.is_some())
+}
+
+fn warning(registry: &AttrRegistry, attr_id: i32, used: DeprecatedUse) -> DeprecationWarning {
+    let meta = registry.get_by_id(attr_id).expect("attr_id was looked up in registry");
+    DeprecationWarning {
+        attr_id,
+        attr_name: meta.attr_name.to_string(),
+        replacement: registry.replacement_of(&meta.attr_name).map(|target| target.attr_name.to_string()),
+        note: meta.deprecated.as_ref().and_then(|d| d.note.clone()),
+        used,
+    }
+}
+
+fn precedence_warnings(registry: &AttrRegistry, ranks: BTreeMap<i32, BTreeSet<i32>>) -> Vec<DeprecationWarning> {
+    ranks
+        .into_iter()
+        .map(|(attr_id, ranks)| {
+            let ranks = ranks.into_iter().collect();
+            warning(registry, attr_id, DeprecatedUse::Precedence { ranks })
+        })
+        .collect()
+}
+
+fn renamed<'a>(renames: &'a HashMap<&str, AttrName>, name: &'a str) -> &'a str {
+    renames.get(name).map_or(name, |target| target.as_str())
+}
+
+/// A deprecated one of `names` whose migrated name another of them has too.
+fn first_repeat<'a>(
+    names: impl Iterator<Item = &'a str> + Clone,
+    renames: &HashMap<&str, AttrName>,
+) -> Option<&'a str> {
+    let mut seen = HashSet::new();
+    let repeated = renamed(renames, names.clone().find(|&name| !seen.insert(renamed(renames, name)))?);
+    names.filter(|name| renames.contains_key(name)).find(|&name| renamed(renames, name) == repeated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::config_value::{AttrRole, Deprecation};
+    use serde_json::json;
+
+    /// `region` is deprecated for `country`, `limit` for `max_items`, `channel` for nothing.
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        for name in ["region", "channel", "country"] {
+            registry.register(name, AttrRole::Match, "str").unwrap();
+        }
+        for name in ["limit", "max_items"] {
+            registry.register(name, AttrRole::Param, "int").unwrap();
+        }
+        let deprecation = |replacement: Option<&str>, note: Option<&str>| {
+            Some(Deprecation { replacement: replacement.map(str::to_string), note: note.map(str::to_string) })
+        };
+        registry.set_deprecated("region", deprecation(Some("country"), Some("regions were split"))).unwrap();
+        registry.set_deprecated("limit", deprecation(Some("max_items"), None)).unwrap();
+        registry.set_deprecated("channel", deprecation(None, None)).unwrap();
+        registry
+    }
+
+    fn matrix() -> Vec<MatrixRow> {
+        serde_json::from_value(json!([
+            { "rank": 1, "region": 1, "channel": 1 },
+            { "rank": 2, "region": 1, "channel": 0 },
+            { "rank": 3, "country": 1 },
+        ]))
+        .unwrap()
+    }
+
+    fn envelope() -> ConfigEnvelope {
+        serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [
+                { "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 5 }] },
+                { "match": { "channel": "web" }, "params": [] },
+                { "match": { "country": "de" }, "params": [{ "key": "limit", "type": "int", "value": 3 }] },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn uses(warnings: &[DeprecationWarning]) -> Vec<(&str, &DeprecatedUse)> {
+        warnings.iter().map(|w| (w.attr_name.as_str(), &w.used)).collect()
+    }
+
+    #[test]
+    fn envelope_keys_warn_by_attribute_and_role() {
+        let registry = registry();
+        let warnings = envelope_deprecations(&envelope(), &registry);
+        assert_eq!(
+            uses(&warnings),
+            [
+                ("region", &DeprecatedUse::MatchKey { rows: vec![0] }),
+                ("channel", &DeprecatedUse::MatchKey { rows: vec![1] }),
+                ("limit", &DeprecatedUse::ParamKey { rows: vec![0, 2] }),
+            ]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "Attribute 'region' is deprecated, use 'country' instead (regions were split): match key of rows [0]"
+        );
+        assert_eq!(warnings[1].to_string(), "Attribute 'channel' is deprecated: match key of rows [1]");
+    }
+
+    #[test]
+    fn matrix_columns_and_rules_warn_by_rank() {
+        let registry = registry();
+        let expected = [
+            ("region", &DeprecatedUse::Precedence { ranks: vec![1, 2] }),
+            ("channel", &DeprecatedUse::Precedence { ranks: vec![1, 2] }),
+        ];
+        assert_eq!(uses(&matrix_deprecations(&matrix(), &registry)), expected);
+        let matrix = serde_json::to_string(&matrix()).unwrap();
+        let rules = matrix_json_to_tall(&matrix, 1, &registry).unwrap();
+        let warnings = rule_deprecations(&rules, &registry);
+        assert_eq!(uses(&warnings), expected);
+        assert_eq!(warnings[0].replacement.as_deref(), Some("country"));
+    }
+
+    #[test]
+    fn migration_rewrites_names_with_a_replacement() {
+        let registry = registry();
+        let (mut matrix, mut envelopes) = (matrix(), vec![envelope()]);
+        let summaries = migrate_deprecated(&registry, &mut matrix, &mut envelopes).unwrap();
+        assert_eq!(
+            summaries,
+            BTreeMap::from([
+                ("limit".to_string(), RenameSummary { matrix_cells: 0, match_keys: 0, param_keys: 2 }),
+                ("region".to_string(), RenameSummary { matrix_cells: 2, match_keys: 1, param_keys: 0 }),
+            ])
+        );
+        assert_eq!((matrix[0].get("country"), matrix[0].get("channel")), (Some(1), Some(1)));
+        assert_eq!(envelopes[0].rows[0].match_part.attrs["country"], json!("eu"));
+        assert_eq!(envelopes[0].rows[2].params[0].key, "max_items");
+        let remaining = envelope_deprecations(&envelopes[0], &registry);
+        assert_eq!(uses(&remaining), [("channel", &DeprecatedUse::MatchKey { rows: vec![1] })]);
+    }
+
+    #[test]
+    fn a_migration_that_would_repeat_a_key_changes_nothing() {
+        let registry = registry();
+        let mut matrix = matrix();
+        let mut envelopes = vec![envelope()];
+        envelopes[0].rows[2].match_part.attrs.insert("region".into(), json!("eu"));
+        let err = migrate_deprecated(&registry, &mut matrix, &mut envelopes).unwrap_err();
+        assert!(err.to_string().contains("row 2 of 'c' would have 'country' twice"), "{err}");
+        assert_eq!(matrix, self::matrix());
+        assert_eq!(envelopes[0].rows[0], envelope().rows[0]);
+
+        matrix[2].attrs.push(("region".into(), 0));
+        let err = migrate_deprecated(&registry, &mut matrix, &mut []).unwrap_err();
+        assert!(err.to_string().contains("matrix rank 3 would have 'country' twice"), "{err}");
+    }
+}
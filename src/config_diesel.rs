@@ -0,0 +1,264 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_sql::ValueColumns;
+use crate::config_value::{AttrMeta, ConfigValue};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::str::FromStr;
+
+/// Diesel `table!` definitions for the tall tables, named as in `config_postgres`.
+pub mod schema {
+    diesel::table! {
+        config_attr (attr_id) {
+            attr_id -> Int4,
+            attr_name -> Text,
+            attr_role -> Text,
+            data_type -> Text,
+        }
+    }
+
+    diesel::table! {
+        config_row (config_row_id) {
+            config_row_id -> Int4,
+            config_version_id -> Int4,
+        }
+    }
+
+    diesel::table! {
+        config_value (config_row_id, attr_id) {
+            config_row_id -> Int4,
+            attr_id -> Int4,
+            val_int -> Nullable<Int8>,
+            val_dec -> Nullable<Numeric>,
+            val_str -> Nullable<Text>,
+            val_bool -> Nullable<Bool>,
+            val_dt -> Nullable<Timestamp>,
+        }
+    }
+
+    diesel::table! {
+        config_precedence_rule (config_version_id, rank, attr_id) {
+            config_version_id -> Int4,
+            rank -> Int4,
+            attr_id -> Int4,
+            match_type -> Int2,
+        }
+    }
+
+    diesel::joinable!(config_value -> config_row (config_row_id));
+    diesel::allow_tables_to_appear_in_same_query!(config_attr, config_row, config_value, config_precedence_rule);
+}
+
+/// A `config_attr` row. Defaults, enum values and constraints have no columns.
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::config_attr)]
+pub struct AttrRow {
+    pub attr_id: i32,
+    pub attr_name: String,
+    pub attr_role: String,
+    pub data_type: String,
+}
+
+impl From<&AttrMeta> for AttrRow {
+    fn from(meta: &AttrMeta) -> Self {
+        Self {
+            attr_id: meta.attr_id,
+            attr_name: meta.attr_name.to_string(),
+            attr_role: meta.role.as_str().to_string(),
+            data_type: meta.data_type.clone(),
+        }
+    }
+}
+
+impl TryFrom<AttrRow> for AttrMeta {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(row: AttrRow) -> Result<Self> {
+        Ok(AttrMeta {
+            attr_id: row.attr_id,
+            attr_name: row.attr_name.into(),
+            data_type: row.data_type,
+            role: row.attr_role.parse()?,
+            default: None,
+            enum_values: Vec::new(),
+            constraints: None,
+            patterns: false,
+            parents: Default::default(),
+            deprecated: None,
+            aliases: Vec::new(),
+        })
+    }
+}
+
+/// A `config_precedence_rule` row; `match_type` is the 0/1 MATCH_TYPE.
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::config_precedence_rule)]
+pub struct RuleRow {
+    pub config_version_id: i32,
+    pub rank: i32,
+    pub attr_id: i32,
+    pub match_type: i16,
+}
+
+impl From<&ConfigPrecedenceRule> for RuleRow {
+    fn from(rule: &ConfigPrecedenceRule) -> Self {
+        Self {
+            config_version_id: rule.config_version_id,
+            rank: rule.rank,
+            attr_id: rule.attr_id,
+            match_type: u8::from(rule.match_type) as i16,
+        }
+    }
+}
+
+impl TryFrom<RuleRow> for ConfigPrecedenceRule {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(row: RuleRow) -> Result<Self> {
+        let match_type = u8::try_from(row.match_type)
+            .ok()
+            .and_then(|raw| MatchType::try_from(raw).ok())
+            .ok_or(PrecedenceConfigError::InvalidMatchType {
+                rank: row.rank,
+                attr: AttrRef::Id(row.attr_id),
+                match_type: row.match_type.clamp(0, u8::MAX as i16) as u8,
+            })?;
+        Ok(ConfigPrecedenceRule {
+            config_version_id: row.config_version_id,
+            rank: row.rank,
+            attr_id: row.attr_id,
+            match_type,
+        })
+    }
+}
+
+/// A `config_value` row: the value split into its typed columns by [`ValueColumns`],
+/// with `config_row_id` standing in for the value's `match_id`.
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = schema::config_value)]
+pub struct ValueRow {
+    pub config_row_id: i32,
+    pub attr_id: i32,
+    pub val_int: Option<i64>,
+    pub val_dec: Option<BigDecimal>,
+    pub val_str: Option<String>,
+    pub val_bool: Option<bool>,
+    pub val_dt: Option<NaiveDateTime>,
+}
+
+impl ValueRow {
+    /// Splits `value` into columns, keyed by its `match_id`.
+    pub fn from_value(value: &ConfigValue) -> Result<Self> {
+        let columns = ValueColumns::from_typed(&value.value);
+        let val_dec = columns
+            .val_dec
+            .map(|raw| {
+                BigDecimal::from_str(&raw).map_err(|e| PrecedenceConfigError::InvalidValue {
+                    key: format!("attr_id {}", value.attr_id),
+                    data_type: "dec".to_string(),
+                    value: raw.clone(),
+                    reason: e.to_string(),
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            config_row_id: value.match_id,
+            attr_id: value.attr_id,
+            val_int: columns.val_int,
+            val_dec,
+            val_str: columns.val_str,
+            val_bool: columns.val_bool,
+            val_dt: columns.val_dt,
+        })
+    }
+
+    /// Types the columns back by the attribute's entry in `registry`, which also supplies the role.
+    pub fn to_value(&self, registry: &AttrRegistry) -> Result<ConfigValue> {
+        let meta = registry.get_by_id(self.attr_id).ok_or(PrecedenceConfigError::UnknownValueAttr {
+            match_id: self.config_row_id,
+            attr_id: self.attr_id,
+        })?;
+        let columns = ValueColumns {
+            val_int: self.val_int,
+            val_dec: self.val_dec.as_ref().map(BigDecimal::to_plain_string),
+            val_str: self.val_str.clone(),
+            val_bool: self.val_bool,
+            val_dt: self.val_dt,
+        };
+        Ok(ConfigValue {
+            match_id: self.config_row_id,
+            attr_id: self.attr_id,
+            role: meta.role,
+            value: columns.to_typed(meta)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::{AttrRole, TypedValue};
+
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.register("rate", AttrRole::Param, "dec").unwrap();
+        registry
+    }
+
+    #[test]
+    fn attrs_and_rules_round_trip_through_rows() {
+        let registry = registry();
+        let meta = registry.get_by_id(1).unwrap();
+        let row = AttrRow::from(meta);
+        assert_eq!((row.attr_role.as_str(), row.data_type.as_str()), ("match", "str"));
+        let back = AttrMeta::try_from(row).unwrap();
+        assert_eq!((back.attr_id, back.attr_name.as_str(), back.role), (1, "region", AttrRole::Match));
+
+        let rule = ConfigPrecedenceRule { config_version_id: 3, rank: 2, attr_id: 1, match_type: MatchType::Exact };
+        let row = RuleRow::from(&rule);
+        assert_eq!(row.match_type, 1);
+        assert_eq!(ConfigPrecedenceRule::try_from(row).unwrap(), rule);
+    }
+
+    #[test]
+    fn a_match_type_outside_zero_and_one_is_refused() {
+        let row = RuleRow { config_version_id: 3, rank: 2, attr_id: 1, match_type: 7 };
+        assert!(matches!(
+            ConfigPrecedenceRule::try_from(row),
+            Err(PrecedenceConfigError::InvalidMatchType { rank: 2, attr: AttrRef::Id(1), match_type: 7 })
+        ));
+    }
+
+    fn round_trip(value: &ConfigValue, registry: &AttrRegistry) -> (i32, AttrRole, TypedValue) {
+        let back = ValueRow::from_value(value).unwrap().to_value(registry).unwrap();
+        (back.match_id, back.role, back.value)
+    }
+
+    #[test]
+    fn values_round_trip_through_rows() {
+        let registry = registry();
+        let rate = registry.get_by_id(2).unwrap();
+        let value = ConfigValue {
+            match_id: 5,
+            attr_id: 2,
+            role: AttrRole::Param,
+            value: crate::config_value::parse_str_value(rate, "0.125").unwrap(),
+        };
+        let row = ValueRow::from_value(&value).unwrap();
+        let val_dec = row.val_dec.as_ref().map(BigDecimal::to_plain_string);
+        assert_eq!((row.config_row_id, val_dec.as_deref()), (5, Some("0.125")));
+        assert_eq!(round_trip(&value, &registry), (5, AttrRole::Param, value.value.clone()));
+
+        let eu = TypedValue::Str("eu".into());
+        let region = ConfigValue { match_id: 5, attr_id: 1, role: AttrRole::Match, value: eu.clone() };
+        assert_eq!(round_trip(&region, &registry), (5, AttrRole::Match, eu));
+        let unknown = ValueRow { attr_id: 9, ..ValueRow::from_value(&region).unwrap() };
+        assert!(matches!(
+            unknown.to_value(&registry),
+            Err(PrecedenceConfigError::UnknownValueAttr { match_id: 5, attr_id: 9 })
+        ));
+    }
+}
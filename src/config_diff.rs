@@ -0,0 +1,502 @@
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_types::{AuditInfo, ConfigEnvelope, ConfigMeta, ConfigRow, Param};
+use crate::config_value::{parse_param_value, TypedValue};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A row's match attributes, sorted by attribute name.
+pub type MatchTuple = BTreeMap<String, serde_json::Value>;
+
+/// What changed between two versions of a `ConfigEnvelope`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    /// `(old, new)` when the config name/version metadata differs.
+    pub config: Option<(ConfigMeta, ConfigMeta)>,
+    pub added_rows: Vec<RowRef>,
+    pub removed_rows: Vec<RowRef>,
+    pub changed_rows: Vec<RowChange>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.config.is_none()
+            && self.added_rows.is_empty()
+            && self.removed_rows.is_empty()
+            && self.changed_rows.is_empty()
+    }
+}
+
+/// A row present on only one side, by its index in that envelope.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowRef {
+    pub index: usize,
+    pub match_tuple: MatchTuple,
+    /// The row's params as written in that envelope.
+    pub params: Vec<Param>,
+    pub audit: Option<AuditInfo>,
+}
+
+/// A row present on both sides whose params or match tuple changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowChange {
+    pub old_index: usize,
+    pub new_index: usize,
+    pub old_match: MatchTuple,
+    pub new_match: MatchTuple,
+    pub params: Vec<ParamChange>,
+    /// The rows' audit fields; a change to these alone does not make a `RowChange`.
+    pub old_audit: Option<AuditInfo>,
+    pub new_audit: Option<AuditInfo>,
+}
+
+impl RowChange {
+    pub fn match_changed(&self) -> bool {
+        self.old_match != self.new_match
+    }
+}
+
+/// One param that was added (`old = None`), removed (`new = None`) or changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamChange {
+    pub key: String,
+    pub old: Option<TypedValue>,
+    pub new: Option<TypedValue>,
+}
+
+/// Diffs two envelopes row by row.
+///
+/// Rows are paired by identical match tuple first, and their params compared
+/// as typed values. Rows left over on both sides with identical params are
+/// paired next and reported as a match tuple change; anything still unpaired
+/// is added/removed. A param value that doesn't parse as its declared type is
+/// compared as its raw JSON text.
+pub fn diff_envelopes(old: &ConfigEnvelope, new: &ConfigEnvelope) -> ConfigDiff {
+    let old_rows: Vec<IndexedRow> = old.rows.iter().enumerate().map(IndexedRow::new).collect();
+    let new_rows: Vec<IndexedRow> = new.rows.iter().enumerate().map(IndexedRow::new).collect();
+
+    let mut diff = ConfigDiff::default();
+    if old.config != new.config {
+        diff.config = Some((old.config.clone(), new.config.clone()));
+    }
+
+    // Pass 1: same match tuple
+    let mut new_by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, row) in new_rows.iter().enumerate().rev() {
+        new_by_key.entry(row.key.clone()).or_default().push(i);
+    }
+    let mut new_paired = vec![false; new_rows.len()];
+    let mut old_unpaired = Vec::new();
+    for old_row in &old_rows {
+        match new_by_key.get_mut(&old_row.key).and_then(|candidates| candidates.pop()) {
+            Some(i) => {
+                new_paired[i] = true;
+                let params = diff_params(&old_row.params, &new_rows[i].params);
+                if !params.is_empty() {
+                    diff.changed_rows.push(RowChange::between(old_row, &new_rows[i], params));
+                }
+            }
+            None => old_unpaired.push(old_row),
+        }
+    }
+
+    // Pass 2: same params, different match tuple
+    for old_row in old_unpaired {
+        let candidate = new_rows
+            .iter()
+            .enumerate()
+            .find(|(i, new_row)| !new_paired[*i] && new_row.params == old_row.params);
+        match candidate {
+            Some((i, new_row)) => {
+                new_paired[i] = true;
+                diff.changed_rows.push(RowChange::between(old_row, new_row, Vec::new()));
+            }
+            None => diff.removed_rows.push(old_row.row_ref()),
+        }
+    }
+
+    for (i, new_row) in new_rows.iter().enumerate() {
+        if !new_paired[i] {
+            diff.added_rows.push(new_row.row_ref());
+        }
+    }
+
+    diff.changed_rows.sort_by_key(|c| c.new_index);
+    diff
+}
+
+struct IndexedRow<'a> {
+    row: &'a ConfigRow,
+    index: usize,
+    match_tuple: MatchTuple,
+    /// canonical JSON of `match_tuple`, for pairing
+    key: String,
+    params: BTreeMap<String, TypedValue>,
+}
+
+impl<'a> IndexedRow<'a> {
+    fn new((index, row): (usize, &'a ConfigRow)) -> Self {
+        let match_tuple: MatchTuple = row.match_part.attrs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        let key = serde_json::to_string(&match_tuple).unwrap_or_default();
+        let params = row
+            .params
+            .iter()
+            .map(|p| {
+                let value = parse_param_value(p).unwrap_or_else(|_| TypedValue::Str(p.value.to_string()));
+                (p.key.to_string(), value)
+            })
+            .collect();
+        Self { row, index, match_tuple, key, params }
+    }
+
+    fn row_ref(&self) -> RowRef {
+        RowRef {
+            index: self.index,
+            match_tuple: self.match_tuple.clone(),
+            params: self.row.params.clone(),
+            audit: self.row.audit.clone(),
+        }
+    }
+}
+
+impl RowChange {
+    fn between(old: &IndexedRow, new: &IndexedRow, params: Vec<ParamChange>) -> Self {
+        Self {
+            old_index: old.index,
+            new_index: new.index,
+            old_match: old.match_tuple.clone(),
+            new_match: new.match_tuple.clone(),
+            params,
+            old_audit: old.row.audit.clone(),
+            new_audit: new.row.audit.clone(),
+        }
+    }
+}
+
+fn diff_params(old: &BTreeMap<String, TypedValue>, new: &BTreeMap<String, TypedValue>) -> Vec<ParamChange> {
+    let mut changes = Vec::new();
+
+    for (key, old_value) in old {
+        match new.get(key) {
+            Some(new_value) if new_value == old_value => {}
+            new_value => changes.push(ParamChange {
+                key: key.clone(),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            }),
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            changes.push(ParamChange { key: key.clone(), old: None, new: Some(new_value.clone()) });
+        }
+    }
+
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+/// Layout of [`render_diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// One line per change.
+    #[default]
+    Text,
+    /// One bullet per change, tuples and keys as code, for tickets and chat.
+    Markdown,
+}
+
+/// Summarizes a diff for people, one change per line: the metadata, then
+/// removed, changed and added rows, e.g.
+/// `row {channel: web, country: DE}: discount_pct 0.1 → 0.125`.
+pub fn render_diff(diff: &ConfigDiff, format: ReportFormat) -> String {
+    let code = |text: &str| match format {
+        ReportFormat::Text => text.to_string(),
+        ReportFormat::Markdown => format!("`{}`", text),
+    };
+    let mut lines = Vec::new();
+
+    if let Some((old, new)) = &diff.config {
+        let mut fields = Vec::new();
+        let mut field = |name: &str, old: String, new: String| {
+            if old != new {
+                fields.push(format!("{} {} → {}", code(name), old, new));
+            }
+        };
+        let instant = |at: &Option<chrono::DateTime<chrono::Utc>>| at.map_or("unset".to_string(), |at| at.to_rfc3339());
+        field("name", old.name.clone(), new.name.clone());
+        field("version", old.version.to_string(), new.version.to_string());
+        field("version_name", old.version_name.clone(), new.version_name.clone());
+        field("status", old.status.to_string(), new.status.to_string());
+        field("valid_from", instant(&old.valid_from), instant(&new.valid_from));
+        field("valid_to", instant(&old.valid_to), instant(&new.valid_to));
+        if old.audit != new.audit {
+            fields.push(format!("{} changed", code("audit")));
+        }
+        lines.push(format!("config: {}", fields.join(", ")));
+    }
+    for row in &diff.removed_rows {
+        lines.push(format!("removed row {}", code(&render_tuple(&row.match_tuple))));
+    }
+    for change in &diff.changed_rows {
+        let mut row = code(&render_tuple(&change.old_match));
+        if change.match_changed() {
+            row = format!("{} → {}", row, code(&render_tuple(&change.new_match)));
+            if change.params.is_empty() {
+                lines.push(format!("row {}: match changed", row));
+            }
+        }
+        for param in &change.params {
+            let key = code(&param.key);
+            lines.push(match (&param.old, &param.new) {
+                (Some(old), Some(new)) => format!("row {}: {} {} → {}", row, key, old, new),
+                (None, Some(new)) => format!("row {}: {} added = {}", row, key, new),
+                (Some(old), None) => format!("row {}: {} removed (was {})", row, key, old),
+                (None, None) => continue,
+            });
+        }
+    }
+    for row in &diff.added_rows {
+        let params: Vec<String> = row
+            .params
+            .iter()
+            .map(|p| {
+                let value = parse_param_value(p).map_or_else(|_| p.value.to_string(), |value| value.to_string());
+                format!("{} = {}", code(&p.key), value)
+            })
+            .collect();
+        let tuple = code(&render_tuple(&row.match_tuple));
+        lines.push(if params.is_empty() {
+            format!("added row {}", tuple)
+        } else {
+            format!("added row {}: {}", tuple, params.join(", "))
+        });
+    }
+
+    if lines.is_empty() {
+        lines.push("no changes".to_string());
+    }
+    let prefix = if format == ReportFormat::Markdown { "- " } else { "" };
+    lines.iter().map(|line| format!("{}{}\n", prefix, line)).collect()
+}
+
+/// `{channel: web, country: DE}`: strings bare, other values as JSON; `{}` for the default row.
+pub(crate) fn render_tuple(match_tuple: &MatchTuple) -> String {
+    let attrs: Vec<String> = match_tuple
+        .iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(s) => format!("{}: {}", name, s),
+            other => format!("{}: {}", name, other),
+        })
+        .collect();
+    format!("{{{}}}", attrs.join(", "))
+}
+
+/// What changed between two versions of a precedence table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PrecedenceDiff {
+    pub added_ranks: Vec<i32>,
+    pub removed_ranks: Vec<i32>,
+    /// Per-(rank, attr) changes within ranks present on both sides.
+    pub cell_changes: Vec<MatchTypeChange>,
+}
+
+impl PrecedenceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_ranks.is_empty() && self.removed_ranks.is_empty() && self.cell_changes.is_empty()
+    }
+}
+
+/// A (rank, attr) cell whose MATCH_TYPE flipped, or that only one side has (`None`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatchTypeChange {
+    pub rank: i32,
+    pub attr_id: i32,
+    pub old: Option<MatchType>,
+    pub new: Option<MatchType>,
+}
+
+impl MatchTypeChange {
+    /// Both sides have the cell and its MATCH_TYPE differs.
+    pub fn is_flip(&self) -> bool {
+        self.old.is_some() && self.new.is_some()
+    }
+}
+
+/// Diffs two tall precedence tables (convert matrix input with `matrix_json_to_tall` first).
+///
+/// `config_version_id` is ignored, so two versions of the same table compare
+/// cell for cell. Changes are ordered by rank, then attr_id.
+pub fn diff_precedence(old: &[ConfigPrecedenceRule], new: &[ConfigPrecedenceRule]) -> PrecedenceDiff {
+    let old_cells = cells_by_rank(old);
+    let new_cells = cells_by_rank(new);
+
+    let mut diff = PrecedenceDiff::default();
+    for (rank, old_attrs) in &old_cells {
+        let Some(new_attrs) = new_cells.get(rank) else {
+            diff.removed_ranks.push(*rank);
+            continue;
+        };
+
+        let attr_ids: BTreeSet<i32> = old_attrs.keys().chain(new_attrs.keys()).copied().collect();
+        for attr_id in attr_ids {
+            let old_type = old_attrs.get(&attr_id).copied();
+            let new_type = new_attrs.get(&attr_id).copied();
+            if old_type != new_type {
+                diff.cell_changes.push(MatchTypeChange { rank: *rank, attr_id, old: old_type, new: new_type });
+            }
+        }
+    }
+    diff.added_ranks = new_cells.keys().filter(|rank| !old_cells.contains_key(rank)).copied().collect();
+    diff
+}
+
+fn cells_by_rank(rules: &[ConfigPrecedenceRule]) -> BTreeMap<i32, BTreeMap<i32, MatchType>> {
+    let mut cells: BTreeMap<i32, BTreeMap<i32, MatchType>> = BTreeMap::new();
+    for rule in rules {
+        cells.entry(rule.rank).or_default().insert(rule.attr_id, rule.match_type);
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn envelope(version: i32, rows: Value) -> ConfigEnvelope {
+        serde_json::from_value(json!({
+            "config": { "name": "c", "version": version, "version_name": format!("v{version}") },
+            "rows": rows,
+        }))
+        .unwrap()
+    }
+
+    fn limit(value: Value) -> Value {
+        json!([{ "key": "limit", "type": "int", "value": value }])
+    }
+
+    #[test]
+    fn identical_envelopes_have_an_empty_diff() {
+        let rows = json!([{ "match": { "region": "eu" }, "params": limit(json!(5)) }]);
+        assert!(diff_envelopes(&envelope(1, rows.clone()), &envelope(1, rows)).is_empty());
+    }
+
+    #[test]
+    fn rows_pair_by_match_tuple_and_compare_typed_params() {
+        let old = envelope(
+            1,
+            json!([
+                { "match": { "region": "eu" }, "params": limit(json!(5)) },
+                { "match": { "region": "us" }, "params": limit(json!(7)) },
+                { "match": { "region": "apac" }, "params": limit(json!(1)) },
+            ]),
+        );
+        let new = envelope(
+            2,
+            json!([
+                // "5" and 5 are the same int
+                { "match": { "region": "eu" }, "params": limit(json!("5")) },
+                { "match": { "region": "us" }, "params": limit(json!(8)) },
+                { "match": { "region": "latam" }, "params": limit(json!(2)) },
+            ]),
+        );
+        let diff = diff_envelopes(&old, &new);
+        assert_eq!(diff.config.as_ref().map(|(old, new)| (old.version, new.version)), Some((1, 2)));
+
+        assert_eq!(diff.changed_rows.len(), 1);
+        let change = &diff.changed_rows[0];
+        assert_eq!((change.old_index, change.new_index, change.match_changed()), (1, 1, false));
+        assert_eq!(
+            change.params,
+            vec![ParamChange { key: "limit".into(), old: Some(TypedValue::Int(7)), new: Some(TypedValue::Int(8)) }]
+        );
+        assert_eq!(diff.removed_rows.iter().map(|r| r.index).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(diff.added_rows[0].match_tuple["region"], json!("latam"));
+    }
+
+    #[test]
+    fn a_row_with_the_same_params_and_a_new_match_is_a_match_change() {
+        let old = envelope(1, json!([{ "match": { "region": "eu" }, "params": limit(json!(5)) }]));
+        let new = envelope(1, json!([{ "match": { "region": "eu", "channel": "web" }, "params": limit(json!(5)) }]));
+        let diff = diff_envelopes(&old, &new);
+        assert!(diff.added_rows.is_empty() && diff.removed_rows.is_empty());
+        let change = &diff.changed_rows[0];
+        assert!(change.match_changed());
+        assert!(change.params.is_empty());
+        assert_eq!(change.new_match.get("channel"), Some(&json!("web")));
+    }
+
+    #[test]
+    fn added_and_removed_params_have_one_side() {
+        let old = envelope(1, json!([{ "match": {}, "params": limit(json!(5)) }]));
+        let new = envelope(1, json!([{ "match": {}, "params": [{ "key": "tier", "type": "str", "value": "gold" }] }]));
+        let params = &diff_envelopes(&old, &new).changed_rows[0].params;
+        let sides: Vec<(&str, bool, bool)> =
+            params.iter().map(|p| (p.key.as_str(), p.old.is_some(), p.new.is_some())).collect();
+        assert_eq!(sides, vec![("limit", true, false), ("tier", false, true)]);
+    }
+
+    fn rule(config_version_id: i32, rank: i32, attr_id: i32, match_type: MatchType) -> ConfigPrecedenceRule {
+        ConfigPrecedenceRule { config_version_id, rank, attr_id, match_type }
+    }
+
+    #[test]
+    fn precedence_diffs_report_ranks_and_flips() {
+        use MatchType::{Exact, Ignore};
+        let old = [rule(1, 1, 1, Exact), rule(1, 1, 2, Exact), rule(1, 2, 1, Exact), rule(1, 2, 2, Ignore)];
+        let new = [rule(2, 1, 1, Exact), rule(2, 1, 2, Ignore), rule(2, 3, 1, Ignore), rule(2, 3, 3, Exact)];
+        let diff = diff_precedence(&old, &new);
+        assert_eq!((diff.added_ranks.clone(), diff.removed_ranks.clone()), (vec![3], vec![2]));
+        let flip = MatchTypeChange { rank: 1, attr_id: 2, old: Some(Exact), new: Some(Ignore) };
+        assert_eq!(diff.cell_changes, vec![flip]);
+        assert!(diff.cell_changes[0].is_flip());
+    }
+
+    #[test]
+    fn precedence_diffs_ignore_the_version_and_report_one_sided_cells() {
+        use MatchType::Exact;
+        let old = [rule(1, 1, 1, Exact)];
+        assert!(diff_precedence(&old, &[rule(9, 1, 1, Exact)]).is_empty());
+        let diff = diff_precedence(&old, &[rule(1, 1, 1, Exact), rule(1, 1, 2, Exact)]);
+        let change = &diff.cell_changes[0];
+        assert_eq!((change.attr_id, change.old, change.new, change.is_flip()), (2, None, Some(Exact), false));
+    }
+
+    #[test]
+    fn reports_list_one_change_per_line() {
+        let old = envelope(
+            1,
+            json!([
+                { "match": { "region": "eu", "channel": "web" }, "params": limit(json!(5)) },
+                { "match": { "region": "us" }, "params": limit(json!(7)) },
+                { "match": { "region": "apac" }, "params": limit(json!(1)) },
+            ]),
+        );
+        let new = envelope(
+            2,
+            json!([
+                { "match": { "region": "eu", "channel": "web" }, "params": limit(json!(6)) },
+                { "match": { "region": "us", "tier": 2 }, "params": limit(json!(7)) },
+                { "match": {}, "params": [{ "key": "fee", "type": "dec", "value": "0.5" }] },
+            ]),
+        );
+        let diff = diff_envelopes(&old, &new);
+        let text = "\
+config: version 1 → 2, version_name v1 → v2
+removed row {region: apac}
+row {channel: web, region: eu}: limit 5 → 6
+row {region: us} → {region: us, tier: 2}: match changed
+added row {}: fee = 0.5
+";
+        assert_eq!(render_diff(&diff, ReportFormat::Text), text);
+        let markdown = "\
+- config: `version` 1 → 2, `version_name` v1 → v2
+- removed row `{region: apac}`
+- row `{channel: web, region: eu}`: `limit` 5 → 6
+- row `{region: us}` → `{region: us, tier: 2}`: match changed
+- added row `{}`: `fee` = 0.5
+";
+        assert_eq!(render_diff(&diff, ReportFormat::Markdown), markdown);
+        assert_eq!(render_diff(&ConfigDiff::default(), ReportFormat::Markdown), "- no changes\n");
+    }
+}
@@ -0,0 +1,623 @@
+use crate::config_attr::{AttrName, AttrRegistry};
+use crate::config_deprecation::{envelope_deprecations, DeprecationWarning};
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use crate::config_resolver::WILDCARD;
+use crate::config_types::{
+    check_validity, AuditInfo, ConfigEnvelope, ConfigMeta, ConfigRow, MatchPart, Param, ParamType,
+    ENVELOPE_SCHEMA_VERSION,
+};
+use crate::config_value::{
+    data_type_of, is_pattern_for, is_regex_object, parse_json_value, parse_one_of_value, parse_pattern_value,
+    parse_range_value, parse_regex_value, AttrMeta, AttrRole, ConfigValue, TypedValue,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// A `ConfigEnvelope` whose rows have been checked against the attribute catalog.
+#[derive(Debug, Clone)]
+pub struct ValidatedEnvelope {
+    pub config: ConfigMeta,
+    /// Tall CONFIG_VALUE rows; each envelope row gets `match_id = row index + 1`.
+    pub values: Vec<ConfigValue>,
+    /// match_id -> audit, for the rows that carry one.
+    pub row_audits: BTreeMap<i32, AuditInfo>,
+    /// Deprecated attributes the rows use, see [`envelope_deprecations`].
+    pub warnings: Vec<DeprecationWarning>,
+}
+
+/// Which params the rows of one config must / may carry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigSchema {
+    /// Every row must set these.
+    #[serde(default)]
+    pub required: BTreeSet<String>,
+    /// Rows may set these; any param in neither set is rejected.
+    #[serde(default)]
+    pub optional: BTreeSet<String>,
+}
+
+impl ConfigSchema {
+    pub fn allows(&self, key: &str) -> bool {
+        self.required.contains(key) || self.optional.contains(key)
+    }
+}
+
+/// Knobs for [`validate_envelope_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct EnvelopeOptions {
+    /// When set, rows must carry every required param and nothing outside the schema.
+    pub schema: Option<ConfigSchema>,
+}
+
+/// Checks every match key and param key against `registry` (known attribute,
+/// correct role, value fits `data_type` and the param's constraints, no param
+/// key repeated within a row) and converts them into typed `ConfigValue`s. A
+/// `valid_to` must come after `valid_from`.
+///
+/// A `null` match value becomes `TypedValue::Null` and `"ALL"` stays the string
+/// wildcard whatever the attribute's type; both leave the attribute unconstrained.
+/// An object match value is a regex if it has a `pattern` (see [`parse_regex_value`])
+/// and a range otherwise (see [`parse_range_value`]), and an array
+/// is a set of alternatives unless the attribute has a list type (see
+/// [`parse_one_of_value`]). A string holding a `*` is a pattern when the attribute
+/// takes patterns (see [`parse_pattern_value`]).
+///
+/// Deprecated attributes are not errors; they come back as warnings.
+pub fn validate_envelope(envelope: &ConfigEnvelope, registry: &AttrRegistry) -> Result<ValidatedEnvelope> {
+    validate_envelope_with_options(envelope, registry, &EnvelopeOptions::default())
+}
+
+/// Flattens an envelope into tall CONFIG_VALUE rows: match_id = row index + 1,
+/// match attrs and params typed through `registry`, roles checked. The ingest
+/// path for callers that don't need the config metadata; see [`validate_envelope`].
+pub fn envelope_to_config_values(envelope: &ConfigEnvelope, registry: &AttrRegistry) -> Result<Vec<ConfigValue>> {
+    validate_envelope(envelope, registry).map(|validated| validated.values)
+}
+
+/// Same as [`validate_envelope`], with [`EnvelopeOptions`].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "validate_envelope",
+        level = "debug",
+        skip_all,
+        fields(name = %envelope.config.name, version = envelope.config.version, rows = envelope.rows.len()),
+        err
+    )
+)]
+pub fn validate_envelope_with_options(
+    envelope: &ConfigEnvelope,
+    registry: &AttrRegistry,
+    options: &EnvelopeOptions,
+) -> Result<ValidatedEnvelope> {
+    let meta = &envelope.config;
+    check_validity(&meta.name, meta.version, meta.valid_from, meta.valid_to)?;
+    let mut values = Vec::new();
+    for (row_index, row) in envelope.rows.iter().enumerate() {
+        push_row_values(row_index, row, registry, options, &mut values)?;
+    }
+    Ok(validated(envelope, registry, values))
+}
+
+/// Same as [`validate_envelope_with_options`], but checks every row and reports
+/// the first problem of each bad row instead of stopping at the first bad row.
+pub fn validate_envelope_report(
+    envelope: &ConfigEnvelope,
+    registry: &AttrRegistry,
+    options: &EnvelopeOptions,
+) -> std::result::Result<ValidatedEnvelope, ValidationReport> {
+    let mut report = ValidationReport::collect_all();
+    let meta = &envelope.config;
+    let outcome = check_validity(&meta.name, meta.version, meta.valid_from, meta.valid_to).and_then(|()| {
+        let mut values = Vec::new();
+        for (row_index, row) in envelope.rows.iter().enumerate() {
+            if let Err(err) = push_row_values(row_index, row, registry, options, &mut values) {
+                report.push(err)?;
+            }
+        }
+        Ok(validated(envelope, registry, values))
+    });
+    report.finish(outcome)
+}
+
+/// [`validate_envelope_with_options`] with the rows checked on the rayon thread
+/// pool; values keep row order and the error, if any, is that of the first bad row.
+#[cfg(feature = "parallel")]
+pub fn validate_envelope_par(
+    envelope: &ConfigEnvelope,
+    registry: &AttrRegistry,
+    options: &EnvelopeOptions,
+) -> Result<ValidatedEnvelope> {
+    use rayon::prelude::*;
+
+    let meta = &envelope.config;
+    check_validity(&meta.name, meta.version, meta.valid_from, meta.valid_to)?;
+    let rows: Vec<Result<Vec<ConfigValue>>> = envelope
+        .rows
+        .par_iter()
+        .enumerate()
+        .map(|(row_index, row)| {
+            let mut values = Vec::new();
+            push_row_values(row_index, row, registry, options, &mut values).map(|()| values)
+        })
+        .collect();
+    let mut values = Vec::with_capacity(rows.iter().map(|row| row.as_ref().map_or(0, Vec::len)).sum());
+    for row in rows {
+        values.extend(row?);
+    }
+    Ok(validated(envelope, registry, values))
+}
+
+/// Checks row `row_index` of an envelope and appends its typed values.
+fn push_row_values(
+    row_index: usize,
+    row: &ConfigRow,
+    registry: &AttrRegistry,
+    options: &EnvelopeOptions,
+    values: &mut Vec<ConfigValue>,
+) -> Result<()> {
+    let match_id = row_index as i32 + 1;
+    let at = |field| in_row(row_index, row, field);
+
+    let mut match_keys: Vec<&AttrName> = row.match_part.attrs.keys().collect();
+    match_keys.sort();
+    let mut matched: HashSet<i32> = HashSet::new();
+    for key in match_keys {
+        let value = &row.match_part.attrs[key];
+        let in_row = at(RowField::Match(key));
+        let meta = lookup(registry, key, AttrRole::Match).map_err(in_row)?;
+        if !matched.insert(meta.attr_id) {
+            return Err(in_row(PrecedenceConfigError::DuplicateMatchAttr { match_id, attr_id: meta.attr_id }));
+        }
+        let value = if value.as_str() == Some(WILDCARD) {
+            TypedValue::Str(WILDCARD.to_string())
+        } else if is_regex_object(value) {
+            parse_regex_value(meta, value).map_err(in_row)?
+        } else if value.is_object() {
+            parse_range_value(meta, value).map_err(in_row)?
+        } else if value.is_array() && !matches!(data_type_of(meta).map_err(in_row)?, ParamType::List(_)) {
+            parse_one_of_value(meta, value).map_err(in_row)?
+        } else if let Some(text) = value.as_str().filter(|text| is_pattern_for(meta, text)) {
+            parse_pattern_value(meta, text).map_err(in_row)?
+        } else {
+            parse_json_value(meta, value).map_err(in_row)?
+        };
+        values.push(ConfigValue {
+            match_id,
+            attr_id: meta.attr_id,
+            role: AttrRole::Match,
+            value,
+        });
+    }
+
+    // attribute name (an alias's attribute for an alias) -> index in params
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (index, param) in row.params.iter().enumerate() {
+        let [key_field, type_field, value_field] =
+            ["key", "type", "value"].map(|name| at(RowField::Param(index, name)));
+        let name = registry.get(&param.key).map_or(param.key.as_str(), |meta| meta.attr_name.as_str());
+        if let Some(&first_index) = seen.get(name) {
+            return Err(key_field(PrecedenceConfigError::DuplicateParamKey {
+                key: param.key.to_string(),
+                first_index,
+            }));
+        }
+        seen.insert(name, index);
+
+        if let Some(schema) = &options.schema
+            && !schema.allows(name)
+        {
+            return Err(key_field(PrecedenceConfigError::ParamNotInSchema { key: param.key.to_string() }));
+        }
+        let meta = lookup(registry, &param.key, AttrRole::Param).map_err(key_field)?;
+        if meta.data_type.parse::<ParamType>().ok().as_ref() != Some(&param.ty) {
+            return Err(type_field(PrecedenceConfigError::DataTypeMismatch {
+                key: param.key.to_string(),
+                declared: param.ty.to_string(),
+                expected: meta.data_type.clone(),
+            }));
+        }
+        let value = parse_json_value(meta, &param.value).map_err(value_field)?;
+        meta.check_constraints(&value).map_err(value_field)?;
+        values.push(ConfigValue {
+            match_id,
+            attr_id: meta.attr_id,
+            role: AttrRole::Param,
+            value,
+        });
+    }
+
+    if let Some(schema) = &options.schema
+        && let Some(key) = schema.required.iter().find(|key| !seen.contains_key(key.as_str()))
+    {
+        return Err(at(RowField::Params)(PrecedenceConfigError::MissingRequiredParam { key: key.clone() }));
+    }
+    Ok(())
+}
+
+/// The field of an envelope row an error is about.
+#[derive(Clone, Copy)]
+enum RowField<'a> {
+    /// `match.<key>`
+    Match(&'a str),
+    /// `params.<index>.<name>`
+    Param(usize, &'static str),
+    /// `params` as a whole
+    Params,
+}
+
+/// Wraps an error of row `row_index` with the JSON Pointer of `field` and the
+/// row's match tuple.
+fn in_row<'a>(
+    row_index: usize,
+    row: &'a ConfigRow,
+    field: RowField<'a>,
+) -> impl Fn(PrecedenceConfigError) -> PrecedenceConfigError + Copy + 'a {
+    move |source| PrecedenceConfigError::InvalidEnvelopeRow {
+        row: row_index,
+        pointer: json_pointer(row_index, field),
+        match_tuple: row.match_part.attrs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+        source: Box::new(source),
+    }
+}
+
+/// The JSON Pointer (RFC 6901) of `field` in row `row_index` of an envelope.
+fn json_pointer(row_index: usize, field: RowField<'_>) -> String {
+    match field {
+        RowField::Match(key) => format!("/rows/{}/match/{}", row_index, key.replace('~', "~0").replace('/', "~1")),
+        RowField::Param(index, name) => format!("/rows/{}/params/{}/{}", row_index, index, name),
+        RowField::Params => format!("/rows/{}/params", row_index),
+    }
+}
+
+fn validated(envelope: &ConfigEnvelope, registry: &AttrRegistry, values: Vec<ConfigValue>) -> ValidatedEnvelope {
+    let row_audits = envelope
+        .rows
+        .iter()
+        .enumerate()
+        .filter_map(|(row_index, row)| row.audit.clone().map(|audit| (row_index as i32 + 1, audit)))
+        .collect();
+    ValidatedEnvelope {
+        config: envelope.config.clone(),
+        values,
+        row_audits,
+        warnings: envelope_deprecations(envelope, registry),
+    }
+}
+
+/// Rebuilds an envelope from tall CONFIG_VALUE rows (the inverse of
+/// [`envelope_to_config_values`]): one row per match_id in ascending order,
+/// match attrs and params split by role, values written in their envelope JSON
+/// form. Values whose attr_id is not in `registry` are left out, as are params
+/// whose `data_type` doesn't parse.
+pub fn config_values_to_envelope(values: &[ConfigValue], meta: &ConfigMeta, registry: &AttrRegistry) -> ConfigEnvelope {
+    config_values_to_envelope_with_audits(values, meta, &BTreeMap::new(), registry)
+}
+
+/// [`config_values_to_envelope`], attaching `row_audits` (match_id -> audit) to their rows.
+pub fn config_values_to_envelope_with_audits(
+    values: &[ConfigValue],
+    meta: &ConfigMeta,
+    row_audits: &BTreeMap<i32, AuditInfo>,
+    registry: &AttrRegistry,
+) -> ConfigEnvelope {
+    let mut rows: BTreeMap<i32, ConfigRow> = BTreeMap::new();
+
+    for v in values {
+        let Some(attr) = registry.get_by_id(v.attr_id) else {
+            continue;
+        };
+        let row = rows.entry(v.match_id).or_insert_with(|| ConfigRow {
+            match_part: MatchPart { attrs: HashMap::new() },
+            params: Vec::new(),
+            audit: row_audits.get(&v.match_id).cloned(),
+        });
+
+        match v.role {
+            AttrRole::Match => {
+                row.match_part.attrs.insert(attr.attr_name.clone(), v.value.to_json_value());
+            }
+            AttrRole::Param => {
+                let Ok(ty) = attr.data_type.parse::<ParamType>() else {
+                    continue;
+                };
+                row.params.push(Param { key: attr.attr_name.clone(), ty, value: v.value.to_json_value() });
+            }
+        }
+    }
+
+    ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config: meta.clone(), rows: rows.into_values().collect() }
+}
+
+fn lookup<'a>(registry: &'a AttrRegistry, key: &str, role: AttrRole) -> Result<&'a AttrMeta> {
+    let Some(meta) = registry.get(key) else {
+        return Err(PrecedenceConfigError::UnknownAttrKey { key: key.to_string() });
+    };
+    if meta.role != role {
+        return Err(PrecedenceConfigError::WrongRole {
+            key: key.to_string(),
+            expected: role,
+            found: meta.role,
+        });
+    }
+    Ok(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::TypedValue;
+    use crate::test_fixtures::region_registry;
+    use serde_json::json;
+
+    fn envelope_of(rows: serde_json::Value) -> ConfigEnvelope {
+        serde_json::from_value(json!({ "config": { "name": "c", "version": 1, "version_name": "v1" }, "rows": rows }))
+            .unwrap()
+    }
+
+    /// The row index and cause of the row error validating `rows` fails with.
+    fn row_error(rows: serde_json::Value) -> (usize, PrecedenceConfigError) {
+        match validate_envelope(&envelope_of(rows), &region_registry()) {
+            Err(PrecedenceConfigError::InvalidEnvelopeRow { row, source, .. }) => (row, *source),
+            other => panic!("expected a row error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_rows_become_typed_values() {
+        let rows = json!([
+            { "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 5 }] },
+            { "match": { "region": "us" }, "params": [{ "key": "limit", "type": "int", "value": "7" }] },
+        ]);
+        let validated = validate_envelope(&envelope_of(rows), &region_registry()).unwrap();
+        assert_eq!(validated.config.name, "c");
+        let values: Vec<(i32, i32, AttrRole, TypedValue)> =
+            validated.values.into_iter().map(|v| (v.match_id, v.attr_id, v.role, v.value)).collect();
+        assert_eq!(
+            values,
+            vec![
+                (1, 1, AttrRole::Match, TypedValue::Str("eu".into())),
+                (1, 2, AttrRole::Param, TypedValue::Int(5)),
+                (2, 1, AttrRole::Match, TypedValue::Str("us".into())),
+                (2, 2, AttrRole::Param, TypedValue::Int(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_wrong_roles_are_refused() {
+        let (row, source) = row_error(json!([{ "match": { "planet": "mars" }, "params": [] }]));
+        assert_eq!(row, 0);
+        assert!(matches!(source, PrecedenceConfigError::UnknownAttrKey { ref key } if key == "planet"), "{source}");
+
+        let (row, source) = row_error(json!([
+            { "match": {}, "params": [] },
+            { "match": { "limit": 5 }, "params": [] },
+        ]));
+        assert_eq!(row, 1);
+        assert!(
+            matches!(
+                source,
+                PrecedenceConfigError::WrongRole { expected: AttrRole::Match, found: AttrRole::Param, ref key }
+                    if key == "limit"
+            ),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn params_must_match_the_catalog_type() {
+        let (_, source) =
+            row_error(json!([{ "match": {}, "params": [{ "key": "limit", "type": "str", "value": "5" }] }]));
+        assert!(matches!(source, PrecedenceConfigError::DataTypeMismatch { .. }), "{source}");
+
+        let (_, source) =
+            row_error(json!([{ "match": {}, "params": [{ "key": "limit", "type": "int", "value": "five" }] }]));
+        assert!(matches!(source, PrecedenceConfigError::InvalidValue { ref key, .. } if key == "limit"), "{source}");
+    }
+
+    #[test]
+    fn param_constraints_apply_at_ingest() {
+        let mut meta = region_registry().get("limit").unwrap().clone();
+        meta.constraints = Some(serde_json::from_value(json!({ "max": 100.0 })).unwrap());
+        let mut registry = region_registry();
+        registry.remove("limit");
+        registry.insert(meta).unwrap();
+        let rows = json!([{ "match": {}, "params": [{ "key": "limit", "type": "int", "value": 250 }] }]);
+        let result = validate_envelope(&envelope_of(rows), &registry);
+        let Err(PrecedenceConfigError::InvalidEnvelopeRow { row: 0, source, .. }) = result else {
+            panic!("expected a row error");
+        };
+        assert!(matches!(*source, PrecedenceConfigError::ConstraintViolation { .. }), "{source}");
+    }
+
+    fn channel_catalog() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("channel", AttrRole::Match, "enum").unwrap();
+        let mut meta = registry.remove("channel").unwrap();
+        meta.enum_values = vec!["web".into(), "app".into()];
+        registry.insert(meta).unwrap();
+        registry
+    }
+
+    #[test]
+    fn enum_match_values_must_be_allowed() {
+        let registry = channel_catalog();
+        let channel = |value: serde_json::Value| {
+            let rows = json!([{ "match": { "channel": value }, "params": [] }]);
+            validate_envelope(&envelope_of(rows), &registry).map(|validated| validated.values)
+        };
+        assert_eq!(channel(json!("web")).unwrap()[0].value, TypedValue::Enum("web".into()));
+        assert!(matches!(channel(json!(["web", "app"])).unwrap()[0].value, TypedValue::OneOf(_)));
+        assert_eq!(channel(json!("ALL")).unwrap()[0].value, TypedValue::Str(WILDCARD.into()));
+        for typo in [json!("weeb"), json!(["web", "weeb"])] {
+            let Err(PrecedenceConfigError::InvalidEnvelopeRow { source, .. }) = channel(typo.clone()) else {
+                panic!("{typo} should be refused");
+            };
+            assert!(
+                matches!(*source, PrecedenceConfigError::InvalidValue { ref value, ref reason, .. }
+                    if value == "weeb" && reason == "expected one of [web, app]"),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_schema_requires_and_limits_params() {
+        let mut registry = region_registry();
+        registry.register("tier", AttrRole::Param, "str").unwrap();
+        let schema: ConfigSchema = serde_json::from_value(json!({ "required": ["limit"] })).unwrap();
+        assert!(schema.allows("limit") && !schema.allows("tier"));
+        let options = EnvelopeOptions { schema: Some(schema) };
+        let tier = json!({ "key": "tier", "type": "str", "value": "gold" });
+        let limit = json!({ "key": "limit", "type": "int", "value": 5 });
+        let check = |params: serde_json::Value| {
+            let rows = json!([{ "match": {}, "params": params }]);
+            match validate_envelope_with_options(&envelope_of(rows), &registry, &options) {
+                Ok(_) => None,
+                Err(PrecedenceConfigError::InvalidEnvelopeRow { row, source, .. }) => Some((row, *source)),
+                Err(other) => panic!("expected a row error, got {other:?}"),
+            }
+        };
+        assert!(check(json!([limit])).is_none());
+
+        let (row, source) = check(json!([])).unwrap();
+        assert_eq!(row, 0);
+        assert!(matches!(source, PrecedenceConfigError::MissingRequiredParam { ref key } if key == "limit"));
+        let (_, source) = check(json!([limit, tier])).unwrap();
+        assert!(matches!(source, PrecedenceConfigError::ParamNotInSchema { ref key } if key == "tier"));
+        // without a schema anything in the catalog goes
+        assert!(validate_envelope(&envelope_of(json!([{ "match": {}, "params": [tier] }])), &registry).is_ok());
+    }
+
+    #[test]
+    fn envelope_rows_flatten_to_config_values() {
+        let mut registry = region_registry();
+        registry.register("tags", AttrRole::Match, "list<str>").unwrap();
+        let rows = json!([{ "match": { "region": "eu", "tags": ["a", "b"] }, "params": [] }]);
+        let values = envelope_to_config_values(&envelope_of(rows), &registry).unwrap();
+        let tags = values.iter().find(|v| v.attr_id == 3).unwrap();
+        assert_eq!(tags.value, TypedValue::List(vec![TypedValue::Str("a".into()), TypedValue::Str("b".into())]));
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn values_rebuild_the_envelope_they_came_from() {
+        let mut registry = region_registry();
+        registry.register("countries", AttrRole::Param, "list<str>").unwrap();
+        let envelope = envelope_of(json!([
+            {
+                "match": { "region": "eu" },
+                "params": [
+                    { "key": "limit", "type": "int", "value": 5 },
+                    { "key": "countries", "type": "list<str>", "value": ["DE", "FR"] },
+                ],
+            },
+            { "match": {}, "params": [{ "key": "limit", "type": "int", "value": null }] },
+        ]));
+        let validated = validate_envelope(&envelope, &registry).unwrap();
+        let rebuilt = config_values_to_envelope(&validated.values, &validated.config, &registry);
+        assert_eq!(rebuilt, envelope);
+
+        // attr_ids the registry doesn't know are dropped
+        let mut values = validated.values.clone();
+        values.push(ConfigValue { match_id: 1, attr_id: 99, role: AttrRole::Param, value: TypedValue::Int(1) });
+        let plain = config_values_to_envelope(&values, &validated.config, &registry);
+        assert_eq!(plain.rows[0].params.len(), 2);
+    }
+
+    fn tags_envelope(match_value: serde_json::Value) -> ConfigEnvelope {
+        envelope_of(json!([{ "match": { "tags": match_value }, "params": [] }]))
+    }
+
+    fn tags_registry(data_type: &str) -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("tags", AttrRole::Match, data_type).unwrap();
+        registry
+    }
+
+    #[test]
+    fn array_is_a_set_of_alternatives_unless_the_attribute_is_a_list() {
+        let values = envelope_to_config_values(&tags_envelope(json!(["a", "b"])), &tags_registry("str")).unwrap();
+        assert!(matches!(values[0].value, TypedValue::OneOf(_)));
+        let values =
+            envelope_to_config_values(&tags_envelope(json!(["a", "b"])), &tags_registry("list<str>")).unwrap();
+        assert!(matches!(values[0].value, TypedValue::List(_)));
+    }
+
+    #[test]
+    fn malformed_list_type_is_not_a_list() {
+        let err = envelope_to_config_values(&tags_envelope(json!(["a", "b"])), &tags_registry("list<str")).unwrap_err();
+        let PrecedenceConfigError::InvalidEnvelopeRow { row: 0, source, .. } = err else {
+            panic!("expected a row error, got {err}");
+        };
+        assert!(matches!(*source, PrecedenceConfigError::UnsupportedDataType { .. }), "{source}");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_validation_keeps_row_order_and_the_first_error() {
+        let rows: Vec<serde_json::Value> = (0..300)
+            .map(|i| {
+                let params = json!([{ "key": "limit", "type": "int", "value": i }]);
+                json!({ "match": { "region": format!("r{i}") }, "params": params })
+            })
+            .collect();
+        let envelope = envelope_of(json!(rows));
+        let options = EnvelopeOptions::default();
+        let fields = |validated: ValidatedEnvelope| -> Vec<(i32, i32, TypedValue)> {
+            validated.values.into_iter().map(|v| (v.match_id, v.attr_id, v.value)).collect()
+        };
+        let sequential = validate_envelope_with_options(&envelope, &region_registry(), &options).unwrap();
+        assert_eq!(fields(validate_envelope_par(&envelope, &region_registry(), &options).unwrap()), fields(sequential));
+
+        let mut bad = envelope.clone();
+        for i in [120, 40, 250] {
+            bad.rows[i].params[0].value = json!("many");
+        }
+        let err = validate_envelope_par(&bad, &region_registry(), &options).unwrap_err();
+        assert!(err.to_string().contains("/rows/40/"), "{err}");
+        let expected = validate_envelope_with_options(&bad, &region_registry(), &options).unwrap_err();
+        assert_eq!(err.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn aliases_stand_for_their_attribute_in_rows() {
+        let mut registry = region_registry();
+        registry.set_aliases("region", ["rgn".to_string()]).unwrap();
+        registry.set_aliases("limit", ["max".to_string()]).unwrap();
+        let rows = json!([{ "match": { "rgn": "eu" }, "params": [{ "key": "max", "type": "int", "value": 5 }] }]);
+        let values = validate_envelope(&envelope_of(rows), &registry).unwrap().values;
+        let ids: Vec<(i32, TypedValue)> = values.into_iter().map(|v| (v.attr_id, v.value)).collect();
+        assert_eq!(ids, [(1, TypedValue::Str("eu".into())), (2, TypedValue::Int(5))]);
+
+        let source = |rows: serde_json::Value| match validate_envelope(&envelope_of(rows), &registry) {
+            Err(PrecedenceConfigError::InvalidEnvelopeRow { source, .. }) => *source,
+            other => panic!("expected a row error, got {other:?}"),
+        };
+        let twice = source(json!([{ "match": { "rgn": "eu", "region": "us" }, "params": [] }]));
+        assert!(matches!(twice, PrecedenceConfigError::DuplicateMatchAttr { attr_id: 1, .. }), "{twice}");
+        let params = json!([
+            { "key": "limit", "type": "int", "value": 5 },
+            { "key": "max", "type": "int", "value": 6 },
+        ]);
+        let twice = source(json!([{ "match": {}, "params": params }]));
+        assert!(matches!(twice, PrecedenceConfigError::DuplicateParamKey { first_index: 0, .. }), "{twice}");
+    }
+
+    #[test]
+    fn row_errors_carry_the_match_tuple_and_an_escaped_pointer() {
+        let rows = json!([
+            { "match": { "region": "eu" }, "params": [] },
+            { "match": { "region": "us", "a/b~c": 1 }, "params": [] },
+        ]);
+        let err = validate_envelope(&envelope_of(rows), &region_registry()).unwrap_err();
+        let PrecedenceConfigError::InvalidEnvelopeRow { row, pointer, match_tuple, .. } = &err else {
+            panic!("expected a row error, got {err}");
+        };
+        assert_eq!((*row, pointer.as_str()), (1, "/rows/1/match/a~1b~0c"));
+        assert_eq!(match_tuple.len(), 2);
+        assert_eq!(match_tuple["region"], json!("us"));
+        assert!(err.to_string().contains("at /rows/1/match/a~1b~0c"), "{err}");
+    }
+}
@@ -0,0 +1,535 @@
+use crate::config_diff::{render_tuple, MatchTuple};
+use crate::config_types::ConfigStatus;
+use chrono::{DateTime, Utc};
+use crate::config_value::AttrRole;
+use std::fmt;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, PrecedenceConfigError>;
+
+/// An attribute as the caller referred to it: by name in matrix/param payloads, by id in tall rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrRef {
+    Id(i32),
+    Name(String),
+}
+
+impl fmt::Display for AttrRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrRef::Id(id) => write!(f, "attr_id {}", id),
+            AttrRef::Name(name) => write!(f, "attr '{}'", name),
+        }
+    }
+}
+
+/// Errors returned by the conversion, validation and resolution APIs.
+#[derive(Debug, Error)]
+pub enum PrecedenceConfigError {
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(#[source] serde_json::Error),
+
+    #[error("Invalid {format}: {reason}")]
+    InvalidFormat { format: &'static str, reason: String },
+
+    #[error("Invalid CSV at line {line}: {reason}")]
+    InvalidCsv { line: u64, reason: String },
+
+    /// `row` is 1-based, 0 for the sheet as a whole.
+    #[error("Invalid sheet '{sheet}' at row {row}: {reason}")]
+    InvalidXlsx { sheet: String, row: usize, reason: String },
+
+    #[error("Binary config format version {found} is not supported (expected {expected})")]
+    BinaryFormatVersion { found: u16, expected: u16 },
+
+    #[error("Envelope schema_version {found} is newer than this crate reads (up to {supported})")]
+    UnsupportedSchemaVersion { found: u64, supported: u32 },
+
+    #[error("Line {line}: {source}")]
+    InvalidNdjsonLine { line: u64, source: Box<PrecedenceConfigError> },
+
+    #[error("I/O error: {0}")]
+    Io(#[source] std::io::Error),
+
+    #[cfg(feature = "sqlx-postgres")]
+    #[error("Database error: {0}")]
+    Database(#[source] sqlx::Error),
+
+    #[cfg(feature = "watch")]
+    #[error("File watch error: {0}")]
+    Watch(#[source] notify::Error),
+
+    #[error("Rank must be >= 1 (found {rank} in row {index})")]
+    InvalidRank { index: usize, rank: i32 },
+
+    #[error("MATCH_TYPE must be 0 or 1 (found {match_type} for {attr} at rank {rank})")]
+    InvalidMatchType { rank: i32, attr: AttrRef, match_type: u8 },
+
+    #[error("Unknown {attr} at rank {rank}")]
+    UnknownRuleAttr { rank: i32, attr: AttrRef },
+
+    #[error("Duplicate (rank, attr): ({rank}, {attr_name} / attr_id {attr_id})")]
+    DuplicateRankAttr { rank: i32, attr_id: i32, attr_name: String },
+
+    #[error("No precedence rules to process")]
+    NoPrecedenceRules,
+
+    #[error("Triangular count check failed: found {found} distinct ranks, expected {expected} for A={attr_count}")]
+    NonTriangularRankCount { found: usize, expected: usize, attr_count: usize },
+
+    #[error("{scheme} rank count check failed: found {found} distinct ranks, expected {expected} for A={attr_count}")]
+    UnexpectedRankCount { scheme: &'static str, found: usize, expected: usize, attr_count: usize },
+
+    #[error("A power-set matrix holds at most {max} attributes (found {count})")]
+    TooManyPowerSetAttrs { count: usize, max: usize },
+
+    #[error("Contiguity check failed at rank {rank}: expected {expected}")]
+    NonContiguousRank { rank: i32, expected: i32 },
+
+    #[error("Rank masks hold at most {} attributes (found {count})", u64::BITS)]
+    TooManyMaskAttrs { count: usize },
+
+    #[error("Cannot {op} attribute '{attr_name}': {reason}")]
+    InvalidAttributeEdit { op: &'static str, attr_name: String, reason: String },
+
+    #[error("Cannot move rank {from_rank} to {to_rank}: {reason}")]
+    InvalidRankMove { from_rank: i32, to_rank: i32, reason: String },
+
+    #[error("Attribute name '{attr_name}' is already registered")]
+    DuplicateAttrName { attr_name: String },
+
+    #[error("attr_id {attr_id} is already registered")]
+    DuplicateAttrId { attr_id: i32 },
+
+    #[error("attr_id {attr_id} leaves no attr_id to allocate after it")]
+    AttrIdOverflow { attr_id: i32 },
+
+    #[error("Unknown attribute key: {key}")]
+    UnknownAttrKey { key: String },
+
+    #[error("MATCH_TYPE must be 0 or 1 (found {0})")]
+    InvalidMatchTypeValue(u8),
+
+    #[error("Role must be 'match' or 'param' (found '{0}')")]
+    InvalidRole(String),
+
+    #[error("Attribute '{key}' is not a {expected} (role = {found})")]
+    WrongRole { key: String, expected: AttrRole, found: AttrRole },
+
+    #[error("Unknown data type '{0}' (expected int, dec, str, bool, dt, date, time, duration, enum or list<T>)")]
+    InvalidDataType(String),
+
+    #[error("Unsupported data type '{data_type}' for attribute '{key}'")]
+    UnsupportedDataType { key: String, data_type: String },
+
+    #[error("Invalid {data_type} value '{value}' for attribute '{key}': {reason}")]
+    InvalidValue { key: String, data_type: String, value: String, reason: String },
+
+    #[error("params[{index}] ('{key}'): {source}")]
+    InvalidParam { index: usize, key: String, source: Box<PrecedenceConfigError> },
+
+    #[error("Duplicate param key '{key}' (first seen at params[{first_index}])")]
+    DuplicateParamKey { key: String, first_index: usize },
+
+    #[error("Param '{key}' declares type '{declared}' but the attribute is '{expected}'")]
+    DataTypeMismatch { key: String, declared: String, expected: String },
+
+    #[error("Value '{value}' for attribute '{key}' violates its constraints: {reason}")]
+    ConstraintViolation { key: String, value: String, reason: String },
+
+    #[error("Required param '{key}' is missing")]
+    MissingRequiredParam { key: String },
+
+    #[error("Param '{key}' is not in the config schema")]
+    ParamNotInSchema { key: String },
+
+    /// `pointer` is the JSON Pointer of the offending field in the envelope
+    /// (`/rows/17/params/2/value`), `match_tuple` the row's match values.
+    #[error("Row {row} {} at {pointer}: {source}", render_tuple(.match_tuple))]
+    InvalidEnvelopeRow {
+        row: usize,
+        pointer: String,
+        match_tuple: MatchTuple,
+        source: Box<PrecedenceConfigError>,
+    },
+
+    #[error("Duplicate match attr_id {attr_id} on match_id {match_id}")]
+    DuplicateMatchAttr { match_id: i32, attr_id: i32 },
+
+    #[error("match_ids {match_id} and {other_match_id} have identical match values on attr_ids {attr_ids:?}")]
+    ConflictingRows { match_id: i32, other_match_id: i32, attr_ids: Vec<i32> },
+
+    #[error("match_ids {match_id} and {other_match_id} are both default rows (no match attribute constrained)")]
+    DuplicateDefaultRow { match_id: i32, other_match_id: i32 },
+
+    #[error("Unknown attr_id {attr_id} on match_id {match_id}")]
+    UnknownValueAttr { match_id: i32, attr_id: i32 },
+
+    #[error("Config '{name}' already exists")]
+    DuplicateConfig { name: String },
+
+    #[error("Unknown config '{name}'")]
+    UnknownConfig { name: String },
+
+    #[error("Config '{name}' already has version {version}")]
+    DuplicateConfigVersion { name: String, version: i32 },
+
+    #[error("Config '{name}' has no version {version}")]
+    UnknownConfigVersion { name: String, version: i32 },
+
+    #[error("Attribute '{attr_name}' is still used by config '{name}' version {version}")]
+    AttrInUse { attr_name: String, name: String, version: i32 },
+
+    #[error("Config '{name}' version {version} cannot go from {from} to {to}")]
+    InvalidStatusTransition { name: String, version: i32, from: ConfigStatus, to: ConfigStatus },
+
+    #[error("Config '{name}' version {version} is {status}; only drafts can be edited")]
+    VersionNotEditable { name: String, version: i32, status: ConfigStatus },
+
+    #[error("Config '{name}' version {version} is {status}; strict mode only resolves published versions")]
+    UnpublishedVersion { name: String, version: i32, status: ConfigStatus },
+
+    #[error("Config '{name}' version {version} has valid_to {valid_to} not after valid_from {valid_from}")]
+    InvalidValidityWindow { name: String, version: i32, valid_from: DateTime<Utc>, valid_to: DateTime<Utc> },
+
+    #[error("Overlay for config '{name}' is based on version {based_on}, but the base given is '{found_name}' version {found_version}")]
+    OverlayBaseMismatch { name: String, based_on: i32, found_name: String, found_version: i32 },
+
+    #[error("Row override {index}: {reason}")]
+    InvalidRowOverride { index: usize, reason: String },
+
+    #[error("Config '{name}' version {version} is not based on another version")]
+    NoBaseVersion { name: String, version: i32 },
+
+    #[error("Override {key}: {source}")]
+    InvalidOverride { key: String, source: Box<PrecedenceConfigError> },
+
+    #[error("Patch op {index}: {reason}")]
+    InvalidPatchOp { index: usize, reason: String },
+
+    #[error("No config version has config_version_id {config_version_id}")]
+    UnknownConfigVersionId { config_version_id: i32 },
+
+    #[error("Unknown snapshot {snapshot_id}")]
+    UnknownSnapshot { snapshot_id: u64 },
+
+    #[error("Config '{name}' version {version} is at revision {found}, not the expected {expected}")]
+    RevisionConflict { name: String, version: i32, expected: u64, found: u64 },
+
+    #[error("Rows {match_ids:?} constrain attr_id {attr_id}, which no precedence rank matches exactly")]
+    UnruledMatchAttr { attr_id: i32, match_ids: Vec<i32> },
+
+    #[error("Invalid range: {reason}")]
+    InvalidRange { reason: String },
+
+    #[error("Param '{key}' is not set")]
+    MissingParam { key: String },
+
+    #[error("Param '{key}' has type {found}, expected {expected}")]
+    ParamTypeMismatch { key: String, expected: &'static str, found: &'static str },
+
+    #[error("Attribute '{attr_name}': the parents of '{value}' lead back to it")]
+    HierarchyCycle { attr_name: String, value: String },
+
+    #[error("Rows {match_ids:?} match equally well at rank {rank}")]
+    AmbiguousMatch { rank: i32, match_ids: Vec<i32> },
+
+    #[error("Weight {weight} of attribute '{attr_name}' is not a finite number")]
+    InvalidAttrWeight { attr_name: String, weight: f64 },
+
+    #[error("No row matches the lookup context, and there is no default row")]
+    NoMatchingRow,
+
+    #[error("Match {attr} has no small enumerable domain (bool, enum, allowed values or an int min..max)")]
+    UnenumerableDomain { attr: AttrRef },
+
+    #[error("A lookup table would have {cells} cells, more than the {max_cells} allowed")]
+    TableTooLarge { cells: u128, max_cells: usize },
+
+    #[error("Value {value} of '{attr_name}' is outside the lookup table's domain")]
+    OutOfTableDomain { attr_name: String, value: String },
+
+    #[error("Invalid generator options: {reason}")]
+    InvalidGeneratorOptions { reason: String },
+}
+
+impl PrecedenceConfigError {
+    /// The stable code of the variant (`"PC0001"` for `DuplicateRankAttr`), for CI
+    /// annotations, dashboards and suppression lists; a code is never reused. Codes
+    /// are grouped by hundreds:
+    ///
+    /// - `PC00xx` precedence matrix cells,
+    /// - `PC01xx` rank layouts (and rules against rows),
+    /// - `PC02xx` the attribute catalog,
+    /// - `PC03xx` values and params,
+    /// - `PC04xx` envelope rows,
+    /// - `PC05xx` document formats,
+    /// - `PC06xx` the config store: versions, overlays, patches,
+    /// - `PC07xx` resolution and lookup tables,
+    /// - `PC09xx` I/O.
+    ///
+    /// Warnings use `xx50` and up of their group, see
+    /// [`config_findings`](crate::config_findings).
+    pub fn code(&self) -> &'static str {
+        match self {
+            PrecedenceConfigError::DuplicateRankAttr { .. } => "PC0001",
+            PrecedenceConfigError::InvalidRank { .. } => "PC0002",
+            PrecedenceConfigError::InvalidMatchType { .. } => "PC0003",
+            PrecedenceConfigError::UnknownRuleAttr { .. } => "PC0004",
+            PrecedenceConfigError::InvalidMatchTypeValue(..) => "PC0005",
+            PrecedenceConfigError::NoPrecedenceRules => "PC0101",
+            PrecedenceConfigError::NonContiguousRank { .. } => "PC0102",
+            PrecedenceConfigError::UnexpectedRankCount { .. } => "PC0103",
+            PrecedenceConfigError::InvalidRankMove { .. } => "PC0104",
+            PrecedenceConfigError::UnruledMatchAttr { .. } => "PC0105",
+            PrecedenceConfigError::TooManyMaskAttrs { .. } => "PC0106",
+            PrecedenceConfigError::NonTriangularRankCount { .. } => "PC0107",
+            PrecedenceConfigError::TooManyPowerSetAttrs { .. } => "PC0108",
+            PrecedenceConfigError::DuplicateAttrName { .. } => "PC0201",
+            PrecedenceConfigError::DuplicateAttrId { .. } => "PC0202",
+            PrecedenceConfigError::InvalidRole(..) => "PC0203",
+            PrecedenceConfigError::InvalidDataType(..) => "PC0204",
+            PrecedenceConfigError::InvalidAttributeEdit { .. } => "PC0205",
+            PrecedenceConfigError::HierarchyCycle { .. } => "PC0206",
+            PrecedenceConfigError::InvalidAttrWeight { .. } => "PC0207",
+            PrecedenceConfigError::AttrInUse { .. } => "PC0208",
+            PrecedenceConfigError::AttrIdOverflow { .. } => "PC0209",
+            PrecedenceConfigError::UnknownAttrKey { .. } => "PC0301",
+            PrecedenceConfigError::WrongRole { .. } => "PC0302",
+            PrecedenceConfigError::UnsupportedDataType { .. } => "PC0303",
+            PrecedenceConfigError::InvalidValue { .. } => "PC0304",
+            PrecedenceConfigError::InvalidParam { .. } => "PC0305",
+            PrecedenceConfigError::DuplicateParamKey { .. } => "PC0306",
+            PrecedenceConfigError::DataTypeMismatch { .. } => "PC0307",
+            PrecedenceConfigError::ConstraintViolation { .. } => "PC0308",
+            PrecedenceConfigError::MissingRequiredParam { .. } => "PC0309",
+            PrecedenceConfigError::ParamNotInSchema { .. } => "PC0310",
+            PrecedenceConfigError::InvalidRange { .. } => "PC0311",
+            PrecedenceConfigError::MissingParam { .. } => "PC0312",
+            PrecedenceConfigError::ParamTypeMismatch { .. } => "PC0313",
+            PrecedenceConfigError::UnknownValueAttr { .. } => "PC0314",
+            PrecedenceConfigError::InvalidEnvelopeRow { .. } => "PC0401",
+            PrecedenceConfigError::DuplicateMatchAttr { .. } => "PC0402",
+            PrecedenceConfigError::ConflictingRows { .. } => "PC0403",
+            PrecedenceConfigError::DuplicateDefaultRow { .. } => "PC0404",
+            PrecedenceConfigError::InvalidValidityWindow { .. } => "PC0405",
+            PrecedenceConfigError::InvalidJson(..) => "PC0501",
+            PrecedenceConfigError::InvalidFormat { .. } => "PC0502",
+            PrecedenceConfigError::InvalidCsv { .. } => "PC0503",
+            PrecedenceConfigError::InvalidXlsx { .. } => "PC0504",
+            PrecedenceConfigError::BinaryFormatVersion { .. } => "PC0505",
+            PrecedenceConfigError::UnsupportedSchemaVersion { .. } => "PC0506",
+            PrecedenceConfigError::InvalidNdjsonLine { .. } => "PC0507",
+            PrecedenceConfigError::DuplicateConfig { .. } => "PC0601",
+            PrecedenceConfigError::UnknownConfig { .. } => "PC0602",
+            PrecedenceConfigError::DuplicateConfigVersion { .. } => "PC0603",
+            PrecedenceConfigError::UnknownConfigVersion { .. } => "PC0604",
+            PrecedenceConfigError::InvalidStatusTransition { .. } => "PC0605",
+            PrecedenceConfigError::VersionNotEditable { .. } => "PC0606",
+            PrecedenceConfigError::UnpublishedVersion { .. } => "PC0607",
+            PrecedenceConfigError::OverlayBaseMismatch { .. } => "PC0608",
+            PrecedenceConfigError::InvalidRowOverride { .. } => "PC0609",
+            PrecedenceConfigError::NoBaseVersion { .. } => "PC0610",
+            PrecedenceConfigError::InvalidOverride { .. } => "PC0611",
+            PrecedenceConfigError::InvalidPatchOp { .. } => "PC0612",
+            PrecedenceConfigError::UnknownConfigVersionId { .. } => "PC0613",
+            PrecedenceConfigError::UnknownSnapshot { .. } => "PC0614",
+            PrecedenceConfigError::RevisionConflict { .. } => "PC0615",
+            PrecedenceConfigError::AmbiguousMatch { .. } => "PC0701",
+            PrecedenceConfigError::NoMatchingRow => "PC0702",
+            PrecedenceConfigError::UnenumerableDomain { .. } => "PC0703",
+            PrecedenceConfigError::TableTooLarge { .. } => "PC0704",
+            PrecedenceConfigError::OutOfTableDomain { .. } => "PC0705",
+            PrecedenceConfigError::InvalidGeneratorOptions { .. } => "PC0706",
+            PrecedenceConfigError::Io(..) => "PC0901",
+            #[cfg(feature = "sqlx-postgres")]
+            PrecedenceConfigError::Database(..) => "PC0902",
+            #[cfg(feature = "watch")]
+            PrecedenceConfigError::Watch(..) => "PC0903",
+        }
+    }
+
+    /// The snake_case name of the variant (`"duplicate_rank_attr"`).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PrecedenceConfigError::InvalidJson(..) => "invalid_json",
+            PrecedenceConfigError::InvalidFormat { .. } => "invalid_format",
+            PrecedenceConfigError::InvalidCsv { .. } => "invalid_csv",
+            PrecedenceConfigError::InvalidXlsx { .. } => "invalid_xlsx",
+            PrecedenceConfigError::BinaryFormatVersion { .. } => "binary_format_version",
+            PrecedenceConfigError::UnsupportedSchemaVersion { .. } => "unsupported_schema_version",
+            PrecedenceConfigError::InvalidNdjsonLine { .. } => "invalid_ndjson_line",
+            PrecedenceConfigError::Io(..) => "io",
+            #[cfg(feature = "sqlx-postgres")]
+            PrecedenceConfigError::Database(..) => "database",
+            #[cfg(feature = "watch")]
+            PrecedenceConfigError::Watch(..) => "watch",
+            PrecedenceConfigError::InvalidRank { .. } => "invalid_rank",
+            PrecedenceConfigError::InvalidMatchType { .. } => "invalid_match_type",
+            PrecedenceConfigError::UnknownRuleAttr { .. } => "unknown_rule_attr",
+            PrecedenceConfigError::DuplicateRankAttr { .. } => "duplicate_rank_attr",
+            PrecedenceConfigError::NoPrecedenceRules => "no_precedence_rules",
+            PrecedenceConfigError::NonTriangularRankCount { .. } => "non_triangular_rank_count",
+            PrecedenceConfigError::UnexpectedRankCount { .. } => "unexpected_rank_count",
+            PrecedenceConfigError::TooManyPowerSetAttrs { .. } => "too_many_power_set_attrs",
+            PrecedenceConfigError::NonContiguousRank { .. } => "non_contiguous_rank",
+            PrecedenceConfigError::TooManyMaskAttrs { .. } => "too_many_mask_attrs",
+            PrecedenceConfigError::InvalidAttributeEdit { .. } => "invalid_attribute_edit",
+            PrecedenceConfigError::InvalidRankMove { .. } => "invalid_rank_move",
+            PrecedenceConfigError::DuplicateAttrName { .. } => "duplicate_attr_name",
+            PrecedenceConfigError::DuplicateAttrId { .. } => "duplicate_attr_id",
+            PrecedenceConfigError::AttrIdOverflow { .. } => "attr_id_overflow",
+            PrecedenceConfigError::UnknownAttrKey { .. } => "unknown_attr_key",
+            PrecedenceConfigError::InvalidMatchTypeValue(..) => "invalid_match_type_value",
+            PrecedenceConfigError::InvalidRole(..) => "invalid_role",
+            PrecedenceConfigError::WrongRole { .. } => "wrong_role",
+            PrecedenceConfigError::InvalidDataType(..) => "invalid_data_type",
+            PrecedenceConfigError::UnsupportedDataType { .. } => "unsupported_data_type",
+            PrecedenceConfigError::InvalidValue { .. } => "invalid_value",
+            PrecedenceConfigError::InvalidParam { .. } => "invalid_param",
+            PrecedenceConfigError::DuplicateParamKey { .. } => "duplicate_param_key",
+            PrecedenceConfigError::DataTypeMismatch { .. } => "data_type_mismatch",
+            PrecedenceConfigError::ConstraintViolation { .. } => "constraint_violation",
+            PrecedenceConfigError::MissingRequiredParam { .. } => "missing_required_param",
+            PrecedenceConfigError::ParamNotInSchema { .. } => "param_not_in_schema",
+            PrecedenceConfigError::InvalidEnvelopeRow { .. } => "invalid_envelope_row",
+            PrecedenceConfigError::DuplicateMatchAttr { .. } => "duplicate_match_attr",
+            PrecedenceConfigError::ConflictingRows { .. } => "conflicting_rows",
+            PrecedenceConfigError::DuplicateDefaultRow { .. } => "duplicate_default_row",
+            PrecedenceConfigError::UnknownValueAttr { .. } => "unknown_value_attr",
+            PrecedenceConfigError::DuplicateConfig { .. } => "duplicate_config",
+            PrecedenceConfigError::UnknownConfig { .. } => "unknown_config",
+            PrecedenceConfigError::DuplicateConfigVersion { .. } => "duplicate_config_version",
+            PrecedenceConfigError::UnknownConfigVersion { .. } => "unknown_config_version",
+            PrecedenceConfigError::AttrInUse { .. } => "attr_in_use",
+            PrecedenceConfigError::InvalidStatusTransition { .. } => "invalid_status_transition",
+            PrecedenceConfigError::VersionNotEditable { .. } => "version_not_editable",
+            PrecedenceConfigError::UnpublishedVersion { .. } => "unpublished_version",
+            PrecedenceConfigError::InvalidValidityWindow { .. } => "invalid_validity_window",
+            PrecedenceConfigError::OverlayBaseMismatch { .. } => "overlay_base_mismatch",
+            PrecedenceConfigError::InvalidRowOverride { .. } => "invalid_row_override",
+            PrecedenceConfigError::NoBaseVersion { .. } => "no_base_version",
+            PrecedenceConfigError::InvalidOverride { .. } => "invalid_override",
+            PrecedenceConfigError::InvalidPatchOp { .. } => "invalid_patch_op",
+            PrecedenceConfigError::UnknownConfigVersionId { .. } => "unknown_config_version_id",
+            PrecedenceConfigError::UnknownSnapshot { .. } => "unknown_snapshot",
+            PrecedenceConfigError::RevisionConflict { .. } => "revision_conflict",
+            PrecedenceConfigError::UnruledMatchAttr { .. } => "unruled_match_attr",
+            PrecedenceConfigError::InvalidRange { .. } => "invalid_range",
+            PrecedenceConfigError::MissingParam { .. } => "missing_param",
+            PrecedenceConfigError::ParamTypeMismatch { .. } => "param_type_mismatch",
+            PrecedenceConfigError::HierarchyCycle { .. } => "hierarchy_cycle",
+            PrecedenceConfigError::AmbiguousMatch { .. } => "ambiguous_match",
+            PrecedenceConfigError::InvalidAttrWeight { .. } => "invalid_attr_weight",
+            PrecedenceConfigError::NoMatchingRow => "no_matching_row",
+            PrecedenceConfigError::UnenumerableDomain { .. } => "unenumerable_domain",
+            PrecedenceConfigError::TableTooLarge { .. } => "table_too_large",
+            PrecedenceConfigError::OutOfTableDomain { .. } => "out_of_table_domain",
+            PrecedenceConfigError::InvalidGeneratorOptions { .. } => "invalid_generator_options",
+        }
+    }
+}
+
+/// Every error found during a validation pass, in the order encountered.
+///
+/// Built in one of two modes: fail-fast (the first pushed error is returned
+/// straight away) or collect-all (errors accumulate and the pass keeps going).
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<PrecedenceConfigError>,
+    fail_fast: bool,
+}
+
+impl ValidationReport {
+    pub(crate) fn fail_fast() -> Self {
+        Self { errors: Vec::new(), fail_fast: true }
+    }
+
+    pub(crate) fn collect_all() -> Self {
+        Self::default()
+    }
+
+    /// Records `err`; in fail-fast mode hands it straight back instead.
+    pub(crate) fn push(&mut self, err: PrecedenceConfigError) -> Result<()> {
+        if self.fail_fast {
+            return Err(err);
+        }
+        self.errors.push(err);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Finishes a collect-all pass: `Ok(value)` when nothing was recorded.
+    pub(crate) fn finish<T>(mut self, outcome: Result<T>) -> std::result::Result<T, ValidationReport> {
+        match outcome {
+            Ok(value) if self.errors.is_empty() => Ok(value),
+            Ok(_) => Err(self),
+            Err(err) => {
+                self.errors.push(err);
+                Err(self)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} validation error(s)", self.errors.len())?;
+        for err in &self.errors {
+            write!(f, "\n  - {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_attr::AttrRegistry;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+
+    #[test]
+    fn invalid_json_keeps_the_serde_message() {
+        let err = matrix_json_to_tall(r#"[{"rank": 1, "country": }]"#, 1, &AttrRegistry::new()).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidJson(_)));
+        let message = err.to_string();
+        assert!(message.starts_with("Invalid JSON: "), "{message}");
+        assert!(message.contains("line 1 column"), "{message}");
+    }
+
+    #[test]
+    fn codes_are_pinned() {
+        let err = PrecedenceConfigError::NonTriangularRankCount { found: 5, expected: 6, attr_count: 3 };
+        assert_eq!((err.code(), err.kind()), ("PC0107", "non_triangular_rank_count"));
+        let err = PrecedenceConfigError::UnknownAttrKey { key: "planet".to_string() };
+        assert_eq!((err.code(), err.kind()), ("PC0301", "unknown_attr_key"));
+        assert_eq!(PrecedenceConfigError::NoPrecedenceRules.code(), "PC0101");
+    }
+
+    /// Every code, error or warning, is distinct and listed in the README's table
+    /// with its kind.
+    #[test]
+    fn codes_are_unique_and_documented() {
+        let readme = include_str!("../README.md");
+        let source = include_str!("config_error.rs");
+        let errors = source.split("=> \"PC").skip(1).map(|rest| format!("PC{}", &rest[..4]));
+        let warnings = crate::config_findings::WARNING_CODES.iter().map(|(code, _)| code.to_string());
+        let mut seen = std::collections::HashSet::new();
+        for code in errors.chain(warnings) {
+            assert!(seen.insert(code.clone()), "{code} is used twice");
+            assert!(readme.contains(&format!("| `{}` |", code)), "{code} is missing from the README");
+        }
+        for (code, kind) in crate::config_findings::WARNING_CODES {
+            assert!(readme.contains(&format!("| `{}` | `{}` | warning |", code, kind)), "{code}");
+        }
+    }
+}
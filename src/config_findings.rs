@@ -0,0 +1,441 @@
+//! Validation output with severities: every problem, warning and note of a
+//! validation pass as a [`Finding`], so pipelines can gate on errors alone (or on
+//! warnings too) and tools can show the rest.
+//!
+//! The `_findings` functions run the checks of an envelope, a precedence matrix
+//! or a version's rows against its rules and gather what the underlying
+//! validators, lints and warnings report. Anything can be turned into a finding:
+//! [`PrecedenceConfigError`]s and [`ValidationReport`]s are errors, deprecations,
+//! lint findings, unused rule attributes and dead rows are warnings.
+//!
+//! Every finding has a stable code: an error's [`PrecedenceConfigError::code`], or
+//! one of [`WARNING_CODES`].
+
+use crate::config_attr::AttrRegistry;
+use crate::config_deprecation::{rule_deprecations, DeprecationWarning};
+use crate::config_diff::render_tuple;
+use crate::config_envelope::{validate_envelope_report, EnvelopeOptions};
+use crate::config_error::{PrecedenceConfigError, ValidationReport};
+use crate::config_lint::{
+    find_dead_rows, lint_precedence, validate_row_conflicts_report, validate_rule_coverage_report, DeadRow,
+    LintFinding, UnusedRuleAttr,
+};
+use crate::config_precidence_rules::{
+    convert_matrix_row_refs, validate_ranks, ConfigPrecedenceRule, ConversionOptions, MatrixRowRef, RankScheme,
+};
+use crate::config_types::ConfigEnvelope;
+use crate::config_value::{AttrRole, ConfigValue};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// (code, kind) of the warnings the `_findings` functions report, `xx50` and up in
+/// the group of [`PrecedenceConfigError::code`] their check belongs to.
+pub const WARNING_CODES: [(&str, &str); 9] = [
+    ("PC0050", "unknown_attr_skipped"),
+    ("PC0150", "duplicate_rank"),
+    ("PC0151", "shadowed_rank"),
+    ("PC0152", "catch_all_above_specific"),
+    ("PC0153", "unused_rule_attr"),
+    ("PC0250", "deprecated_attr"),
+    ("PC0251", "unused_attr"),
+    ("PC0450", "unreachable_row"),
+    ("PC0451", "shadowed_row"),
+];
+
+/// How much a finding matters, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, nothing to fix.
+    Info,
+    /// The config loads and resolves, but probably not as meant.
+    Warning,
+    /// The config is rejected.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// One result of a validation pass.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Stable code (`"PC0302"`, `"PC0151"`): for errors the
+    /// [`PrecedenceConfigError::code`], for warnings one of [`WARNING_CODES`].
+    pub code: String,
+    /// Snake_case name of the code (`"wrong_role"`, `"shadowed_rank"`).
+    pub kind: String,
+    /// JSON Pointer of the offending field in the checked document, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(
+        severity: Severity,
+        code: impl Into<String>,
+        kind: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self { severity, code: code.into(), kind: kind.into(), path: None, message: message.into() }
+    }
+
+    /// An error finding for `err`, coded after `err` itself.
+    fn error(err: &PrecedenceConfigError, message: String) -> Self {
+        Finding::new(Severity::Error, err.code(), err.kind(), message)
+    }
+
+    /// A warning finding of `kind`, one of [`WARNING_CODES`].
+    fn warning(kind: &str, message: String) -> Self {
+        let (code, _) = WARNING_CODES.iter().find(|(_, k)| *k == kind).expect("kind is a known warning");
+        Finding::new(Severity::Warning, *code, kind, message)
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{} {}]", self.severity, self.code, self.kind)?;
+        if let Some(path) = &self.path {
+            write!(f, " at {}", path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// An error, coded after the error itself; a row error of an envelope after its
+/// cause, with the row's pointer as path.
+impl From<&PrecedenceConfigError> for Finding {
+    fn from(err: &PrecedenceConfigError) -> Self {
+        match err {
+            PrecedenceConfigError::InvalidEnvelopeRow { row, pointer, match_tuple, source } => {
+                let message = format!("Row {} {}: {}", row, render_tuple(match_tuple), source);
+                Finding::error(source, message).with_path(pointer.as_str())
+            }
+            PrecedenceConfigError::InvalidParam { index, source, .. } => {
+                Finding::error(source, err.to_string()).with_path(format!("/{}", index))
+            }
+            PrecedenceConfigError::InvalidRank { index, .. } => {
+                Finding::error(err, err.to_string()).with_path(format!("/{}/rank", index))
+            }
+            _ => Finding::error(err, err.to_string()),
+        }
+    }
+}
+
+impl From<&DeprecationWarning> for Finding {
+    fn from(warning: &DeprecationWarning) -> Self {
+        Finding::warning("deprecated_attr", warning.to_string())
+    }
+}
+
+impl From<&LintFinding> for Finding {
+    fn from(finding: &LintFinding) -> Self {
+        let kind = match finding {
+            LintFinding::DuplicateRank { .. } => "duplicate_rank",
+            LintFinding::ShadowedRank { .. } => "shadowed_rank",
+            LintFinding::CatchAllAboveSpecific { .. } => "catch_all_above_specific",
+        };
+        Finding::warning(kind, finding.to_string())
+    }
+}
+
+impl From<&UnusedRuleAttr> for Finding {
+    fn from(unused: &UnusedRuleAttr) -> Self {
+        Finding::warning("unused_rule_attr", unused.to_string())
+    }
+}
+
+impl From<&DeadRow> for Finding {
+    fn from(dead: &DeadRow) -> Self {
+        let kind = match dead {
+            DeadRow::NoMatchingRank { .. } => "unreachable_row",
+            DeadRow::Shadowed { .. } => "shadowed_row",
+        };
+        Finding::warning(kind, dead.to_string())
+    }
+}
+
+/// The findings of a validation pass, in the order found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Findings {
+    pub findings: Vec<Finding>,
+}
+
+impl Findings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, finding: Finding) {
+        self.findings.push(finding);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Finding> {
+        self.findings.iter()
+    }
+
+    /// Findings at `severity` or above.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |finding| finding.severity >= severity)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|finding| finding.severity == severity).count()
+    }
+
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.fails(Severity::Error)
+    }
+
+    /// Whether a gate at `threshold` fails: some finding is at `threshold` or above.
+    pub fn fails(&self, threshold: Severity) -> bool {
+        self.at_least(threshold).next().is_some()
+    }
+
+    /// Drops the findings whose code is in `codes`, e.g. a suppression list kept
+    /// next to a config (`["PC0251"]`).
+    pub fn suppress<S: AsRef<str>>(&mut self, codes: &[S]) {
+        self.findings.retain(|finding| !codes.iter().any(|code| code.as_ref() == finding.code));
+    }
+}
+
+impl Extend<Finding> for Findings {
+    fn extend<I: IntoIterator<Item = Finding>>(&mut self, findings: I) {
+        self.findings.extend(findings);
+    }
+}
+
+impl FromIterator<Finding> for Findings {
+    fn from_iter<I: IntoIterator<Item = Finding>>(findings: I) -> Self {
+        Self { findings: findings.into_iter().collect() }
+    }
+}
+
+impl From<&ValidationReport> for Findings {
+    fn from(report: &ValidationReport) -> Self {
+        report.errors.iter().map(Finding::from).collect()
+    }
+}
+
+impl fmt::Display for Findings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} error(s), {} warning(s), {} info",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info)
+        )?;
+        for finding in &self.findings {
+            write!(f, "\n  - {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks an envelope: every bad row (see
+/// [`validate_envelope_report`]) and rows with identical match values are errors;
+/// deprecated attributes and catalog attributes no row uses are warnings. An
+/// attribute with a default, or deprecated, doesn't count as unused. Warnings
+/// are only looked for once the rows are valid.
+pub fn envelope_findings(envelope: &ConfigEnvelope, registry: &AttrRegistry, options: &EnvelopeOptions) -> Findings {
+    let validated = match validate_envelope_report(envelope, registry, options) {
+        Ok(validated) => validated,
+        Err(report) => return Findings::from(&report),
+    };
+    let mut findings = match validate_row_conflicts_report(&validated.values) {
+        Ok(()) => Findings::new(),
+        Err(report) => Findings::from(&report),
+    };
+    findings.extend(validated.warnings.iter().map(Finding::from));
+
+    let used: HashSet<i32> = validated.values.iter().map(|v| v.attr_id).collect();
+    let unused = registry
+        .iter()
+        .filter(|meta| !used.contains(&meta.attr_id) && meta.default.is_none() && meta.deprecated.is_none());
+    findings.extend(unused.map(|meta| {
+        let message = format!("Attribute '{}' ({}) is never used in any row", meta.attr_name, meta.role);
+        Finding::warning("unused_attr", message)
+    }));
+    findings
+}
+
+/// Checks a JSON precedence matrix: what [`matrix_json_to_tall_report`] rejects,
+/// and with a `scheme` a rank layout [`validate_ranks`] rejects, are errors.
+/// Columns missing from the catalog are errors with `strict_unknown_attrs` and
+/// warnings (the conversion skips them) without; deprecated columns and what
+/// [`lint_precedence`] finds are warnings too.
+///
+/// [`matrix_json_to_tall_report`]: crate::config_precidence_rules::matrix_json_to_tall_report
+pub fn matrix_findings(
+    json: &str,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    scheme: Option<&RankScheme>,
+) -> Findings {
+    let matrix: Vec<MatrixRowRef<'_>> = match serde_json::from_str(json) {
+        Ok(matrix) => matrix,
+        Err(err) => return Findings::from_iter([Finding::from(&PrecedenceConfigError::InvalidJson(err))]),
+    };
+    let mut findings = Findings::new();
+    if !options.strict_unknown_attrs {
+        for (index, row) in matrix.iter().enumerate() {
+            for (column, _) in row.attrs.iter().filter(|(column, _)| registry.id_of(column).is_none()) {
+                let message = format!("Column '{}' at rank {} is not a known attribute; skipped", column, row.rank);
+                let path = format!("/{}/{}", index, column.replace('~', "~0").replace('/', "~1"));
+                findings.push(Finding::warning("unknown_attr_skipped", message).with_path(path));
+            }
+        }
+    }
+
+    let mut report = ValidationReport::collect_all();
+    let outcome = convert_matrix_row_refs(&matrix, 1, registry, options, &mut report);
+    let tall = match report.finish(outcome) {
+        Ok(tall) => tall,
+        Err(report) => {
+            findings.extend(Findings::from(&report).findings);
+            return findings;
+        }
+    };
+    if let Some(scheme) = scheme {
+        let attr_count = registry.with_role(AttrRole::Match).count();
+        if let Err(err) = validate_ranks(&tall, attr_count, scheme) {
+            findings.push(Finding::from(&err));
+        }
+    }
+    findings.extend(rule_deprecations(&tall, registry).iter().map(Finding::from));
+    findings.extend(lint_precedence(&tall).iter().map(Finding::from));
+    findings
+}
+
+/// Cross-checks one version's rows against its precedence rules: an attribute
+/// rows constrain but no rank matches exactly is an error (see
+/// [`validate_rule_coverage_report`]); attributes exact at some rank that no
+/// row constrains, and rows no context resolves to ([`find_dead_rows`]), are
+/// warnings.
+pub fn coverage_findings(rules: &[ConfigPrecedenceRule], values: &[ConfigValue]) -> Findings {
+    let mut findings = match validate_rule_coverage_report(rules, values) {
+        Ok(unused) => unused.iter().map(Finding::from).collect(),
+        Err(report) => Findings::from(&report),
+    };
+    findings.extend(find_dead_rows(rules, values).iter().map(Finding::from));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::region_channel_registry;
+    use serde_json::json;
+
+    fn envelope_of(rows: serde_json::Value) -> ConfigEnvelope {
+        serde_json::from_value(json!({ "config": { "name": "c", "version": 1, "version_name": "v1" }, "rows": rows }))
+            .unwrap()
+    }
+
+    fn codes(findings: &Findings) -> Vec<&str> {
+        findings.iter().map(|finding| finding.code.as_str()).collect()
+    }
+
+    #[test]
+    fn gates_by_severity_and_suppresses_by_code() {
+        let mut findings = Findings::from_iter([
+            Finding::new(Severity::Info, "PC9999", "note", "fyi"),
+            Finding::warning("unused_attr", "unused".to_string()),
+        ]);
+        assert_eq!(findings.max_severity(), Some(Severity::Warning));
+        assert!(!findings.has_errors());
+        assert!(findings.fails(Severity::Warning));
+        assert_eq!(findings.at_least(Severity::Warning).count(), 1);
+
+        findings.push(Finding::from(&PrecedenceConfigError::UnknownAttrKey { key: "planet".to_string() }));
+        assert!(findings.has_errors());
+        assert_eq!((findings.count(Severity::Error), findings.count(Severity::Info)), (1, 1));
+        findings.suppress(&["PC0251", "PC9999"]);
+        assert_eq!(codes(&findings), ["PC0301"]);
+        assert_eq!(findings.findings[0].kind, "unknown_attr_key");
+        assert_eq!(Findings::new().max_severity(), None);
+    }
+
+    #[test]
+    fn renders_severity_code_and_path() {
+        let finding = Finding::warning("shadowed_row", "never matched".to_string()).with_path("/rows/3");
+        assert_eq!(finding.to_string(), "warning[PC0451 shadowed_row] at /rows/3: never matched");
+        let findings = Findings::from_iter([finding]);
+        assert!(findings.to_string().starts_with("0 error(s), 1 warning(s), 0 info\n  - warning[PC0451"));
+        assert_eq!(serde_json::to_value(Severity::Warning).unwrap(), json!("warning"));
+    }
+
+    #[test]
+    fn row_errors_are_coded_after_their_cause() {
+        let findings = envelope_findings(
+            &envelope_of(json!([{ "match": { "planet": "mars" }, "params": [] }])),
+            &region_channel_registry(),
+            &EnvelopeOptions::default(),
+        );
+        assert_eq!(codes(&findings), ["PC0301"]);
+        assert_eq!(findings.findings[0].path.as_deref(), Some("/rows/0/match/planet"));
+    }
+
+    #[test]
+    fn unused_attrs_are_warnings() {
+        let rows = json!([{ "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 5 }] }]);
+        let findings = envelope_findings(&envelope_of(rows), &region_channel_registry(), &EnvelopeOptions::default());
+        assert!(!findings.has_errors(), "{findings}");
+        assert_eq!(codes(&findings), ["PC0251"]);
+        assert!(findings.findings[0].message.contains("'channel'"), "{findings}");
+    }
+
+    #[test]
+    fn unknown_columns_are_warnings_unless_strict() {
+        let json = r#"[{ "rank": 1, "region": 1, "channel": 1, "planet": 1 }, { "rank": 2, "region": 1 }]"#;
+        let findings = matrix_findings(json, &region_channel_registry(), &ConversionOptions::default(), None);
+        assert!(!findings.has_errors(), "{findings}");
+        assert!(codes(&findings).contains(&"PC0050"), "{findings}");
+        let skipped = findings.iter().find(|finding| finding.code == "PC0050").unwrap();
+        assert_eq!(skipped.path.as_deref(), Some("/0/planet"));
+
+        let strict = ConversionOptions { strict_unknown_attrs: true };
+        let findings = matrix_findings(json, &region_channel_registry(), &strict, None);
+        assert!(findings.has_errors(), "{findings}");
+        assert!(!codes(&findings).contains(&"PC0050"), "{findings}");
+    }
+
+    #[test]
+    fn duplicate_ranks_are_warnings() {
+        let json = r#"[{ "rank": 1, "region": 1, "channel": 1 }, { "rank": 2, "region": 1, "channel": 1 }]"#;
+        let findings = matrix_findings(json, &region_channel_registry(), &ConversionOptions::default(), None);
+        assert!(!findings.has_errors(), "{findings}");
+        assert!(codes(&findings).contains(&"PC0150"), "{findings}");
+        let findings = matrix_findings("not json", &region_channel_registry(), &ConversionOptions::default(), None);
+        assert_eq!(codes(&findings), ["PC0501"]);
+    }
+}
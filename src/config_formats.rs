@@ -0,0 +1,468 @@
+//! NDJSON, YAML (`yaml` feature) and TOML (`toml` feature) front-ends for the
+//! envelope and matrix formats. They only swap the parser: matrices go through
+//! the same conversion/validation as `matrix_json_to_tall`, envelopes
+//! deserialize into the same `ConfigEnvelope` for `validate_envelope`.
+//! `render_matrix` prints a matrix as a table for people to read.
+
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+#[cfg(any(feature = "yaml", feature = "toml"))]
+use crate::config_precidence_rules::convert_matrix_rows;
+use crate::config_precidence_rules::{
+    tall_to_matrix_rows_with_options, ConfigPrecedenceRule, ConversionOptions, MatrixRow, MatrixRowConverter,
+};
+use crate::config_types::{ConfigEnvelope, ConfigMeta, ConfigRow, ENVELOPE_SCHEMA_VERSION};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, BufRead};
+
+/// Calls `on_record` with each non-blank line of `reader` parsed as JSON, and
+/// tags any error (parse or from `on_record`) with its 1-based line number.
+fn read_ndjson<T: DeserializeOwned>(reader: impl io::Read, mut on_record: impl FnMut(T) -> Result<()>) -> Result<()> {
+    for (line, text) in (1..).zip(io::BufReader::new(reader).lines()) {
+        let text = text.map_err(PrecedenceConfigError::Io)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        serde_json::from_str(&text)
+            .map_err(ndjson_error)
+            .and_then(&mut on_record)
+            .map_err(|e| PrecedenceConfigError::InvalidNdjsonLine { line, source: Box::new(e) })?;
+    }
+    Ok(())
+}
+
+fn ndjson_error(e: serde_json::Error) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "NDJSON", reason: e.to_string() }
+}
+
+fn write_ndjson<'a, T: Serialize + 'a>(
+    records: impl IntoIterator<Item = &'a T>,
+    mut writer: impl io::Write,
+) -> Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut writer, record).map_err(PrecedenceConfigError::InvalidJson)?;
+        writer.write_all(b"\n").map_err(PrecedenceConfigError::Io)?;
+    }
+    writer.flush().map_err(PrecedenceConfigError::Io)
+}
+
+/// Converts a matrix given as one `{ "rank": .., <attr>: 0|1, .. }` object per
+/// line, validating each line as it is read. Blank lines are skipped.
+pub fn matrix_ndjson_to_tall(
+    reader: impl io::Read,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut report = ValidationReport::fail_fast();
+    let mut converter = MatrixRowConverter::new(config_version_id, registry, options);
+    let mut tall = Vec::new();
+    read_ndjson(reader, |row: MatrixRow| {
+        converter.convert(row.rank, &row.attrs, &mut report, &mut |rule| tall.push(rule))
+    })?;
+    converter.finish(&mut report)?;
+    Ok(tall)
+}
+
+/// Reads tall rules, one `ConfigPrecedenceRule` object per line.
+pub fn ndjson_to_tall(reader: impl io::Read) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut tall = Vec::new();
+    read_ndjson(reader, |rule| {
+        tall.push(rule);
+        Ok(())
+    })?;
+    Ok(tall)
+}
+
+/// Writes tall rules, one object per line.
+pub fn tall_to_ndjson(tall: &[ConfigPrecedenceRule], writer: impl io::Write) -> Result<()> {
+    write_ndjson(tall, writer)
+}
+
+/// Writes the matrix form of `tall`, one rank per line.
+pub fn tall_to_matrix_ndjson(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    writer: impl io::Write,
+) -> Result<()> {
+    let rows = tall_to_matrix_rows_with_options(tall, registry, options)?;
+    write_ndjson(&rows, writer)
+}
+
+/// An envelope as NDJSON: the `config` object on the first line, then one row per line.
+impl ConfigEnvelope {
+    pub fn from_ndjson(reader: impl io::Read) -> Result<Self> {
+        let mut config: Option<ConfigMeta> = None;
+        let mut rows = Vec::new();
+        read_ndjson(reader, |record: serde_json::Value| {
+            if config.is_none() {
+                config = Some(serde_json::from_value(record).map_err(ndjson_error)?);
+            } else {
+                rows.push(serde_json::from_value::<ConfigRow>(record).map_err(ndjson_error)?);
+            }
+            Ok(())
+        })?;
+
+        let config = config.ok_or_else(|| PrecedenceConfigError::InvalidFormat {
+            format: "NDJSON",
+            reason: "missing the config header line".to_string(),
+        })?;
+        Ok(Self { schema_version: ENVELOPE_SCHEMA_VERSION, config, rows })
+    }
+
+    pub fn to_ndjson(&self, mut writer: impl io::Write) -> Result<()> {
+        write_ndjson([&self.config], &mut writer)?;
+        write_ndjson(&self.rows, writer)
+    }
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_error(e: serde_yaml::Error) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "YAML", reason: e.to_string() }
+}
+
+#[cfg(feature = "yaml")]
+impl ConfigEnvelope {
+    pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml).map_err(yaml_error)
+    }
+
+    pub fn to_yaml_string(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(yaml_error)
+    }
+}
+
+/// YAML counterpart of `matrix_json_to_tall_with_options`; expects a sequence of
+/// `{ rank, <attr>: 0|1, ... }` mappings.
+#[cfg(feature = "yaml")]
+pub fn matrix_yaml_to_tall(
+    yaml: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let matrix_rows: Vec<MatrixRow> = serde_yaml::from_str(yaml).map_err(yaml_error)?;
+    let mut report = ValidationReport::fail_fast();
+    convert_matrix_rows(matrix_rows, config_version_id, registry, options, &mut report)
+}
+
+#[cfg(feature = "yaml")]
+pub fn tall_to_matrix_yaml(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<String> {
+    let rows = tall_to_matrix_rows_with_options(tall, registry, options)?;
+    serde_yaml::to_string(&rows).map_err(yaml_error)
+}
+
+/// TOML has no top-level arrays, so a matrix lives under `precedence_rank`.
+#[cfg(feature = "toml")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct TomlMatrix {
+    precedence_rank: Vec<MatrixRow>,
+}
+
+#[cfg(feature = "toml")]
+fn toml_error(e: impl std::fmt::Display) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "TOML", reason: e.to_string() }
+}
+
+#[cfg(feature = "toml")]
+impl ConfigEnvelope {
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(toml_error)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string(self).map_err(toml_error)
+    }
+}
+
+/// TOML counterpart of `matrix_json_to_tall_with_options`. The ranks live under
+/// `precedence_rank` (the envelope JSON's key for them):
+///  ```toml
+/// [[precedence_rank]]
+/// rank = 1
+/// col_1 = 1
+/// col_2 = 1
+/// ```
+#[cfg(feature = "toml")]
+pub fn matrix_toml_to_tall(
+    toml: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let matrix: TomlMatrix = toml::from_str(toml).map_err(toml_error)?;
+    let mut report = ValidationReport::fail_fast();
+    convert_matrix_rows(matrix.precedence_rank, config_version_id, registry, options, &mut report)
+}
+
+#[cfg(feature = "toml")]
+pub fn tall_to_matrix_toml(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<String> {
+    let precedence_rank = tall_to_matrix_rows_with_options(tall, registry, options)?;
+    toml::to_string(&TomlMatrix { precedence_rank }).map_err(toml_error)
+}
+
+/// Layout of [`render_matrix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableFormat {
+    /// Boxed with `+`, `-` and `|`, for terminals.
+    #[default]
+    Ascii,
+    /// A GitHub-flavoured Markdown table, for PR descriptions.
+    Markdown,
+}
+
+/// Renders a matrix as an aligned table for reading: one line per rank, one
+/// column per attribute (in first-seen order across rows), `✓` for an exact cell
+/// and `·` for an ignored one. Cells a row doesn't have are blank; match types
+/// other than 0 and 1 are shown as numbers.
+pub fn render_matrix(rows: &[MatrixRow], format: TableFormat) -> String {
+    let mut columns: Vec<&str> = Vec::new();
+    for row in rows {
+        for (name, _) in &row.attrs {
+            if !columns.contains(&name.as_str()) {
+                columns.push(name);
+            }
+        }
+    }
+
+    let mut lines: Vec<Vec<String>> = Vec::with_capacity(rows.len() + 1);
+    lines.push(std::iter::once("rank").chain(columns.iter().copied()).map(str::to_string).collect());
+    for row in rows {
+        let cells = columns.iter().map(|name| match row.get(name) {
+            Some(0) => "·".to_string(),
+            Some(1) => "✓".to_string(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        });
+        lines.push(std::iter::once(row.rank.to_string()).chain(cells).collect());
+    }
+    // a Markdown rule cell needs three dashes
+    let min_width = if format == TableFormat::Markdown { 3 } else { 1 };
+    let widths: Vec<usize> = (0..=columns.len())
+        .map(|i| lines.iter().map(|line| line[i].chars().count()).max().unwrap_or(0).max(min_width))
+        .collect();
+
+    // rank right-aligned, cells centered; format widths count chars, so `✓` pads right
+    let render_line = |line: &[String]| -> String {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &w))| if i == 0 { format!(" {:>w$} ", cell) } else { format!(" {:^w$} ", cell) })
+            .collect();
+        format!("|{}|\n", cells.join("|"))
+    };
+    let mut out = String::new();
+    match format {
+        TableFormat::Ascii => {
+            let border: String = widths.iter().map(|w| format!("+{}", "-".repeat(w + 2))).collect::<String>() + "+\n";
+            out.push_str(&border);
+            out.push_str(&render_line(&lines[0]));
+            out.push_str(&border);
+            for line in &lines[1..] {
+                out.push_str(&render_line(line));
+            }
+            out.push_str(&border);
+        }
+        TableFormat::Markdown => {
+            out.push_str(&render_line(&lines[0]));
+            let rule: Vec<String> = widths
+                .iter()
+                .enumerate()
+                // `rank` is 4 wide, so its rule cell keeps three dashes before the colon
+                .map(|(i, &w)| {
+                    if i == 0 { format!(" {}: ", "-".repeat(w - 1)) } else { format!(":{}:", "-".repeat(w)) }
+                })
+                .collect();
+            out.push_str(&format!("|{}|\n", rule.join("|")));
+            for line in &lines[1..] {
+                out.push_str(&render_line(line));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::test_fixtures::region_channel_registry;
+
+    const MATRIX: &str = r#"[
+        { "rank": 1, "region": 1, "channel": 1 },
+        { "rank": 2, "region": 1, "channel": 0 },
+        { "rank": 3, "region": 0, "channel": 1 }
+    ]"#;
+
+    fn envelope() -> ConfigEnvelope {
+        serde_json::from_value(serde_json::json!({
+            "config": { "name": "c", "version": 2, "version_name": "v2" },
+            "rows": [
+                {
+                    "match": { "region": "eu", "channel": "ALL" },
+                    "params": [{ "key": "limit", "type": "int", "value": 5 }],
+                },
+                { "match": { "region": "us" }, "params": [] },
+            ],
+        }))
+        .unwrap()
+    }
+
+    /// Export orders the columns by name, so compare rules by (rank, attr_id).
+    fn sorted(mut tall: Vec<ConfigPrecedenceRule>) -> Vec<ConfigPrecedenceRule> {
+        tall.sort_by_key(|rule| (rule.rank, rule.attr_id));
+        tall
+    }
+
+    fn format_of(err: PrecedenceConfigError) -> &'static str {
+        match err {
+            PrecedenceConfigError::InvalidFormat { format, .. } => format,
+            other => panic!("expected InvalidFormat, got {other:?}"),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_matrices_convert_like_json() {
+        let registry = region_channel_registry();
+        let expected = sorted(matrix_json_to_tall(MATRIX, 1, &registry).unwrap());
+        let yaml = tall_to_matrix_yaml(&expected, &registry, &ConversionOptions::default()).unwrap();
+        assert!(yaml.contains("region: 1"), "{yaml}");
+        assert_eq!(sorted(matrix_yaml_to_tall(&yaml, 1, &registry, &ConversionOptions::default()).unwrap()), expected);
+
+        let strict = ConversionOptions { strict_unknown_attrs: true };
+        let err = matrix_yaml_to_tall("- { rank: 1, planet: 1 }", 1, &registry, &strict).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::UnknownRuleAttr { rank: 1, .. }), "{err}");
+        assert_eq!(format_of(matrix_yaml_to_tall("rank: [", 1, &registry, &strict).unwrap_err()), "YAML");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_envelopes_round_trip() {
+        let envelope = envelope();
+        let yaml = envelope.to_yaml_string().unwrap();
+        let back = ConfigEnvelope::from_yaml_str(&yaml).unwrap();
+        assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(envelope).unwrap());
+        assert_eq!(format_of(ConfigEnvelope::from_yaml_str("config: [").unwrap_err()), "YAML");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_matrices_convert_like_json() {
+        let registry = region_channel_registry();
+        let expected = sorted(matrix_json_to_tall(MATRIX, 1, &registry).unwrap());
+        let toml = tall_to_matrix_toml(&expected, &registry, &ConversionOptions::default()).unwrap();
+        assert!(toml.contains("[[precedence_rank]]"), "{toml}");
+        assert_eq!(sorted(matrix_toml_to_tall(&toml, 1, &registry, &ConversionOptions::default()).unwrap()), expected);
+        let err = matrix_toml_to_tall("rank = 1", 1, &registry, &ConversionOptions::default()).unwrap_err();
+        assert_eq!(format_of(err), "TOML");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_envelopes_round_trip() {
+        let envelope = envelope();
+        let toml = envelope.to_toml_string().unwrap();
+        let back = ConfigEnvelope::from_toml_str(&toml).unwrap();
+        assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(envelope).unwrap());
+        assert_eq!(format_of(ConfigEnvelope::from_toml_str("config = 1").unwrap_err()), "TOML");
+    }
+
+#[cfg(test)]
+    #[test]
+    fn ndjson_matrices_convert_like_json() {
+        let registry = region_channel_registry();
+        let options = ConversionOptions::default();
+        let expected = sorted(matrix_json_to_tall(MATRIX, 1, &registry).unwrap());
+        let mut out = Vec::new();
+        tall_to_matrix_ndjson(&expected, &registry, &options, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        let padded = format!("\n{}\n  \n", text);
+        assert_eq!(sorted(matrix_ndjson_to_tall(padded.as_bytes(), 1, &registry, &options).unwrap()), expected);
+
+        let mut out = Vec::new();
+        tall_to_ndjson(&expected, &mut out).unwrap();
+        assert_eq!(ndjson_to_tall(out.as_slice()).unwrap(), expected);
+    }
+
+    #[test]
+    fn ndjson_errors_carry_the_line_number() {
+        let registry = region_channel_registry();
+        let text = "{ \"rank\": 1, \"region\": 1 }\n\n{ \"rank\": 2, \"region\": 1, \"channel\": 2 }\n";
+        let err = matrix_ndjson_to_tall(text.as_bytes(), 1, &registry, &ConversionOptions::default()).unwrap_err();
+        let PrecedenceConfigError::InvalidNdjsonLine { line, source } = err else {
+            panic!("expected a line error, got {err:?}");
+        };
+        assert_eq!(line, 3);
+        assert!(matches!(*source, PrecedenceConfigError::InvalidMatchType { rank: 2, .. }), "{source}");
+
+        let text = "{ \"config_version_id\": 1, \"rank\": 1, \"attr_id\": 1, \"match_type\": 1 }\n{";
+        let err = ndjson_to_tall(text.as_bytes()).unwrap_err();
+        let PrecedenceConfigError::InvalidNdjsonLine { line: 2, source } = err else {
+            panic!("expected a line 2 error, got {err:?}");
+        };
+        assert_eq!(format_of(*source), "NDJSON");
+    }
+
+    #[test]
+    fn ndjson_envelopes_round_trip() {
+        let envelope = envelope();
+        let mut out = Vec::new();
+        envelope.to_ndjson(&mut out).unwrap();
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 3, "the config line, then one line per row");
+        let back = ConfigEnvelope::from_ndjson(out.as_slice()).unwrap();
+        assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(envelope).unwrap());
+        assert_eq!(format_of(ConfigEnvelope::from_ndjson("\n".as_bytes()).unwrap_err()), "NDJSON");
+    }
+
+    /// Rank 10 has no region cell and a match type other than 0 and 1.
+    fn render_rows() -> Vec<MatrixRow> {
+        serde_json::from_str(
+            r#"[
+                { "rank": 1, "region": 1, "channel": 1 },
+                { "rank": 2, "region": 1, "channel": 0 },
+                { "rank": 10, "channel": 2 }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matrices_render_as_ascii_tables() {
+        let expected = "\
++------+--------+---------+
+| rank | region | channel |
++------+--------+---------+
+|    1 |   ✓    |    ✓    |
+|    2 |   ✓    |    ·    |
+|   10 |        |    2    |
++------+--------+---------+
+";
+        assert_eq!(render_matrix(&render_rows(), TableFormat::Ascii), expected);
+    }
+
+    #[test]
+    fn matrices_render_as_markdown_tables() {
+        let expected = "\
+| rank | region | channel |
+| ---: |:------:|:-------:|
+|    1 |   ✓    |    ✓    |
+|    2 |   ✓    |    ·    |
+|   10 |        |    2    |
+";
+        assert_eq!(render_matrix(&render_rows(), TableFormat::Markdown), expected);
+        assert_eq!(render_matrix(&[], TableFormat::Markdown), "| rank |\n| ---: |\n");
+    }
+}
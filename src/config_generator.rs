@@ -0,0 +1,266 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_envelope::validate_envelope;
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use crate::config_precidence_rules::{
+    convert_matrix_rows, generate_triangular_matrix, ConfigPrecedenceRule, ConversionOptions, MatrixRow,
+};
+use crate::config_resolver::Context;
+use crate::config_types::{ConfigEnvelope, ConfigMeta, ConfigRow, MatchPart, Param, ParamType, ENVELOPE_SCHEMA_VERSION};
+use crate::config_value::{AttrMeta, AttrRole, ConfigValue, TypedValue};
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+
+/// Shape of the config [`generate_config`] makes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratorOptions {
+    /// `str` match attributes `attr_1`, `attr_2`, .., most significant first; 1 to 64.
+    pub match_attrs: usize,
+    /// Params `param_1`, `param_2`, .., cycling through `dec`, `int`, `bool`, `str` and `date`.
+    pub params: usize,
+    /// Rows, the default row included; at least 1.
+    pub rows: usize,
+    pub contexts: usize,
+    /// Distinct values of each match attribute; at least 1.
+    pub cardinality: usize,
+    /// The same options and seed give the same config.
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self { match_attrs: 4, params: 3, rows: 1000, contexts: 1000, cardinality: 50, seed: 0 }
+    }
+}
+
+/// A synthetic config version and lookup contexts for it, from [`generate_config`].
+#[derive(Debug, Clone)]
+pub struct GeneratedConfig {
+    pub registry: AttrRegistry,
+    /// Triangular over the match attributes, see [`generate_triangular_matrix`].
+    pub matrix: Vec<MatrixRow>,
+    /// `matrix` as tall rules, with `config_version_id` 1.
+    pub rules: Vec<ConfigPrecedenceRule>,
+    pub envelope: ConfigEnvelope,
+    /// `envelope` validated against `registry`.
+    pub values: Vec<ConfigValue>,
+    pub contexts: Vec<Context>,
+}
+
+/// Generates a valid config for load, soak and integration tests that should
+/// not depend on production data.
+///
+/// The first row is a default row, so every context resolves. Every other row
+/// takes the exact attributes of a random rank and leaves the rest as
+/// wildcards; match values are skewed towards the first few of each
+/// attribute's `cardinality` values, as real traffic is, and rows repeating a
+/// match tuple are dropped, so small cardinalities can leave fewer than `rows`.
+/// Each context starts from a random row's values and fills the attributes the
+/// row leaves open from the same skewed distribution.
+pub fn generate_config(options: &GeneratorOptions) -> Result<GeneratedConfig> {
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidGeneratorOptions { reason: reason.to_string() };
+    if !(1..=64).contains(&options.match_attrs) {
+        return Err(invalid("match_attrs must be 1 to 64"));
+    }
+    if options.rows == 0 {
+        return Err(invalid("rows must be at least 1"));
+    }
+    if options.cardinality == 0 {
+        return Err(invalid("cardinality must be at least 1"));
+    }
+
+    let mut rng = SplitMix64(options.seed);
+    let match_names: Vec<String> = (1..=options.match_attrs).map(|i| format!("attr_{}", i)).collect();
+    let params: Vec<(String, ParamType)> = (1..=options.params)
+        .map(|i| (format!("param_{}", i), PARAM_TYPES[(i - 1) % PARAM_TYPES.len()].clone()))
+        .collect();
+
+    let mut metas: Vec<AttrMeta> = Vec::new();
+    for name in &match_names {
+        metas.push(attr_meta(metas.len() as i32 + 1, name, AttrRole::Match, &ParamType::Str));
+    }
+    for (name, ty) in &params {
+        metas.push(attr_meta(metas.len() as i32 + 1, name, AttrRole::Param, ty));
+    }
+    let registry = AttrRegistry::from_metas(metas)?;
+
+    let names: Vec<&str> = match_names.iter().map(String::as_str).collect();
+    let matrix = generate_triangular_matrix(&names);
+    let mut report = ValidationReport::fail_fast();
+    let rules = convert_matrix_rows(matrix.clone(), 1, &registry, &ConversionOptions::default(), &mut report)?;
+
+    let mut rows = Vec::with_capacity(options.rows);
+    let mut tuples = HashSet::new();
+    // the default row, then up to 10 draws per requested row
+    for attempt in 0..options.rows * 10 {
+        if rows.len() == options.rows {
+            break;
+        }
+        let mut attrs = HashMap::new();
+        if attempt > 0 {
+            let rank = &matrix[rng.below(matrix.len())];
+            for (name, match_type) in &rank.attrs {
+                if *match_type == 1 {
+                    attrs.insert(name.clone(), serde_json::Value::String(match_value(&mut rng, name, options)));
+                }
+            }
+        }
+        let mut tuple: Vec<String> = attrs.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        tuple.sort();
+        if !tuples.insert(tuple) {
+            continue;
+        }
+        let params = params
+            .iter()
+            .map(|(key, ty)| {
+                let value = param_value(&mut rng, ty).to_json_value();
+                Param { key: key.into(), ty: ty.clone(), value }
+            })
+            .collect();
+        rows.push(ConfigRow { match_part: MatchPart { attrs }, params, audit: None });
+    }
+
+    let contexts = (0..options.contexts)
+        .map(|_| {
+            let row = &rows[rng.below(rows.len())];
+            match_names
+                .iter()
+                .map(|name| {
+                    let value = match row.match_part.attrs.get(name.as_str()).and_then(|v| v.as_str()) {
+                        Some(value) => value.to_string(),
+                        None => match_value(&mut rng, name, options),
+                    };
+                    (name.clone(), TypedValue::Str(value))
+                })
+                .collect()
+        })
+        .collect();
+
+    let config = ConfigMeta {
+        name: "generated".to_string(),
+        version: 1,
+        version_name: format!("seed-{}", options.seed),
+        status: Default::default(),
+        valid_from: None,
+        valid_to: None,
+        audit: None,
+    };
+    let envelope = ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config, rows };
+    let values = validate_envelope(&envelope, &registry)?.values;
+    Ok(GeneratedConfig { registry, matrix, rules, envelope, values, contexts })
+}
+
+const PARAM_TYPES: [ParamType; 5] = [ParamType::Dec, ParamType::Int, ParamType::Bool, ParamType::Str, ParamType::Date];
+
+const LABELS: [&str; 8] = ["standard", "premium", "basic", "trial", "legacy", "partner", "internal", "promo"];
+
+fn attr_meta(attr_id: i32, attr_name: &str, role: AttrRole, ty: &ParamType) -> AttrMeta {
+    AttrMeta {
+        attr_id,
+        attr_name: attr_name.into(),
+        data_type: ty.to_string(),
+        role,
+        default: None,
+        enum_values: Vec::new(),
+        constraints: None,
+        patterns: false,
+        parents: Default::default(),
+        deprecated: None,
+        aliases: Vec::new(),
+    }
+}
+
+/// `"attr_1-7"`: value 7 of `attr_1`, low values far more likely than high ones.
+fn match_value(rng: &mut SplitMix64, attr_name: &str, options: &GeneratorOptions) -> String {
+    let skewed = rng.unit() * rng.unit();
+    format!("{}-{}", attr_name, (skewed * options.cardinality as f64) as usize)
+}
+
+fn param_value(rng: &mut SplitMix64, ty: &ParamType) -> TypedValue {
+    match ty {
+        // prices: mostly tens, some hundreds, in cents
+        ParamType::Dec => TypedValue::Dec((10f64.powf(1.0 + rng.unit() * 1.5) * 100.0).round() / 100.0),
+        ParamType::Int => TypedValue::Int(rng.below(1000) as i64),
+        ParamType::Bool => TypedValue::Bool(rng.unit() < 0.8),
+        ParamType::Str => TypedValue::Str(LABELS[rng.below(LABELS.len())].to_string()),
+        // Date, the last of PARAM_TYPES
+        _ => {
+            let first = NaiveDate::from_ymd_opt(2026, 1, 1).expect("valid");
+            TypedValue::Date(first + chrono::Days::new(rng.below(365) as u64))
+        }
+    }
+}
+
+/// The SplitMix64 generator: fast, seedable and good enough for test data.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, for `n > 0`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_resolver::Resolver;
+
+    fn options(rows: usize, cardinality: usize, seed: u64) -> GeneratorOptions {
+        GeneratorOptions { match_attrs: 3, params: 5, rows, contexts: 50, cardinality, seed }
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_config() {
+        let generated = generate_config(&options(40, 20, 7)).unwrap();
+        assert_eq!(generated.envelope, generate_config(&options(40, 20, 7)).unwrap().envelope);
+        assert_ne!(generated.envelope, generate_config(&options(40, 20, 8)).unwrap().envelope);
+        assert_eq!(generated.contexts, generate_config(&options(40, 20, 7)).unwrap().contexts);
+    }
+
+    #[test]
+    fn the_config_validates_and_every_context_resolves() {
+        let generated = generate_config(&options(40, 20, 1)).unwrap();
+        assert_eq!((generated.registry.len(), generated.matrix.len()), (8, 6));
+        let keys: Vec<&str> = generated.envelope.rows[0].params.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["param_1", "param_2", "param_3", "param_4", "param_5"]);
+        assert_eq!(generated.envelope.rows.len(), 40);
+        assert!(generated.envelope.rows[0].match_part.attrs.is_empty());
+        assert_eq!(generated.contexts.len(), 50);
+        let resolver = Resolver::new(&generated.rules, &generated.values, &generated.registry).unwrap();
+        for context in &generated.contexts {
+            assert!(resolver.try_resolve(context).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn repeated_match_tuples_are_dropped() {
+        // one value per attribute leaves the default row and one row per rank
+        let generated = generate_config(&options(40, 1, 3)).unwrap();
+        assert_eq!(generated.envelope.rows.len(), 1 + generated.matrix.len());
+    }
+
+    #[test]
+    fn bad_options_are_rejected() {
+        for bad in [
+            GeneratorOptions { match_attrs: 0, ..GeneratorOptions::default() },
+            GeneratorOptions { match_attrs: 65, ..GeneratorOptions::default() },
+            GeneratorOptions { rows: 0, ..GeneratorOptions::default() },
+            GeneratorOptions { cardinality: 0, ..GeneratorOptions::default() },
+        ] {
+            assert!(matches!(generate_config(&bad), Err(PrecedenceConfigError::InvalidGeneratorOptions { .. })));
+        }
+    }
+}
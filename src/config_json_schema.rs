@@ -0,0 +1,327 @@
+//! JSON Schemas (draft 2020-12) of the documents this crate reads, built from
+//! an attribute catalog, so forms, validators and code in other languages can
+//! check a document before sending it.
+//!
+//! The schemas know the catalog: match keys, param keys and matrix columns must
+//! be registered attributes (or their aliases) of the right role, and values must
+//! take the JSON form of their `data_type`, within the enum values and numeric and
+//! length constraints. What needs more than one value at a time (repeated param
+//! keys, the precedence table as a whole, validity windows) is left to validation.
+
+use crate::config_attr::AttrRegistry;
+use crate::config_error::Result;
+use crate::config_resolver::WILDCARD;
+use crate::config_types::{ParamType, ENVELOPE_SCHEMA_VERSION};
+use crate::config_value::{AttrMeta, AttrRole};
+use serde_json::{json, Map, Value};
+
+/// `$schema` of every schema built here.
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Schema of a [`ConfigEnvelope`](crate::config_types::ConfigEnvelope) in the
+/// current layout. Fails if an attribute's `data_type` doesn't parse.
+pub fn envelope_json_schema(registry: &AttrRegistry) -> Result<Value> {
+    let mut match_attrs = Map::new();
+    for meta in registry.with_role(AttrRole::Match) {
+        let schema = match_value_schema(meta, &meta.data_type.parse()?);
+        for name in names_of(meta) {
+            match_attrs.insert(name.to_string(), schema.clone());
+        }
+    }
+    Ok(json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": "ConfigEnvelope",
+        "type": "object",
+        "required": ["config", "rows"],
+        "properties": {
+            "schema_version": { "const": ENVELOPE_SCHEMA_VERSION },
+            "config": { "$ref": "#/$defs/config" },
+            "rows": { "type": "array", "items": { "$ref": "#/$defs/row" } },
+        },
+        "$defs": {
+            "config": {
+                "type": "object",
+                "required": ["name", "version", "version_name"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "version": { "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX },
+                    "version_name": { "type": "string" },
+                    "status": { "enum": ["draft", "review", "published", "archived"] },
+                    "valid_from": { "type": "string", "format": "date-time" },
+                    "valid_to": { "type": "string", "format": "date-time" },
+                    "audit": { "$ref": "#/$defs/audit" },
+                },
+            },
+            "audit": {
+                "type": "object",
+                "properties": {
+                    "created_by": { "type": "string" },
+                    "created_at": { "type": "string", "format": "date-time" },
+                    "reason": { "type": "string" },
+                },
+            },
+            "row": {
+                "type": "object",
+                "required": ["match", "params"],
+                "properties": {
+                    "match": { "type": "object", "properties": match_attrs, "additionalProperties": false },
+                    "params": { "$ref": "#/$defs/params" },
+                    "audit": { "$ref": "#/$defs/audit" },
+                },
+            },
+            "params": params_schema(registry)?,
+        },
+    }))
+}
+
+/// Schema of a row's `params` array, `[{ "key": .., "type": .., "value": .. }]`.
+pub fn params_json_schema(registry: &AttrRegistry) -> Result<Value> {
+    let mut schema = params_schema(registry)?;
+    let fields = schema.as_object_mut().expect("params schema is an object");
+    fields.insert("$schema".to_string(), JSON_SCHEMA_DIALECT.into());
+    fields.insert("title".to_string(), "params".into());
+    Ok(schema)
+}
+
+/// Schema of a precedence matrix, an array of `{ "rank": n, "<attr>": 0 | 1, .. }`.
+/// Columns must be match attributes, as with `ConversionOptions::strict_unknown_attrs`.
+pub fn matrix_json_schema(registry: &AttrRegistry) -> Value {
+    let mut columns = Map::new();
+    columns.insert("rank".to_string(), json!({ "type": "integer", "minimum": 1, "maximum": i32::MAX }));
+    for meta in registry.with_role(AttrRole::Match) {
+        for name in names_of(meta) {
+            columns.insert(name.to_string(), json!({ "enum": [0, 1] }));
+        }
+    }
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": "precedence matrix",
+        "type": "array",
+        "items": { "type": "object", "required": ["rank"], "properties": columns, "additionalProperties": false },
+    })
+}
+
+fn params_schema(registry: &AttrRegistry) -> Result<Value> {
+    let mut params = Vec::new();
+    for meta in registry.with_role(AttrRole::Param) {
+        let ty: ParamType = meta.data_type.parse()?;
+        params.push(json!({
+            "type": "object",
+            "required": ["key", "type", "value"],
+            "properties": {
+                "key": { "enum": names_of(meta).collect::<Vec<_>>() },
+                "type": { "const": ty.to_string() },
+                "value": nullable(value_schema(meta, &ty)),
+            },
+        }));
+    }
+    Ok(if params.is_empty() {
+        json!({ "type": "array", "maxItems": 0 })
+    } else {
+        json!({ "type": "array", "items": { "oneOf": params } })
+    })
+}
+
+/// An attribute's name, then its aliases.
+fn names_of(meta: &AttrMeta) -> impl Iterator<Item = &str> {
+    std::iter::once(meta.attr_name.as_str()).chain(meta.aliases.iter().map(String::as_str))
+}
+
+/// The JSON forms of a non-null value of type `ty`: its native JSON type or, as
+/// `parse_json_value` also takes, its text form.
+fn value_schema(meta: &AttrMeta, ty: &ParamType) -> Value {
+    let constraints = meta.constraints.as_ref();
+    let mut schema = match ty {
+        ParamType::Int => json!({ "type": ["integer", "string"] }),
+        ParamType::Dec => json!({ "type": ["number", "string"] }),
+        ParamType::Bool => json!({ "type": ["boolean", "string"] }),
+        ParamType::Enum if !meta.enum_values.is_empty() => json!({ "enum": meta.enum_values }),
+        ParamType::Date => json!({ "type": "string", "format": "date" }),
+        #[cfg(feature = "uuid")]
+        ParamType::Uuid => json!({ "type": "string", "format": "uuid" }),
+        ParamType::List(item) => return json!({ "type": "array", "items": value_schema(meta, item) }),
+        _ => json!({ "type": "string" }),
+    };
+    let fields = schema.as_object_mut().expect("value schemas are objects");
+    if let Some(constraints) = constraints {
+        if matches!(ty, ParamType::Int | ParamType::Dec) {
+            if let Some(min) = constraints.min {
+                fields.insert("minimum".to_string(), min.into());
+            }
+            if let Some(max) = constraints.max {
+                fields.insert("maximum".to_string(), max.into());
+            }
+        }
+        if matches!(ty, ParamType::Str) {
+            if let Some(max_len) = constraints.max_len {
+                fields.insert("maxLength".to_string(), max_len.into());
+            }
+            if let Some(pattern) = &constraints.pattern {
+                fields.insert("pattern".to_string(), format!("^(?:{})$", pattern).into());
+            }
+            if let Some(allowed) = &constraints.allowed {
+                fields.insert("enum".to_string(), allowed.clone().into());
+            }
+        }
+    }
+    schema
+}
+
+/// The JSON forms of a match value: a value, `null` or the `"ALL"` wildcard, and
+/// whichever of alternatives, ranges, regexes (with the `regex` feature) and `*`
+/// patterns the type takes.
+fn match_value_schema(meta: &AttrMeta, ty: &ParamType) -> Value {
+    let value = value_schema(meta, ty);
+    let mut forms = vec![value.clone(), json!({ "type": "null" }), json!({ "const": WILDCARD })];
+    if !matches!(ty, ParamType::List(_)) {
+        forms.push(json!({ "type": "array", "minItems": 1, "items": value.clone() }));
+    }
+    if matches!(
+        ty,
+        ParamType::Int | ParamType::Dec | ParamType::Dt | ParamType::Date | ParamType::Time | ParamType::Duration
+    ) {
+        let bounds: Map<String, Value> =
+            ["gt", "gte", "lt", "lte"].iter().map(|end| (end.to_string(), value.clone())).collect();
+        forms.push(json!({
+            "type": "object",
+            "properties": bounds,
+            "minProperties": 1,
+            "additionalProperties": false,
+        }));
+    }
+    if matches!(ty, ParamType::Str | ParamType::Enum) {
+        #[cfg(feature = "regex")]
+        forms.push(json!({
+            "type": "object",
+            "required": ["pattern"],
+            "properties": { "pattern": { "type": "string" } },
+            "additionalProperties": false,
+        }));
+        if meta.patterns {
+            forms.push(json!({ "type": "string", "pattern": "\\*" }));
+        }
+    }
+    json!({ "anyOf": forms })
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::ValueConstraints;
+
+    fn catalog() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.set_aliases("region", ["rgn".to_string()]).unwrap();
+        registry.register("amount", AttrRole::Match, "dec").unwrap();
+        registry.register("limit", AttrRole::Param, "int").unwrap();
+        registry.register("label", AttrRole::Param, "str").unwrap();
+        registry
+    }
+
+    /// `catalog()` with `constraints` on the `attr_name` param.
+    fn constrained(attr_name: &str, constraints: ValueConstraints) -> AttrRegistry {
+        let mut registry = catalog();
+        let mut meta = registry.remove(attr_name).unwrap();
+        meta.constraints = Some(constraints);
+        registry.insert(meta).unwrap();
+        registry
+    }
+
+    /// The `value` schema of the `key` param in a params schema.
+    fn param_value<'a>(params: &'a Value, key: &str) -> &'a Value {
+        let forms = params["items"]["oneOf"].as_array().unwrap();
+        let param = forms.iter().find(|form| form["properties"]["key"]["enum"][0] == key).unwrap();
+        &param["properties"]["value"]["anyOf"][0]
+    }
+
+    #[test]
+    fn envelope_schema_lists_match_attrs_and_their_aliases() {
+        let schema = envelope_json_schema(&catalog()).unwrap();
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DIALECT);
+        assert_eq!(schema["properties"]["schema_version"]["const"], ENVELOPE_SCHEMA_VERSION);
+        let matches = &schema["$defs"]["row"]["properties"]["match"];
+        assert_eq!(matches["additionalProperties"], false);
+        let attrs: Vec<&str> = matches["properties"].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(attrs.len(), 3);
+        for name in ["region", "rgn", "amount"] {
+            assert!(attrs.contains(&name), "{name} missing from {attrs:?}");
+        }
+        assert_eq!(matches["properties"]["rgn"], matches["properties"]["region"]);
+        assert_eq!(schema["$defs"]["params"], params_schema(&catalog()).unwrap());
+    }
+
+    #[test]
+    fn match_values_take_wildcards_alternatives_and_ranges() {
+        let schema = envelope_json_schema(&catalog()).unwrap();
+        let forms = |attr: &str| schema["$defs"]["row"]["properties"]["match"]["properties"][attr]["anyOf"].clone();
+        let amount = forms("amount");
+        assert!(amount.as_array().unwrap().contains(&json!({ "const": WILDCARD })));
+        assert!(amount.as_array().unwrap().iter().any(|form| form["properties"]["gte"].is_object()));
+        let region = forms("region");
+        assert!(region.as_array().unwrap().iter().all(|form| form["properties"]["gte"].is_null()));
+        let alternatives = json!({ "type": "array", "minItems": 1, "items": { "type": "string" } });
+        assert!(region.as_array().unwrap().contains(&alternatives));
+    }
+
+    #[test]
+    fn params_schema_has_one_form_per_param() {
+        let schema = params_json_schema(&catalog()).unwrap();
+        assert_eq!(schema["title"], "params");
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DIALECT);
+        let forms = schema["items"]["oneOf"].as_array().unwrap();
+        assert_eq!(forms.len(), 2);
+        assert_eq!(param_value(&schema, "limit"), &json!({ "type": ["integer", "string"] }));
+        let limit = forms.iter().find(|form| form["properties"]["key"]["enum"][0] == "limit").unwrap();
+        assert_eq!(limit["properties"]["type"]["const"], "int");
+
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        assert_eq!(params_json_schema(&registry).unwrap()["maxItems"], 0);
+    }
+
+    #[test]
+    fn constraints_carry_into_value_schemas() {
+        let mut bounds = ValueConstraints::default();
+        (bounds.min, bounds.max) = (Some(1.0), Some(10.0));
+        let limit = params_json_schema(&constrained("limit", bounds)).unwrap();
+        assert_eq!(param_value(&limit, "limit")["minimum"], 1.0);
+        assert_eq!(param_value(&limit, "limit")["maximum"], 10.0);
+
+        let mut text = ValueConstraints::default();
+        (text.max_len, text.pattern) = (Some(8), Some("a|b".to_string()));
+        let label = params_json_schema(&constrained("label", text)).unwrap();
+        assert_eq!(param_value(&label, "label")["maxLength"], 8);
+        assert_eq!(param_value(&label, "label")["pattern"], "^(?:a|b)$");
+    }
+
+    #[test]
+    fn bad_data_types_fail() {
+        let mut registry = catalog();
+        let mut meta = registry.remove("limit").unwrap();
+        meta.data_type = "integer".to_string();
+        registry.insert(meta).unwrap();
+        assert!(envelope_json_schema(&registry).is_err());
+        assert!(params_json_schema(&registry).is_err());
+    }
+
+    #[test]
+    fn matrix_schema_has_a_column_per_match_attr_name() {
+        let schema = matrix_json_schema(&catalog());
+        assert_eq!(schema["type"], "array");
+        let items = &schema["items"];
+        assert_eq!(items["required"], json!(["rank"]));
+        assert_eq!(items["additionalProperties"], false);
+        let columns = items["properties"].as_object().unwrap();
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns["rank"]["minimum"], 1);
+        for name in ["region", "rgn", "amount"] {
+            assert_eq!(columns[name], json!({ "enum": [0, 1] }));
+        }
+        assert!(!columns.contains_key("limit"));
+    }
+}
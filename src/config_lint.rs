@@ -0,0 +1,491 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_envelope::validate_envelope;
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_resolver::{is_wildcard, ValueKey};
+use crate::config_types::ConfigEnvelope;
+use crate::config_value::{AttrRole, ConfigValue};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// A rank the resolver can never (or only accidentally) reach, found by [`lint_precedence`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LintFinding {
+    /// `rank` lists exactly the same attributes and match types as the earlier `same_as`.
+    DuplicateRank { rank: i32, same_as: i32 },
+    /// `rank` requires the same exact attributes as the earlier `shadowed_by`, so no
+    /// row ever reaches it: any row matching it matched `shadowed_by` first.
+    ShadowedRank { rank: i32, shadowed_by: i32, exact: Vec<i32> },
+    /// `rank` requires no exact attributes, so a fully-wildcard row matches every
+    /// context there and the more specific `specific_ranks` after it are never tried.
+    CatchAllAboveSpecific { rank: i32, specific_ranks: Vec<i32> },
+}
+
+impl LintFinding {
+    /// The rank the finding is about.
+    pub fn rank(&self) -> i32 {
+        match self {
+            LintFinding::DuplicateRank { rank, .. }
+            | LintFinding::ShadowedRank { rank, .. }
+            | LintFinding::CatchAllAboveSpecific { rank, .. } => *rank,
+        }
+    }
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintFinding::DuplicateRank { rank, same_as } => {
+                write!(f, "Rank {} duplicates rank {}", rank, same_as)
+            }
+            LintFinding::ShadowedRank { rank, shadowed_by, exact } => write!(
+                f,
+                "Rank {} is unreachable: rank {} matches the same exact attr_ids {:?}",
+                rank, shadowed_by, exact
+            ),
+            LintFinding::CatchAllAboveSpecific { rank, specific_ranks } => {
+                write!(f, "Rank {} matches everything but comes before more specific ranks {:?}", rank, specific_ranks)
+            }
+        }
+    }
+}
+
+/// Looks for dead or misplaced ranks in one config version's tall precedence rules.
+///
+/// Ranks are considered from 1 upwards, the order the resolver tries them. A rank
+/// whose cells repeat an earlier rank is a [`LintFinding::DuplicateRank`]; one that
+/// differs only in its `Ignore` cells is a [`LintFinding::ShadowedRank`], since the
+/// resolver only looks at the exact attributes. A rank with no exact attribute that
+/// comes before ranks with some is a [`LintFinding::CatchAllAboveSpecific`].
+/// Findings come back ordered by rank.
+pub fn lint_precedence(tall: &[ConfigPrecedenceRule]) -> Vec<LintFinding> {
+    let mut ranks: BTreeMap<i32, BTreeMap<i32, MatchType>> = BTreeMap::new();
+    for r in tall {
+        ranks.entry(r.rank).or_default().insert(r.attr_id, r.match_type);
+    }
+
+    let exact_of = |cells: &BTreeMap<i32, MatchType>| -> BTreeSet<i32> {
+        cells.iter().filter(|(_, m)| **m == MatchType::Exact).map(|(id, _)| *id).collect()
+    };
+
+    let mut findings = Vec::new();
+    let mut first_by_cells: BTreeMap<&BTreeMap<i32, MatchType>, i32> = BTreeMap::new();
+    let mut first_by_exact: BTreeMap<BTreeSet<i32>, i32> = BTreeMap::new();
+
+    for (&rank, cells) in &ranks {
+        let exact = exact_of(cells);
+
+        if let Some(&same_as) = first_by_cells.get(cells) {
+            findings.push(LintFinding::DuplicateRank { rank, same_as });
+        } else if let Some(&shadowed_by) = first_by_exact.get(&exact) {
+            findings.push(LintFinding::ShadowedRank { rank, shadowed_by, exact: exact.iter().copied().collect() });
+        }
+        first_by_cells.entry(cells).or_insert(rank);
+
+        if exact.is_empty() {
+            let specific_ranks: Vec<i32> = ranks
+                .range(rank + 1..)
+                .filter(|(_, later)| !exact_of(later).is_empty())
+                .map(|(&later, _)| later)
+                .collect();
+            if !specific_ranks.is_empty() && !first_by_exact.contains_key(&exact) {
+                findings.push(LintFinding::CatchAllAboveSpecific { rank, specific_ranks });
+            }
+        }
+        first_by_exact.entry(exact).or_insert(rank);
+    }
+
+    findings
+}
+
+/// Checks that no two config rows of one version share a match tuple. Wildcard
+/// values (`null`, `"ALL"`) count as absent, so a row that spells out `"ALL"` and
+/// one that omits the attribute conflict. Two such rows are both candidates for
+/// the same contexts at the same rank, so which one wins is down to `match_id`.
+/// Two default rows (no attribute constrained) are a `DuplicateDefaultRow`.
+pub fn validate_row_conflicts(values: &[ConfigValue]) -> Result<()> {
+    let mut report = ValidationReport::fail_fast();
+    check_row_conflicts(values, &mut report)
+}
+
+/// Same as [`validate_row_conflicts`], but returns every conflicting pair: each
+/// row is reported against the lowest match_id with the same tuple.
+pub fn validate_row_conflicts_report(values: &[ConfigValue]) -> std::result::Result<(), ValidationReport> {
+    let mut report = ValidationReport::collect_all();
+    let outcome = check_row_conflicts(values, &mut report);
+    report.finish(outcome)
+}
+
+fn check_row_conflicts(values: &[ConfigValue], report: &mut ValidationReport) -> Result<()> {
+    let mut tuples: BTreeMap<i32, BTreeMap<i32, ValueKey>> = BTreeMap::new();
+    for v in values {
+        let tuple = tuples.entry(v.match_id).or_default();
+        if v.role == AttrRole::Match && !is_wildcard(&v.value) {
+            tuple.insert(v.attr_id, ValueKey::from(&v.value));
+        }
+    }
+
+    let mut first_by_tuple: HashMap<Vec<(i32, ValueKey)>, i32> = HashMap::new();
+    for (match_id, tuple) in tuples {
+        let attr_ids: Vec<i32> = tuple.keys().copied().collect();
+        match first_by_tuple.entry(tuple.into_iter().collect()) {
+            std::collections::hash_map::Entry::Occupied(first) if attr_ids.is_empty() => {
+                let first = *first.get();
+                report.push(PrecedenceConfigError::DuplicateDefaultRow { match_id: first, other_match_id: match_id })?;
+            }
+            std::collections::hash_map::Entry::Occupied(first) => {
+                report.push(PrecedenceConfigError::ConflictingRows {
+                    match_id: *first.get(),
+                    other_match_id: match_id,
+                    attr_ids,
+                })?;
+            }
+            std::collections::hash_map::Entry::Vacant(slot) => {
+                slot.insert(match_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A match attribute the precedence rules match exactly at some rank but no row
+/// constrains, found by [`validate_rule_coverage`]: those ranks select nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnusedRuleAttr {
+    pub attr_id: i32,
+    /// Ranks with an exact cell for the attribute.
+    pub ranks: Vec<i32>,
+}
+
+impl fmt::Display for UnusedRuleAttr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "attr_id {} is matched exactly at ranks {:?}, but no row constrains it", self.attr_id, self.ranks)
+    }
+}
+
+/// Cross-checks one version's rows against its precedence rules.
+///
+/// Every match attribute a row constrains must be an exact cell at some rank, or
+/// the row can never be selected; the first attribute that is not fails with
+/// [`PrecedenceConfigError::UnruledMatchAttr`]. Wildcard values count as absent,
+/// as in [`Resolver`](crate::config_resolver::Resolver). Attributes going the
+/// other way, exact at some rank but constrained by no row, are only warnings
+/// and come back ordered by attr_id.
+pub fn validate_rule_coverage(rules: &[ConfigPrecedenceRule], values: &[ConfigValue]) -> Result<Vec<UnusedRuleAttr>> {
+    let mut report = ValidationReport::fail_fast();
+    check_rule_coverage(rules, values, &mut report)
+}
+
+/// Same as [`validate_rule_coverage`], but reports every unruled attribute.
+pub fn validate_rule_coverage_report(
+    rules: &[ConfigPrecedenceRule],
+    values: &[ConfigValue],
+) -> std::result::Result<Vec<UnusedRuleAttr>, ValidationReport> {
+    let mut report = ValidationReport::collect_all();
+    let outcome = check_rule_coverage(rules, values, &mut report);
+    report.finish(outcome)
+}
+
+/// [`validate_rule_coverage`] for an envelope, after validating it against the catalog.
+pub fn validate_envelope_rule_coverage(
+    envelope: &ConfigEnvelope,
+    rules: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+) -> Result<Vec<UnusedRuleAttr>> {
+    validate_rule_coverage(rules, &validate_envelope(envelope, registry)?.values)
+}
+
+fn check_rule_coverage(
+    rules: &[ConfigPrecedenceRule],
+    values: &[ConfigValue],
+    report: &mut ValidationReport,
+) -> Result<Vec<UnusedRuleAttr>> {
+    // attr_id -> ranks with an exact cell for it
+    let mut exact: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for r in rules.iter().filter(|r| r.match_type == MatchType::Exact) {
+        exact.entry(r.attr_id).or_default().insert(r.rank);
+    }
+
+    // attr_id -> rows constraining it
+    let mut constrained: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for v in values.iter().filter(|v| v.role == AttrRole::Match && !is_wildcard(&v.value)) {
+        constrained.entry(v.attr_id).or_default().insert(v.match_id);
+    }
+
+    for (&attr_id, match_ids) in &constrained {
+        if !exact.contains_key(&attr_id) {
+            let match_ids = match_ids.iter().copied().collect();
+            report.push(PrecedenceConfigError::UnruledMatchAttr { attr_id, match_ids })?;
+        }
+    }
+
+    Ok(exact
+        .into_iter()
+        .filter(|(attr_id, _)| !constrained.contains_key(attr_id))
+        .map(|(attr_id, ranks)| UnusedRuleAttr { attr_id, ranks: ranks.into_iter().collect() })
+        .collect())
+}
+
+/// A config row that [`find_dead_rows`] showed can never be the resolver's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeadRow {
+    /// No rank requires exactly the attributes the row constrains, so it matches at no rank.
+    NoMatchingRank { match_id: i32, attr_ids: Vec<i32> },
+    /// Every context the row matches at `rank` is already won by `shadowed_by` at
+    /// `shadowed_at`: an earlier rank, or the same rank with a lower match_id.
+    Shadowed { match_id: i32, rank: i32, shadowed_by: i32, shadowed_at: i32 },
+}
+
+impl DeadRow {
+    /// The match_id of the dead row.
+    pub fn match_id(&self) -> i32 {
+        match self {
+            DeadRow::NoMatchingRank { match_id, .. } | DeadRow::Shadowed { match_id, .. } => *match_id,
+        }
+    }
+}
+
+impl fmt::Display for DeadRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadRow::NoMatchingRank { match_id, attr_ids } => {
+                write!(f, "match_id {} is never selected: no rank matches exactly attr_ids {:?}", match_id, attr_ids)
+            }
+            DeadRow::Shadowed { match_id, rank, shadowed_by, shadowed_at } => write!(
+                f,
+                "match_id {} is never selected: wherever it matches at rank {}, match_id {} wins at rank {}",
+                match_id, rank, shadowed_by, shadowed_at
+            ),
+        }
+    }
+}
+
+/// Finds the config rows of one version that no context can ever resolve to.
+///
+/// A row only matches at ranks whose exact attributes are the ones it constrains,
+/// and the first such rank is the only one that counts. From there the row is
+/// dead if some other row wins every context it matches: one at an earlier rank
+/// whose match tuple is a subset of this row's with the same values, or one at the
+/// same rank with an identical tuple and a lower match_id. Otherwise the context
+/// made of just the row's own match values resolves to it. Wildcard values count
+/// as absent, as in [`Resolver`](crate::config_resolver::Resolver). Dead rows come
+/// back ordered by match_id.
+pub fn find_dead_rows(rules: &[ConfigPrecedenceRule], values: &[ConfigValue]) -> Vec<DeadRow> {
+    let mut ranks: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+    for r in rules {
+        let exact = ranks.entry(r.rank).or_default();
+        if r.match_type == MatchType::Exact {
+            exact.insert(r.attr_id);
+        }
+    }
+
+    let mut tuples: BTreeMap<i32, BTreeMap<i32, ValueKey>> = BTreeMap::new();
+    for v in values {
+        let tuple = tuples.entry(v.match_id).or_default();
+        if v.role == AttrRole::Match && !is_wildcard(&v.value) {
+            tuple.insert(v.attr_id, ValueKey::from(&v.value));
+        }
+    }
+
+    // match_id -> first rank whose exact attrs are the row's constrained attrs
+    let reached: BTreeMap<i32, i32> = tuples
+        .iter()
+        .filter_map(|(&match_id, tuple)| {
+            ranks
+                .iter()
+                .find(|(_, exact)| exact.iter().eq(tuple.keys()))
+                .map(|(&rank, _)| (match_id, rank))
+        })
+        .collect();
+
+    let mut dead = Vec::new();
+    for (&match_id, tuple) in &tuples {
+        let Some(&rank) = reached.get(&match_id) else {
+            dead.push(DeadRow::NoMatchingRank { match_id, attr_ids: tuple.keys().copied().collect() });
+            continue;
+        };
+
+        // the winner for the row's own values: lowest rank first, then lowest match_id
+        let winner = reached
+            .iter()
+            .filter(|&(&other, &other_rank)| {
+                other != match_id
+                    && (other_rank < rank || (other_rank == rank && other < match_id))
+                    && tuples[&other].iter().all(|(attr_id, value)| tuple.get(attr_id) == Some(value))
+            })
+            .min_by_key(|&(&other, &other_rank)| (other_rank, other));
+        if let Some((&shadowed_by, &shadowed_at)) = winner {
+            dead.push(DeadRow::Shadowed { match_id, rank, shadowed_by, shadowed_at });
+        }
+    }
+
+    dead
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_resolver::WILDCARD;
+    use crate::config_value::TypedValue;
+
+    /// Rank `n` is `cells[n - 1]`, attr_id `i + 1` its `i`th cell.
+    fn rules(cells: &[&str]) -> Vec<ConfigPrecedenceRule> {
+        let mut rules = Vec::new();
+        for (rank, row) in (1..).zip(cells) {
+            for (attr_id, cell) in (1..).zip(row.chars()) {
+                let match_type = if cell == '1' { MatchType::Exact } else { MatchType::Ignore };
+                rules.push(ConfigPrecedenceRule { config_version_id: 1, rank, attr_id, match_type });
+            }
+        }
+        rules
+    }
+
+    #[test]
+    fn a_triangular_matrix_has_no_findings() {
+        assert!(lint_precedence(&rules(&["111", "110", "101", "100", "010", "001"])).is_empty());
+    }
+
+    #[test]
+    fn duplicate_and_shadowed_ranks_are_found() {
+        let mut tall = rules(&["11", "10", "11", "01"]);
+        // rank 5: rank 4's exact attribute, different Ignore cells
+        tall.push(ConfigPrecedenceRule { config_version_id: 1, rank: 5, attr_id: 2, match_type: MatchType::Exact });
+        tall.push(ConfigPrecedenceRule { config_version_id: 1, rank: 5, attr_id: 3, match_type: MatchType::Ignore });
+        let findings = lint_precedence(&tall);
+        assert_eq!(
+            findings,
+            [
+                LintFinding::DuplicateRank { rank: 3, same_as: 1 },
+                LintFinding::ShadowedRank { rank: 5, shadowed_by: 4, exact: vec![2] },
+            ]
+        );
+        assert_eq!(findings.iter().map(LintFinding::rank).collect::<Vec<_>>(), [3, 5]);
+        assert_eq!(findings[0].to_string(), "Rank 3 duplicates rank 1");
+    }
+
+    #[test]
+    fn a_catch_all_before_specific_ranks_is_found_once() {
+        let findings = lint_precedence(&rules(&["10", "00", "01", "00"]));
+        assert_eq!(
+            findings,
+            [
+                LintFinding::CatchAllAboveSpecific { rank: 2, specific_ranks: vec![3] },
+                LintFinding::DuplicateRank { rank: 4, same_as: 2 },
+            ]
+        );
+        assert!(lint_precedence(&rules(&["10", "01", "00"])).is_empty());
+        let json = serde_json::to_value(&findings[0]).unwrap();
+        assert_eq!(json["kind"], "catch_all_above_specific");
+    }
+
+    /// Config rows from `(match_id, [(attr_id, value)])`, each with an int param as attr_id 9.
+    fn rows(rows: &[(i32, &[(i32, TypedValue)])]) -> Vec<ConfigValue> {
+        let mut values = Vec::new();
+        for (match_id, tuple) in rows {
+            for (attr_id, value) in tuple.iter() {
+                let value = value.clone();
+                values.push(ConfigValue { match_id: *match_id, attr_id: *attr_id, role: AttrRole::Match, value });
+            }
+            let value = TypedValue::Int(1);
+            values.push(ConfigValue { match_id: *match_id, attr_id: 9, role: AttrRole::Param, value });
+        }
+        values
+    }
+
+    fn s(value: &str) -> TypedValue {
+        TypedValue::Str(value.to_string())
+    }
+
+    #[test]
+    fn rows_with_the_same_tuple_conflict() {
+        let values = rows(&[
+            (1, &[(1, s("EU")), (2, s("web"))]),
+            (2, &[(2, s("web")), (1, s("EU"))]),
+            (3, &[(1, s("US"))]),
+        ]);
+        let err = validate_row_conflicts(&values).unwrap_err();
+        assert!(matches!(
+            err,
+            PrecedenceConfigError::ConflictingRows { match_id: 1, other_match_id: 2, ref attr_ids }
+                if attr_ids == &[1, 2]
+        ));
+        assert!(validate_row_conflicts(&rows(&[(1, &[(1, s("EU"))]), (2, &[(1, s("US"))])])).is_ok());
+    }
+
+    #[test]
+    fn wildcards_count_as_absent() {
+        let values = rows(&[
+            (1, &[(1, s("EU"))]),
+            (2, &[(1, s("EU")), (2, s(WILDCARD))]),
+            (3, &[(1, s("EU")), (2, TypedValue::Null)]),
+        ]);
+        let report = validate_row_conflicts_report(&values).unwrap_err();
+        assert_eq!(report.len(), 2);
+        assert!(report.errors.iter().all(|e| matches!(e, PrecedenceConfigError::ConflictingRows { match_id: 1, .. })));
+    }
+
+    #[test]
+    fn two_default_rows_are_a_duplicate_default() {
+        let values = rows(&[(4, &[]), (7, &[(1, s(WILDCARD))])]);
+        assert!(matches!(
+            validate_row_conflicts(&values),
+            Err(PrecedenceConfigError::DuplicateDefaultRow { match_id: 4, other_match_id: 7 })
+        ));
+    }
+
+    #[test]
+    fn rows_no_rank_matches_are_dead() {
+        let values = rows(&[(1, &[(1, s("EU")), (2, s("web"))]), (2, &[(2, s("web"))])]);
+        let dead = find_dead_rows(&rules(&["11", "10"]), &values);
+        assert_eq!(dead, [DeadRow::NoMatchingRank { match_id: 2, attr_ids: vec![2] }]);
+        assert_eq!(dead[0].match_id(), 2);
+    }
+
+    #[test]
+    fn rows_won_by_an_earlier_rank_or_lower_match_id_are_dead() {
+        let tall = rules(&["10", "11"]);
+        let values = rows(&[
+            (1, &[(1, s("EU"))]),
+            (2, &[(1, s("EU")), (2, s("web"))]),
+            (3, &[(1, s("US")), (2, s("web"))]),
+            (4, &[(1, s(WILDCARD)), (2, TypedValue::Null)]),
+            (5, &[(1, s("EU"))]),
+        ]);
+        let dead = find_dead_rows(&tall, &values);
+        assert_eq!(
+            dead,
+            [
+                DeadRow::Shadowed { match_id: 2, rank: 2, shadowed_by: 1, shadowed_at: 1 },
+                DeadRow::NoMatchingRank { match_id: 4, attr_ids: vec![] },
+                DeadRow::Shadowed { match_id: 5, rank: 1, shadowed_by: 1, shadowed_at: 1 },
+            ]
+        );
+        assert_eq!(
+            dead[0].to_string(),
+            "match_id 2 is never selected: wherever it matches at rank 2, match_id 1 wins at rank 1"
+        );
+    }
+
+    #[test]
+    fn constrained_attrs_must_be_ruled_and_ruled_attrs_used() {
+        // attr 2 is exact at rank 1 only; attr 3 at rank 2 only, and no row constrains it
+        let tall = rules(&["110", "101"]);
+        let values = rows(&[(1, &[(1, s("EU")), (2, s("web"))]), (2, &[(1, s("US")), (2, s(WILDCARD))])]);
+        assert_eq!(validate_rule_coverage(&tall, &values).unwrap(), [UnusedRuleAttr { attr_id: 3, ranks: vec![2] }]);
+
+        let values = rows(&[(1, &[(4, s("x"))]), (2, &[(5, s("y"))]), (3, &[(5, s("z"))])]);
+        assert!(matches!(
+            validate_rule_coverage(&tall, &values),
+            Err(PrecedenceConfigError::UnruledMatchAttr { attr_id: 4, ref match_ids }) if match_ids == &[1]
+        ));
+        let report = validate_rule_coverage_report(&tall, &values).unwrap_err();
+        assert!(matches!(
+            &report.errors[..],
+            [_, PrecedenceConfigError::UnruledMatchAttr { attr_id: 5, match_ids }] if match_ids == &[2, 3]
+        ));
+    }
+}
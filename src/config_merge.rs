@@ -0,0 +1,283 @@
+use crate::config_attr::AttrName;
+use crate::config_diff::MatchTuple;
+use crate::config_types::{ConfigEnvelope, ConfigMeta, ConfigRow, Param, ENVELOPE_SCHEMA_VERSION};
+use crate::config_value::{parse_param_value, TypedValue};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+/// Which side of a [`merge_envelopes`] made a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+/// A change both sides made differently.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MergeConflict {
+    /// Both sides changed the config metadata.
+    Config { base: Box<ConfigMeta>, ours: Box<ConfigMeta>, theirs: Box<ConfigMeta> },
+    /// One side removed a row the other side changed.
+    RemovedAndModified { match_tuple: MatchTuple, removed_by: MergeSide },
+    /// Both sides set (or one unset) the same param of a row to different values;
+    /// `None` means the param is absent on that side.
+    Param {
+        match_tuple: MatchTuple,
+        key: String,
+        base: Option<TypedValue>,
+        ours: Option<TypedValue>,
+        theirs: Option<TypedValue>,
+    },
+}
+
+/// The conflicts of a failed [`merge_envelopes`], along with the merge that takes
+/// our side of every conflict, as a starting point for resolving them by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Error)]
+#[error("{} conflicting change(s) between ours and theirs", .conflicts.len())]
+pub struct MergeConflicts {
+    pub conflicts: Vec<MergeConflict>,
+    pub ours_resolved: Box<ConfigEnvelope>,
+}
+
+/// Three-way merge of two envelopes edited from a common `base`.
+///
+/// Rows are keyed by match tuple and params by key; values are compared typed,
+/// as in [`diff_envelopes`](crate::config_diff::diff_envelopes). A row or param
+/// changed on one side only takes that side's change, one changed identically on
+/// both sides is taken once, and anything else is a [`MergeConflict`]. Row order
+/// follows `ours`, with rows only `theirs` added appended in their order.
+pub fn merge_envelopes(
+    base: &ConfigEnvelope,
+    ours: &ConfigEnvelope,
+    theirs: &ConfigEnvelope,
+) -> Result<ConfigEnvelope, MergeConflicts> {
+    let mut conflicts = Vec::new();
+
+    let config = match pick(&base.config, &ours.config, &theirs.config) {
+        Some(MergeSide::Ours) => ours.config.clone(),
+        Some(MergeSide::Theirs) => theirs.config.clone(),
+        None => {
+            conflicts.push(MergeConflict::Config {
+                base: Box::new(base.config.clone()),
+                ours: Box::new(ours.config.clone()),
+                theirs: Box::new(theirs.config.clone()),
+            });
+            ours.config.clone()
+        }
+    };
+
+    let base_rows = keyed_rows(base);
+    let our_rows = keyed_rows(ours);
+    let their_rows = keyed_rows(theirs);
+
+    // ours' order, then the tuples only theirs has; a tuple both sides removed is in neither
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    let order: Vec<String> = ours
+        .rows
+        .iter()
+        .chain(&theirs.rows)
+        .map(|row| key_of(row).1)
+        .filter(|key| seen.insert(key.clone()))
+        .collect();
+
+    let mut rows = Vec::new();
+    for key in &order {
+        let (b, o, t) = (base_rows.get(key), our_rows.get(key), their_rows.get(key));
+        match (b, o, t) {
+            (_, Some(o), Some(t)) => rows.push(merge_row(b, o, t, &mut conflicts)),
+            (Some(b), Some(row), None) | (Some(b), None, Some(row)) => {
+                if row.params != b.params {
+                    let removed_by = if o.is_none() { MergeSide::Ours } else { MergeSide::Theirs };
+                    let match_tuple = row.match_tuple.clone();
+                    conflicts.push(MergeConflict::RemovedAndModified { match_tuple, removed_by });
+                    if o.is_some() {
+                        rows.push(row.row.clone());
+                    }
+                }
+            }
+            (None, Some(row), None) | (None, None, Some(row)) => rows.push(row.row.clone()),
+            (_, None, None) => {}
+        }
+    }
+
+    let merged = ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config, rows };
+    if conflicts.is_empty() { Ok(merged) } else { Err(MergeConflicts { conflicts, ours_resolved: Box::new(merged) }) }
+}
+
+/// Both sides have the row (the base may not): merged param by param.
+fn merge_row(
+    base: Option<&KeyedRow>,
+    ours: &KeyedRow,
+    theirs: &KeyedRow,
+    conflicts: &mut Vec<MergeConflict>,
+) -> ConfigRow {
+    let mut keys: Vec<&AttrName> = ours.row.params.iter().map(|p| &p.key).collect();
+    for p in &theirs.row.params {
+        if !keys.contains(&&p.key) {
+            keys.push(&p.key);
+        }
+    }
+    if let Some(base) = base {
+        for p in &base.row.params {
+            if !keys.contains(&&p.key) {
+                keys.push(&p.key);
+            }
+        }
+    }
+
+    let mut params = Vec::new();
+    for key in keys {
+        let b = base.and_then(|b| b.param(key));
+        let (o, t) = (ours.param(key), theirs.param(key));
+        let taken = match pick(&typed(b), &typed(o), &typed(t)) {
+            Some(MergeSide::Ours) => o,
+            Some(MergeSide::Theirs) => t,
+            None => {
+                conflicts.push(MergeConflict::Param {
+                    match_tuple: ours.match_tuple.clone(),
+                    key: key.to_string(),
+                    base: typed(b).cloned(),
+                    ours: typed(o).cloned(),
+                    theirs: typed(t).cloned(),
+                });
+                o
+            }
+        };
+        if let Some((param, _)) = taken {
+            params.push(param.clone());
+        }
+    }
+    // the audit of the side that changed the row, ours if both did
+    let audit = match base {
+        Some(base) if ours.params == base.params => theirs.row.audit.clone(),
+        _ => ours.row.audit.clone(),
+    };
+    ConfigRow { match_part: ours.row.match_part.clone(), params, audit }
+}
+
+fn typed<'a>(side: Option<(&'a Param, &'a TypedValue)>) -> Option<&'a TypedValue> {
+    side.map(|(_, value)| value)
+}
+
+/// The side whose value the merge takes, or `None` when both changed it differently.
+fn pick<T: PartialEq>(base: &T, ours: &T, theirs: &T) -> Option<MergeSide> {
+    if ours == theirs || theirs == base {
+        Some(MergeSide::Ours)
+    } else if ours == base {
+        Some(MergeSide::Theirs)
+    } else {
+        None
+    }
+}
+
+struct KeyedRow<'a> {
+    row: &'a ConfigRow,
+    match_tuple: MatchTuple,
+    /// (key, typed value), by key
+    params: Vec<(String, TypedValue)>,
+}
+
+impl KeyedRow<'_> {
+    fn param(&self, key: &str) -> Option<(&Param, &TypedValue)> {
+        let param = self.row.params.iter().find(|p| p.key == key)?;
+        let value = self.params.iter().find(|(k, _)| k == key).map(|(_, value)| value)?;
+        Some((param, value))
+    }
+}
+
+/// Rows by canonical match tuple; a repeated tuple keeps its first row.
+fn keyed_rows(envelope: &ConfigEnvelope) -> HashMap<String, KeyedRow<'_>> {
+    let mut rows = HashMap::new();
+    for row in &envelope.rows {
+        let (match_tuple, key) = key_of(row);
+        let mut params: Vec<(String, TypedValue)> = row
+            .params
+            .iter()
+            .map(|p| (p.key.to_string(), parse_param_value(p).unwrap_or_else(|_| TypedValue::Str(p.value.to_string()))))
+            .collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+        rows.entry(key).or_insert(KeyedRow { row, match_tuple, params });
+    }
+    rows
+}
+
+fn key_of(row: &ConfigRow) -> (MatchTuple, String) {
+    let match_tuple: MatchTuple = row.match_part.attrs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+    let key = serde_json::to_string(&match_tuple).unwrap_or_default();
+    (match_tuple, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn envelope(rows: Value) -> ConfigEnvelope {
+        serde_json::from_value(json!({ "config": { "name": "c", "version": 1, "version_name": "v1" }, "rows": rows }))
+            .unwrap()
+    }
+
+    fn row(region: &str, params: Value) -> Value {
+        json!({ "match": { "region": region }, "params": params })
+    }
+
+    fn fee(value: Value) -> Value {
+        json!([{ "key": "fee", "type": "dec", "value": value }])
+    }
+
+    fn base() -> ConfigEnvelope {
+        envelope(json!([row("EU", fee(json!(1.5))), row("US", fee(json!(2)))]))
+    }
+
+    fn rows(envelope: &ConfigEnvelope) -> Value {
+        serde_json::to_value(&envelope.rows).unwrap()
+    }
+
+    #[test]
+    fn changes_on_one_side_or_identical_on_both_are_taken() {
+        let ours = envelope(json!([row("EU", fee(json!(3))), row("US", fee(json!(2)))]));
+        let mut theirs = envelope(json!([row("APAC", fee(json!(4))), row("EU", fee(json!("3.0")))]));
+        theirs.config.version_name = "theirs".to_string();
+        let merged = merge_envelopes(&base(), &ours, &theirs).unwrap();
+        assert_eq!(merged.config.version_name, "theirs");
+        // theirs removed US untouched by ours; the EU values are equal typed
+        assert_eq!(rows(&merged), json!([row("EU", fee(json!(3))), row("APAC", fee(json!(4)))]));
+    }
+
+    #[test]
+    fn competing_changes_conflict_and_ours_is_kept() {
+        let ours = envelope(json!([row("EU", fee(json!(3))), row("US", fee(json!(5)))]));
+        let mut theirs = envelope(json!([row("EU", fee(json!(4)))]));
+        let mut ours_meta = ours.clone();
+        ours_meta.config.version_name = "ours".to_string();
+        theirs.config.version_name = "theirs".to_string();
+        let err = merge_envelopes(&base(), &ours_meta, &theirs).unwrap_err();
+        assert_eq!(err.to_string(), "3 conflicting change(s) between ours and theirs");
+        assert!(matches!(err.conflicts[0], MergeConflict::Config { .. }));
+        assert!(matches!(
+            &err.conflicts[1],
+            MergeConflict::Param { key, ours: Some(_), theirs: Some(_), .. } if key == "fee"
+        ));
+        assert!(matches!(
+            &err.conflicts[2],
+            MergeConflict::RemovedAndModified { removed_by: MergeSide::Theirs, match_tuple }
+                if match_tuple["region"] == "US"
+        ));
+        assert_eq!(err.ours_resolved.config.version_name, "ours");
+        assert_eq!(rows(&err.ours_resolved), rows(&ours));
+    }
+
+    #[test]
+    fn a_param_unset_on_one_side_and_changed_on_the_other_conflicts() {
+        let ours = envelope(json!([row("EU", json!([])), row("US", fee(json!(2)))]));
+        let theirs = envelope(json!([row("EU", fee(json!(9))), row("US", fee(json!(2)))]));
+        let err = merge_envelopes(&base(), &ours, &theirs).unwrap_err();
+        assert!(matches!(
+            &err.conflicts[..],
+            [MergeConflict::Param { ours: None, theirs: Some(_), base: Some(_), .. }]
+        ));
+    }
+}
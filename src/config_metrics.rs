@@ -0,0 +1,250 @@
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_resolver::{ResolvedConfig, DEFAULT_RANK};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Receives what resolvers and caches observe, for counters and histograms in
+/// the caller's metrics system. Attach one with `Resolver::set_metrics` or
+/// `ResolverCache::set_metrics`; [`InMemoryMetrics`] is a ready-made sink.
+///
+/// Called on the lookup path, so implementations should be cheap and must not block.
+pub trait MetricsSink: Send + Sync {
+    /// One `Resolver` / `CompiledConfig` lookup finished.
+    fn resolution(&self, event: &ResolutionEvent);
+
+    /// A `ResolverCache` lookup hit or missed the cache.
+    fn cache_lookup(&self, _hit: bool) {}
+}
+
+/// One finished lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolutionEvent {
+    /// See `Resolver::config_version_id`.
+    pub config_version_id: Option<i32>,
+    pub outcome: ResolutionOutcome,
+    pub elapsed: Duration,
+}
+
+/// How a lookup ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ResolutionOutcome {
+    /// A row matched at `rank` (`SCORED_RANK` under weighted scoring).
+    Matched { rank: i32, match_id: i32 },
+    /// Nothing matched and the default row applied.
+    Default { match_id: i32 },
+    /// Nothing matched, not even a default row (`None` or `NoMatchingRow`).
+    NoMatch,
+    /// The lookup failed otherwise, e.g. with `AmbiguousMatch`.
+    Failed,
+}
+
+impl ResolutionOutcome {
+    pub fn of(outcome: &Result<Option<ResolvedConfig>>) -> Self {
+        match outcome {
+            Ok(Some(resolved)) if resolved.rank == DEFAULT_RANK => {
+                ResolutionOutcome::Default { match_id: resolved.match_id }
+            }
+            Ok(Some(resolved)) => ResolutionOutcome::Matched { rank: resolved.rank, match_id: resolved.match_id },
+            Ok(None) | Err(PrecedenceConfigError::NoMatchingRow) => ResolutionOutcome::NoMatch,
+            Err(_) => ResolutionOutcome::Failed,
+        }
+    }
+}
+
+/// An optional shared [`MetricsSink`], as resolvers and caches hold it.
+#[derive(Clone, Default)]
+pub(crate) struct Metrics(pub(crate) Option<Arc<dyn MetricsSink>>);
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Metrics(sink)" } else { "Metrics(none)" })
+    }
+}
+
+/// Upper bounds, in microseconds, of the latency buckets of [`InMemoryMetrics`];
+/// a last bucket takes everything slower.
+pub const LATENCY_BUCKETS_US: [u64; 8] = [1, 5, 10, 50, 100, 500, 1_000, 10_000];
+
+/// A [`MetricsSink`] that counts in memory, for tests, debugging endpoints, or
+/// scraping into another system with [`InMemoryMetrics::snapshot`].
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    resolutions: AtomicU64,
+    no_match: AtomicU64,
+    default_hits: AtomicU64,
+    failures: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    /// per bucket of [`LATENCY_BUCKETS_US`], then the overflow bucket
+    latency: [AtomicU64; LATENCY_BUCKETS_US.len() + 1],
+    total_latency_us: AtomicU64,
+    hits_by_rank: Mutex<BTreeMap<i32, u64>>,
+}
+
+/// The counters of an [`InMemoryMetrics`] at one moment.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    pub resolutions: u64,
+    pub no_match: u64,
+    /// Lookups answered by the default row.
+    pub default_hits: u64,
+    pub failures: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Lookups a row matched, by the rank it matched at.
+    pub hits_by_rank: BTreeMap<i32, u64>,
+    /// Lookups per latency bucket: one count per bound of [`LATENCY_BUCKETS_US`]
+    /// (at most that many microseconds), then one for slower lookups.
+    pub latency_buckets: Vec<u64>,
+    pub total_latency_us: u64,
+}
+
+impl MetricsSnapshot {
+    /// Cache hits over cache lookups; `None` before the first lookup.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// Share of lookups answered by the default row; `None` before the first lookup.
+    pub fn default_rate(&self) -> Option<f64> {
+        (self.resolutions > 0).then(|| self.default_hits as f64 / self.resolutions as f64)
+    }
+
+    /// Mean lookup latency; `None` before the first lookup.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.resolutions > 0).then(|| Duration::from_micros(self.total_latency_us / self.resolutions))
+    }
+}
+
+impl InMemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            resolutions: load(&self.resolutions),
+            no_match: load(&self.no_match),
+            default_hits: load(&self.default_hits),
+            failures: load(&self.failures),
+            cache_hits: load(&self.cache_hits),
+            cache_misses: load(&self.cache_misses),
+            hits_by_rank: self.hits_by_rank.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone(),
+            latency_buckets: self.latency.iter().map(load).collect(),
+            total_latency_us: load(&self.total_latency_us),
+        }
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn resolution(&self, event: &ResolutionEvent) {
+        let count = |counter: &AtomicU64| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        };
+        count(&self.resolutions);
+        match event.outcome {
+            ResolutionOutcome::Matched { rank, .. } => {
+                let mut hits = self.hits_by_rank.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                *hits.entry(rank).or_default() += 1;
+            }
+            ResolutionOutcome::Default { .. } => count(&self.default_hits),
+            ResolutionOutcome::NoMatch => count(&self.no_match),
+            ResolutionOutcome::Failed => count(&self.failures),
+        }
+        let us = event.elapsed.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = LATENCY_BUCKETS_US.iter().position(|&bound| us <= bound).unwrap_or(LATENCY_BUCKETS_US.len());
+        count(&self.latency[bucket]);
+        self.total_latency_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    fn cache_lookup(&self, hit: bool) {
+        let counter = if hit { &self.cache_hits } else { &self.cache_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_envelope::envelope_to_config_values;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::config_resolver::Resolver;
+    use crate::test_fixtures::{region_context, region_registry};
+    use serde_json::json;
+
+    fn event(outcome: ResolutionOutcome, elapsed_us: u64) -> ResolutionEvent {
+        ResolutionEvent { config_version_id: Some(1), outcome, elapsed: Duration::from_micros(elapsed_us) }
+    }
+
+    #[test]
+    fn lookups_are_counted_by_outcome_and_latency() {
+        let metrics = InMemoryMetrics::new();
+        metrics.resolution(&event(ResolutionOutcome::Matched { rank: 2, match_id: 7 }, 3));
+        metrics.resolution(&event(ResolutionOutcome::Matched { rank: 2, match_id: 8 }, 5));
+        metrics.resolution(&event(ResolutionOutcome::Default { match_id: 9 }, 40));
+        metrics.resolution(&event(ResolutionOutcome::NoMatch, 20_000));
+        metrics.resolution(&event(ResolutionOutcome::Failed, 0));
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            (snapshot.resolutions, snapshot.default_hits, snapshot.no_match, snapshot.failures),
+            (5, 1, 1, 1)
+        );
+        assert_eq!(snapshot.hits_by_rank, BTreeMap::from([(2, 2)]));
+        assert_eq!(snapshot.latency_buckets, [1, 2, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(snapshot.total_latency_us, 20_048);
+        assert_eq!(snapshot.default_rate(), Some(0.2));
+        assert_eq!(snapshot.mean_latency(), Some(Duration::from_micros(4_009)));
+        assert_eq!(snapshot.cache_hit_rate(), None);
+
+        metrics.cache_lookup(true);
+        metrics.cache_lookup(true);
+        metrics.cache_lookup(false);
+        metrics.cache_lookup(true);
+        assert_eq!(metrics.snapshot().cache_hit_rate(), Some(0.75));
+        assert_eq!(MetricsSnapshot::default().default_rate(), None);
+    }
+
+    #[test]
+    fn outcomes_follow_the_lookup_result() {
+        let resolved = |rank| Ok(Some(ResolvedConfig { match_id: 4, rank, params: Vec::new() }));
+        assert_eq!(ResolutionOutcome::of(&resolved(3)), ResolutionOutcome::Matched { rank: 3, match_id: 4 });
+        assert_eq!(ResolutionOutcome::of(&resolved(DEFAULT_RANK)), ResolutionOutcome::Default { match_id: 4 });
+        assert_eq!(ResolutionOutcome::of(&Ok(None)), ResolutionOutcome::NoMatch);
+        assert_eq!(ResolutionOutcome::of(&Err(PrecedenceConfigError::NoMatchingRow)), ResolutionOutcome::NoMatch);
+        let ambiguous = Err(PrecedenceConfigError::AmbiguousMatch { rank: 1, match_ids: vec![1, 2] });
+        assert_eq!(ResolutionOutcome::of(&ambiguous), ResolutionOutcome::Failed);
+    }
+
+    #[test]
+    fn a_resolver_reports_each_lookup() {
+        let registry = region_registry();
+        let envelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [
+                { "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 5 }] },
+                { "match": {}, "params": [{ "key": "limit", "type": "int", "value": 1 }] },
+            ],
+        }))
+        .unwrap();
+        let values = envelope_to_config_values(&envelope, &registry).unwrap();
+        let rules = matrix_json_to_tall(r#"[{ "rank": 1, "region": 1 }]"#, 1, &registry).unwrap();
+        let mut resolver = Resolver::new(&rules, &values, &registry).unwrap();
+        let metrics = Arc::new(InMemoryMetrics::new());
+        resolver.set_metrics(metrics.clone());
+
+        resolver.resolve(&region_context("eu"));
+        resolver.resolve(&region_context("eu"));
+        resolver.resolve(&region_context("us"));
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.resolutions, snapshot.default_hits), (3, 1));
+        assert_eq!(snapshot.hits_by_rank, BTreeMap::from([(1, 2)]));
+        assert_eq!(snapshot.latency_buckets.iter().sum::<u64>(), 3);
+    }
+}
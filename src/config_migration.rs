@@ -0,0 +1,304 @@
+//! Upgrading envelope documents of older layouts to the current one.
+//!
+//! Layouts by `schema_version`:
+//!
+//! 1. Metadata (`name`, `version`, ..) at the top level; each row's `params` an
+//!    object of `{ "type", "value" }` by key.
+//! 2. Metadata under `config`; `params` an array of `{ "key", "type", "value" }`.
+//!    Written without a `schema_version`.
+//! 3. Version 2 with a `schema_version`, the current [`ENVELOPE_SCHEMA_VERSION`].
+//!
+//! Documents without a `schema_version` are version 2 if they have a `config`
+//! object and version 1 otherwise.
+//!
+//! Older exports also spelled some fields differently (`configVersion`,
+//! `versionName`, `matchAttrs`, ..); see [`MigrationOptions::lenient_field_names`].
+
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_types::{ConfigEnvelope, ENVELOPE_SCHEMA_VERSION};
+use serde_json::{Map, Value};
+
+/// Old spellings of envelope fields -> the current ones, by where they appear.
+const ENVELOPE_SPELLINGS: [(&str, &str); 1] = [("schemaVersion", "schema_version")];
+const META_SPELLINGS: [(&str, &str); 4] = [
+    ("configVersion", "version"),
+    ("versionName", "version_name"),
+    ("validFrom", "valid_from"),
+    ("validTo", "valid_to"),
+];
+const ROW_SPELLINGS: [(&str, &str); 1] = [("matchAttrs", "match")];
+const AUDIT_SPELLINGS: [(&str, &str); 2] = [("createdBy", "created_by"), ("createdAt", "created_at")];
+
+/// Knobs for [`migrate_envelope_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+    /// Accept the camelCase and alternate field names of older exports
+    /// (`schemaVersion`, `configVersion` for `version`, `versionName`, `validFrom`,
+    /// `validTo`, `matchAttrs` for `match`, and `createdBy` / `createdAt` in audits)
+    /// and rename them to the current ones. Off by default, where a document
+    /// using any of them is rejected; with both spellings of a field it always is.
+    pub lenient_field_names: bool,
+}
+
+/// Top-level fields of a version 1 document that moved under `config`.
+const V1_META_FIELDS: [&str; 7] = ["name", "version", "version_name", "status", "valid_from", "valid_to", "audit"];
+
+/// The layout version of an envelope document, read from its `schema_version` or,
+/// without one, from its shape (see the [module docs](self)).
+pub fn envelope_schema_version(document: &Value) -> Result<u64> {
+    schema_version_of(document.as_object().ok_or_else(not_an_object)?)
+}
+
+fn schema_version_of(fields: &Map<String, Value>) -> Result<u64> {
+    match fields.get("schema_version") {
+        Some(version) => match version.as_u64() {
+            Some(version @ 1..) => Ok(version),
+            _ => Err(invalid(format!("schema_version {} is not a positive integer", version))),
+        },
+        None if fields.get("config").is_some_and(Value::is_object) => Ok(2),
+        None => Ok(1),
+    }
+}
+
+/// Upgrades an envelope document of any layout this crate has written to the
+/// current one, one version at a time, and deserializes it. A current document
+/// only deserializes; one of a newer version than [`ENVELOPE_SCHEMA_VERSION`]
+/// fails with [`PrecedenceConfigError::UnsupportedSchemaVersion`], and anything
+/// else wrong with the document with an `InvalidFormat` of `"envelope"`.
+pub fn migrate_envelope(document: Value) -> Result<ConfigEnvelope> {
+    migrate_envelope_with_options(document, &MigrationOptions::default())
+}
+
+/// Same as [`migrate_envelope`], with [`MigrationOptions`].
+pub fn migrate_envelope_with_options(document: Value, options: &MigrationOptions) -> Result<ConfigEnvelope> {
+    let Value::Object(mut fields) = document else {
+        return Err(not_an_object());
+    };
+    normalize_spellings(&mut fields, options.lenient_field_names)?;
+    let version = schema_version_of(&fields)?;
+    if version > u64::from(ENVELOPE_SCHEMA_VERSION) {
+        return Err(PrecedenceConfigError::UnsupportedSchemaVersion {
+            found: version,
+            supported: ENVELOPE_SCHEMA_VERSION,
+        });
+    }
+    if version < 2 {
+        fields = v1_to_v2(fields)?;
+    }
+    fields.insert("schema_version".to_string(), ENVELOPE_SCHEMA_VERSION.into());
+    serde_json::from_value(Value::Object(fields)).map_err(|e| invalid(e.to_string()))
+}
+
+/// Moves the metadata under `config` and turns each row's `params` object into an array.
+fn v1_to_v2(mut fields: Map<String, Value>) -> Result<Map<String, Value>> {
+    let mut config = Map::new();
+    for key in V1_META_FIELDS {
+        if let Some(value) = fields.remove(key) {
+            config.insert(key.to_string(), value);
+        }
+    }
+    fields.insert("config".to_string(), Value::Object(config));
+
+    let rows = fields.get_mut("rows").and_then(Value::as_array_mut).into_iter().flatten();
+    for (row_index, row) in rows.enumerate() {
+        let Some(params) = row.get_mut("params") else {
+            continue;
+        };
+        let Value::Object(by_key) = std::mem::take(params) else {
+            return Err(invalid(format!("rows[{}].params is not an object of params by key", row_index)));
+        };
+        let mut list = Vec::with_capacity(by_key.len());
+        for (key, param) in by_key {
+            let Value::Object(mut param) = param else {
+                return Err(invalid(format!("rows[{}].params.{} is not an object", row_index, key)));
+            };
+            param.insert("key".to_string(), Value::String(key));
+            list.push(Value::Object(param));
+        }
+        *params = Value::Array(list);
+    }
+    Ok(fields)
+}
+
+/// Renames (or, unless `lenient`, rejects) the old spellings of the document's
+/// fields. Metadata is looked for under `config`, or at the top level of a
+/// version 1 document.
+fn normalize_spellings(fields: &mut Map<String, Value>, lenient: bool) -> Result<()> {
+    respell(fields, "", &ENVELOPE_SPELLINGS, lenient)?;
+    match fields.get_mut("config") {
+        Some(Value::Object(config)) => respell_meta(config, "config.", lenient)?,
+        _ => respell_meta(fields, "", lenient)?,
+    }
+    let rows = fields.get_mut("rows").and_then(Value::as_array_mut).into_iter().flatten();
+    for (row_index, row) in rows.enumerate() {
+        if let Value::Object(row) = row {
+            let at = format!("rows[{}].", row_index);
+            respell(row, &at, &ROW_SPELLINGS, lenient)?;
+            respell_audit(row, &at, lenient)?;
+        }
+    }
+    Ok(())
+}
+
+fn respell_meta(meta: &mut Map<String, Value>, at: &str, lenient: bool) -> Result<()> {
+    respell(meta, at, &META_SPELLINGS, lenient)?;
+    respell_audit(meta, at, lenient)
+}
+
+fn respell_audit(parent: &mut Map<String, Value>, at: &str, lenient: bool) -> Result<()> {
+    match parent.get_mut("audit") {
+        Some(Value::Object(audit)) => respell(audit, &format!("{}audit.", at), &AUDIT_SPELLINGS, lenient),
+        _ => Ok(()),
+    }
+}
+
+/// Renames the `old` fields of `object` (at path prefix `at`) to their `new` spelling.
+fn respell(object: &mut Map<String, Value>, at: &str, spellings: &[(&str, &str)], lenient: bool) -> Result<()> {
+    for &(old, new) in spellings {
+        let Some(value) = object.remove(old) else {
+            continue;
+        };
+        if object.contains_key(new) {
+            return Err(invalid(format!("both {}{} and {}{} are set", at, old, at, new)));
+        }
+        if !lenient {
+            return Err(invalid(format!(
+                "{}{} is an old spelling of {}, accepted only with lenient_field_names",
+                at, old, new
+            )));
+        }
+        object.insert(new.to_string(), value);
+    }
+    Ok(())
+}
+
+fn not_an_object() -> PrecedenceConfigError {
+    invalid("not a JSON object".to_string())
+}
+
+fn invalid(reason: String) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "envelope", reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn v2() -> Value {
+        json!({
+            "config": { "name": "fees", "version": 4, "version_name": "v4" },
+            "rows": [{ "match": { "country": "DE" }, "params": [{ "key": "fee", "type": "dec", "value": 2.5 }] }],
+        })
+    }
+
+    #[test]
+    fn migrates_v1() {
+        let v1 = json!({
+            "name": "fees",
+            "version": 4,
+            "version_name": "v4",
+            "rows": [{ "match": { "country": "DE" }, "params": { "fee": { "type": "dec", "value": 2.5 } } }],
+        });
+        assert_eq!(envelope_schema_version(&v1).unwrap(), 1);
+        let envelope = migrate_envelope(v1).unwrap();
+        assert_eq!(envelope, migrate_envelope(v2()).unwrap());
+        assert_eq!(envelope.schema_version, ENVELOPE_SCHEMA_VERSION);
+        assert_eq!(envelope.config.name, "fees");
+        assert_eq!(envelope.rows[0].params[0].key, "fee");
+    }
+
+    #[test]
+    fn migrates_v2() {
+        assert_eq!(envelope_schema_version(&v2()).unwrap(), 2);
+        let envelope = migrate_envelope(v2()).unwrap();
+        assert_eq!(envelope.schema_version, ENVELOPE_SCHEMA_VERSION);
+        assert_eq!(envelope.config.version, 4);
+        assert_eq!(envelope.rows.len(), 1);
+    }
+
+    #[test]
+    fn malformed_envelope_fails_as_an_envelope() {
+        let mut document = v2();
+        document["config"].as_object_mut().unwrap().remove("version_name");
+        let err = migrate_envelope(document).unwrap_err();
+        let PrecedenceConfigError::InvalidFormat { format, reason } = &err else {
+            panic!("expected an envelope format error, got {err}");
+        };
+        assert_eq!(*format, "envelope");
+        assert!(reason.contains("version_name"), "{reason}");
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut document = v2();
+        document["schema_version"] = json!(ENVELOPE_SCHEMA_VERSION + 1);
+        let err = migrate_envelope(document).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::UnsupportedSchemaVersion { .. }), "{err}");
+    }
+
+    #[test]
+    fn old_spellings_need_lenient_field_names() {
+        let mut document = v2();
+        let config = document["config"].as_object_mut().unwrap();
+        let version_name = config.remove("version_name").unwrap();
+        config.insert("versionName".to_string(), version_name);
+
+        assert!(migrate_envelope(document.clone()).is_err());
+        let lenient = MigrationOptions { lenient_field_names: true };
+        let envelope = migrate_envelope_with_options(document, &lenient).unwrap();
+        assert_eq!(envelope.config.version_name, "v4");
+    }
+
+    #[test]
+    fn reads_the_layout_version() {
+        let mut document = v2();
+        document["schema_version"] = json!(ENVELOPE_SCHEMA_VERSION);
+        assert_eq!(envelope_schema_version(&document).unwrap(), u64::from(ENVELOPE_SCHEMA_VERSION));
+        assert_eq!(envelope_schema_version(&json!({ "config": "fees", "rows": [] })).unwrap(), 1);
+        for bad in [json!(0), json!(-1), json!("3")] {
+            document["schema_version"] = bad;
+            assert!(matches!(envelope_schema_version(&document), Err(PrecedenceConfigError::InvalidFormat { .. })));
+        }
+        assert!(envelope_schema_version(&json!([])).is_err());
+        assert!(migrate_envelope(json!([])).is_err());
+    }
+
+    #[test]
+    fn v1_params_must_be_an_object_by_key() {
+        let v1 = json!({
+            "name": "fees",
+            "version": 4,
+            "version_name": "v4",
+            "rows": [{ "match": {}, "params": [{ "key": "fee", "type": "dec", "value": 2.5 }] }],
+        });
+        let err = migrate_envelope(v1).unwrap_err();
+        assert!(err.to_string().contains("rows[0].params"), "{err}");
+    }
+
+    #[test]
+    fn lenient_field_names_respell_v1_rows_and_audits() {
+        let v1 = json!({
+            "schemaVersion": 1,
+            "name": "fees",
+            "configVersion": 4,
+            "versionName": "v4",
+            "audit": { "createdBy": "ops" },
+            "rows": [{ "matchAttrs": { "country": "DE" }, "params": { "fee": { "type": "dec", "value": 2.5 } } }],
+        });
+        let lenient = MigrationOptions { lenient_field_names: true };
+        let mut envelope = migrate_envelope_with_options(v1, &lenient).unwrap();
+        let audit = envelope.config.audit.take().unwrap();
+        assert_eq!(audit.created_by.as_deref(), Some("ops"));
+        assert_eq!(envelope, migrate_envelope(v2()).unwrap());
+    }
+
+    #[test]
+    fn both_spellings_are_rejected_even_when_lenient() {
+        let mut document = v2();
+        document["rows"][0]["matchAttrs"] = json!({ "country": "FR" });
+        let lenient = MigrationOptions { lenient_field_names: true };
+        let err = migrate_envelope_with_options(document, &lenient).unwrap_err();
+        assert!(err.to_string().contains("both rows[0].matchAttrs and rows[0].match"), "{err}");
+    }
+}
@@ -0,0 +1,220 @@
+use crate::config_diff::{diff_envelopes, ConfigDiff, MatchTuple};
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_types::{AuditInfo, ConfigEnvelope, ConfigMeta, ConfigRow, MatchPart, Param, ENVELOPE_SCHEMA_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A config version declared as another version of the same config plus row
+/// overrides, instead of a full copy of its rows.
+///
+/// ```json
+/// { "config": { "name": "pricing", "version": 4, "version_name": "eu" }, "based_on": 3,
+///   "rows": [{ "match": { "region": "EU" }, "params": [{ "key": "fee", "type": "dec", "value": 2.5 }] }] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ConfigOverlay {
+    pub config: ConfigMeta,
+    /// Version number of the base, within `config.name`.
+    pub based_on: i32,
+    pub rows: Vec<RowOverride>,
+}
+
+/// Changes to the base row with the same match tuple, or a new row if the base has none.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RowOverride {
+    #[serde(flatten)]
+    pub match_part: MatchPart,
+    /// Set on the row, replacing the base param with the same key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<Param>,
+    /// Param keys dropped from the base row.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unset: Vec<String>,
+    /// Drops the base row altogether; `params` and `unset` must then be empty.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub remove: bool,
+    /// Replaces the base row's audit fields; a new row gets them as they are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditInfo>,
+}
+
+impl ConfigOverlay {
+    /// The merged flat version: `base` rows in their order with the overrides
+    /// applied, then the overrides that matched no base row as new rows.
+    ///
+    /// `base` must be version `based_on` of `config.name`. Rows are paired by
+    /// identical match tuple, as in [`diff_envelopes`]. An override may appear
+    /// once per match tuple, and `remove` / `unset` must name something the
+    /// base row has.
+    pub fn materialize(&self, base: &ConfigEnvelope) -> Result<ConfigEnvelope> {
+        if base.config.name != self.config.name || base.config.version != self.based_on {
+            return Err(PrecedenceConfigError::OverlayBaseMismatch {
+                name: self.config.name.clone(),
+                based_on: self.based_on,
+                found_name: base.config.name.clone(),
+                found_version: base.config.version,
+            });
+        }
+
+        let mut overrides: HashMap<String, usize> = HashMap::new();
+        for (index, row) in self.rows.iter().enumerate() {
+            if row.remove && (!row.params.is_empty() || !row.unset.is_empty()) {
+                return Err(override_error(index, "a removed row cannot also set or unset params"));
+            }
+            if let Some(first) = overrides.insert(tuple_key(&row.match_part), index) {
+                return Err(override_error(index, &format!("same match tuple as override {}", first)));
+            }
+        }
+
+        let mut rows = Vec::with_capacity(base.rows.len() + self.rows.len());
+        for row in &base.rows {
+            match overrides.remove(&tuple_key(&row.match_part)) {
+                Some(index) => {
+                    if let Some(merged) = apply(row, &self.rows[index], index)? {
+                        rows.push(merged);
+                    }
+                }
+                None => rows.push(row.clone()),
+            }
+        }
+
+        let mut added: Vec<usize> = overrides.into_values().collect();
+        added.sort_unstable();
+        for index in added {
+            let row = &self.rows[index];
+            if row.remove || !row.unset.is_empty() {
+                return Err(override_error(index, "no base row has this match tuple"));
+            }
+            let (match_part, params, audit) = (row.match_part.clone(), row.params.clone(), row.audit.clone());
+            rows.push(ConfigRow { match_part, params, audit });
+        }
+
+        Ok(ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config: self.config.clone(), rows })
+    }
+
+    /// What this overlay changes relative to `base`, as a [`ConfigDiff`] of
+    /// `base` against [`ConfigOverlay::materialize`].
+    pub fn diff_from_base(&self, base: &ConfigEnvelope) -> Result<ConfigDiff> {
+        Ok(diff_envelopes(base, &self.materialize(base)?))
+    }
+}
+
+fn apply(base: &ConfigRow, row: &RowOverride, index: usize) -> Result<Option<ConfigRow>> {
+    if row.remove {
+        return Ok(None);
+    }
+    let mut params = base.params.clone();
+    for key in &row.unset {
+        let before = params.len();
+        params.retain(|p| &p.key != key);
+        if params.len() == before {
+            return Err(override_error(index, &format!("base row has no param '{}' to unset", key)));
+        }
+    }
+    for param in &row.params {
+        match params.iter_mut().find(|p| p.key == param.key) {
+            Some(existing) => *existing = param.clone(),
+            None => params.push(param.clone()),
+        }
+    }
+    let audit = row.audit.clone().or_else(|| base.audit.clone());
+    Ok(Some(ConfigRow { match_part: base.match_part.clone(), params, audit }))
+}
+
+/// Canonical JSON of the match tuple, for pairing rows.
+fn tuple_key(match_part: &MatchPart) -> String {
+    let tuple: MatchTuple = match_part.attrs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+    serde_json::to_string(&tuple).unwrap_or_default()
+}
+
+fn override_error(index: usize, reason: &str) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidRowOverride { index, reason: reason.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn base() -> ConfigEnvelope {
+        serde_json::from_value(json!({
+            "config": { "name": "pricing", "version": 3, "version_name": "base" },
+            "rows": [
+                { "match": { "region": "EU" }, "params": [
+                    { "key": "fee", "type": "dec", "value": 1.5 },
+                    { "key": "cap", "type": "int", "value": 10 },
+                ] },
+                { "match": { "region": "US" }, "params": [{ "key": "fee", "type": "dec", "value": 1.0 }] },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn overlay(rows: Value) -> ConfigOverlay {
+        serde_json::from_value(json!({
+            "config": { "name": "pricing", "version": 4, "version_name": "eu" },
+            "based_on": 3,
+            "rows": rows,
+        }))
+        .unwrap()
+    }
+
+    fn rows(envelope: &ConfigEnvelope) -> Value {
+        serde_json::to_value(&envelope.rows).unwrap()
+    }
+
+    #[test]
+    fn overrides_set_unset_remove_and_add_rows() {
+        let overlay = overlay(json!([
+            {
+                "match": { "region": "EU" },
+                "params": [{ "key": "fee", "type": "dec", "value": 2.5 }],
+                "unset": ["cap"],
+            },
+            { "match": { "region": "APAC" }, "params": [{ "key": "fee", "type": "dec", "value": 3.0 }] },
+            { "match": { "region": "US" }, "remove": true },
+        ]));
+        let merged = overlay.materialize(&base()).unwrap();
+        assert_eq!((merged.config.version, merged.config.version_name.as_str()), (4, "eu"));
+        assert_eq!(
+            rows(&merged),
+            json!([
+                { "match": { "region": "EU" }, "params": [{ "key": "fee", "type": "dec", "value": 2.5 }] },
+                { "match": { "region": "APAC" }, "params": [{ "key": "fee", "type": "dec", "value": 3.0 }] },
+            ])
+        );
+        let diff = overlay.diff_from_base(&base()).unwrap();
+        assert_eq!((diff.added_rows.len(), diff.removed_rows.len(), diff.changed_rows.len()), (1, 1, 1));
+    }
+
+    #[test]
+    fn an_empty_overlay_is_the_base() {
+        let merged = overlay(json!([])).materialize(&base()).unwrap();
+        assert_eq!(rows(&merged), rows(&base()));
+    }
+
+    #[test]
+    fn overlays_must_match_their_base() {
+        let mut other = base();
+        other.config.version = 2;
+        assert!(matches!(
+            overlay(json!([])).materialize(&other),
+            Err(PrecedenceConfigError::OverlayBaseMismatch { based_on: 3, found_version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn bad_overrides_are_refused_by_index() {
+        let index_of = |rows: Value| match overlay(rows).materialize(&base()) {
+            Err(PrecedenceConfigError::InvalidRowOverride { index, reason }) => (index, reason),
+            other => panic!("expected InvalidRowOverride, got {other:?}"),
+        };
+        let eu = json!({ "region": "EU" });
+        assert_eq!(index_of(json!([{ "match": eu }, { "match": eu }])).0, 1);
+        let (index, reason) = index_of(json!([{ "match": eu, "remove": true, "unset": ["cap"] }]));
+        assert_eq!((index, reason.as_str()), (0, "a removed row cannot also set or unset params"));
+        assert!(index_of(json!([{ "match": eu, "unset": ["rate"] }])).1.contains("no param 'rate'"));
+        let (index, reason) = index_of(json!([{ "match": { "region": "FR" }, "remove": true }]));
+        assert_eq!((index, reason.as_str()), (0, "no base row has this match tuple"));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_resolver::ResolvedConfig;
+use crate::config_value::{parse_json_value, parse_str_value, AttrMeta, AttrRole, ConfigValue, TypedValue};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::time::Duration;
+
+/// A resolved row's params keyed by attribute name, with typed getters.
+///
+/// `get_*` fail with `MissingParam` when the key is absent or `null`, and with
+/// `ParamTypeMismatch` when the value has a different type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedParams {
+    values: BTreeMap<String, TypedValue>,
+}
+
+impl ResolvedParams {
+    /// Names each value through the registry; values with an unknown attr_id are dropped.
+    pub fn from_values(values: &[ConfigValue], registry: &AttrRegistry) -> Self {
+        let values = values
+            .iter()
+            .filter_map(|v| registry.name_of(v.attr_id).map(|name| (name.to_string(), v.value.clone())))
+            .collect();
+        Self { values }
+    }
+
+    /// The raw value, `None` when the key is absent.
+    pub fn value(&self, key: &str) -> Option<&TypedValue> {
+        self.values.get(key)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    /// Converts the value under `key`; absent and `null` are both `MissingParam`.
+    pub fn get<T: FromTypedValue>(&self, key: &str) -> Result<T> {
+        self.get_opt(key)?.ok_or_else(|| PrecedenceConfigError::MissingParam { key: key.to_string() })
+    }
+
+    /// Like [`ResolvedParams::get`], but absent and `null` are `Ok(None)`.
+    pub fn get_opt<T: FromTypedValue>(&self, key: &str) -> Result<Option<T>> {
+        match self.values.get(key) {
+            None | Some(TypedValue::Null) => Ok(None),
+            Some(value) => T::from_typed_value(value).map(Some).ok_or_else(|| {
+                PrecedenceConfigError::ParamTypeMismatch {
+                    key: key.to_string(),
+                    expected: T::TYPE_NAME,
+                    found: value.type_name(),
+                }
+            }),
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<i64> {
+        self.get(key)
+    }
+
+    pub fn get_dec(&self, key: &str) -> Result<f64> {
+        self.get(key)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<&str> {
+        match self.values.get(key) {
+            Some(TypedValue::Str(s) | TypedValue::Enum(s)) => Ok(s),
+            None | Some(TypedValue::Null) => Err(PrecedenceConfigError::MissingParam { key: key.to_string() }),
+            Some(other) => Err(PrecedenceConfigError::ParamTypeMismatch {
+                key: key.to_string(),
+                expected: "str",
+                found: other.type_name(),
+            }),
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool> {
+        self.get(key)
+    }
+
+    /// UTC datetime; an offset-carrying value is converted to UTC.
+    pub fn get_dt(&self, key: &str) -> Result<NaiveDateTime> {
+        self.get(key)
+    }
+
+    /// Converts all params into `T` at once, see [`FromResolvedConfig`].
+    pub fn extract<T: FromResolvedConfig>(&self) -> Result<T> {
+        T::from_resolved_params(self)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TypedValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Builds a user struct from resolved params; usually derived with
+/// `#[derive(FromResolvedConfig)]` (`derive` feature).
+pub trait FromResolvedConfig: Sized {
+    fn from_resolved_params(params: &ResolvedParams) -> Result<Self>;
+}
+
+impl ResolvedConfig {
+    /// This row's params keyed by attribute name.
+    pub fn typed_params(&self, registry: &AttrRegistry) -> ResolvedParams {
+        ResolvedParams::from_values(&self.params, registry)
+    }
+}
+
+/// Prefix of the environment variables read by [`Overlay::from_env`].
+pub const OVERLAY_ENV_PREFIX: &str = "PC__";
+
+/// Param overrides applied on top of resolved configs, for turning a knob
+/// without publishing a new config version.
+///
+/// Overrides are per config name and param attribute, and every value is parsed
+/// by the attribute's `data_type` and checked against its enum values and
+/// constraints when it is added, so a bad override fails at startup rather than
+/// at lookup. An override wins over whatever the resolved row (or the attribute
+/// default) says, for every context.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overlay {
+    /// config name -> attr_id -> value
+    overrides: BTreeMap<String, BTreeMap<i32, TypedValue>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads every `PC__<config>__<attr>=value` variable of the process environment.
+    /// Names that aren't UTF-8 are skipped; a `PC__` variable whose value isn't is
+    /// an [`PrecedenceConfigError::InvalidOverride`].
+    pub fn from_env(registry: &AttrRegistry) -> Result<Self> {
+        Self::from_os_vars(std::env::vars_os(), registry)
+    }
+
+    fn from_os_vars(vars: impl IntoIterator<Item = (OsString, OsString)>, registry: &AttrRegistry) -> Result<Self> {
+        let mut utf8 = Vec::new();
+        for (key, raw) in vars {
+            let Some(key) = key.into_string().ok().filter(|key| key.starts_with(OVERLAY_ENV_PREFIX)) else {
+                continue;
+            };
+            let raw = raw.into_string().map_err(|raw| PrecedenceConfigError::InvalidOverride {
+                key: key.clone(),
+                source: Box::new(PrecedenceConfigError::InvalidFormat {
+                    format: "override",
+                    reason: format!("value {:?} is not UTF-8", raw),
+                }),
+            })?;
+            utf8.push((key, raw));
+        }
+        Self::from_vars(utf8, registry)
+    }
+
+    /// Like [`Overlay::from_env`] over any list of variables. Names without the
+    /// [`OVERLAY_ENV_PREFIX`] are skipped; the config name runs up to the next
+    /// `__` and the attribute name is the rest. Values are parsed as with
+    /// [`Overlay::set`].
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>, registry: &AttrRegistry) -> Result<Self> {
+        let mut overlay = Self::new();
+        for (key, raw) in vars {
+            let Some(rest) = key.strip_prefix(OVERLAY_ENV_PREFIX) else {
+                continue;
+            };
+            let in_var = |source: PrecedenceConfigError| PrecedenceConfigError::InvalidOverride {
+                key: key.clone(),
+                source: Box::new(source),
+            };
+            let (config, attr_name) = rest
+                .split_once("__")
+                .filter(|(config, attr_name)| !config.is_empty() && !attr_name.is_empty())
+                .ok_or_else(|| {
+                    in_var(PrecedenceConfigError::InvalidFormat {
+                        format: "override",
+                        reason: format!("expected {}<config>__<attr>", OVERLAY_ENV_PREFIX),
+                    })
+                })?;
+            overlay.set(config, attr_name, &raw, registry).map_err(in_var)?;
+        }
+        Ok(overlay)
+    }
+
+    /// Overrides from a map of attribute name -> JSON value, e.g. a deserialized
+    /// overrides file; see [`Overlay::set_json`].
+    pub fn from_map(
+        config: &str,
+        map: &serde_json::Map<String, serde_json::Value>,
+        registry: &AttrRegistry,
+    ) -> Result<Self> {
+        let mut overlay = Self::new();
+        for (attr_name, value) in map {
+            overlay.set_json(config, attr_name, value, registry)?;
+        }
+        Ok(overlay)
+    }
+
+    /// Overrides `attr_name` in `config` with a raw string value (see `parse_str_value`).
+    pub fn set(&mut self, config: &str, attr_name: &str, raw: &str, registry: &AttrRegistry) -> Result<()> {
+        let meta = param_meta(attr_name, registry)?;
+        let value = parse_str_value(meta, raw)?;
+        self.insert(config, meta, value)
+    }
+
+    /// Overrides `attr_name` in `config` with a JSON value (see `parse_json_value`).
+    pub fn set_json(
+        &mut self,
+        config: &str,
+        attr_name: &str,
+        value: &serde_json::Value,
+        registry: &AttrRegistry,
+    ) -> Result<()> {
+        let meta = param_meta(attr_name, registry)?;
+        let value = parse_json_value(meta, value)?;
+        self.insert(config, meta, value)
+    }
+
+    /// Drops one override, returning its value.
+    pub fn remove(&mut self, config: &str, attr_name: &str, registry: &AttrRegistry) -> Option<TypedValue> {
+        let attr_id = registry.id_of(attr_name)?;
+        let overrides = self.overrides.get_mut(config)?;
+        let removed = overrides.remove(&attr_id);
+        if overrides.is_empty() {
+            self.overrides.remove(config);
+        }
+        removed
+    }
+
+    /// `config`'s overrides as (attr_id, value), by attr_id.
+    pub fn overrides(&self, config: &str) -> impl Iterator<Item = (i32, &TypedValue)> {
+        self.overrides.get(config).into_iter().flatten().map(|(&attr_id, value)| (attr_id, value))
+    }
+
+    /// Replaces (or adds) the overridden params of `resolved`, a row of `config`.
+    pub fn apply(&self, config: &str, resolved: &mut ResolvedConfig) {
+        for (attr_id, value) in self.overrides(config) {
+            match resolved.params.iter_mut().find(|p| p.attr_id == attr_id) {
+                Some(param) => param.value = value.clone(),
+                None => resolved.params.push(ConfigValue {
+                    match_id: resolved.match_id,
+                    attr_id,
+                    role: AttrRole::Param,
+                    value: value.clone(),
+                }),
+            }
+        }
+    }
+
+    /// Number of overrides across all configs.
+    pub fn len(&self) -> usize {
+        self.overrides.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    fn insert(&mut self, config: &str, meta: &AttrMeta, value: TypedValue) -> Result<()> {
+        meta.check_constraints(&value)?;
+        self.overrides.entry(config.to_string()).or_default().insert(meta.attr_id, value);
+        Ok(())
+    }
+}
+
+fn param_meta<'a>(attr_name: &str, registry: &'a AttrRegistry) -> Result<&'a AttrMeta> {
+    let meta = registry
+        .get(attr_name)
+        .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: attr_name.to_string() })?;
+    if meta.role != AttrRole::Param {
+        return Err(PrecedenceConfigError::WrongRole {
+            key: attr_name.to_string(),
+            expected: AttrRole::Param,
+            found: meta.role,
+        });
+    }
+    Ok(meta)
+}
+
+/// Conversion from a (non-null) `TypedValue` into a Rust type, for [`ResolvedParams::get`].
+pub trait FromTypedValue: Sized {
+    /// Param type named in `ParamTypeMismatch`.
+    const TYPE_NAME: &'static str;
+
+    /// `None` when `value` is not of this type.
+    fn from_typed_value(value: &TypedValue) -> Option<Self>;
+}
+
+macro_rules! from_typed_value {
+    ($ty:ty, $name:literal, $($pattern:pat => $out:expr),+ $(,)?) => {
+        impl FromTypedValue for $ty {
+            const TYPE_NAME: &'static str = $name;
+
+            fn from_typed_value(value: &TypedValue) -> Option<Self> {
+                match value {
+                    $($pattern => Some($out),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_typed_value!(i64, "int", TypedValue::Int(v) => *v);
+#[cfg(not(feature = "rust_decimal"))]
+from_typed_value!(f64, "dec", TypedValue::Dec(v) => *v);
+#[cfg(feature = "rust_decimal")]
+from_typed_value!(f64, "dec",
+    TypedValue::Dec(v) => *v,
+    TypedValue::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(v)?,
+);
+#[cfg(feature = "rust_decimal")]
+from_typed_value!(rust_decimal::Decimal, "dec", TypedValue::Decimal(v) => *v);
+from_typed_value!(String, "str", TypedValue::Str(v) | TypedValue::Enum(v) => v.clone());
+from_typed_value!(bool, "bool", TypedValue::Bool(v) => *v);
+from_typed_value!(NaiveDateTime, "dt",
+    TypedValue::Dt(v) => *v,
+    TypedValue::DtTz(v) => v.naive_utc(),
+);
+from_typed_value!(DateTime<FixedOffset>, "dt",
+    TypedValue::DtTz(v) => *v,
+    TypedValue::Dt(v) => v.and_utc().fixed_offset(),
+);
+from_typed_value!(NaiveDate, "date", TypedValue::Date(v) => *v);
+from_typed_value!(NaiveTime, "time", TypedValue::Time(v) => *v);
+from_typed_value!(Duration, "duration", TypedValue::Duration(v) => *v);
+#[cfg(feature = "uuid")]
+from_typed_value!(uuid::Uuid, "uuid", TypedValue::Uuid(v) => *v);
+
+impl<T: FromTypedValue> FromTypedValue for Vec<T> {
+    const TYPE_NAME: &'static str = "list";
+
+    fn from_typed_value(value: &TypedValue) -> Option<Self> {
+        match value {
+            TypedValue::List(items) => items.iter().map(T::from_typed_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::{parse_str_value, AttrRole};
+
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        for (name, data_type) in [
+            ("max_orders_day", "int"),
+            ("tier", "str"),
+            ("enabled", "bool"),
+            ("starts_at", "dt"),
+            ("countries", "list<str>"),
+            ("note", "str"),
+        ] {
+            registry.register(name, AttrRole::Param, data_type).unwrap();
+        }
+        registry
+    }
+
+    fn params() -> ResolvedParams {
+        let registry = registry();
+        let raw = [
+            ("max_orders_day", "250"),
+            ("tier", "gold"),
+            ("enabled", "true"),
+            ("starts_at", "2025-08-22T02:00:00+02:00"),
+            ("countries", "US,CA"),
+        ];
+        let mut values: Vec<ConfigValue> = raw
+            .iter()
+            .map(|(key, text)| {
+                let meta = registry.get(key).unwrap();
+                let value = parse_str_value(meta, text).unwrap();
+                ConfigValue { match_id: 1, attr_id: meta.attr_id, role: AttrRole::Param, value }
+            })
+            .collect();
+        values.push(ConfigValue { match_id: 1, attr_id: 6, role: AttrRole::Param, value: TypedValue::Null });
+        // unknown to the registry, so dropped
+        values.push(ConfigValue { match_id: 1, attr_id: 99, role: AttrRole::Param, value: TypedValue::Int(1) });
+        ResolvedParams::from_values(&values, &registry)
+    }
+
+    #[test]
+    fn getters_convert_by_type() {
+        let params = params();
+        assert_eq!(params.len(), 6);
+        assert_eq!(params.get_int("max_orders_day").unwrap(), 250);
+        assert_eq!(params.get_str("tier").unwrap(), "gold");
+        assert!(params.get_bool("enabled").unwrap());
+        let utc = NaiveDateTime::parse_from_str("2025-08-22 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(params.get_dt("starts_at").unwrap(), utc);
+        assert_eq!(params.get::<DateTime<FixedOffset>>("starts_at").unwrap().offset().local_minus_utc(), 7_200);
+        assert_eq!(params.get::<Vec<String>>("countries").unwrap(), ["US", "CA"]);
+    }
+
+    #[test]
+    fn missing_null_and_mismatched_params_are_errors() {
+        let params = params();
+        for key in ["note", "absent"] {
+            assert!(matches!(params.get_int(key), Err(PrecedenceConfigError::MissingParam { .. })), "{key}");
+            assert_eq!(params.get_opt::<i64>(key).unwrap(), None);
+        }
+        assert!(params.contains("note") && !params.contains("absent"));
+        let err = params.get_int("tier").unwrap_err();
+        assert!(
+            matches!(err, PrecedenceConfigError::ParamTypeMismatch { expected: "int", found: "str", .. }),
+            "{err}"
+        );
+        assert!(matches!(
+            params.get_str("enabled"),
+            Err(PrecedenceConfigError::ParamTypeMismatch { expected: "str", found: "bool", .. })
+        ));
+        assert!(params.get_opt::<bool>("tier").is_err());
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn env_overrides_are_parsed_per_config_and_attribute() {
+        let registry = registry();
+        let env = vars(&[("PC__pricing__max_orders_day", "10"), ("PC__pricing__enabled", "false"), ("HOME", "/root")]);
+        let overlay = Overlay::from_vars(env, &registry).unwrap();
+        assert_eq!(overlay.len(), 2);
+        let overrides: Vec<(i32, &TypedValue)> = overlay.overrides("pricing").collect();
+        assert_eq!(overrides, [(1, &TypedValue::Int(10)), (3, &TypedValue::Bool(false))]);
+        assert_eq!(overlay.overrides("other").count(), 0);
+    }
+
+    #[test]
+    fn bad_env_overrides_name_the_variable() {
+        let registry = registry();
+        for (key, raw) in [("PC__pricing", "1"), ("PC____tier", "x"), ("PC__pricing__max_orders_day", "ten")] {
+            let err = Overlay::from_vars(vars(&[(key, raw)]), &registry).unwrap_err();
+            assert!(matches!(err, PrecedenceConfigError::InvalidOverride { key: ref k, .. } if k == key), "{key}");
+        }
+    }
+
+    #[test]
+    fn overrides_replace_or_add_resolved_params() {
+        let mut registry = registry();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        let map = serde_json::json!({ "tier": "silver", "countries": ["FR"] });
+        let mut overlay = Overlay::from_map("pricing", map.as_object().unwrap(), &registry).unwrap();
+        assert!(matches!(
+            overlay.set("pricing", "region", "eu", &registry),
+            Err(PrecedenceConfigError::WrongRole { expected: AttrRole::Param, .. })
+        ));
+        let unknown = overlay.set("pricing", "nope", "1", &registry);
+        assert!(matches!(unknown, Err(PrecedenceConfigError::UnknownAttrKey { .. })));
+
+        let tier = registry.id_of("tier").unwrap();
+        let value = TypedValue::Str("gold".into());
+        let gold = ConfigValue { match_id: 4, attr_id: tier, role: AttrRole::Param, value };
+        let mut resolved = ResolvedConfig { match_id: 4, rank: 1, params: vec![gold] };
+        overlay.apply("pricing", &mut resolved);
+        let params = ResolvedParams::from_values(&resolved.params, &registry);
+        assert_eq!(params.get_str("tier").unwrap(), "silver");
+        assert_eq!(params.get::<Vec<String>>("countries").unwrap(), ["FR"]);
+
+        let countries = TypedValue::List(vec![TypedValue::Str("FR".into())]);
+        assert_eq!(overlay.remove("pricing", "countries", &registry), Some(countries));
+        assert_eq!(overlay.remove("pricing", "tier", &registry), Some(TypedValue::Str("silver".into())));
+        assert!(overlay.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_env_names_are_skipped_and_values_refused() {
+        use std::os::unix::ffi::OsStringExt;
+        let registry = registry();
+        let bad = || OsString::from_vec(vec![0x66, 0xff]);
+        let name = |key: &str| OsString::from(key);
+        let skipped = vec![(bad(), name("1")), (name("PC__pricing__max_orders_day"), name("10"))];
+        assert_eq!(Overlay::from_os_vars(skipped, &registry).unwrap().len(), 1);
+        let err = Overlay::from_os_vars(vec![(name("PC__pricing__tier"), bad())], &registry).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidOverride { ref key, .. } if key == "PC__pricing__tier"));
+        // a value that isn't UTF-8 under someone else's name is none of our business
+        assert!(Overlay::from_os_vars(vec![(name("HOME"), bad())], &registry).unwrap().is_empty());
+    }
+}
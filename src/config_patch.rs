@@ -0,0 +1,379 @@
+use crate::config_diff::{ConfigDiff, MatchTuple};
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_types::{ConfigEnvelope, ConfigMeta, ConfigRow, MatchPart, Param, ParamType};
+use crate::config_value::TypedValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A reviewed change to a config, as a list of operations applied in order.
+///
+/// ```json
+/// { "ops": [
+///   { "op": "set_param", "match": { "region": "EU" }, "key": "fee", "value": { "type": "dec", "value": "2.5" } },
+///   { "op": "remove_row", "match": { "region": "US" } },
+///   { "op": "set_precedence", "rank": 2, "attr_id": 3, "match_type": 1 }
+/// ] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ConfigPatch {
+    pub ops: Vec<PatchOp>,
+}
+
+impl ConfigPatch {
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// One patch operation. Rows are addressed by their exact match tuple, as in
+/// [`diff_envelopes`](crate::config_diff::diff_envelopes).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PatchOp {
+    /// Replaces the config metadata.
+    SetConfig { config: ConfigMeta },
+    /// Appends a row; no row may have its match tuple yet.
+    AddRow { row: ConfigRow },
+    RemoveRow {
+        #[serde(rename = "match")]
+        match_tuple: MatchTuple,
+    },
+    /// Gives a row a new match tuple, keeping its params.
+    SetMatch {
+        #[serde(rename = "match")]
+        match_tuple: MatchTuple,
+        to: MatchTuple,
+    },
+    /// Sets a param, keeping its declared type if the row has it already.
+    SetParam {
+        #[serde(rename = "match")]
+        match_tuple: MatchTuple,
+        key: String,
+        value: TypedValue,
+    },
+    UnsetParam {
+        #[serde(rename = "match")]
+        match_tuple: MatchTuple,
+        key: String,
+    },
+    /// Sets a (rank, attr) cell of the precedence table; `None` removes it.
+    SetPrecedence { rank: i32, attr_id: i32, match_type: Option<MatchType> },
+}
+
+/// Applies the row and metadata operations of `patch` to `envelope`, in order.
+/// `SetPrecedence` operations are left to [`apply_precedence_patch`].
+///
+/// Every operation must find what it refers to (the row, the param) and must not
+/// create a second row with the same match tuple. The patch applies as a whole:
+/// on error `envelope` is left unchanged.
+pub fn apply_patch(envelope: &mut ConfigEnvelope, patch: &ConfigPatch) -> Result<()> {
+    let mut patched = envelope.clone();
+    for (index, op) in patch.ops.iter().enumerate() {
+        apply_op(&mut patched, op).map_err(|reason| PrecedenceConfigError::InvalidPatchOp { index, reason })?;
+    }
+    *envelope = patched;
+    Ok(())
+}
+
+fn apply_op(envelope: &mut ConfigEnvelope, op: &PatchOp) -> std::result::Result<(), String> {
+    match op {
+        PatchOp::SetConfig { config } => envelope.config = config.clone(),
+        PatchOp::AddRow { row } => {
+            let tuple = tuple_of(&row.match_part);
+            if find_row(envelope, &tuple).is_some() {
+                return Err(format!("a row with match {} exists already", render(&tuple)));
+            }
+            envelope.rows.push(row.clone());
+        }
+        PatchOp::RemoveRow { match_tuple } => {
+            let i = require_row(envelope, match_tuple)?;
+            envelope.rows.remove(i);
+        }
+        PatchOp::SetMatch { match_tuple, to } => {
+            let i = require_row(envelope, match_tuple)?;
+            if to != match_tuple && find_row(envelope, to).is_some() {
+                return Err(format!("a row with match {} exists already", render(to)));
+            }
+            envelope.rows[i].match_part = match_part_of(to);
+        }
+        PatchOp::SetParam { match_tuple, key, value } => {
+            let i = require_row(envelope, match_tuple)?;
+            let params = &mut envelope.rows[i].params;
+            match params.iter_mut().find(|p| &p.key == key) {
+                Some(param) => param.value = value.to_json_value(),
+                None => {
+                    let ty = param_type_of(value)
+                        .ok_or_else(|| format!("cannot add param '{}': the type of {} is ambiguous", key, value))?;
+                    params.push(Param { key: key.into(), ty, value: value.to_json_value() });
+                }
+            }
+        }
+        PatchOp::UnsetParam { match_tuple, key } => {
+            let i = require_row(envelope, match_tuple)?;
+            let params = &mut envelope.rows[i].params;
+            let before = params.len();
+            params.retain(|p| &p.key != key);
+            if params.len() == before {
+                return Err(format!("row {} has no param '{}'", render(match_tuple), key));
+            }
+        }
+        PatchOp::SetPrecedence { .. } => {}
+    }
+    Ok(())
+}
+
+/// Applies the `SetPrecedence` operations of `patch` to tall rules, in order;
+/// everything else is left to [`apply_patch`]. Added cells take the
+/// `config_version_id` of the existing rules (0 if there are none). Removing a
+/// cell that does not exist is an error, and on error `rules` are left unchanged.
+pub fn apply_precedence_patch(rules: &mut Vec<ConfigPrecedenceRule>, patch: &ConfigPatch) -> Result<()> {
+    let config_version_id = rules.first().map_or(0, |r| r.config_version_id);
+    let mut patched = rules.clone();
+    for (index, op) in patch.ops.iter().enumerate() {
+        let PatchOp::SetPrecedence { rank, attr_id, match_type } = *op else {
+            continue;
+        };
+        let existing = patched.iter().position(|r| r.rank == rank && r.attr_id == attr_id);
+        match (existing, match_type) {
+            (Some(i), Some(match_type)) => patched[i].match_type = match_type,
+            (Some(i), None) => {
+                patched.remove(i);
+            }
+            (None, Some(match_type)) => {
+                if rank <= 0 {
+                    return Err(PrecedenceConfigError::InvalidRank { index, rank });
+                }
+                patched.push(ConfigPrecedenceRule { config_version_id, rank, attr_id, match_type });
+            }
+            (None, None) => {
+                return Err(PrecedenceConfigError::InvalidPatchOp {
+                    index,
+                    reason: format!("no precedence cell at rank {} attr_id {}", rank, attr_id),
+                });
+            }
+        }
+    }
+    *rules = patched;
+    Ok(())
+}
+
+/// The patch that replays `diff` on its old envelope: the new metadata, removed
+/// rows, match and param changes, then added rows.
+pub fn patch_from_diff(diff: &ConfigDiff) -> ConfigPatch {
+    let mut ops = Vec::new();
+    if let Some((_, new)) = &diff.config {
+        ops.push(PatchOp::SetConfig { config: new.clone() });
+    }
+    for removed in &diff.removed_rows {
+        ops.push(PatchOp::RemoveRow { match_tuple: removed.match_tuple.clone() });
+    }
+    for change in &diff.changed_rows {
+        if change.match_changed() {
+            ops.push(PatchOp::SetMatch { match_tuple: change.old_match.clone(), to: change.new_match.clone() });
+        }
+        for param in &change.params {
+            let match_tuple = change.new_match.clone();
+            let key = param.key.clone();
+            ops.push(match &param.new {
+                Some(value) => PatchOp::SetParam { match_tuple, key, value: value.clone() },
+                None => PatchOp::UnsetParam { match_tuple, key },
+            });
+        }
+    }
+    for added in &diff.added_rows {
+        let row = ConfigRow {
+            match_part: match_part_of(&added.match_tuple),
+            params: added.params.clone(),
+            audit: added.audit.clone(),
+        };
+        ops.push(PatchOp::AddRow { row });
+    }
+    ConfigPatch { ops }
+}
+
+/// `SetPrecedence` operations turning the `old` precedence table into `new`,
+/// by rank then attr_id.
+pub fn patch_from_precedence(old: &[ConfigPrecedenceRule], new: &[ConfigPrecedenceRule]) -> ConfigPatch {
+    let cells = |rules: &[ConfigPrecedenceRule]| -> BTreeMap<(i32, i32), MatchType> {
+        rules.iter().map(|r| ((r.rank, r.attr_id), r.match_type)).collect()
+    };
+    let (old, new) = (cells(old), cells(new));
+
+    let mut keys: Vec<&(i32, i32)> = old.keys().chain(new.keys()).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    let ops = keys
+        .into_iter()
+        .filter(|key| old.get(key) != new.get(key))
+        .map(|&key| PatchOp::SetPrecedence { rank: key.0, attr_id: key.1, match_type: new.get(&key).copied() })
+        .collect();
+    ConfigPatch { ops }
+}
+
+fn tuple_of(match_part: &MatchPart) -> MatchTuple {
+    match_part.attrs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+fn match_part_of(match_tuple: &MatchTuple) -> MatchPart {
+    MatchPart { attrs: match_tuple.iter().map(|(k, v)| (k.as_str().into(), v.clone())).collect() }
+}
+
+fn find_row(envelope: &ConfigEnvelope, match_tuple: &MatchTuple) -> Option<usize> {
+    envelope.rows.iter().position(|row| &tuple_of(&row.match_part) == match_tuple)
+}
+
+fn require_row(envelope: &ConfigEnvelope, match_tuple: &MatchTuple) -> std::result::Result<usize, String> {
+    find_row(envelope, match_tuple).ok_or_else(|| format!("no row with match {}", render(match_tuple)))
+}
+
+fn render(match_tuple: &MatchTuple) -> String {
+    serde_json::to_string(match_tuple).unwrap_or_default()
+}
+
+/// Declared type for a param added by `SetParam`; `None` for `null`, match-only
+/// values (ranges, alternatives, patterns, regexes) and empty lists.
+fn param_type_of(value: &TypedValue) -> Option<ParamType> {
+    Some(match value {
+        TypedValue::Int(_) => ParamType::Int,
+        TypedValue::Dec(_) => ParamType::Dec,
+        #[cfg(feature = "rust_decimal")]
+        TypedValue::Decimal(_) => ParamType::Dec,
+        TypedValue::Str(_) => ParamType::Str,
+        TypedValue::Enum(_) => ParamType::Enum,
+        TypedValue::Bool(_) => ParamType::Bool,
+        TypedValue::Dt(_) | TypedValue::DtTz(_) => ParamType::Dt,
+        TypedValue::Date(_) => ParamType::Date,
+        TypedValue::Time(_) => ParamType::Time,
+        TypedValue::Duration(_) => ParamType::Duration,
+        #[cfg(feature = "uuid")]
+        TypedValue::Uuid(_) => ParamType::Uuid,
+        TypedValue::List(items) => ParamType::List(Box::new(param_type_of(items.first()?)?)),
+        TypedValue::Range(_) | TypedValue::OneOf(_) | TypedValue::Pattern(_) | TypedValue::Null => return None,
+        #[cfg(feature = "regex")]
+        TypedValue::Regex(_) => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_diff::diff_envelopes;
+    use serde_json::{json, Value};
+
+    fn envelope(rows: Value) -> ConfigEnvelope {
+        serde_json::from_value(json!({ "config": { "name": "c", "version": 1, "version_name": "v1" }, "rows": rows }))
+            .unwrap()
+    }
+
+    fn base() -> ConfigEnvelope {
+        envelope(json!([
+            { "match": { "region": "EU" }, "params": [{ "key": "fee", "type": "int", "value": 1 }] },
+            { "match": { "region": "US" }, "params": [{ "key": "fee", "type": "int", "value": 2 }] },
+        ]))
+    }
+
+    fn patch(ops: Value) -> ConfigPatch {
+        serde_json::from_value(json!({ "ops": ops })).unwrap()
+    }
+
+    fn rows(envelope: &ConfigEnvelope) -> Value {
+        serde_json::to_value(&envelope.rows).unwrap()
+    }
+
+    #[test]
+    fn row_ops_apply_in_order() {
+        let mut envelope = base();
+        let gold = json!({ "type": "str", "value": "gold" });
+        let ops = patch(json!([
+            { "op": "set_param", "match": { "region": "EU" }, "key": "fee", "value": { "type": "int", "value": 5 } },
+            { "op": "set_param", "match": { "region": "EU" }, "key": "tier", "value": gold },
+            { "op": "unset_param", "match": { "region": "EU" }, "key": "fee" },
+            { "op": "remove_row", "match": { "region": "US" } },
+            { "op": "set_match", "match": { "region": "EU" }, "to": { "region": "FR" } },
+            { "op": "add_row", "row": { "match": { "region": "US" }, "params": [] } },
+            { "op": "set_precedence", "rank": 1, "attr_id": 1, "match_type": 1 },
+        ]));
+        apply_patch(&mut envelope, &ops).unwrap();
+        assert_eq!(
+            rows(&envelope),
+            json!([
+                { "match": { "region": "FR" }, "params": [{ "key": "tier", "type": "str", "value": "gold" }] },
+                { "match": { "region": "US" }, "params": [] },
+            ])
+        );
+    }
+
+    #[test]
+    fn a_failing_op_leaves_the_envelope_unchanged() {
+        let mut envelope = base();
+        let eu = json!({ "region": "EU" });
+        let cases = [
+            (json!({ "op": "remove_row", "match": { "region": "FR" } }), "no row with match"),
+            (json!({ "op": "set_match", "match": eu, "to": { "region": "US" } }), "exists already"),
+            (json!({ "op": "unset_param", "match": eu, "key": "tier" }), "has no param 'tier'"),
+            (json!({ "op": "set_param", "match": eu, "key": "tier", "value": { "type": "null" } }), "ambiguous"),
+        ];
+        for (op, expected) in cases {
+            let fee = json!({ "type": "int", "value": 9 });
+            let ops = patch(json!([{ "op": "set_param", "match": eu, "key": "fee", "value": fee }, op]));
+            match apply_patch(&mut envelope, &ops) {
+                Err(PrecedenceConfigError::InvalidPatchOp { index: 1, reason }) => {
+                    assert!(reason.contains(expected), "{reason}")
+                }
+                other => panic!("expected InvalidPatchOp at 1, got {other:?}"),
+            }
+            assert_eq!(rows(&envelope), rows(&base()));
+        }
+    }
+
+    fn rule(rank: i32, attr_id: i32, match_type: MatchType) -> ConfigPrecedenceRule {
+        ConfigPrecedenceRule { config_version_id: 7, rank, attr_id, match_type }
+    }
+
+    #[test]
+    fn precedence_ops_set_and_remove_cells() {
+        let mut rules = vec![rule(1, 1, MatchType::Exact), rule(1, 2, MatchType::Exact)];
+        let ops = patch(json!([
+            { "op": "set_precedence", "rank": 1, "attr_id": 2, "match_type": 0 },
+            { "op": "set_precedence", "rank": 1, "attr_id": 1, "match_type": null },
+            { "op": "set_precedence", "rank": 2, "attr_id": 1, "match_type": 1 },
+            { "op": "remove_row", "match": { "region": "US" } },
+        ]));
+        apply_precedence_patch(&mut rules, &ops).unwrap();
+        assert_eq!(rules, [rule(1, 2, MatchType::Ignore), rule(2, 1, MatchType::Exact)]);
+
+        let before = rules.clone();
+        let missing = patch(json!([{ "op": "set_precedence", "rank": 9, "attr_id": 1, "match_type": null }]));
+        let err = apply_precedence_patch(&mut rules, &missing).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidPatchOp { .. }));
+        let rank_zero = patch(json!([{ "op": "set_precedence", "rank": 0, "attr_id": 1, "match_type": 1 }]));
+        let err = apply_precedence_patch(&mut rules, &rank_zero).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidRank { rank: 0, .. }));
+        assert_eq!(rules, before);
+    }
+
+    #[test]
+    fn patches_from_diffs_replay_the_change() {
+        let old = base();
+        let mut new = envelope(json!([
+            { "match": { "region": "EU" }, "params": [{ "key": "fee", "type": "int", "value": 3 }] },
+            { "match": { "region": "APAC" }, "params": [{ "key": "fee", "type": "int", "value": 4 }] },
+        ]));
+        new.config.version_name = "v1b".to_string();
+        let ops = patch_from_diff(&diff_envelopes(&old, &new));
+        let mut replayed = old.clone();
+        apply_patch(&mut replayed, &ops).unwrap();
+        assert_eq!((rows(&replayed), replayed.config.version_name.as_str()), (rows(&new), "v1b"));
+
+        let old_rules = vec![rule(1, 1, MatchType::Exact), rule(2, 2, MatchType::Exact)];
+        let new_rules = vec![rule(1, 1, MatchType::Ignore), rule(3, 2, MatchType::Exact)];
+        let ops = patch_from_precedence(&old_rules, &new_rules);
+        assert_eq!(ops.ops.len(), 3);
+        let mut replayed = old_rules;
+        apply_precedence_patch(&mut replayed, &ops).unwrap();
+        replayed.sort_by_key(|r| (r.rank, r.attr_id));
+        assert_eq!(replayed, new_rules);
+        assert!(patch_from_precedence(&new_rules, &new_rules).is_empty());
+    }
+}
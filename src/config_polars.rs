@@ -0,0 +1,321 @@
+//! Polars DataFrames (`polars` feature) of tall precedence rules and config
+//! values, to inspect, join and pivot configs in notebooks.
+
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_value::{ConfigValue, TypedValue};
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike};
+use polars::prelude::{Column, DataFrame, DataType, IntoColumn, NamedFrom, PolarsError, Series, TimeUnit, TimeZone};
+
+/// Tall rules as a frame of `config_version_id`, `rank`, `attr_id` (i32) and
+/// `match_type` (u8, 0/1).
+pub fn rules_to_polars(rules: &[ConfigPrecedenceRule]) -> Result<DataFrame> {
+    let columns = vec![
+        Column::new("config_version_id".into(), rules.iter().map(|r| r.config_version_id).collect::<Vec<_>>()),
+        Column::new("rank".into(), rules.iter().map(|r| r.rank).collect::<Vec<_>>()),
+        Column::new("attr_id".into(), rules.iter().map(|r| r.attr_id).collect::<Vec<_>>()),
+        Column::new("match_type".into(), rules.iter().map(|r| u8::from(r.match_type)).collect::<Vec<_>>()),
+    ];
+    DataFrame::new(rules.len(), columns).map_err(polars_error)
+}
+
+/// Reads back a frame of [`rules_to_polars`]'s columns; any integer column type is
+/// accepted, other columns are ignored.
+pub fn rules_from_polars(frame: &DataFrame) -> Result<Vec<ConfigPrecedenceRule>> {
+    let config_version_id = int_column(frame, "config_version_id")?;
+    let rank = int_column(frame, "rank")?;
+    let attr_id = int_column(frame, "attr_id")?;
+    let match_type = int_column(frame, "match_type")?;
+
+    (0..frame.height())
+        .map(|row| {
+            let match_type = u8::try_from(required(&match_type, "match_type", row)?)
+                .map_err(|_| invalid(format!("row {}: match_type out of range", row)))?;
+            Ok(ConfigPrecedenceRule {
+                config_version_id: required_i32(&config_version_id, "config_version_id", row)?,
+                rank: required_i32(&rank, "rank", row)?,
+                attr_id: required_i32(&attr_id, "attr_id", row)?,
+                match_type: MatchType::try_from(match_type)?,
+            })
+        })
+        .collect()
+}
+
+/// Config values as a frame of `match_id`, `attr_id`, `role`, `type` (the
+/// `TypedValue::type_name`), then one nullable column per kind of value, of which
+/// each row sets at most one: `int_value` (i64), `dec_value` (f64), `str_value`
+/// (`str`, `enum`), `bool_value`, `dt_value` (datetime(us, UTC)), `date_value`,
+/// `time_value`, `duration_value` (duration(us)), and `json_value` with the
+/// tagged JSON (`{ "type": .., "value": .. }`) of anything else.
+///
+/// `dt` values with an offset are stored in UTC and read back without it.
+pub fn values_to_polars(values: &[ConfigValue]) -> Result<DataFrame> {
+    let mut cells = ValueColumns::default();
+    for value in values {
+        cells.push(&value.value)?;
+    }
+    let utc = DataType::Datetime(TimeUnit::Microseconds, Some(TimeZone::UTC));
+    let columns = vec![
+        Column::new("match_id".into(), values.iter().map(|v| v.match_id).collect::<Vec<_>>()),
+        Column::new("attr_id".into(), values.iter().map(|v| v.attr_id).collect::<Vec<_>>()),
+        Column::new("role".into(), values.iter().map(|v| v.role.as_str()).collect::<Vec<_>>()),
+        Column::new("type".into(), values.iter().map(|v| v.value.type_name()).collect::<Vec<_>>()),
+        Column::new("int_value".into(), cells.int),
+        Column::new("dec_value".into(), cells.dec),
+        Column::new("str_value".into(), cells.str),
+        Column::new("bool_value".into(), cells.bool),
+        typed_column("dt_value", cells.dt, &utc)?,
+        typed_column("date_value", cells.date, &DataType::Date)?,
+        typed_column("time_value", cells.time, &DataType::Time)?,
+        typed_column("duration_value", cells.duration, &DataType::Duration(TimeUnit::Microseconds))?,
+        Column::new("json_value".into(), cells.json),
+    ];
+    DataFrame::new(values.len(), columns).map_err(polars_error)
+}
+
+/// Reads back a frame of [`values_to_polars`]'s columns: each row's value comes
+/// from `json_value` if set, else from the column its `type` belongs in.
+pub fn values_from_polars(frame: &DataFrame) -> Result<Vec<ConfigValue>> {
+    let match_id = int_column(frame, "match_id")?;
+    let attr_id = int_column(frame, "attr_id")?;
+    let role = str_column(frame, "role")?;
+    let ty = str_column(frame, "type")?;
+    let int = int_column(frame, "int_value")?;
+    let dec = physical(frame, "dec_value", &DataType::Float64)?.f64().map_err(polars_error)?.to_vec();
+    let str = str_column(frame, "str_value")?;
+    let bool = physical(frame, "bool_value", &DataType::Boolean)?;
+    let bool: Vec<_> = bool.bool().map_err(polars_error)?.iter().collect();
+    let dt = int_column(frame, "dt_value")?;
+    let date = int_column(frame, "date_value")?;
+    let time = int_column(frame, "time_value")?;
+    let duration = int_column(frame, "duration_value")?;
+    let json = str_column(frame, "json_value")?;
+
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid");
+    (0..frame.height())
+        .map(|row| {
+            let type_name = required(&ty, "type", row)?;
+            let at = |column: &str| invalid(format!("row {}: no {} for a '{}' value", row, column, type_name));
+            let value = if let Some(json) = &json[row] {
+                serde_json::from_str(json).map_err(|e| invalid(format!("row {}: json_value: {}", row, e)))?
+            } else {
+                match type_name.as_str() {
+                    "int" => TypedValue::Int(int[row].ok_or_else(|| at("int_value"))?),
+                    "dec" => TypedValue::Dec(dec[row].ok_or_else(|| at("dec_value"))?),
+                    "str" => TypedValue::Str(str[row].clone().ok_or_else(|| at("str_value"))?),
+                    "enum" => TypedValue::Enum(str[row].clone().ok_or_else(|| at("str_value"))?),
+                    "bool" => TypedValue::Bool(bool[row].ok_or_else(|| at("bool_value"))?),
+                    "dt" => {
+                        let micros = dt[row].ok_or_else(|| at("dt_value"))?;
+                        let dt = DateTime::from_timestamp_micros(micros).ok_or_else(|| at("valid dt_value"))?;
+                        TypedValue::Dt(dt.naive_utc())
+                    }
+                    "date" => {
+                        let days = date[row].ok_or_else(|| at("date_value"))?;
+                        TypedValue::Date(epoch + chrono::Duration::days(days))
+                    }
+                    "time" => {
+                        let nanos = time[row].ok_or_else(|| at("time_value"))?;
+                        let (secs, nanos) = (nanos.div_euclid(1_000_000_000), nanos.rem_euclid(1_000_000_000));
+                        let time = u32::try_from(secs)
+                            .ok()
+                            .and_then(|secs| NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos as u32))
+                            .ok_or_else(|| at("valid time_value"))?;
+                        TypedValue::Time(time)
+                    }
+                    "duration" => {
+                        let micros = duration[row].ok_or_else(|| at("duration_value"))?;
+                        let micros = u64::try_from(micros).map_err(|_| at("non-negative duration_value"))?;
+                        TypedValue::Duration(std::time::Duration::from_micros(micros))
+                    }
+                    "null" => TypedValue::Null,
+                    _ => return Err(at("json_value")),
+                }
+            };
+            Ok(ConfigValue {
+                match_id: required_i32(&match_id, "match_id", row)?,
+                attr_id: required_i32(&attr_id, "attr_id", row)?,
+                role: required(&role, "role", row)?.parse()?,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// The typed value columns of [`values_to_polars`], one entry per config value.
+#[derive(Default)]
+struct ValueColumns {
+    int: Vec<Option<i64>>,
+    dec: Vec<Option<f64>>,
+    str: Vec<Option<String>>,
+    bool: Vec<Option<bool>>,
+    dt: Vec<Option<i64>>,
+    date: Vec<Option<i32>>,
+    /// nanoseconds since midnight, the physical type of Polars times
+    time: Vec<Option<i64>>,
+    duration: Vec<Option<i64>>,
+    json: Vec<Option<String>>,
+}
+
+impl ValueColumns {
+    /// Appends a row of nulls, then sets the column `value` belongs in.
+    fn push(&mut self, value: &TypedValue) -> Result<()> {
+        self.int.push(None);
+        self.dec.push(None);
+        self.str.push(None);
+        self.bool.push(None);
+        self.dt.push(None);
+        self.date.push(None);
+        self.time.push(None);
+        self.duration.push(None);
+        self.json.push(None);
+
+        match value {
+            TypedValue::Int(v) => *last(&mut self.int) = Some(*v),
+            TypedValue::Dec(v) => *last(&mut self.dec) = Some(*v),
+            TypedValue::Str(v) | TypedValue::Enum(v) => *last(&mut self.str) = Some(v.clone()),
+            TypedValue::Bool(v) => *last(&mut self.bool) = Some(*v),
+            TypedValue::Dt(v) => *last(&mut self.dt) = Some(v.and_utc().timestamp_micros()),
+            TypedValue::DtTz(v) => *last(&mut self.dt) = Some(v.timestamp_micros()),
+            TypedValue::Date(v) => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid");
+                *last(&mut self.date) = Some(v.signed_duration_since(epoch).num_days() as i32);
+            }
+            TypedValue::Time(v) => {
+                let nanos = v.num_seconds_from_midnight() as i64 * 1_000_000_000 + v.nanosecond() as i64;
+                *last(&mut self.time) = Some(nanos);
+            }
+            TypedValue::Duration(v) => {
+                *last(&mut self.duration) = Some(i64::try_from(v.as_micros()).unwrap_or(i64::MAX));
+            }
+            TypedValue::Null => {}
+            other => {
+                let json = serde_json::to_string(other).map_err(PrecedenceConfigError::InvalidJson)?;
+                *last(&mut self.json) = Some(json);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn last<T>(column: &mut [Option<T>]) -> &mut Option<T> {
+    column.last_mut().expect("a row was just pushed")
+}
+
+/// A column of physical values cast to its logical `dtype`.
+fn typed_column<T>(name: &str, values: Vec<Option<T>>, dtype: &DataType) -> Result<Column>
+where
+    Series: NamedFrom<Vec<Option<T>>, [Option<T>]>,
+{
+    let series = Series::new(name.into(), values).cast(dtype).map_err(polars_error)?;
+    Ok(series.into_column())
+}
+
+/// Column `name` cast to `dtype`; logical types (dates, times, durations) cast to
+/// their integer representation.
+fn physical(frame: &DataFrame, name: &str, dtype: &DataType) -> Result<Series> {
+    let column = frame.column(name).map_err(polars_error)?;
+    let column = column.to_physical_repr();
+    column.cast(dtype).map(|c| c.as_materialized_series().clone()).map_err(polars_error)
+}
+
+fn int_column(frame: &DataFrame, name: &str) -> Result<Vec<Option<i64>>> {
+    Ok(physical(frame, name, &DataType::Int64)?.i64().map_err(polars_error)?.to_vec())
+}
+
+fn str_column(frame: &DataFrame, name: &str) -> Result<Vec<Option<String>>> {
+    let series = physical(frame, name, &DataType::String)?;
+    let strings = series.str().map_err(polars_error)?;
+    Ok(strings.iter().map(|s| s.map(str::to_string)).collect())
+}
+
+fn required<T: Clone>(column: &[Option<T>], name: &str, row: usize) -> Result<T> {
+    column[row].clone().ok_or_else(|| invalid(format!("row {}: {} is null", row, name)))
+}
+
+fn required_i32(column: &[Option<i64>], name: &str, row: usize) -> Result<i32> {
+    i32::try_from(required(column, name, row)?).map_err(|_| invalid(format!("row {}: {} out of range", row, name)))
+}
+
+fn invalid(reason: String) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "Polars", reason }
+}
+
+fn polars_error(e: PolarsError) -> PrecedenceConfigError {
+    invalid(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::AttrRole;
+    use chrono::{TimeZone, Utc};
+
+    fn rules() -> Vec<ConfigPrecedenceRule> {
+        vec![
+            ConfigPrecedenceRule { config_version_id: 7, rank: 1, attr_id: 1, match_type: MatchType::Exact },
+            ConfigPrecedenceRule { config_version_id: 7, rank: 2, attr_id: 1, match_type: MatchType::Ignore },
+        ]
+    }
+
+    fn value(attr_id: i32, value: TypedValue) -> ConfigValue {
+        ConfigValue { match_id: 1, attr_id, role: AttrRole::Param, value }
+    }
+
+    fn fields(values: &[ConfigValue]) -> Vec<(i32, i32, AttrRole, TypedValue)> {
+        values.iter().map(|v| (v.match_id, v.attr_id, v.role, v.value.clone())).collect()
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let frame = rules_to_polars(&rules()).unwrap();
+        assert_eq!(frame.shape(), (2, 4));
+        assert_eq!(rules_from_polars(&frame).unwrap(), rules());
+
+        // any integer type reads back, and extra columns are ignored
+        let columns = vec![
+            Column::new("config_version_id".into(), [7i64, 7]),
+            Column::new("rank".into(), [1i64, 2]),
+            Column::new("attr_id".into(), [1u32, 1]),
+            Column::new("match_type".into(), [1i64, 0]),
+            Column::new("note".into(), ["a", "b"]),
+        ];
+        assert_eq!(rules_from_polars(&DataFrame::new(2, columns).unwrap()).unwrap(), rules());
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let at = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        let values = vec![
+            value(1, TypedValue::Int(5)),
+            value(2, TypedValue::Dec(2.5)),
+            value(3, TypedValue::Enum("gold".to_string())),
+            value(4, TypedValue::Bool(true)),
+            value(5, TypedValue::Dt(at.naive_utc())),
+            value(6, TypedValue::Date(NaiveDate::from_ymd_opt(2026, 3, 1).unwrap())),
+            value(7, TypedValue::Time(NaiveTime::from_hms_micro_opt(9, 30, 0, 5).unwrap())),
+            value(8, TypedValue::Duration(std::time::Duration::from_secs(90))),
+            value(9, TypedValue::List(vec![TypedValue::Str("a".to_string())])),
+            value(10, TypedValue::Null),
+        ];
+        let frame = values_to_polars(&values).unwrap();
+        assert_eq!(frame.shape(), (10, 13));
+        assert_eq!(fields(&values_from_polars(&frame).unwrap()), fields(&values));
+
+        // an offset is not kept
+        let frame = values_to_polars(&[value(1, TypedValue::DtTz(at.fixed_offset()))]).unwrap();
+        assert_eq!(values_from_polars(&frame).unwrap()[0].value, TypedValue::Dt(at.naive_utc()));
+    }
+
+    #[test]
+    fn missing_or_null_cells_are_rejected() {
+        let frame = rules_to_polars(&rules()).unwrap().drop("rank").unwrap();
+        let err = rules_from_polars(&frame).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidFormat { format: "Polars", .. }), "{err}");
+
+        let mut frame = values_to_polars(&[value(1, TypedValue::Int(5))]).unwrap();
+        frame.replace("int_value", Column::new("int_value".into(), [None::<i64>])).unwrap();
+        let err = values_from_polars(&frame).unwrap_err();
+        assert!(err.to_string().contains("row 0: no int_value for a 'int' value"), "{err}");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{ConfigPrecedenceRule, MatchType};
+use crate::config_sql::ValueColumns;
+use crate::config_value::{AttrMeta, ConfigValue};
+use sqlx::{PgConnection, Postgres, QueryBuilder, Row};
+use std::collections::BTreeMap;
+
+/// Rows per multi-row INSERT; keeps the bind count under Postgres' 65535 limit.
+const BATCH_ROWS: usize = 1000;
+
+fn db(err: sqlx::Error) -> PrecedenceConfigError {
+    PrecedenceConfigError::Database(err)
+}
+
+/// Reads `config_attr` into a registry. Defaults, enum values and constraints are
+/// not part of the table and come back empty.
+pub async fn load_attrs(conn: &mut PgConnection) -> Result<AttrRegistry> {
+    let rows = sqlx::query("SELECT attr_id, attr_name, attr_role, data_type FROM config_attr ORDER BY attr_id")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(db)?;
+
+    let metas = rows
+        .iter()
+        .map(|row| {
+            let role: String = row.try_get("attr_role").map_err(db)?;
+            Ok(AttrMeta {
+                attr_id: row.try_get("attr_id").map_err(db)?,
+                attr_name: row.try_get::<String, _>("attr_name").map_err(db)?.into(),
+                data_type: row.try_get("data_type").map_err(db)?,
+                role: role.parse()?,
+                default: None,
+                enum_values: Vec::new(),
+                constraints: None,
+                patterns: false,
+                parents: Default::default(),
+                deprecated: None,
+                aliases: Vec::new(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    AttrRegistry::from_metas(metas)
+}
+
+/// Upserts every attribute of `registry` into `config_attr` by attr_id.
+pub async fn save_attrs(conn: &mut PgConnection, registry: &AttrRegistry) -> Result<()> {
+    let metas: Vec<&AttrMeta> = registry.iter().collect();
+    for chunk in metas.chunks(BATCH_ROWS) {
+        let mut insert =
+            QueryBuilder::<Postgres>::new("INSERT INTO config_attr (attr_id, attr_name, attr_role, data_type) ");
+        insert.push_values(chunk, |mut row, meta| {
+            row.push_bind(meta.attr_id)
+                .push_bind(meta.attr_name.as_str())
+                .push_bind(meta.role.as_str())
+                .push_bind(&meta.data_type);
+        });
+        insert.push(
+            " ON CONFLICT (attr_id) DO UPDATE SET attr_name = EXCLUDED.attr_name, \
+             attr_role = EXCLUDED.attr_role, data_type = EXCLUDED.data_type",
+        );
+        insert.build().execute(&mut *conn).await.map_err(db)?;
+    }
+    Ok(())
+}
+
+/// Reads one version's precedence rules, ordered by rank then attr_id.
+pub async fn load_rules(conn: &mut PgConnection, config_version_id: i32) -> Result<Vec<ConfigPrecedenceRule>> {
+    let rows = sqlx::query(
+        "SELECT config_version_id, rank, attr_id, match_type FROM config_precedence_rule \
+         WHERE config_version_id = $1 ORDER BY rank, attr_id",
+    )
+    .bind(config_version_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db)?;
+
+    rows.iter()
+        .map(|row| {
+            let rank: i32 = row.try_get("rank").map_err(db)?;
+            let attr_id: i32 = row.try_get("attr_id").map_err(db)?;
+            let raw: i16 = row.try_get("match_type").map_err(db)?;
+            let match_type = u8::try_from(raw)
+                .ok()
+                .and_then(|raw| MatchType::try_from(raw).ok())
+                .ok_or(PrecedenceConfigError::InvalidMatchType {
+                    rank,
+                    attr: AttrRef::Id(attr_id),
+                    match_type: raw.clamp(0, u8::MAX as i16) as u8,
+                })?;
+            Ok(ConfigPrecedenceRule {
+                config_version_id: row.try_get("config_version_id").map_err(db)?,
+                rank,
+                attr_id,
+                match_type,
+            })
+        })
+        .collect()
+}
+
+/// Inserts rules (e.g. the output of `matrix_json_to_tall`) in batches of
+/// multi-row INSERTs, returning the number of rows written.
+pub async fn save_rules(conn: &mut PgConnection, rules: &[ConfigPrecedenceRule]) -> Result<u64> {
+    let mut written = 0;
+    for chunk in rules.chunks(BATCH_ROWS) {
+        let mut insert = QueryBuilder::<Postgres>::new(
+            "INSERT INTO config_precedence_rule (config_version_id, rank, attr_id, match_type) ",
+        );
+        insert.push_values(chunk, |mut row, rule| {
+            row.push_bind(rule.config_version_id)
+                .push_bind(rule.rank)
+                .push_bind(rule.attr_id)
+                .push_bind(u8::from(rule.match_type) as i16);
+        });
+        written += insert.build().execute(&mut *conn).await.map_err(db)?.rows_affected();
+    }
+    Ok(written)
+}
+
+/// Reads one version's config values; `match_id` is the `config_row_id`. Each
+/// value is typed by its attribute in `registry`, which also supplies the role.
+pub async fn load_values(
+    conn: &mut PgConnection,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+) -> Result<Vec<ConfigValue>> {
+    let rows = sqlx::query(
+        "SELECT v.config_row_id, v.attr_id, v.val_int, v.val_dec::text AS val_dec, v.val_str, v.val_bool, v.val_dt \
+         FROM config_value v JOIN config_row r ON r.config_row_id = v.config_row_id \
+         WHERE r.config_version_id = $1 ORDER BY v.config_row_id, v.attr_id",
+    )
+    .bind(config_version_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db)?;
+
+    rows.iter()
+        .map(|row| {
+            let match_id: i32 = row.try_get("config_row_id").map_err(db)?;
+            let attr_id: i32 = row.try_get("attr_id").map_err(db)?;
+            let meta = registry
+                .get_by_id(attr_id)
+                .ok_or(PrecedenceConfigError::UnknownValueAttr { match_id, attr_id })?;
+            let columns = ValueColumns {
+                val_int: row.try_get("val_int").map_err(db)?,
+                val_dec: row.try_get("val_dec").map_err(db)?,
+                val_str: row.try_get("val_str").map_err(db)?,
+                val_bool: row.try_get("val_bool").map_err(db)?,
+                val_dt: row.try_get("val_dt").map_err(db)?,
+            };
+            Ok(ConfigValue { match_id, attr_id, role: meta.role, value: columns.to_typed(meta)? })
+        })
+        .collect()
+}
+
+/// Writes `values` as new rows of `config_version_id`: one `config_row` per
+/// distinct match_id, then the values in batched INSERTs. Returns match_id ->
+/// the `config_row_id` Postgres allocated for it.
+pub async fn save_values(
+    conn: &mut PgConnection,
+    config_version_id: i32,
+    values: &[ConfigValue],
+) -> Result<BTreeMap<i32, i32>> {
+    let mut row_ids: BTreeMap<i32, i32> = values.iter().map(|v| (v.match_id, 0)).collect();
+    for row_id in row_ids.values_mut() {
+        *row_id = sqlx::query_scalar("INSERT INTO config_row (config_version_id) VALUES ($1) RETURNING config_row_id")
+            .bind(config_version_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(db)?;
+    }
+
+    for chunk in values.chunks(BATCH_ROWS) {
+        let mut insert = QueryBuilder::<Postgres>::new(
+            "INSERT INTO config_value (config_row_id, attr_id, val_int, val_dec, val_str, val_bool, val_dt) ",
+        );
+        insert.push_values(chunk, |mut row, value| {
+            let columns = ValueColumns::from_typed(&value.value);
+            row.push_bind(row_ids[&value.match_id])
+                .push_bind(value.attr_id)
+                .push_bind(columns.val_int)
+                .push_bind(columns.val_dec)
+                .push_unseparated("::numeric")
+                .push_bind(columns.val_str)
+                .push_bind(columns.val_bool)
+                .push_bind(columns.val_dt);
+        });
+        insert.build().execute(&mut *conn).await.map_err(db)?;
+    }
+    Ok(row_ids)
+}
+
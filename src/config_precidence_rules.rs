@@ -1,5 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
 
 /// Incoming/outgoing Matrix row (wide) with dynamic attribute keys.
 /// Expecting JSON like:
@@ -11,158 +13,1687 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 /// ]
 /// ```
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatrixRow {
     pub rank: i32,
-    #[serde(flatten)]
-    pub attrs: HashMap<String, u8>,
+    /// (attribute column, match type) in column order: the order read from the
+    /// source document, or the order requested when exporting.
+    pub attrs: Vec<(AttrName, u8)>,
+}
+
+impl MatrixRow {
+    /// Match type of column `attr_name`, if this row has it.
+    pub fn get(&self, attr_name: &str) -> Option<u8> {
+        self.attrs.iter().find(|(name, _)| name == attr_name).map(|(_, match_type)| *match_type)
+    }
+}
+
+impl Serialize for MatrixRow {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.attrs.len() + 1))?;
+        map.serialize_entry("rank", &self.rank)?;
+        for (name, match_type) in &self.attrs {
+            map.serialize_entry(name, match_type)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for MatrixRow {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> de::Visitor<'de> for RowVisitor {
+            type Value = MatrixRow;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str(MATRIX_ROW_EXPECTED)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> std::result::Result<MatrixRow, A::Error> {
+                let (rank, attrs) = visit_matrix_row(map, |map| map.next_key::<AttrName>())?;
+                Ok(MatrixRow { rank, attrs })
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+/// A [`MatrixRow`] whose column names borrow from the JSON it was parsed from,
+/// so parsing a large matrix does not allocate a `String` per cell. A name is
+/// only copied when the JSON escapes a character in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixRowRef<'a> {
+    pub rank: i32,
+    /// (attribute column, match type) in document order
+    pub attrs: Vec<(Cow<'a, str>, u8)>,
+}
+
+impl MatrixRowRef<'_> {
+    /// Match type of column `attr_name`, if this row has it.
+    pub fn get(&self, attr_name: &str) -> Option<u8> {
+        self.attrs.iter().find(|(name, _)| name == attr_name).map(|(_, match_type)| *match_type)
+    }
+
+    pub fn into_owned(self) -> MatrixRow {
+        let attrs = self.attrs.into_iter().map(|(name, m)| (AttrName::new(&name), m)).collect();
+        MatrixRow { rank: self.rank, attrs }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for MatrixRowRef<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> de::Visitor<'de> for RowVisitor {
+            type Value = MatrixRowRef<'de>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str(MATRIX_ROW_EXPECTED)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> std::result::Result<MatrixRowRef<'de>, A::Error> {
+                let next_key = |map: &mut A| Ok(map.next_key::<BorrowedName>()?.map(|name| name.0));
+                let (rank, attrs) = visit_matrix_row(map, next_key)?;
+                Ok(MatrixRowRef { rank, attrs })
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+const MATRIX_ROW_EXPECTED: &str = "a matrix row with a `rank` and one match type per attribute";
+
+/// (column, match type) cells of a matrix row, in document order.
+type MatrixCells<K> = Vec<(K, u8)>;
+
+/// The `rank` and the cells of a matrix row object, with each key read by `next_key`.
+fn visit_matrix_row<'de, A: de::MapAccess<'de>, K: AsRef<str>>(
+    mut map: A,
+    mut next_key: impl FnMut(&mut A) -> std::result::Result<Option<K>, A::Error>,
+) -> std::result::Result<(i32, MatrixCells<K>), A::Error> {
+    let mut rank = None;
+    let mut attrs = Vec::new();
+    while let Some(key) = next_key(&mut map)? {
+        if key.as_ref() == "rank" {
+            if rank.is_some() {
+                return Err(de::Error::duplicate_field("rank"));
+            }
+            rank = Some(map.next_value()?);
+        } else {
+            attrs.push((key, map.next_value()?));
+        }
+    }
+    let rank = rank.ok_or_else(|| de::Error::missing_field("rank"))?;
+    Ok((rank, attrs))
+}
+
+/// A column name borrowed from the input where it can be.
+struct BorrowedName<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for BorrowedName<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl<'de> de::Visitor<'de> for NameVisitor {
+            type Value = BorrowedName<'de>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an attribute name")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> std::result::Result<Self::Value, E> {
+                Ok(BorrowedName(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+                Ok(BorrowedName(Cow::Owned(v.to_string())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<Self::Value, E> {
+                Ok(BorrowedName(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(NameVisitor)
+    }
 }
 
 
 /// Canonical Tall row (normalized)
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ConfigPrecedenceRule {
     pub config_version_id: i32,
     pub rank: i32,
     pub attr_id: i32,
-    pub match_type: u8, // 0, 1
+    pub match_type: MatchType,
+}
+
+/// MATCH_TYPE of a precedence cell; (de)serialized as the numeric 0/1 used by the tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum MatchType {
+    /// 0: the row must leave the attribute as a wildcard.
+    Ignore = 0,
+    /// 1: the row value must equal the context value.
+    Exact = 1,
+}
+
+impl TryFrom<u8> for MatchType {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(MatchType::Ignore),
+            1 => Ok(MatchType::Exact),
+            other => Err(PrecedenceConfigError::InvalidMatchTypeValue(other)),
+        }
+    }
+}
+
+impl From<MatchType> for u8 {
+    fn from(value: MatchType) -> Self {
+        value as u8
+    }
 }
 
-use anyhow::{anyhow, bail, Context, Result};
+use crate::config_attr::{AttrName, AttrRegistry};
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result, ValidationReport};
+
+/// Knobs for the matrix <-> tall conversions.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    /// Error on attribute names/ids missing from the lookup instead of skipping them.
+    pub strict_unknown_attrs: bool,
+}
 
-/// Converts matrix-style JSON into tall rows with resolved attr_ids.
+/// Converts matrix-style JSON into tall rows with attr_ids resolved through the registry.
 pub fn matrix_json_to_tall(
     json: &str,
     config_version_id: i32,
-    attr_name_to_id: &HashMap<String, i32>,
+    registry: &AttrRegistry,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    matrix_json_to_tall_with_options(json, config_version_id, registry, &ConversionOptions::default())
+}
+
+/// Same as [`matrix_json_to_tall`], with [`ConversionOptions`].
+pub fn matrix_json_to_tall_with_options(
+    json: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut report = ValidationReport::fail_fast();
+    convert_matrix_json(json, config_version_id, registry, options, &mut report)
+}
+
+/// Same as [`matrix_json_to_tall_with_options`], but keeps going after a bad
+/// rank/cell and returns every problem found instead of just the first.
+pub fn matrix_json_to_tall_report(
+    json: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> std::result::Result<Vec<ConfigPrecedenceRule>, ValidationReport> {
+    let mut report = ValidationReport::collect_all();
+    let outcome = convert_matrix_json(json, config_version_id, registry, options, &mut report);
+    report.finish(outcome)
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "matrix_json_to_tall",
+        level = "debug",
+        skip_all,
+        fields(config_version_id = config_version_id),
+        err
+    )
+)]
+fn convert_matrix_json(
+    json: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    report: &mut ValidationReport,
 ) -> Result<Vec<ConfigPrecedenceRule>> {
-    let matrix_rows: Vec<MatrixRow> = serde_json::from_str(json)
-        .with_context(|| "Invalid JSON: expected an array of objects with `rank` and attributes")?;
+    let matrix_rows: Vec<MatrixRowRef<'_>> =
+        serde_json::from_str(json).map_err(PrecedenceConfigError::InvalidJson)?;
+    let rules = convert_matrix_row_refs(&matrix_rows, config_version_id, registry, options, report)?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(rules = rules.len(), "converted precedence matrix");
+    Ok(rules)
+}
 
+pub(crate) fn convert_matrix_rows(
+    matrix_rows: Vec<MatrixRow>,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    report: &mut ValidationReport,
+) -> Result<Vec<ConfigPrecedenceRule>> {
     let mut tall = Vec::new();
-    let mut seen = HashSet::new();
+    let mut converter = MatrixRowConverter::new(config_version_id, registry, options);
+    for row in &matrix_rows {
+        converter.convert(row.rank, &row.attrs, report, &mut |rule| tall.push(rule))?;
+    }
+    converter.finish(report)?;
+    Ok(tall)
+}
+
+/// Same as [`matrix_json_to_tall_with_options`], for rows parsed already, e.g.
+/// borrowed from a payload that is still in use.
+pub fn matrix_row_refs_to_tall(
+    matrix_rows: &[MatrixRowRef<'_>],
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut report = ValidationReport::fail_fast();
+    convert_matrix_row_refs(matrix_rows, config_version_id, registry, options, &mut report)
+}
 
+pub(crate) fn convert_matrix_row_refs(
+    matrix_rows: &[MatrixRowRef<'_>],
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    report: &mut ValidationReport,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut tall = Vec::new();
+    let mut converter = MatrixRowConverter::new(config_version_id, registry, options);
     for row in matrix_rows {
-        if row.rank <= 0 {
-            bail!("Rank must be >= 1 (found {})", row.rank);
-        }
+        converter.convert(row.rank, &row.attrs, report, &mut |rule| tall.push(rule))?;
+    }
+    converter.finish(report)?;
+    Ok(tall)
+}
 
-        for (attr_name, match_type) in row.attrs.iter() {
-            let Some(&attr_id) = attr_name_to_id.get(attr_name) else {
-                continue; // or bail! if strict
-            };
+/// [`matrix_json_to_tall_with_options`] with the rows checked on the rayon thread
+/// pool. Parsing stays on the calling thread; the rules and the error, if any,
+/// are the same as the single-threaded conversion's.
+#[cfg(feature = "parallel")]
+pub fn matrix_json_to_tall_par(
+    json: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    use rayon::prelude::*;
 
-            if *match_type > 1 {
-                bail!("MATCH_TYPE must be 0 or 1 (found {} for attr {})", match_type, attr_name);
-            }
+    let matrix_rows: Vec<MatrixRowRef<'_>> =
+        serde_json::from_str(json).map_err(PrecedenceConfigError::InvalidJson)?;
+    let mut converter = MatrixRowConverter::new(config_version_id, registry, options);
+    let check = converter.check;
+    let rows: Vec<Vec<CheckedCell<'_>>> = matrix_rows
+        .par_iter()
+        .enumerate()
+        .map(|(index, row)| check.cells(index, row.rank, &row.attrs).collect())
+        .collect();
 
-            let key = (row.rank, attr_id);
-            if !seen.insert(key) {
-                bail!("Duplicate (rank, attr_id): ({}, {})", row.rank, attr_id);
+    let mut report = ValidationReport::fail_fast();
+    let mut tall = Vec::new();
+    for cell in rows.into_iter().flatten() {
+        converter.accept(cell, &mut report, &mut |rule| tall.push(rule))?;
+    }
+    converter.finish(&mut report)?;
+    Ok(tall)
+}
+
+/// Converts a JSON matrix array read from `reader` one row at a time, handing
+/// each tall rule to `sink` as soon as its row checks out.
+///
+/// Unlike [`matrix_json_to_tall`] neither the whole payload nor the parsed
+/// matrix is held in memory: only the current row and the (rank, attr) pairs
+/// seen so far for the duplicate check. Stops at the first problem; rules
+/// already passed to `sink` stay delivered. Returns the number of rules emitted.
+pub fn matrix_json_stream_to_tall<R: io::Read>(
+    reader: R,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    mut sink: impl FnMut(ConfigPrecedenceRule),
+) -> Result<usize> {
+    let mut report = ValidationReport::fail_fast();
+    let mut converter = MatrixRowConverter::new(config_version_id, registry, options);
+    let mut failure = None;
+
+    let mut de = serde_json::Deserializer::from_reader(io::BufReader::new(reader));
+    let parsed = de::Deserializer::deserialize_seq(
+        &mut de,
+        MatrixRowSeq {
+            on_row: &mut |rank, attrs: &[(AttrName, u8)]| converter.convert(rank, attrs, &mut report, &mut sink),
+            failure: &mut failure,
+        },
+    )
+    .and_then(|()| de.end());
+
+    if let Some(err) = failure {
+        return Err(err);
+    }
+    parsed.map_err(PrecedenceConfigError::InvalidJson)?;
+    converter.finish(&mut report)?;
+    Ok(converter.emitted)
+}
+
+/// Same as [`matrix_json_stream_to_tall`], collecting the rules.
+pub fn matrix_json_reader_to_tall<R: io::Read>(
+    reader: R,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let mut tall = Vec::new();
+    matrix_json_stream_to_tall(reader, config_version_id, registry, options, |rule| tall.push(rule))?;
+    Ok(tall)
+}
+
+/// Visits a JSON array of matrix rows, handing the rank and cells of each to
+/// `on_row` as it is parsed.
+struct MatrixRowSeq<'f, F> {
+    on_row: &'f mut F,
+    /// where a conversion error is parked while the deserializer unwinds
+    failure: &'f mut Option<PrecedenceConfigError>,
+}
+
+impl<'de, F: FnMut(i32, &[(AttrName, u8)]) -> Result<()>> de::Visitor<'de> for MatrixRowSeq<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an array of matrix rows")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(row) = seq.next_element::<MatrixRow>()? {
+            if let Err(err) = (self.on_row)(row.rank, &row.attrs) {
+                *self.failure = Some(err);
+                return Err(de::Error::custom("matrix row rejected"));
             }
+        }
+        Ok(())
+    }
+}
+
+/// Per-row matrix -> tall conversion, shared by the in-memory and streaming front-ends.
+pub(crate) struct MatrixRowConverter<'a> {
+    check: CellCheck<'a>,
+    index: usize,
+    seen: HashSet<(i32, i32)>,
+    emitted: usize,
+}
+
+/// A matrix cell checked on its own: the rule and the column it came from, or
+/// what is wrong with it. The duplicate check is left to [`MatrixRowConverter::accept`].
+type CheckedCell<'r> = Result<(ConfigPrecedenceRule, &'r str)>;
+
+/// The checks on a matrix row that need no other row, so rows can be checked
+/// on any thread.
+#[derive(Clone, Copy)]
+struct CellCheck<'a> {
+    config_version_id: i32,
+    registry: &'a AttrRegistry,
+    options: &'a ConversionOptions,
+}
+
+impl<'a> CellCheck<'a> {
+    /// The cells of row `index`, in column order; an invalid rank is the only
+    /// entry of its row, and unknown columns are left out unless strict.
+    fn cells<'r, K: AsRef<str>>(
+        self,
+        index: usize,
+        rank: i32,
+        attrs: &'r [(K, u8)],
+    ) -> impl Iterator<Item = CheckedCell<'r>> {
+        let invalid_rank = (rank <= 0).then_some(Err(PrecedenceConfigError::InvalidRank { index, rank }));
+        let attrs = if rank <= 0 { &attrs[..0] } else { attrs };
+        invalid_rank.into_iter().chain(
+            attrs.iter().filter_map(move |(attr_name, match_type)| self.cell(rank, attr_name.as_ref(), *match_type)),
+        )
+    }
 
-            tall.push(ConfigPrecedenceRule {
-                config_version_id,
-                rank: row.rank,
-                attr_id,
-                match_type: *match_type,
+    fn cell<'r>(self, rank: i32, attr_name: &'r str, match_type: u8) -> Option<CheckedCell<'r>> {
+        let Some(attr_id) = self.registry.id_of(attr_name) else {
+            return self.options.strict_unknown_attrs.then(|| {
+                Err(PrecedenceConfigError::UnknownRuleAttr { rank, attr: AttrRef::Name(attr_name.to_string()) })
             });
+        };
+
+        let Ok(match_type) = MatchType::try_from(match_type) else {
+            return Some(Err(PrecedenceConfigError::InvalidMatchType {
+                rank,
+                attr: AttrRef::Name(attr_name.to_string()),
+                match_type,
+            }));
+        };
+
+        let rule = ConfigPrecedenceRule { config_version_id: self.config_version_id, rank, attr_id, match_type };
+        Some(Ok((rule, attr_name)))
+    }
+}
+
+impl<'a> MatrixRowConverter<'a> {
+    pub(crate) fn new(config_version_id: i32, registry: &'a AttrRegistry, options: &'a ConversionOptions) -> Self {
+        Self {
+            check: CellCheck { config_version_id, registry, options },
+            index: 0,
+            seen: HashSet::new(),
+            emitted: 0,
         }
     }
 
-    if tall.is_empty() {
-        bail!("No valid precedence rules parsed from JSON");
+    /// Converts the cells of one matrix row.
+    pub(crate) fn convert<K: AsRef<str>>(
+        &mut self,
+        rank: i32,
+        attrs: &[(K, u8)],
+        report: &mut ValidationReport,
+        emit: &mut impl FnMut(ConfigPrecedenceRule),
+    ) -> Result<()> {
+        let index = self.index;
+        self.index += 1;
+        for cell in self.check.cells(index, rank, attrs) {
+            self.accept(cell, report, emit)?;
+        }
+        Ok(())
     }
 
-    Ok(tall)
+    /// Reports a bad cell, or emits its rule unless the (rank, attr) pair was seen already.
+    fn accept(
+        &mut self,
+        cell: CheckedCell<'_>,
+        report: &mut ValidationReport,
+        emit: &mut impl FnMut(ConfigPrecedenceRule),
+    ) -> Result<()> {
+        let (rule, attr_name) = match cell {
+            Ok(cell) => cell,
+            Err(err) => return report.push(err),
+        };
+        if !self.seen.insert((rule.rank, rule.attr_id)) {
+            return report.push(PrecedenceConfigError::DuplicateRankAttr {
+                rank: rule.rank,
+                attr_id: rule.attr_id,
+                attr_name: attr_name.to_string(),
+            });
+        }
+        self.emitted += 1;
+        emit(rule);
+        Ok(())
+    }
+
+    pub(crate) fn finish(&self, report: &mut ValidationReport) -> Result<()> {
+        if self.emitted == 0 && report.is_empty() {
+            return Err(PrecedenceConfigError::NoPrecedenceRules);
+        }
+        Ok(())
+    }
 }
 
 
-/// Converts tall precedence rules into matrix-style rows using attr_id → attr_name mapping.
+/// Converts tall precedence rules into matrix-style rows using the registry's attr_id → attr_name mapping.
 pub fn tall_to_matrix_rows(
     tall: &[ConfigPrecedenceRule],
-    attr_id_to_name: &HashMap<i32, String>,
+    registry: &AttrRegistry,
+) -> Result<Vec<MatrixRow>> {
+    tall_to_matrix_rows_with_options(tall, registry, &ConversionOptions::default())
+}
+
+/// Same as [`tall_to_matrix_rows`], with [`ConversionOptions`]. Columns come out
+/// in attribute-name order; see [`tall_to_matrix_rows_ordered`] to choose it.
+pub fn tall_to_matrix_rows_with_options(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
 ) -> Result<Vec<MatrixRow>> {
-    let mut by_rank: BTreeMap<i32, BTreeMap<String, u8>> = BTreeMap::new();
+    tall_to_matrix_rows_ordered(tall, registry, options, &[])
+}
+
+/// Same as [`tall_to_matrix_rows_with_options`], with each row's columns in
+/// `column_order`. Attributes not listed follow the listed ones in name order;
+/// listed names a rank doesn't use are left out of that row.
+pub fn tall_to_matrix_rows_ordered(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    column_order: &[&str],
+) -> Result<Vec<MatrixRow>> {
+    let mut by_rank: BTreeMap<i32, BTreeMap<AttrName, u8>> = BTreeMap::new();
     let mut seen = HashSet::new();
 
-    for r in tall {
+    for (index, r) in tall.iter().enumerate() {
         if r.rank <= 0 {
-            bail!("Rank must be positive starting at 1 (found {})", r.rank);
-        }
-        if r.match_type > 1 {
-            bail!("MATCH_TYPE must be 0 or 1 (found {} for attr_id {})", r.match_type, r.attr_id);
+            return Err(PrecedenceConfigError::InvalidRank { index, rank: r.rank });
         }
 
-        let Some(attr_name) = attr_id_to_name.get(&r.attr_id) else {
-            continue; // or bail! if strict
+        let Some(attr_name) = registry.name_of(r.attr_id) else {
+            if options.strict_unknown_attrs {
+                return Err(PrecedenceConfigError::UnknownRuleAttr {
+                    rank: r.rank,
+                    attr: AttrRef::Id(r.attr_id),
+                });
+            }
+            continue;
         };
 
-        let key = (r.rank, attr_name.clone());
-        if !seen.insert(key.clone()) {
-            bail!("Duplicate (rank, attr_name): ({}, {})", key.0, key.1);
+        if !seen.insert((r.rank, attr_name)) {
+            return Err(PrecedenceConfigError::DuplicateRankAttr {
+                rank: r.rank,
+                attr_id: r.attr_id,
+                attr_name: attr_name.to_string(),
+            });
         }
 
         by_rank.entry(r.rank)
             .or_default()
-            .insert(attr_name.clone(), r.match_type);
+            .insert(attr_name.into(), r.match_type.into());
     }
 
     if by_rank.is_empty() {
-        bail!("No rows to emit");
+        return Err(PrecedenceConfigError::NoPrecedenceRules);
     }
 
+    let position: HashMap<&str, usize> = column_order.iter().enumerate().map(|(i, name)| (*name, i)).collect();
     let mut out = Vec::with_capacity(by_rank.len());
     for (rank, attrs) in by_rank {
-        out.push(MatrixRow {
-            rank,
-            attrs: attrs.into_iter().collect(),
-        });
+        // name order already; the stable sort only pulls the listed columns forward
+        let mut attrs: Vec<(AttrName, u8)> = attrs.into_iter().collect();
+        attrs.sort_by_key(|(name, _)| position.get(name.as_str()).copied().unwrap_or(usize::MAX));
+        out.push(MatrixRow { rank, attrs });
     }
 
     Ok(out)
 }
 
+/// What [`verify_round_trip`] found converting a matrix to tall rules and back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoundTripReport {
+    /// The matrix as [`tall_to_matrix_rows`] gives it back.
+    pub matrix: Vec<MatrixRow>,
+    /// Unknown columns in input order, then rank by rank, then the order of ranks.
+    pub losses: Vec<RoundTripLoss>,
+}
+
+impl RoundTripReport {
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty()
+    }
+}
+
+/// A difference between a matrix and its round trip through tall rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RoundTripLoss {
+    /// A column missing from the registry, skipped by the conversion.
+    UnknownAttr { rank: i32, attr_name: String },
+    /// A rank with no column in the registry, which disappears altogether.
+    RankDropped { rank: i32 },
+    /// `rows` input rows share the rank and come back as one.
+    RankMerged { rank: i32, rows: usize },
+    /// Ranks come back in ascending order.
+    RanksReordered { from: Vec<i32>, to: Vec<i32> },
+    /// Columns come back in name order.
+    ColumnsReordered { rank: i32, from: Vec<String>, to: Vec<String> },
+    /// A cell that came back with another match type, or not at all (`None`).
+    ValueChanged { rank: i32, attr_name: String, from: u8, to: Option<u8> },
+}
+
+/// Converts matrix-style JSON to tall rules and back, as
+/// [`matrix_json_to_tall`] and [`tall_to_matrix_rows`] do, and lists everything
+/// that did not survive unchanged. Errors are the conversions' own (bad JSON,
+/// ranks or match types, duplicate cells); a matrix that converts but loses
+/// something is an `Ok` report that is not [`RoundTripReport::is_lossless`].
+/// The tall rules in between all carry `config_version_id` 1: it is only copied
+/// onto them, and [`tall_to_matrix_rows`] drops it, so no version changes the report.
+pub fn verify_round_trip(json: &str, registry: &AttrRegistry) -> Result<RoundTripReport> {
+    let input: Vec<MatrixRow> = serde_json::from_str(json).map_err(PrecedenceConfigError::InvalidJson)?;
+    let mut report = ValidationReport::fail_fast();
+    let tall = convert_matrix_rows(input.clone(), 1, registry, &ConversionOptions::default(), &mut report)?;
+    let matrix = tall_to_matrix_rows(&tall, registry)?;
+
+    struct InputRank<'a> {
+        rank: i32,
+        rows: usize,
+        /// known columns, in input order
+        columns: Vec<(&'a str, u8)>,
+    }
+
+    let mut losses = Vec::new();
+    let mut ranks: Vec<InputRank> = Vec::new();
+    for row in &input {
+        let i = match ranks.iter().position(|r| r.rank == row.rank) {
+            Some(i) => i,
+            None => {
+                ranks.push(InputRank { rank: row.rank, rows: 0, columns: Vec::new() });
+                ranks.len() - 1
+            }
+        };
+        ranks[i].rows += 1;
+        for (attr_name, match_type) in &row.attrs {
+            if registry.id_of(attr_name).is_some() {
+                ranks[i].columns.push((attr_name, *match_type));
+            } else {
+                losses.push(RoundTripLoss::UnknownAttr { rank: row.rank, attr_name: attr_name.to_string() });
+            }
+        }
+    }
+
+    let mut kept = Vec::new();
+    for InputRank { rank, rows, columns } in ranks {
+        let Some(out) = matrix.iter().find(|row| row.rank == rank) else {
+            losses.push(RoundTripLoss::RankDropped { rank });
+            continue;
+        };
+        kept.push(rank);
+        if rows > 1 {
+            losses.push(RoundTripLoss::RankMerged { rank, rows });
+        }
+        for &(attr_name, from) in &columns {
+            let to = out.get(attr_name);
+            if to != Some(from) {
+                losses.push(RoundTripLoss::ValueChanged { rank, attr_name: attr_name.to_string(), from, to });
+            }
+        }
+        let from: Vec<String> = columns.iter().map(|(name, _)| name.to_string()).collect();
+        let to: Vec<String> = out.attrs.iter().map(|(name, _)| name.to_string()).collect();
+        if from != to {
+            losses.push(RoundTripLoss::ColumnsReordered { rank, from, to });
+        }
+    }
+    let returned: Vec<i32> = matrix.iter().map(|row| row.rank).collect();
+    if kept != returned {
+        losses.push(RoundTripLoss::RanksReordered { from: kept, to: returned });
+    }
+
+    Ok(RoundTripReport { matrix, losses })
+}
+
+/// Which family of attribute combinations a precedence matrix is expected to cover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RankScheme {
+    /// T(A) = A(A+1)/2 ranks, see [`generate_triangular_matrix`].
+    Triangular,
+    /// Every non-empty combination: 2^A - 1 ranks, see [`generate_power_set_matrix`].
+    PowerSet,
+    /// Caller-defined ranks; each entry lists the attributes that must match exactly, in rank order.
+    Custom(Vec<Vec<String>>),
+}
+
+impl RankScheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RankScheme::Triangular => "triangular",
+            RankScheme::PowerSet => "power-set",
+            RankScheme::Custom(_) => "custom",
+        }
+    }
+
+    /// Number of ranks a matrix over `attr_count` attributes must have under this scheme.
+    pub fn expected_rank_count(&self, attr_count: usize) -> usize {
+        match self {
+            RankScheme::Triangular => attr_count * (attr_count + 1) / 2,
+            RankScheme::PowerSet => 1usize
+                .checked_shl(attr_count as u32)
+                .map_or(usize::MAX, |n| n - 1),
+            RankScheme::Custom(ranks) => ranks.len(),
+        }
+    }
+
+    /// Emits the matrix for `attrs` (most-significant first) under this scheme.
+    /// Fails for a power set of more than [`MAX_POWER_SET_ATTRS`] attributes.
+    pub fn generate(&self, attrs: &[&str]) -> Result<Vec<MatrixRow>> {
+        Ok(match self {
+            RankScheme::Triangular => generate_triangular_matrix(attrs),
+            RankScheme::PowerSet => generate_power_set_matrix(attrs)?,
+            RankScheme::Custom(ranks) => ranks
+                .iter()
+                .zip(1..)
+                .map(|(exact, rank)| matrix_row(rank, attrs, |i| exact.iter().any(|e| e == attrs[i])))
+                .collect(),
+        })
+    }
+}
+
+/// Generates the canonical triangular matrix for `attrs` ordered most-significant first.
+///
+/// Each rank keeps the `p` most significant attributes exact plus one more
+/// attribute `q >= p`, walked from the most specific combination down, which
+/// yields exactly T(A) = A(A+1)/2 ranks. For `["a", "b", "c"]`:
+///  ```text
+/// rank 1: a=1 b=1 c=1
+/// rank 2: a=1 b=1 c=0
+/// rank 3: a=1 b=0 c=1
+/// rank 4: a=1 b=0 c=0
+/// rank 5: a=0 b=1 c=0
+/// rank 6: a=0 b=0 c=1
+/// ```
+/// Attribute names are expected to be unique.
+pub fn generate_triangular_matrix(attrs: &[&str]) -> Vec<MatrixRow> {
+    let mut out = Vec::with_capacity(attrs.len() * (attrs.len() + 1) / 2);
+    let mut rank = 1;
+
+    for prefix in (0..attrs.len()).rev() {
+        for extra in prefix..attrs.len() {
+            out.push(matrix_row(rank, attrs, |i| i < prefix || i == extra));
+            rank += 1;
+        }
+    }
 
+    out
+}
 
-/// Validate ranks are exactly 1 SUM_ATTR with no gaps using a triangular sum check.
+/// Most attributes a power-set matrix may have: 2^20 - 1 (about a million) ranks.
+pub const MAX_POWER_SET_ATTRS: usize = 20;
+
+/// Generates every non-empty combination of `attrs` (most-significant first) as its own rank.
+///
+/// Combinations with more exact attributes come first; among equally specific
+/// ones, the combination keeping the more significant attributes wins. For
+/// `["a", "b", "c"]` that is `111, 110, 101, 011, 100, 010, 001`.
+/// Fails for more than [`MAX_POWER_SET_ATTRS`] attributes.
+pub fn generate_power_set_matrix(attrs: &[&str]) -> Result<Vec<MatrixRow>> {
+    let n = attrs.len();
+    if n > MAX_POWER_SET_ATTRS {
+        return Err(PrecedenceConfigError::TooManyPowerSetAttrs { count: n, max: MAX_POWER_SET_ATTRS });
+    }
+    // bit (n - 1 - i) set => attrs[i] exact, so a larger mask keeps more significant attrs
+    let mut masks: Vec<u32> = (1..(1u32 << n)).collect();
+    masks.sort_by(|a, b| b.count_ones().cmp(&a.count_ones()).then(b.cmp(a)));
+
+    Ok(masks
+        .into_iter()
+        .zip(1..)
+        .map(|(mask, rank)| matrix_row(rank, attrs, |i| mask & (1 << (n - 1 - i)) != 0))
+        .collect())
+}
+
+/// How [`add_attribute`] or [`remove_attribute`] changed a matrix, pairing old
+/// and new ranks by the set of attributes they match exactly.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MatrixChange {
+    /// old rank -> new rank, for the combinations both matrices have
+    pub kept: RenumberMap,
+    /// ranks of the new matrix whose combination the old one lacks
+    pub added_ranks: Vec<i32>,
+    /// ranks of the old matrix whose combination the new one lacks
+    pub removed_ranks: Vec<i32>,
+}
+
+impl MatrixChange {
+    /// No rank was added or removed and none moved.
+    pub fn is_empty(&self) -> bool {
+        self.added_ranks.is_empty() && self.removed_ranks.is_empty() && self.kept.iter().all(|(old, new)| old == new)
+    }
+}
+
+/// Inserts column `attr_name` into `matrix` at `position` (0 is the most
+/// significant) and regenerates its ranks under `scheme`, triangular or power-set.
+///
+/// The columns are taken from the rows in order of first appearance; the ranks
+/// are rebuilt from scratch, so hand edits to them are dropped (and show up in
+/// the returned change). `matrix` is left unchanged on error.
+pub fn add_attribute(
+    matrix: &mut Vec<MatrixRow>,
+    attr_name: &str,
+    position: usize,
+    scheme: &RankScheme,
+) -> Result<MatrixChange> {
+    let invalid = |reason: String| PrecedenceConfigError::InvalidAttributeEdit {
+        op: "add",
+        attr_name: attr_name.to_string(),
+        reason,
+    };
+    let mut columns = matrix_columns(matrix);
+    if columns.iter().any(|name| name == attr_name) {
+        return Err(invalid("the matrix has that column already".to_string()));
+    }
+    if position > columns.len() {
+        return Err(invalid(format!("position {} is past the {} column(s)", position, columns.len())));
+    }
+    columns.insert(position, attr_name.into());
+    regenerate_matrix(matrix, &columns, scheme).map_err(invalid)
+}
+
+/// Deletes column `attr_name` from `matrix` and regenerates its ranks under
+/// `scheme`, as [`add_attribute`] does. Ranks that matched `attr_name` exactly
+/// are the removed ones of the returned change.
+pub fn remove_attribute(matrix: &mut Vec<MatrixRow>, attr_name: &str, scheme: &RankScheme) -> Result<MatrixChange> {
+    let invalid = |reason: String| PrecedenceConfigError::InvalidAttributeEdit {
+        op: "remove",
+        attr_name: attr_name.to_string(),
+        reason,
+    };
+    let mut columns = matrix_columns(matrix);
+    let Some(position) = columns.iter().position(|name| name == attr_name) else {
+        return Err(invalid("the matrix has no such column".to_string()));
+    };
+    columns.remove(position);
+    if columns.is_empty() {
+        return Err(invalid("it is the only column".to_string()));
+    }
+    regenerate_matrix(matrix, &columns, scheme).map_err(invalid)
+}
+
+/// Column names of `matrix` in order of first appearance.
+fn matrix_columns(matrix: &[MatrixRow]) -> Vec<AttrName> {
+    let mut columns: Vec<AttrName> = Vec::new();
+    for (name, _) in matrix.iter().flat_map(|row| &row.attrs) {
+        if !columns.contains(name) {
+            columns.push(name.clone());
+        }
+    }
+    columns
+}
+
+fn regenerate_matrix(
+    matrix: &mut Vec<MatrixRow>,
+    columns: &[AttrName],
+    scheme: &RankScheme,
+) -> std::result::Result<MatrixChange, String> {
+    let names: Vec<&str> = columns.iter().map(AttrName::as_str).collect();
+    let regenerated = match scheme {
+        RankScheme::Triangular => generate_triangular_matrix(&names),
+        RankScheme::PowerSet => generate_power_set_matrix(&names).map_err(|e| e.to_string())?,
+        RankScheme::Custom(_) => return Err("custom rank schemes can't be regenerated".to_string()),
+    };
+
+    let exact_set = |row: &MatrixRow| -> BTreeSet<AttrName> {
+        let exact = u8::from(MatchType::Exact);
+        row.attrs.iter().filter(|(_, match_type)| *match_type == exact).map(|(name, _)| name.clone()).collect()
+    };
+    let mut new_ranks: HashMap<BTreeSet<AttrName>, i32> = HashMap::new();
+    for row in &regenerated {
+        new_ranks.entry(exact_set(row)).or_insert(row.rank);
+    }
+
+    let mut change = MatrixChange::default();
+    let mut paired = HashSet::new();
+    for row in matrix.iter() {
+        match new_ranks.get(&exact_set(row)) {
+            Some(&new) if paired.insert(new) => {
+                change.kept.insert(row.rank, new);
+            }
+            _ => change.removed_ranks.push(row.rank),
+        }
+    }
+    change.removed_ranks.sort_unstable();
+    change.added_ranks = regenerated.iter().map(|row| row.rank).filter(|rank| !paired.contains(rank)).collect();
+    *matrix = regenerated;
+    Ok(change)
+}
+
+fn matrix_row(rank: i32, attrs: &[&str], exact: impl Fn(usize) -> bool) -> MatrixRow {
+    let attrs = attrs
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let match_type = if exact(i) { MatchType::Exact } else { MatchType::Ignore };
+            ((*name).into(), match_type.into())
+        })
+        .collect();
+    MatrixRow { rank, attrs }
+}
 
 /// Validate that ranks are exactly 1..=T(A) with no gaps,
 /// where A = number of attributes (sum_attr).
 pub fn validate_ranks_contiguous_and_triangular(tall: &[ConfigPrecedenceRule], attr_count: usize) -> Result<()> {
+    validate_ranks(tall, attr_count, &RankScheme::Triangular)
+}
+
+/// Validate that ranks are exactly 1..=N with no gaps, where N is the rank
+/// count `scheme` expects for `attr_count` attributes.
+pub fn validate_ranks(tall: &[ConfigPrecedenceRule], attr_count: usize, scheme: &RankScheme) -> Result<()> {
     if tall.is_empty() {
-        return Err(anyhow!("No rows provided"));
+        return Err(PrecedenceConfigError::NoPrecedenceRules);
     }
 
-    // T(A) ranks expected for A attributes
-    let t_a: i32 = (attr_count as i32) * ((attr_count as i32) + 1) / 2;
-
     // Collect distinct ranks
     let ranks: BTreeSet<i32> = tall.iter().map(|r| r.rank).collect();
+    check_rank_set(&ranks, attr_count, scheme)
+}
 
-    // Check count matches T(A)
-    if ranks.len() as i32 != t_a {
-        return Err(anyhow!(
-            "Triangular count check failed: found {} distinct ranks, expected {} for A={}",
-            ranks.len(),
-            t_a,
-            attr_count
-        ));
+/// Old rank -> new rank, as [`renumber_ranks`] returns it.
+pub type RenumberMap = BTreeMap<i32, i32>;
+
+/// Compacts the ranks of `tall` to 1..=N, keeping their order, e.g. after a
+/// rank was deleted: the lowest rank becomes 1, the next 2, and so on. Rules
+/// keep their position in `tall`. The returned map has an entry for every rank
+/// found, changed or not, for updating whatever refers to ranks by number.
+pub fn renumber_ranks(tall: &mut [ConfigPrecedenceRule]) -> RenumberMap {
+    let ranks: BTreeSet<i32> = tall.iter().map(|r| r.rank).collect();
+    let map: RenumberMap = ranks.into_iter().zip(1..).collect();
+    for rule in tall.iter_mut() {
+        rule.rank = map[&rule.rank];
+    }
+    map
+}
+
+/// Moves the row at rank `from_rank` of `matrix` to rank `to_rank`, shifting the
+/// rows in between one place, and renumbers every row 1..=N in its new order.
+///
+/// Rows are taken in rank order, so gaps close as in [`renumber_ranks`] and
+/// `to_rank` counts from 1 in the compacted matrix. Returns old rank -> new
+/// rank of every row. `matrix` is left unchanged if `from_rank` is missing,
+/// `to_rank` is outside 1..=N or two rows have one rank. See
+/// [`move_rank_dry_run`](crate::config_simulation::move_rank_dry_run) to check
+/// what a move changes first.
+pub fn move_rank(matrix: &mut Vec<MatrixRow>, from_rank: i32, to_rank: i32) -> Result<RenumberMap> {
+    let invalid = |reason: String| PrecedenceConfigError::InvalidRankMove { from_rank, to_rank, reason };
+    let mut order: Vec<usize> = (0..matrix.len()).collect();
+    order.sort_by_key(|&i| matrix[i].rank);
+    if let Some(pair) = order.windows(2).find(|pair| matrix[pair[0]].rank == matrix[pair[1]].rank) {
+        return Err(invalid(format!("rank {} is given twice", matrix[pair[0]].rank)));
+    }
+    let Some(from) = order.iter().position(|&i| matrix[i].rank == from_rank) else {
+        return Err(invalid("there is no such rank".to_string()));
+    };
+    if !(1..=matrix.len() as i32).contains(&to_rank) {
+        return Err(invalid(format!("ranks run from 1 to {}", matrix.len())));
     }
 
-    // Check contiguity: ranks must be exactly 1..=T(A)
-    let mut expected = 1;
-    for r in &ranks {
+    let row = order.remove(from);
+    order.insert(to_rank as usize - 1, row);
+    let map: RenumberMap = order.iter().zip(1..).map(|(&i, rank)| (matrix[i].rank, rank)).collect();
+    let mut rows: Vec<Option<MatrixRow>> = std::mem::take(matrix).into_iter().map(Some).collect();
+    for (i, rank) in order.into_iter().zip(1..) {
+        let mut row = rows[i].take().expect("each row is placed once");
+        row.rank = rank;
+        matrix.push(row);
+    }
+    Ok(map)
+}
+
+/// The rank checks of [`validate_ranks`] on the distinct ranks of some rules.
+pub(crate) fn check_rank_set(ranks: &BTreeSet<i32>, attr_count: usize, scheme: &RankScheme) -> Result<()> {
+    let expected = scheme.expected_rank_count(attr_count);
+
+    // Check count matches the scheme
+    if ranks.len() != expected {
+        return Err(match scheme {
+            RankScheme::Triangular => PrecedenceConfigError::NonTriangularRankCount {
+                found: ranks.len(),
+                expected,
+                attr_count,
+            },
+            _ => PrecedenceConfigError::UnexpectedRankCount {
+                scheme: scheme.name(),
+                found: ranks.len(),
+                expected,
+                attr_count,
+            },
+        });
+    }
+
+    // Check contiguity: ranks must be exactly 1..=N
+    for (expected, r) in (1..).zip(ranks) {
         if *r != expected {
-            return Err(anyhow!(
-                "Contiguity check failed at rank {}: expected {}",
-                r,
-                expected
-            ));
+            return Err(PrecedenceConfigError::NonContiguousRank { rank: *r, expected });
         }
-        expected += 1;
     }
 
     Ok(())
 }
 
+
+/// Converts a CSV precedence matrix into tall rows with resolved attr_ids.
+/// The header holds a `rank` column plus one column per attribute; blank cells
+/// leave the attribute out of that rank. Expecting CSV like:
+/// ```text
+/// rank,col_1,col_2,col_3
+/// 1,1,1,1
+/// 2,0,1,1
+/// 3,0,0,1
+/// ```
+pub fn matrix_csv_to_tall(
+    csv: &str,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<Vec<ConfigPrecedenceRule>> {
+    let matrix_rows = parse_matrix_csv(csv)?;
+    let mut report = ValidationReport::fail_fast();
+    convert_matrix_rows(matrix_rows, config_version_id, registry, options, &mut report)
+}
+
+fn parse_matrix_csv(csv: &str) -> Result<Vec<MatrixRow>> {
+    let invalid = |line: u64, reason: String| PrecedenceConfigError::InvalidCsv { line, reason };
+    let csv_error = |e: csv::Error| {
+        let line = e.position().map(|p| p.line()).unwrap_or(0);
+        invalid(line, e.to_string())
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let mut rank_col = None;
+    let mut columns = HashSet::new();
+    for (i, name) in headers.iter().enumerate() {
+        if !columns.insert(name) {
+            return Err(invalid(1, format!("duplicate column '{}'", name)));
+        }
+        if name == "rank" {
+            rank_col = Some(i);
+        }
+    }
+    let Some(rank_col) = rank_col else {
+        return Err(invalid(1, "missing `rank` column".to_string()));
+    };
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        let rank = record[rank_col]
+            .parse::<i32>()
+            .map_err(|e| invalid(line, format!("rank '{}': {}", &record[rank_col], e)))?;
+
+        let mut attrs = Vec::new();
+        for (i, cell) in record.iter().enumerate() {
+            if i == rank_col || cell.is_empty() {
+                continue;
+            }
+            let match_type = cell
+                .parse::<u8>()
+                .map_err(|e| invalid(line, format!("column '{}' value '{}': {}", &headers[i], cell, e)))?;
+            attrs.push((headers[i].into(), match_type));
+        }
+
+        rows.push(MatrixRow { rank, attrs });
+    }
+
+    Ok(rows)
+}
+
+/// Converts tall precedence rules into a CSV matrix (`rank` first, then attributes alphabetically).
+pub fn tall_to_matrix_csv(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<String> {
+    tall_to_matrix_csv_ordered(tall, registry, options, &[])
+}
+
+/// Same as [`tall_to_matrix_csv`], with the attribute columns in `column_order`
+/// (see [`tall_to_matrix_rows_ordered`]).
+pub fn tall_to_matrix_csv_ordered(
+    tall: &[ConfigPrecedenceRule],
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+    column_order: &[&str],
+) -> Result<String> {
+    let rows = tall_to_matrix_rows_ordered(tall, registry, options, column_order)?;
+    let names: BTreeSet<&str> = rows.iter().flat_map(|r| r.attrs.iter().map(|(name, _)| name.as_str())).collect();
+    let mut columns: Vec<&str> = names.into_iter().collect();
+    columns.sort_by_key(|name| column_order.iter().position(|c| c == name).unwrap_or(usize::MAX));
+
+    let csv_error = |e: csv::Error| PrecedenceConfigError::InvalidCsv { line: 0, reason: e.to_string() };
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    writer
+        .write_record(std::iter::once("rank").chain(columns.iter().copied()))
+        .map_err(csv_error)?;
+    for row in &rows {
+        let mut record = vec![row.rank.to_string()];
+        for column in &columns {
+            record.push(row.get(column).map(|m| m.to_string()).unwrap_or_default());
+        }
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| PrecedenceConfigError::InvalidCsv { line: 0, reason: e.to_string() })?;
+    Ok(String::from_utf8(bytes).expect("csv writer only emits the UTF-8 it was given"))
+}
+
+/// Stable attr_id -> bit position assignment for [`RankMask`]: bit `i` is the `i`-th smallest attr_id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrBitOrder {
+    attr_ids: Vec<i32>,
+}
+
+impl AttrBitOrder {
+    /// Orders the distinct `attr_ids`; fails past 64 attributes.
+    pub fn new(attr_ids: impl IntoIterator<Item = i32>) -> Result<Self> {
+        let attr_ids: Vec<i32> = attr_ids.into_iter().collect::<BTreeSet<_>>().into_iter().collect();
+        if attr_ids.len() > u64::BITS as usize {
+            return Err(PrecedenceConfigError::TooManyMaskAttrs { count: attr_ids.len() });
+        }
+        Ok(Self { attr_ids })
+    }
+
+    /// Orders every attr_id the rules mention.
+    pub fn from_rules(rules: &[ConfigPrecedenceRule]) -> Result<Self> {
+        Self::new(rules.iter().map(|r| r.attr_id))
+    }
+
+    pub fn bit_of(&self, attr_id: i32) -> Option<u32> {
+        self.attr_ids.binary_search(&attr_id).ok().map(|i| i as u32)
+    }
+
+    pub fn attr_id_of(&self, bit: u32) -> Option<i32> {
+        self.attr_ids.get(bit as usize).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.attr_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attr_ids.is_empty()
+    }
+}
+
+/// The `MatchType::Exact` attributes of one rank as a bitset over an [`AttrBitOrder`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RankMask(pub u64);
+
+impl RankMask {
+    /// Sets the bit of every attr_id; `None` if one isn't in `order`.
+    pub fn from_attr_ids(attr_ids: impl IntoIterator<Item = i32>, order: &AttrBitOrder) -> Option<Self> {
+        let mut mask = RankMask::default();
+        for attr_id in attr_ids {
+            mask.0 |= 1 << order.bit_of(attr_id)?;
+        }
+        Some(mask)
+    }
+
+    /// One mask per rank, from tall rules whose attr_ids are all in `order`.
+    pub fn from_rules(rules: &[ConfigPrecedenceRule], order: &AttrBitOrder) -> Result<BTreeMap<i32, RankMask>> {
+        let mut masks: BTreeMap<i32, RankMask> = BTreeMap::new();
+        for rule in rules {
+            let mask = masks.entry(rule.rank).or_default();
+            let bit = order.bit_of(rule.attr_id).ok_or(PrecedenceConfigError::UnknownRuleAttr {
+                rank: rule.rank,
+                attr: AttrRef::Id(rule.attr_id),
+            })?;
+            if rule.match_type == MatchType::Exact {
+                mask.0 |= 1 << bit;
+            }
+        }
+        Ok(masks)
+    }
+
+    pub fn contains(&self, bit: u32) -> bool {
+        bit < u64::BITS && self.0 & (1 << bit) != 0
+    }
+
+    /// Number of exact attributes.
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn is_subset_of(&self, other: RankMask) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    /// attr_ids of the set bits, in bit order.
+    pub fn attr_ids<'a>(&self, order: &'a AttrBitOrder) -> impl Iterator<Item = i32> + 'a {
+        let bits = self.0;
+        (0..u64::BITS).filter(move |bit| bits & (1 << bit) != 0).filter_map(|bit| order.attr_id_of(bit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::AttrRole;
+
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        for name in ["channel", "region"] {
+            registry.register(name, AttrRole::Match, "str").unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn unknown_columns_are_skipped_unless_strict() {
+        let registry = registry();
+        let json = r#"[{ "rank": 1, "channel": 1, "planet": 1 }]"#;
+        let tall = matrix_json_to_tall(json, 7, &registry).unwrap();
+        assert_eq!(
+            tall,
+            [ConfigPrecedenceRule { config_version_id: 7, rank: 1, attr_id: 1, match_type: MatchType::Exact }]
+        );
+
+        let strict = ConversionOptions { strict_unknown_attrs: true };
+        let err = matrix_json_to_tall_with_options(json, 7, &registry, &strict).unwrap_err();
+        let planet = AttrRef::Name("planet".to_string());
+        assert!(matches!(err, PrecedenceConfigError::UnknownRuleAttr { rank: 1, attr } if attr == planet));
+
+        let unknown = ConfigPrecedenceRule { config_version_id: 7, rank: 1, attr_id: 9, match_type: MatchType::Exact };
+        let rules = [tall[0].clone(), unknown];
+        assert_eq!(tall_to_matrix_rows(&rules, &registry).unwrap().len(), 1);
+        let err = tall_to_matrix_rows_with_options(&rules, &registry, &strict).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::UnknownRuleAttr { rank: 1, attr: AttrRef::Id(9) }));
+    }
+
+    #[test]
+    fn the_report_variant_collects_every_bad_cell() {
+        let json = r#"[
+            { "rank": 0, "channel": 1 },
+            { "rank": 1, "channel": 2, "region": 1 },
+            { "rank": 2, "region": 1 },
+            { "rank": 2, "region": 0 }
+        ]"#;
+        let report = matrix_json_to_tall_report(json, 1, &registry(), &ConversionOptions::default()).unwrap_err();
+        assert!(matches!(
+            report.errors[..],
+            [
+                PrecedenceConfigError::InvalidRank { .. },
+                PrecedenceConfigError::InvalidMatchType { .. },
+                PrecedenceConfigError::DuplicateRankAttr { .. },
+            ]
+        ));
+        // fail-fast stops at the first
+        assert!(matches!(matrix_json_to_tall(json, 1, &registry()), Err(PrecedenceConfigError::InvalidRank { .. })));
+        let good = r#"[{ "rank": 1, "channel": 1 }]"#;
+        assert_eq!(matrix_json_to_tall_report(good, 1, &registry(), &ConversionOptions::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn csv_matrices_convert_both_ways() {
+        let registry = registry();
+        let options = ConversionOptions::default();
+        let csv = "rank, region, channel\n1, 1, 1\n2, 1,\n3, 0, 1\n";
+        let tall = matrix_csv_to_tall(csv, 1, &registry, &options).unwrap();
+        assert_eq!(tall.len(), 5);
+        // blank cells leave the attribute out of the rank
+        assert!(!tall.iter().any(|r| r.rank == 2 && r.attr_id == 1));
+        let expected = "rank,channel,region\n1,1,1\n2,,1\n3,1,0\n";
+        assert_eq!(tall_to_matrix_csv(&tall, &registry, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn bad_csv_names_its_line() {
+        let registry = registry();
+        let options = ConversionOptions::default();
+        let line_of = |csv: &str| match matrix_csv_to_tall(csv, 1, &registry, &options) {
+            Err(PrecedenceConfigError::InvalidCsv { line, .. }) => line,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(line_of("region,channel\n1,1\n"), 1);
+        assert_eq!(line_of("rank,region,region\n1,1,1\n"), 1);
+        assert_eq!(line_of("rank,region\n1,1\n2,yes\n"), 3);
+        assert_eq!(line_of("rank,region\n1,1\nfirst,1\n"), 3);
+    }
+
+    #[test]
+    fn match_type_is_the_numeric_cell() {
+        assert_eq!(MatchType::try_from(0).unwrap(), MatchType::Ignore);
+        assert_eq!(MatchType::try_from(1).unwrap(), MatchType::Exact);
+        assert!(matches!(MatchType::try_from(2), Err(PrecedenceConfigError::InvalidMatchTypeValue(2))));
+        assert_eq!(serde_json::to_string(&MatchType::Exact).unwrap(), "1");
+        assert_eq!(serde_json::from_str::<MatchType>("0").unwrap(), MatchType::Ignore);
+        assert!(serde_json::from_str::<MatchType>("2").is_err());
+        assert!(serde_json::from_str::<MatchType>("\"exact\"").is_err());
+    }
+
+    #[test]
+    fn a_matrix_cell_outside_zero_and_one_is_refused() {
+        let err = matrix_json_to_tall(r#"[{ "rank": 1, "region": 2, "channel": 1 }]"#, 1, &registry()).unwrap_err();
+        assert!(matches!(
+            err,
+            PrecedenceConfigError::InvalidMatchType { rank: 1, attr: AttrRef::Name(ref name), match_type: 2 }
+                if name == "region"
+        ));
+    }
+
+    /// `row`'s cells for `attrs`, in that order, as `1` (exact) and `0`.
+    fn exact_cells(row: &MatrixRow, attrs: &[&str]) -> String {
+        let exact = u8::from(MatchType::Exact);
+        attrs.iter().map(|attr| if row.get(attr) == Some(exact) { '1' } else { '0' }).collect()
+    }
+
+    fn rules_at(ranks: impl IntoIterator<Item = i32>) -> Vec<ConfigPrecedenceRule> {
+        ranks
+            .into_iter()
+            .map(|rank| ConfigPrecedenceRule { config_version_id: 1, rank, attr_id: 1, match_type: MatchType::Exact })
+            .collect()
+    }
+
+    #[test]
+    fn triangular_keeps_a_prefix_plus_one_more() {
+        let matrix = generate_triangular_matrix(&["a", "b", "c"]);
+        let cells: Vec<String> = matrix.iter().map(|row| exact_cells(row, &["a", "b", "c"])).collect();
+        assert_eq!(cells, ["111", "110", "101", "100", "010", "001"]);
+        assert_eq!(matrix.iter().map(|row| row.rank).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+        for attr_count in 0..6 {
+            let names: Vec<String> = (0..attr_count).map(|i| format!("a{i}")).collect();
+            let attrs: Vec<&str> = names.iter().map(String::as_str).collect();
+            let matrix = generate_triangular_matrix(&attrs);
+            assert_eq!(matrix.len(), attr_count * (attr_count + 1) / 2);
+            if attr_count > 0 {
+                validate_ranks_contiguous_and_triangular(&rules_at(matrix.iter().map(|row| row.rank)), attr_count)
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn triangular_validation_checks_count_then_gaps() {
+        assert!(matches!(
+            validate_ranks_contiguous_and_triangular(&rules_at(1..=5), 3),
+            Err(PrecedenceConfigError::NonTriangularRankCount { found: 5, expected: 6, attr_count: 3 })
+        ));
+        assert!(matches!(
+            validate_ranks_contiguous_and_triangular(&rules_at([1, 2, 3, 4, 5, 7]), 3),
+            Err(PrecedenceConfigError::NonContiguousRank { rank: 7, expected: 6 })
+        ));
+        assert!(matches!(
+            validate_ranks_contiguous_and_triangular(&[], 3),
+            Err(PrecedenceConfigError::NoPrecedenceRules)
+        ));
+    }
+
+    #[test]
+    fn power_set_orders_by_specificity_then_significance() {
+        let matrix = generate_power_set_matrix(&["a", "b", "c"]).unwrap();
+        let cells: Vec<String> = matrix.iter().map(|row| exact_cells(row, &["a", "b", "c"])).collect();
+        assert_eq!(cells, ["111", "110", "101", "011", "100", "010", "001"]);
+        assert_eq!(matrix.iter().map(|row| row.rank).collect::<Vec<_>>(), (1..=7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn power_set_rejects_too_many_attributes() {
+        let names: Vec<String> = (0..=MAX_POWER_SET_ATTRS).map(|i| format!("a{i}")).collect();
+        let attrs: Vec<&str> = names.iter().map(String::as_str).collect();
+        for result in [generate_power_set_matrix(&attrs), RankScheme::PowerSet.generate(&attrs)] {
+            assert!(matches!(
+                result,
+                Err(PrecedenceConfigError::TooManyPowerSetAttrs { count, max: MAX_POWER_SET_ATTRS })
+                    if count == MAX_POWER_SET_ATTRS + 1
+            ));
+        }
+        // far past the width of a mask too
+        let names: Vec<String> = (0..70).map(|i| format!("a{i}")).collect();
+        let attrs: Vec<&str> = names.iter().map(String::as_str).collect();
+        assert!(generate_power_set_matrix(&attrs).is_err());
+    }
+
+    #[test]
+    fn generate_follows_the_scheme() {
+        let attrs = ["a", "b", "c"];
+        assert_eq!(RankScheme::Triangular.generate(&attrs).unwrap().len(), 6);
+        assert_eq!(RankScheme::PowerSet.generate(&attrs).unwrap().len(), RankScheme::PowerSet.expected_rank_count(3));
+        let custom = RankScheme::Custom(vec![vec!["b".to_string()], vec![]]);
+        let matrix = custom.generate(&attrs).unwrap();
+        assert_eq!(matrix.iter().map(|row| exact_cells(row, &attrs)).collect::<Vec<_>>(), ["010", "000"]);
+    }
+
+    #[test]
+    fn validation_expects_the_scheme_rank_count() {
+        validate_ranks(&rules_at(1..=7), 3, &RankScheme::PowerSet).unwrap();
+        assert!(matches!(
+            validate_ranks(&rules_at(1..=6), 3, &RankScheme::PowerSet),
+            Err(PrecedenceConfigError::UnexpectedRankCount { scheme: "power-set", found: 6, expected: 7, .. })
+        ));
+        let custom = RankScheme::Custom(vec![vec!["a".to_string()], vec![]]);
+        validate_ranks(&rules_at([1, 2]), 3, &custom).unwrap();
+        assert!(validate_ranks(&rules_at([1, 3]), 3, &custom).is_err());
+    }
+
+    #[test]
+    fn bit_order_is_by_attr_id() {
+        let order = AttrBitOrder::new([30, 10, 20, 10]).unwrap();
+        assert_eq!(order.len(), 3);
+        assert_eq!((order.bit_of(10), order.bit_of(30), order.bit_of(15)), (Some(0), Some(2), None));
+        assert_eq!(order.attr_id_of(1), Some(20));
+        assert!(AttrBitOrder::new(1..=64).is_ok());
+        assert!(matches!(AttrBitOrder::new(1..=65), Err(PrecedenceConfigError::TooManyMaskAttrs { count: 65 })));
+    }
+
+    #[test]
+    fn rank_masks_hold_the_exact_attributes() {
+        let tall = matrix_json_to_tall(
+            r#"[{ "rank": 1, "region": 1, "channel": 1 }, { "rank": 2, "region": 1, "channel": 0 }]"#,
+            1,
+            &registry(),
+        )
+        .unwrap();
+        let order = AttrBitOrder::from_rules(&tall).unwrap();
+        let masks = RankMask::from_rules(&tall, &order).unwrap();
+        assert_eq!((masks[&1], masks[&2]), (RankMask(0b11), RankMask(0b10)));
+        assert!(masks[&2].is_subset_of(masks[&1]) && !masks[&1].is_subset_of(masks[&2]));
+        assert_eq!(masks[&2].count(), 1);
+        assert!(masks[&2].contains(1) && !masks[&2].contains(0) && !masks[&2].contains(64));
+        // region is attr_id 2, so bit 1
+        assert_eq!(masks[&2].attr_ids(&order).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(RankMask::from_attr_ids([1, 2], &order), Some(masks[&1]));
+        assert_eq!(RankMask::from_attr_ids([3], &order), None);
+
+        let narrow = AttrBitOrder::new([1]).unwrap();
+        let err = RankMask::from_rules(&tall, &narrow).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::UnknownRuleAttr { attr: AttrRef::Id(2), .. }), "{err}");
+    }
+
+    const MATRIX: &str = r#"[
+        { "rank": 1, "region": 1, "channel": 1 },
+        { "rank": 2, "region": 1, "channel": 0 },
+        { "rank": 3, "region": 0, "channel": 1 }
+    ]"#;
+
+    /// Matrix rows are maps, so compare rules by (rank, attr_id).
+    fn sorted(mut tall: Vec<ConfigPrecedenceRule>) -> Vec<ConfigPrecedenceRule> {
+        tall.sort_by_key(|rule| (rule.rank, rule.attr_id));
+        tall
+    }
+
+    #[test]
+    fn streaming_converts_like_the_whole_payload() {
+        let registry = registry();
+        let options = ConversionOptions::default();
+        let expected = sorted(matrix_json_to_tall(MATRIX, 1, &registry).unwrap());
+        assert_eq!(sorted(matrix_json_reader_to_tall(MATRIX.as_bytes(), 1, &registry, &options).unwrap()), expected);
+        let mut seen = Vec::new();
+        let emitted = matrix_json_stream_to_tall(MATRIX.as_bytes(), 1, &registry, &options, |rule| seen.push(rule));
+        assert_eq!((emitted.unwrap(), sorted(seen)), (expected.len(), expected));
+    }
+
+    #[test]
+    fn streaming_stops_at_the_first_bad_row() {
+        let registry = registry();
+        let json = r#"[
+            { "rank": 1, "region": 1, "channel": 1 },
+            { "rank": 1, "region": 0 },
+            { "rank": 2, "region": 1 }
+        ]"#;
+        let mut seen = Vec::new();
+        let options = ConversionOptions::default();
+        let result = matrix_json_stream_to_tall(json.as_bytes(), 1, &registry, &options, |rule| seen.push(rule));
+        let err = result.unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateRankAttr { rank: 1, .. }), "{err}");
+        assert_eq!(seen.len(), 2, "the first row was delivered");
+
+        let trailing = format!("{MATRIX} []");
+        let err = matrix_json_reader_to_tall(trailing.as_bytes(), 1, &registry, &options);
+        assert!(matches!(err, Err(PrecedenceConfigError::InvalidJson(_))));
+    }
+
+    fn columns(row: &MatrixRow) -> Vec<&str> {
+        row.attrs.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn matrix_rows_keep_document_column_order() {
+        let row: MatrixRow = serde_json::from_str(r#"{ "region": 1, "rank": 4, "channel": 0 }"#).unwrap();
+        assert_eq!((row.rank, columns(&row)), (4, vec!["region", "channel"]));
+        assert_eq!(row.get("channel"), Some(0));
+        assert_eq!(serde_json::to_string(&row).unwrap(), r#"{"rank":4,"region":1,"channel":0}"#);
+    }
+
+    #[test]
+    fn export_follows_the_requested_column_order() {
+        let mut registry = registry();
+        registry.register("amount", AttrRole::Match, "int").unwrap();
+        let matrix = r#"[{ "rank": 1, "region": 1, "channel": 1, "amount": 0 }]"#;
+        let tall = matrix_json_to_tall(matrix, 1, &registry).unwrap();
+        let options = ConversionOptions::default();
+        let by_name = tall_to_matrix_rows(&tall, &registry).unwrap();
+        assert_eq!(columns(&by_name[0]), ["amount", "channel", "region"]);
+        let ordered = tall_to_matrix_rows_ordered(&tall, &registry, &options, &["region", "planet"]).unwrap();
+        assert_eq!(columns(&ordered[0]), ["region", "amount", "channel"]);
+    }
+
+    #[test]
+    fn round_trip_of_a_canonical_matrix_is_lossless() {
+        let json = r#"[
+            { "rank": 1, "channel": 1, "region": 1 },
+            { "rank": 2, "channel": 1, "region": 0 },
+            { "rank": 3, "channel": 0, "region": 1 }
+        ]"#;
+        let report = verify_round_trip(json, &registry()).unwrap();
+        assert!(report.is_lossless(), "{:?}", report.losses);
+        assert_eq!(report.matrix, serde_json::from_str::<Vec<MatrixRow>>(json).unwrap());
+    }
+
+    #[test]
+    fn round_trip_reports_a_duplicate_rank() {
+        let json = r#"[{ "rank": 1, "channel": 1 }, { "rank": 1, "region": 1 }]"#;
+        let report = verify_round_trip(json, &registry()).unwrap();
+        assert!(report.losses.contains(&RoundTripLoss::RankMerged { rank: 1, rows: 2 }), "{:?}", report.losses);
+    }
+
+    #[test]
+    fn round_trip_reports_reordered_columns() {
+        let json = r#"[{ "rank": 1, "region": 1, "channel": 0 }]"#;
+        let report = verify_round_trip(json, &registry()).unwrap();
+        assert_eq!(
+            report.losses,
+            [RoundTripLoss::ColumnsReordered {
+                rank: 1,
+                from: vec!["region".to_string(), "channel".to_string()],
+                to: vec!["channel".to_string(), "region".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn row_refs_borrow_column_names_unless_escaped() {
+        let json = r#"[{ "rank": 1, "channel": 1, "reg\u0069on": 0 }, { "rank": 2, "region": 1 }]"#;
+        let rows: Vec<MatrixRowRef<'_>> = serde_json::from_str(json).unwrap();
+        assert!(matches!(&rows[0].attrs[0].0, Cow::Borrowed("channel")));
+        assert!(matches!(&rows[0].attrs[1].0, Cow::Owned(name) if name == "region"));
+        assert_eq!((rows[0].get("region"), rows[1].get("channel")), (Some(0), None));
+        assert_eq!(rows[1].clone().into_owned().attrs, [(AttrName::new("region"), 1)]);
+
+        let registry = registry();
+        let options = ConversionOptions::default();
+        assert_eq!(
+            matrix_row_refs_to_tall(&rows, 1, &registry, &options).unwrap(),
+            matrix_json_to_tall_with_options(json, 1, &registry, &options).unwrap()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_conversion_matches_the_sequential_one() {
+        let registry = registry();
+        let options = ConversionOptions::default();
+        let rows: Vec<String> =
+            (1..=200).map(|rank| format!(r#"{{ "rank": {}, "channel": {}, "region": 1 }}"#, rank, rank % 2)).collect();
+        let json = format!("[{}]", rows.join(","));
+        assert_eq!(
+            matrix_json_to_tall_par(&json, 1, &registry, &options).unwrap(),
+            matrix_json_to_tall_with_options(&json, 1, &registry, &options).unwrap()
+        );
+
+        let bad = json.replace(r#""rank": 150, "channel": 0"#, r#""rank": 150, "channel": 2"#);
+        let err = matrix_json_to_tall_par(&bad, 1, &registry, &options).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidMatchType { rank: 150, .. }), "{err}");
+    }
+
+    #[test]
+    fn renumbering_compacts_ranks_in_order() {
+        let rule = |rank, attr_id| {
+            ConfigPrecedenceRule { config_version_id: 1, rank, attr_id, match_type: MatchType::Exact }
+        };
+        let mut tall = vec![rule(5, 1), rule(2, 1), rule(5, 2), rule(9, 1)];
+        let map = renumber_ranks(&mut tall);
+        assert_eq!(map, RenumberMap::from([(2, 1), (5, 2), (9, 3)]));
+        assert_eq!(tall.iter().map(|r| (r.rank, r.attr_id)).collect::<Vec<_>>(), [(2, 1), (1, 1), (2, 2), (3, 1)]);
+        assert_eq!(renumber_ranks(&mut tall), RenumberMap::from([(1, 1), (2, 2), (3, 3)]));
+    }
+
+    #[test]
+    fn adding_and_removing_an_attribute_regenerates_the_ranks() {
+        let mut matrix = generate_triangular_matrix(&["a", "b"]);
+        let change = add_attribute(&mut matrix, "c", 2, &RankScheme::Triangular).unwrap();
+        assert_eq!(matrix, generate_triangular_matrix(&["a", "b", "c"]));
+        // {a, b}, {a} and {b} move down past the new combinations with c
+        assert_eq!(change.kept, RenumberMap::from([(1, 2), (2, 4), (3, 5)]));
+        assert_eq!((change.added_ranks, change.removed_ranks), (vec![1, 3, 6], vec![]));
+
+        let change = remove_attribute(&mut matrix, "c", &RankScheme::Triangular).unwrap();
+        assert_eq!(matrix, generate_triangular_matrix(&["a", "b"]));
+        assert_eq!(change.kept, RenumberMap::from([(2, 1), (4, 2), (5, 3)]));
+        assert_eq!((change.added_ranks, change.removed_ranks), (vec![], vec![1, 3, 6]));
+
+        let mut power_set = generate_power_set_matrix(&["a", "b"]).unwrap();
+        let change = add_attribute(&mut power_set, "c", 0, &RankScheme::PowerSet).unwrap();
+        assert_eq!(power_set, generate_power_set_matrix(&["c", "a", "b"]).unwrap());
+        assert_eq!((change.kept.len(), change.added_ranks.len(), change.removed_ranks.len()), (3, 4, 0));
+        assert!(!change.is_empty() && MatrixChange::default().is_empty());
+    }
+
+    #[test]
+    fn bad_attribute_edits_leave_the_matrix_unchanged() {
+        let original = generate_triangular_matrix(&["a", "b"]);
+        let mut matrix = original.clone();
+        let custom = RankScheme::Custom(vec![vec!["a".to_string()]]);
+        for err in [
+            add_attribute(&mut matrix, "a", 0, &RankScheme::Triangular).unwrap_err(),
+            add_attribute(&mut matrix, "c", 3, &RankScheme::Triangular).unwrap_err(),
+            add_attribute(&mut matrix, "c", 0, &custom).unwrap_err(),
+            remove_attribute(&mut matrix, "z", &RankScheme::Triangular).unwrap_err(),
+        ] {
+            assert!(matches!(err, PrecedenceConfigError::InvalidAttributeEdit { .. }), "{err}");
+        }
+        assert_eq!(matrix, original);
+
+        let mut single = generate_triangular_matrix(&["a"]);
+        let err = remove_attribute(&mut single, "a", &RankScheme::Triangular).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidAttributeEdit { op: "remove", .. }), "{err}");
+    }
+
+    #[test]
+    fn moving_a_rank_renumbers_the_ones_between() {
+        let mut matrix = generate_triangular_matrix(&["a", "b"]);
+        let original = matrix.clone();
+        let map = move_rank(&mut matrix, 3, 1).unwrap();
+        assert_eq!(map, RenumberMap::from([(1, 2), (2, 3), (3, 1)]));
+        assert_eq!(matrix.iter().map(|row| row.rank).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(matrix.iter().map(|row| exact_cells(row, &["a", "b"])).collect::<Vec<_>>(), ["01", "11", "10"]);
+        move_rank(&mut matrix, 1, 3).unwrap();
+        assert_eq!(matrix, original);
+        assert_eq!(move_rank(&mut matrix, 2, 2).unwrap(), RenumberMap::from([(1, 1), (2, 2), (3, 3)]));
+    }
+
+    #[test]
+    fn bad_rank_moves_leave_the_matrix_unchanged() {
+        let mut matrix = generate_triangular_matrix(&["a", "b"]);
+        let original = matrix.clone();
+        for (from_rank, to_rank) in [(4, 1), (1, 0), (1, 4)] {
+            let err = move_rank(&mut matrix, from_rank, to_rank).unwrap_err();
+            assert!(matches!(err, PrecedenceConfigError::InvalidRankMove { .. }), "{err}");
+        }
+        assert_eq!(matrix, original);
+        matrix[2].rank = 2;
+        let err = move_rank(&mut matrix, 1, 2).unwrap_err();
+        assert!(err.to_string().contains("rank 2 is given twice"), "{err}");
+    }
+
+    #[test]
+    fn matrix_columns_may_use_aliases() {
+        let mut registry = registry();
+        registry.set_aliases("region", ["rgn".to_string()]).unwrap();
+        let json = r#"[{ "rank": 1, "channel": 1, "rgn": 1 }, { "rank": 2, "channel": 0, "region": 1 }]"#;
+        let expected = matrix_json_to_tall(&json.replace("rgn", "region"), 1, &registry).unwrap();
+        assert_eq!(matrix_json_to_tall(json, 1, &registry).unwrap(), expected);
+    }
+}
@@ -0,0 +1,142 @@
+//! Renaming an attribute everywhere it is referred to by name.
+
+use crate::config_attr::{AttrName, AttrRegistry};
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::MatrixRow;
+use crate::config_types::ConfigEnvelope;
+use serde::Serialize;
+
+/// What [`rename_attribute`] renamed besides the registry entry, or
+/// [`migrate_deprecated`](crate::config_deprecation::migrate_deprecated) for one old name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RenameSummary {
+    /// matrix rows with the column
+    pub matrix_cells: usize,
+    /// envelope rows with the match key
+    pub match_keys: usize,
+    /// envelope params with the key
+    pub param_keys: usize,
+}
+
+/// Renames attribute `old` to `new` in `registry` and wherever it is referred to
+/// by name: the columns of `matrix` and the match and param keys of every row of
+/// `envelopes`. Tall rules and config values refer to the attr_id and need no change.
+///
+/// Everything is checked before anything changes, so on error all of it is left
+/// as it was. Fails if `old` is not in the registry, or if `new` is taken: by
+/// another attribute, a matrix column, or a match or param key of any row.
+pub fn rename_attribute(
+    registry: &mut AttrRegistry,
+    old: &str,
+    new: &str,
+    matrix: &mut [MatrixRow],
+    envelopes: &mut [ConfigEnvelope],
+) -> Result<RenameSummary> {
+    if registry.get(old).is_none() {
+        return Err(PrecedenceConfigError::UnknownAttrKey { key: old.to_string() });
+    }
+    if old == new {
+        return Ok(RenameSummary::default());
+    }
+    if registry.get(new).is_some() {
+        return Err(PrecedenceConfigError::DuplicateAttrName { attr_name: new.to_string() });
+    }
+    let taken = |by: &str| PrecedenceConfigError::InvalidAttributeEdit {
+        op: "rename",
+        attr_name: old.to_string(),
+        reason: format!("'{}' is already {}", new, by),
+    };
+    if matrix.iter().flat_map(|row| &row.attrs).any(|(name, _)| name == new) {
+        return Err(taken("a matrix column"));
+    }
+    let rows = || envelopes.iter().flat_map(|envelope| &envelope.rows);
+    if rows().any(|row| row.match_part.attrs.contains_key(new) || row.params.iter().any(|p| p.key == new)) {
+        return Err(taken("a key of envelope rows"));
+    }
+
+    registry.rename(old, new)?;
+    let name = AttrName::new(new);
+    let mut summary = RenameSummary::default();
+    for (column, _) in matrix.iter_mut().flat_map(|row| &mut row.attrs).filter(|(column, _)| column == old) {
+        *column = name.clone();
+        summary.matrix_cells += 1;
+    }
+    for row in envelopes.iter_mut().flat_map(|envelope| &mut envelope.rows) {
+        if let Some(value) = row.match_part.attrs.remove(old) {
+            row.match_part.attrs.insert(name.clone(), value);
+            summary.match_keys += 1;
+        }
+        for param in row.params.iter_mut().filter(|p| p.key == old) {
+            param.key = name.clone();
+            summary.param_keys += 1;
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::region_channel_registry;
+    use serde_json::json;
+
+    fn matrix() -> Vec<MatrixRow> {
+        serde_json::from_value(json!([
+            { "rank": 1, "region": 1, "channel": 1 },
+            { "rank": 2, "region": 1, "channel": 0 },
+        ]))
+        .unwrap()
+    }
+
+    fn envelopes() -> Vec<ConfigEnvelope> {
+        let envelope = |version: i32| {
+            let limit = json!([{ "key": "limit", "type": "int", "value": 5 }]);
+            serde_json::from_value(json!({
+                "config": { "name": "c", "version": version, "version_name": format!("v{version}") },
+                "rows": [
+                    { "match": { "region": "eu", "channel": "web" }, "params": limit },
+                    { "match": { "channel": "app" }, "params": [] },
+                ],
+            }))
+            .unwrap()
+        };
+        vec![envelope(1), envelope(2)]
+    }
+
+    #[test]
+    fn a_rename_reaches_the_registry_matrix_and_envelopes() {
+        let (mut registry, mut matrix, mut envelopes) = (region_channel_registry(), matrix(), envelopes());
+        let summary = rename_attribute(&mut registry, "region", "country", &mut matrix, &mut envelopes).unwrap();
+        assert_eq!(summary, RenameSummary { matrix_cells: 2, match_keys: 2, param_keys: 0 });
+        assert_eq!((registry.id_of("country"), registry.id_of("region")), (Some(1), None));
+        assert_eq!(matrix[1].get("country"), Some(1));
+        assert_eq!(envelopes[1].rows[0].match_part.attrs["country"], json!("eu"));
+
+        let summary = rename_attribute(&mut registry, "limit", "max_items", &mut matrix, &mut envelopes).unwrap();
+        assert_eq!(summary, RenameSummary { matrix_cells: 0, match_keys: 0, param_keys: 2 });
+        assert_eq!(envelopes[0].rows[0].params[0].key, "max_items");
+        let unchanged = rename_attribute(&mut registry, "channel", "channel", &mut matrix, &mut envelopes).unwrap();
+        assert_eq!(unchanged, RenameSummary::default());
+    }
+
+    #[test]
+    fn a_taken_name_leaves_everything_as_it_was() {
+        let (mut registry, mut matrix, mut envelopes) = (region_channel_registry(), matrix(), envelopes());
+        let err = rename_attribute(&mut registry, "region", "channel", &mut matrix, &mut envelopes).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateAttrName { .. }), "{err}");
+        let err = rename_attribute(&mut registry, "planet", "moon", &mut matrix, &mut envelopes).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::UnknownAttrKey { .. }), "{err}");
+
+        matrix[0].attrs.push(("country".into(), 0));
+        let err = rename_attribute(&mut registry, "region", "country", &mut matrix, &mut envelopes).unwrap_err();
+        assert!(err.to_string().contains("already a matrix column"), "{err}");
+        matrix[0].attrs.pop();
+        envelopes[1].rows[1].match_part.attrs.insert("country".into(), json!("de"));
+        let err = rename_attribute(&mut registry, "region", "country", &mut matrix, &mut envelopes).unwrap_err();
+        assert!(err.to_string().contains("already a key of envelope rows"), "{err}");
+
+        assert_eq!(registry.id_of("region"), Some(1));
+        assert_eq!(matrix, self::matrix());
+        assert_eq!(envelopes[0], self::envelopes()[0]);
+    }
+}
@@ -0,0 +1,2199 @@
+use crate::config_precidence_rules::{AttrBitOrder, ConfigPrecedenceRule, MatchType, RankMask};
+#[cfg(feature = "regex")]
+use crate::config_value::RegexMatch;
+use crate::config_value::{
+    missing_defaults, parse_str_value, same_value, AttrRole, ConfigValue, StrPattern, TypedValue, ValueRange,
+};
+use crate::config_attr::AttrRegistry;
+#[cfg(feature = "binary")]
+use crate::config_binary::BinaryValue;
+use crate::config_metrics::{Metrics, MetricsSink, ResolutionEvent, ResolutionOutcome};
+use crate::config_table::domain_of;
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Match value a config row uses to say "any value" for an attribute (see README: `ALL`).
+pub const WILDCARD: &str = "ALL";
+
+/// A lookup context: match attribute name -> value.
+pub type Context = HashMap<String, TypedValue>;
+
+/// The `rank` of a row chosen under [`ResolutionStrategy::WeightedScore`], which ignores ranks.
+pub const SCORED_RANK: i32 = 0;
+
+/// The `rank` of the default row when it is returned because no rank had a matching row.
+pub const DEFAULT_RANK: i32 = -1;
+
+/// The winning row for a lookup context.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedConfig {
+    pub match_id: i32,
+    pub rank: i32,
+    pub params: Vec<ConfigValue>,
+}
+
+/// A single config row split into its match tuple and its params.
+#[derive(Debug, Clone)]
+struct CandidateRow {
+    match_id: i32,
+    match_values: BTreeMap<i32, TypedValue>,
+    /// bits of the attrs in `match_values`
+    mask: RankMask,
+    /// (regexes, patterns, characters the patterns fix) among `match_values`;
+    /// lower sorts first, see [`TieBreaker::pick`]
+    specificity: (usize, usize, Reverse<usize>),
+    params: Vec<ConfigValue>,
+}
+
+/// The attributes one rank requires to match exactly, as ids and as a mask.
+#[derive(Debug, Clone, Default)]
+struct RankRule {
+    exact: BTreeSet<i32>,
+    mask: RankMask,
+}
+
+/// How a config row constrains one match attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchValue<'a> {
+    /// Absent, `null` or `"ALL"`: any context value will do.
+    Any,
+    /// The context value must equal this one.
+    Exact(&'a TypedValue),
+    /// The context value must lie within the range.
+    Range(&'a ValueRange),
+    /// The context value must equal one of these.
+    OneOf(&'a [TypedValue]),
+    /// The context value must be a `str` / `enum` fitting the pattern.
+    Pattern(&'a StrPattern),
+    /// The context value must be a `str` / `enum` the regex matches.
+    #[cfg(feature = "regex")]
+    Regex(&'a RegexMatch),
+}
+
+impl<'a> MatchValue<'a> {
+    /// Reads a row's match value.
+    pub fn of(value: &'a TypedValue) -> Self {
+        match value {
+            TypedValue::Range(range) => MatchValue::Range(range),
+            TypedValue::OneOf(items) => MatchValue::OneOf(items),
+            TypedValue::Pattern(pattern) => MatchValue::Pattern(pattern),
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(regex) => MatchValue::Regex(regex),
+            value if is_wildcard(value) => MatchValue::Any,
+            value => MatchValue::Exact(value),
+        }
+    }
+
+    /// Whether the context value `got` satisfies this match value.
+    pub fn matches(&self, got: &TypedValue) -> bool {
+        match self {
+            MatchValue::Any => true,
+            MatchValue::Exact(want) => same_value(want, got),
+            MatchValue::Range(range) => range.contains(got),
+            MatchValue::OneOf(items) => items.iter().any(|item| same_value(item, got)),
+            MatchValue::Pattern(pattern) => match got {
+                TypedValue::Str(text) | TypedValue::Enum(text) => pattern.matches(text),
+                _ => false,
+            },
+            #[cfg(feature = "regex")]
+            MatchValue::Regex(regex) => match got {
+                TypedValue::Str(text) | TypedValue::Enum(text) => regex.is_match(text),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Picks the highest-precedence config row for a context.
+///
+/// Ranks are evaluated from 1 upwards. At each rank an attribute with
+/// `MatchType::Exact` must be set on the row and equal the context value (or,
+/// for a range, a set of alternatives, a pattern or a regex, contain it; see [`MatchValue`]),
+/// and an attribute with `MatchType::Ignore` (or missing from the rank) must be a
+/// wildcard on the row (absent, `null` or `"ALL"`). A `null` context value counts
+/// as not supplied. The first rank with a matching row wins; ties within a rank
+/// go to the most specific row: the one matching the context values themselves
+/// rather than their ancestors (see `AttrMeta::parents`; the nearer the better),
+/// then the fewest regexes (none beats any), then the fewest patterns, then the
+/// most characters fixed by its patterns. Rows still tied are settled by the
+/// [`TieBreakPolicy`], the lowest `match_id` by default.
+///
+/// A row constraining no match attribute is the default row: if no rank has a
+/// matching row, it is returned at [`DEFAULT_RANK`]. A version may have at most
+/// one. With [`Resolver::set_require_match`], finding no row at all is an error.
+///
+/// Under [`ResolutionStrategy::WeightedScore`] ranks are ignored and rows are scored instead.
+///
+/// A row that omits a param attribute gets the attribute's `AttrMeta::default`, if any.
+///
+/// Rows and ranks are compared by [`RankMask`] first, so a resolver covers at
+/// most 64 distinct attributes.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    /// of the first rule, if any
+    config_version_id: Option<i32>,
+    /// rank -> attrs that must match exactly
+    ranks: BTreeMap<i32, RankRule>,
+    /// sorted by match_id
+    rows: Vec<CandidateRow>,
+    attr_name_to_id: HashMap<String, i32>,
+    /// attr_id -> attr_name, the registry's id index
+    attr_names: HashMap<i32, String>,
+    parents: Parents,
+    tie_break: TieBreaker,
+    strategy: ResolutionStrategy,
+    /// attr_id -> weight, under `WeightedScore`
+    weights: Option<HashMap<i32, f64>>,
+    fallback: Fallback,
+    /// attr_id -> every value of a match attribute some row constrains, if it has a small enumerable domain
+    domains: BTreeMap<i32, Option<Vec<TypedValue>>>,
+    metrics: Metrics,
+}
+
+/// The default row and what to do when not even it applies.
+#[derive(Debug, Clone, Copy, Default)]
+struct Fallback {
+    /// position in `rows` of the row constraining no match attribute
+    row: Option<usize>,
+    require_match: bool,
+}
+
+/// How to choose between rows that match equally well at the winning rank: the
+/// same ancestry depth and pattern specificity (see [`Resolver`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum TieBreakPolicy {
+    /// The lookup fails with [`PrecedenceConfigError::AmbiguousMatch`], see [`Resolver::try_resolve`].
+    Error,
+    /// The lowest match_id.
+    #[default]
+    FirstByMatchId,
+    /// The row created last, by the times given to [`Resolver::set_row_created`];
+    /// rows without one lose, and the lowest match_id settles what is left.
+    LatestCreated,
+    /// The row with the highest `int` / `dec` value of the param attribute `param`;
+    /// rows without it lose, and the lowest match_id settles what is left.
+    HighestWeight { param: String },
+}
+
+impl fmt::Display for TieBreakPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TieBreakPolicy::Error => f.write_str("error"),
+            TieBreakPolicy::FirstByMatchId => f.write_str("first_by_match_id"),
+            TieBreakPolicy::LatestCreated => f.write_str("latest_created"),
+            TieBreakPolicy::HighestWeight { param } => write!(f, "highest_weight({})", param),
+        }
+    }
+}
+
+/// How a resolver chooses among the rows matching a context.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum ResolutionStrategy {
+    /// The first rank with a matching row wins (see [`Resolver`]).
+    #[default]
+    RankPrecedence,
+    /// Ranks are ignored. A row matches when every attribute it constrains fits
+    /// the context, and scores the sum of those attributes' weights (1 for an
+    /// attribute missing from `weights`); the highest score wins, with ties
+    /// settled by specificity and the [`TieBreakPolicy`] as within a rank.
+    WeightedScore {
+        /// match attribute name -> weight
+        #[serde(default)]
+        weights: BTreeMap<String, f64>,
+    },
+}
+
+/// A [`TieBreakPolicy`] with what it needs at lookup time.
+#[derive(Debug, Clone, Default)]
+struct TieBreaker {
+    policy: TieBreakPolicy,
+    /// attr_id of the `HighestWeight` param
+    weight_attr: Option<i32>,
+    /// match_id -> creation time, for `LatestCreated`
+    created: HashMap<i32, DateTime<Utc>>,
+}
+
+/// The winner [`TieBreaker::pick`] chose among the rows matching at a rank.
+struct Pick<'r> {
+    row: &'r CandidateRow,
+    /// rows as specific as the winner, the winner included
+    tied: usize,
+}
+
+/// attr_id -> child value -> parent value, for the hierarchical match attributes
+type Parents = HashMap<i32, HashMap<ValueKey, TypedValue>>;
+
+impl Resolver {
+    /// Builds a resolver for one config version from its tall precedence rules and config values.
+    pub fn new(
+        rules: &[ConfigPrecedenceRule],
+        values: &[ConfigValue],
+        registry: &AttrRegistry,
+    ) -> Result<Self> {
+        let mut ranks: BTreeMap<i32, RankRule> = BTreeMap::new();
+        for (index, r) in rules.iter().enumerate() {
+            if r.rank <= 0 {
+                return Err(PrecedenceConfigError::InvalidRank { index, rank: r.rank });
+            }
+            let rule = ranks.entry(r.rank).or_default();
+            if r.match_type == MatchType::Exact {
+                rule.exact.insert(r.attr_id);
+            }
+        }
+
+        let mut rows: BTreeMap<i32, CandidateRow> = BTreeMap::new();
+        for v in values {
+            let row = rows.entry(v.match_id).or_insert_with(|| CandidateRow {
+                match_id: v.match_id,
+                match_values: BTreeMap::new(),
+                mask: RankMask::default(),
+                specificity: (0, 0, Reverse(0)),
+                params: Vec::new(),
+            });
+
+            match v.role {
+                AttrRole::Match => {
+                    if is_wildcard(&v.value) {
+                        continue;
+                    }
+                    if row.match_values.insert(v.attr_id, v.value.clone()).is_some() {
+                        return Err(PrecedenceConfigError::DuplicateMatchAttr {
+                            match_id: v.match_id,
+                            attr_id: v.attr_id,
+                        });
+                    }
+                }
+                AttrRole::Param => row.params.push(v.clone()),
+            }
+        }
+
+        let order = AttrBitOrder::new(
+            rules
+                .iter()
+                .map(|r| r.attr_id)
+                .chain(rows.values().flat_map(|row| row.match_values.keys().copied())),
+        )?;
+        const COVERED: &str = "order covers every attr_id";
+        for rule in ranks.values_mut() {
+            rule.mask = RankMask::from_attr_ids(rule.exact.iter().copied(), &order).expect(COVERED);
+        }
+        for row in rows.values_mut() {
+            row.mask = RankMask::from_attr_ids(row.match_values.keys().copied(), &order).expect(COVERED);
+            let patterns: Vec<&StrPattern> = row
+                .match_values
+                .values()
+                .filter_map(|v| match v {
+                    TypedValue::Pattern(pattern) => Some(pattern),
+                    _ => None,
+                })
+                .collect();
+            row.specificity = (
+                row.match_values.values().filter(|v| is_regex(v)).count(),
+                patterns.len(),
+                Reverse(patterns.iter().map(|p| p.literal_len()).sum()),
+            );
+            let defaults = missing_defaults(row.match_id, &row.params, registry);
+            row.params.extend(defaults);
+        }
+
+        // only the attributes some row constrains can change which row wins
+        let domains = rows
+            .values()
+            .flat_map(|row| row.match_values.keys().copied())
+            .map(|id| (id, registry.get_by_id(id).and_then(domain_of)))
+            .collect();
+        let mut defaults = rows.values().filter(|row| row.match_values.is_empty());
+        if let (Some(first), Some(other)) = (defaults.next(), defaults.next()) {
+            return Err(PrecedenceConfigError::DuplicateDefaultRow {
+                match_id: first.match_id,
+                other_match_id: other.match_id,
+            });
+        }
+        let rows: Vec<CandidateRow> = rows.into_values().collect();
+        let fallback = Fallback { row: rows.iter().position(|row| row.match_values.is_empty()), require_match: false };
+
+        let mut parents = Parents::new();
+        for meta in registry.iter().filter(|m| m.role == AttrRole::Match && !m.parents.is_empty()) {
+            let table = parents.entry(meta.attr_id).or_default();
+            for (child, parent) in &meta.parents {
+                table.insert(ValueKey::from(&parse_str_value(meta, child)?), parse_str_value(meta, parent)?);
+            }
+        }
+
+        Ok(Self {
+            config_version_id: rules.first().map(|r| r.config_version_id),
+            ranks,
+            rows,
+            attr_name_to_id: registry.iter().map(|m| (m.attr_name.to_string(), m.attr_id)).collect(),
+            attr_names: registry.iter().map(|m| (m.attr_id, m.attr_name.to_string())).collect(),
+            parents,
+            tie_break: TieBreaker::default(),
+            strategy: ResolutionStrategy::default(),
+            weights: None,
+            fallback,
+            domains,
+            metrics: Metrics::default(),
+        })
+    }
+
+    /// Reports every lookup (and those of configs compiled from now on) to `sink`.
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Metrics(Some(sink));
+    }
+
+    /// The `config_version_id` of the rules (of the first one), reported on traced lookups.
+    pub fn config_version_id(&self) -> Option<i32> {
+        self.config_version_id
+    }
+
+    /// The match attributes rows constrain, by attr_id, with their enumerable domains.
+    pub(crate) fn domains(&self) -> &BTreeMap<i32, Option<Vec<TypedValue>>> {
+        &self.domains
+    }
+
+    /// The attr name of `attr_id`, if the registry knows it.
+    pub(crate) fn attr_name(&self, attr_id: i32) -> Option<&str> {
+        self.attr_names.get(&attr_id).map(String::as_str)
+    }
+
+    /// The match_id of the default row, if the version has one.
+    pub fn default_match_id(&self) -> Option<i32> {
+        self.fallback.row.map(|i| self.rows[i].match_id)
+    }
+
+    /// Makes [`Resolver::try_resolve`] fail with `NoMatchingRow` instead of
+    /// returning `None` when no row, not even a default one, matches.
+    pub fn set_require_match(&mut self, require_match: bool) {
+        self.fallback.require_match = require_match;
+    }
+
+    pub fn require_match(&self) -> bool {
+        self.fallback.require_match
+    }
+
+    /// Sets how rows are chosen; fails with `UnknownAttrKey` for a weight on an
+    /// unknown attribute and `InvalidAttrWeight` for one that is not finite.
+    pub fn set_strategy(&mut self, strategy: ResolutionStrategy) -> Result<()> {
+        self.weights = match &strategy {
+            ResolutionStrategy::RankPrecedence => None,
+            ResolutionStrategy::WeightedScore { weights } => {
+                let mut by_id = HashMap::new();
+                for (name, &weight) in weights {
+                    let id = *self
+                        .attr_name_to_id
+                        .get(name)
+                        .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: name.clone() })?;
+                    if !weight.is_finite() {
+                        return Err(PrecedenceConfigError::InvalidAttrWeight { attr_name: name.clone(), weight });
+                    }
+                    by_id.insert(id, weight);
+                }
+                Some(by_id)
+            }
+        };
+        self.strategy = strategy;
+        Ok(())
+    }
+
+    pub fn strategy(&self) -> &ResolutionStrategy {
+        &self.strategy
+    }
+
+    /// Sets how rows tied at the winning rank are settled; fails with
+    /// `UnknownAttrKey` if a `HighestWeight` param is not a known attribute.
+    pub fn set_tie_break(&mut self, policy: TieBreakPolicy) -> Result<()> {
+        self.tie_break.weight_attr = match &policy {
+            TieBreakPolicy::HighestWeight { param } => Some(
+                *self
+                    .attr_name_to_id
+                    .get(param)
+                    .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: param.clone() })?,
+            ),
+            _ => None,
+        };
+        self.tie_break.policy = policy;
+        Ok(())
+    }
+
+    pub fn tie_break(&self) -> &TieBreakPolicy {
+        &self.tie_break.policy
+    }
+
+    /// Creation times of rows by match_id, for [`TieBreakPolicy::LatestCreated`]
+    /// (e.g. from `ConfigVersion::row_audits`); replaces any given before.
+    pub fn set_row_created(&mut self, created: impl IntoIterator<Item = (i32, DateTime<Utc>)>) {
+        self.tie_break.created = created.into_iter().collect();
+    }
+
+    /// Returns the highest-precedence row matching `context` (keyed by attr name).
+    /// Under [`TieBreakPolicy::Error`] a tie resolves to `None`; see [`Resolver::try_resolve`].
+    pub fn resolve(&self, context: &Context) -> Option<ResolvedConfig> {
+        self.try_resolve(context).ok().flatten()
+    }
+
+    /// [`Resolver::resolve`], failing with `AmbiguousMatch` when rows tie at the
+    /// winning rank under [`TieBreakPolicy::Error`], and with `NoMatchingRow` when
+    /// nothing matches under [`Resolver::set_require_match`].
+    pub fn try_resolve(&self, context: &Context) -> Result<Option<ResolvedConfig>> {
+        let started = Instant::now();
+        let outcome = self.resolve_untraced(context);
+        observe("resolver", self.config_version_id, &self.metrics, context, &outcome, started);
+        outcome
+    }
+
+    fn resolve_untraced(&self, context: &Context) -> Result<Option<ResolvedConfig>> {
+        let lookup = self.lookup(context);
+        if let Some(weights) = &self.weights {
+            let scored = pick_scored(&self.rows, weights, &lookup, &self.tie_break)?;
+            return self.fallback.apply(&self.rows, scored.map(resolved_by_score), false);
+        }
+
+        for (&rank, rule) in &self.ranks {
+            let matching = self.rows.iter().filter_map(|row| Some((row_matches(row, rule, &lookup)?, row)));
+
+            if let Some(Pick { row, .. }) = self.tie_break.pick(rank, matching)? {
+                return Ok(Some(ResolvedConfig {
+                    match_id: row.match_id,
+                    rank,
+                    params: row.params.clone(),
+                }));
+            }
+        }
+
+        self.fallback.apply(&self.rows, None, true)
+    }
+
+    /// Resolves every context against the same compiled ranks and rows, in input order.
+    pub fn resolve_batch(&self, contexts: &[Context]) -> Vec<Option<ResolvedConfig>> {
+        contexts.iter().map(|context| self.resolve(context)).collect()
+    }
+
+    /// [`Resolver::resolve_batch`] spread over the rayon thread pool; results keep input order.
+    #[cfg(feature = "parallel")]
+    pub fn resolve_batch_par(&self, contexts: &[Context]) -> Vec<Option<ResolvedConfig>> {
+        use rayon::prelude::*;
+
+        contexts.par_iter().map(|context| self.resolve(context)).collect()
+    }
+
+    /// Same lookup as [`Resolver::resolve`], recording how every rank and row was judged.
+    pub fn resolve_explain(&self, context: &Context) -> Explanation {
+        let lookup = self.lookup(context);
+        let attr_ref = |attr_id: &i32| match self.attr_names.get(attr_id) {
+            Some(name) => AttrRef::Name(name.clone()),
+            None => AttrRef::Id(*attr_id),
+        };
+        if let Some(weights) = &self.weights {
+            return self.explain_scored(weights, &lookup, &attr_ref);
+        }
+
+        let mut ranks = Vec::new();
+        for (&rank, rule) in &self.ranks {
+            let exact = &rule.exact;
+            let rows: Vec<RowEvaluation> = self
+                .rows
+                .iter()
+                .map(|row| {
+                    let check = evaluate_row(row, exact, &lookup);
+                    RowEvaluation {
+                        match_id: row.match_id,
+                        is_match: check.is_match(),
+                        matched: check.matched.iter().map(attr_ref).collect(),
+                        mismatched: check.mismatched.iter().map(attr_ref).collect(),
+                        unconstrained: check.unconstrained.iter().map(attr_ref).collect(),
+                        not_wildcard: check.not_wildcard.iter().map(attr_ref).collect(),
+                        score: None,
+                    }
+                })
+                .collect();
+
+            let matching: Vec<(usize, &CandidateRow)> =
+                self.rows.iter().filter_map(|row| Some((row_matches(row, rule, &lookup)?, row))).collect();
+            ranks.push(RankEvaluation {
+                rank,
+                exact_attrs: exact.iter().map(attr_ref).collect(),
+                missing_from_context: exact
+                    .iter()
+                    .filter(|id| !lookup.values.contains_key(id))
+                    .map(attr_ref)
+                    .collect(),
+                rows,
+            });
+
+            let tie_break = self.tie_break.policy.clone();
+            let pick = match self.tie_break.pick(rank, matching.iter().copied()) {
+                Ok(pick) => pick,
+                Err(e) => {
+                    let reason =
+                        format!("rank {} is the first rank with a matching row; {} under the error tie-break", rank, e);
+                    return Explanation { ranks, winner: None, reason, tie_break, strategy: self.strategy.clone() };
+                }
+            };
+            if let Some(Pick { row, tied }) = pick {
+                let match_id = row.match_id;
+                let reason = if matching.len() == 1 {
+                    format!("rank {} is the first rank with a matching row (match_id {})", rank, match_id)
+                } else if tied == 1 {
+                    format!(
+                        "rank {} is the first rank with a matching row; {} rows matched, the most specific \
+                         (match_id {}) wins",
+                        rank,
+                        matching.len(),
+                        match_id
+                    )
+                } else if tied == matching.len() && tie_break == TieBreakPolicy::FirstByMatchId {
+                    format!(
+                        "rank {} is the first rank with a matching row; {} rows matched, lowest match_id {} wins",
+                        rank,
+                        matching.len(),
+                        match_id
+                    )
+                } else {
+                    format!(
+                        "rank {} is the first rank with a matching row; {} rows matched, {} of them equally \
+                         specific, match_id {} wins by the {} tie-break",
+                        rank,
+                        matching.len(),
+                        tied,
+                        match_id,
+                        tie_break
+                    )
+                };
+                return Explanation {
+                    ranks,
+                    winner: Some(ResolvedConfig { match_id, rank, params: row.params.clone() }),
+                    reason,
+                    tie_break,
+                    strategy: self.strategy.clone(),
+                };
+            }
+        }
+
+        let (tie_break, strategy) = (self.tie_break.policy.clone(), self.strategy.clone());
+        let winner = self.fallback.apply(&self.rows, None, true).ok().flatten();
+        let reason = match &winner {
+            Some(winner) => format!(
+                "no row matched at any of the {} ranks; the default row (match_id {}) applies",
+                ranks.len(),
+                winner.match_id
+            ),
+            None if self.fallback.require_match => {
+                format!("no row matched at any of the {} ranks, and there is no default row", ranks.len())
+            }
+            None => format!("no row matched at any of the {} ranks", ranks.len()),
+        };
+        Explanation { ranks, winner, reason, tie_break, strategy }
+    }
+
+    /// [`Resolver::resolve_explain`] under `WeightedScore`: one evaluation, at
+    /// [`SCORED_RANK`], over every attribute some row constrains.
+    fn explain_scored(
+        &self,
+        weights: &HashMap<i32, f64>,
+        lookup: &Lookup,
+        attr_ref: &dyn Fn(&i32) -> AttrRef,
+    ) -> Explanation {
+        let constrained: BTreeSet<i32> = self.rows.iter().flat_map(|row| row.match_values.keys().copied()).collect();
+        let rows: Vec<RowEvaluation> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let check = evaluate_row(row, &constrained, lookup);
+                let is_match = check.mismatched.is_empty();
+                RowEvaluation {
+                    match_id: row.match_id,
+                    is_match,
+                    matched: check.matched.iter().map(attr_ref).collect(),
+                    mismatched: check.mismatched.iter().map(attr_ref).collect(),
+                    unconstrained: check.unconstrained.iter().map(attr_ref).collect(),
+                    not_wildcard: Vec::new(),
+                    score: is_match.then(|| score(row, weights)),
+                }
+            })
+            .collect();
+        let matching = rows.iter().filter(|row| row.is_match).count();
+        let ranks = vec![RankEvaluation {
+            rank: SCORED_RANK,
+            exact_attrs: constrained.iter().map(attr_ref).collect(),
+            missing_from_context: constrained
+                .iter()
+                .filter(|id| !lookup.values.contains_key(id))
+                .map(attr_ref)
+                .collect(),
+            rows,
+        }];
+
+        let (tie_break, strategy) = (self.tie_break.policy.clone(), self.strategy.clone());
+        let (winner, reason) = match pick_scored(&self.rows, weights, lookup, &self.tie_break) {
+            Err(e) => (None, format!("{} under the error tie-break", e)),
+            Ok(None) => (None, "no row matches every attribute it constrains".to_string()),
+            Ok(Some(scored)) => {
+                let Scored { pick: Pick { row, tied }, score, top } = scored;
+                let match_id = row.match_id;
+                let reason = if matching == 1 {
+                    format!("match_id {} is the only matching row (score {})", match_id, score)
+                } else if top == 1 {
+                    format!("match_id {} has the highest score, {}, of {} matching rows", match_id, score, matching)
+                } else if tied == 1 {
+                    format!(
+                        "{} of {} matching rows have the highest score, {}; the most specific (match_id {}) wins",
+                        top, matching, score, match_id
+                    )
+                } else {
+                    format!(
+                        "{} of {} matching rows have the highest score, {}, {} of them equally specific; \
+                         match_id {} wins by the {} tie-break",
+                        top, matching, score, tied, match_id, tie_break
+                    )
+                };
+                (Some(resolved_by_score(scored)), reason)
+            }
+        };
+        Explanation { ranks, winner, reason, tie_break, strategy }
+    }
+
+    /// Indexes the rows of every rank by their match values, see [`CompiledConfig`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(config_version_id = self.config_version_id, ranks = self.ranks.len(), rows = self.rows.len())
+        )
+    )]
+    pub fn compile(&self) -> CompiledConfig {
+        let ranks = self
+            .ranks
+            .iter()
+            .map(|(&rank, RankRule { exact, mask })| {
+                let mut index: HashMap<Vec<ValueKey>, Vec<usize>> = HashMap::new();
+                let mut ranged = Vec::new();
+                let mut patterned = Vec::new();
+                for (i, row) in self.rows.iter().enumerate() {
+                    if row.mask != *mask {
+                        continue;
+                    }
+                    if row.has_patterns() {
+                        patterned.push(i);
+                        continue;
+                    }
+                    if row.match_values.values().any(|v| matches!(v, TypedValue::Range(_))) {
+                        ranged.push(i);
+                        continue;
+                    }
+                    // one key per combination of the row's alternatives
+                    let mut keys: Vec<Vec<ValueKey>> = vec![Vec::new()];
+                    for id in exact {
+                        let options: Vec<ValueKey> = match row.match_values.get(id) {
+                            Some(TypedValue::OneOf(items)) => items.iter().map(ValueKey::from).collect(),
+                            Some(value) => vec![ValueKey::from(value)],
+                            None => Vec::new(),
+                        };
+                        keys = keys
+                            .into_iter()
+                            .flat_map(|key| {
+                                options.iter().map(move |option| key.iter().cloned().chain([option.clone()]).collect())
+                            })
+                            .collect();
+                    }
+                    for key in keys {
+                        index.entry(key).or_default().push(i);
+                    }
+                }
+                CompiledRank { rank, exact: exact.iter().copied().collect(), index, ranged, patterned }
+            })
+            .collect();
+
+        let compiled = CompiledConfig {
+            config_version_id: self.config_version_id,
+            ranks,
+            rows: self.rows.clone(),
+            attr_name_to_id: self.attr_name_to_id.clone(),
+            parents: self.parents.clone(),
+            tie_break: self.tie_break.clone(),
+            strategy: self.strategy.clone(),
+            weights: self.weights.clone(),
+            fallback: self.fallback,
+            metrics: self.metrics.clone(),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(index_entries = compiled.index_len(), "compiled config");
+        compiled
+    }
+
+    fn lookup<'a>(&'a self, context: &'a Context) -> Lookup<'a> {
+        Lookup::new(&self.attr_name_to_id, &self.parents, context)
+    }
+}
+
+/// A [`Resolver`] with each rank's rows hashed by their exact-match values.
+///
+/// Same answers as [`Resolver::resolve`], but a lookup is one hash probe per
+/// rank (until the first hit) instead of a scan over every row. A row listing
+/// alternatives is indexed under each of them; rows with a range value can't be
+/// hashed and are still scanned, and rows with a pattern or a regex are only
+/// scanned when no row matches the context values themselves. A hierarchical
+/// attribute costs one probe per ancestor of its context value. Regexes are
+/// compiled when their values are parsed, never during a lookup. Under
+/// [`ResolutionStrategy::WeightedScore`] every lookup scans the rows.
+#[derive(Debug, Clone)]
+pub struct CompiledConfig {
+    config_version_id: Option<i32>,
+    /// in rank order
+    ranks: Vec<CompiledRank>,
+    rows: Vec<CandidateRow>,
+    attr_name_to_id: HashMap<String, i32>,
+    parents: Parents,
+    tie_break: TieBreaker,
+    strategy: ResolutionStrategy,
+    weights: Option<HashMap<i32, f64>>,
+    fallback: Fallback,
+    metrics: Metrics,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRank {
+    rank: i32,
+    /// attr_ids that must match exactly, ascending; the order of each index key
+    exact: Vec<i32>,
+    /// match values -> positions in `rows` of the matching rows, ascending
+    index: HashMap<Vec<ValueKey>, Vec<usize>>,
+    /// positions in `rows` of the rank's rows with a range value (and no pattern), ascending
+    ranged: Vec<usize>,
+    /// positions in `rows` of the rank's rows with a pattern or a regex, ascending
+    patterned: Vec<usize>,
+}
+
+impl CompiledConfig {
+    /// Builds the index straight from tall rules and config values.
+    pub fn new(rules: &[ConfigPrecedenceRule], values: &[ConfigValue], registry: &AttrRegistry) -> Result<Self> {
+        Ok(Resolver::new(rules, values, registry)?.compile())
+    }
+
+    /// The tie-break policy of the resolver this was compiled from.
+    pub fn tie_break(&self) -> &TieBreakPolicy {
+        &self.tie_break.policy
+    }
+
+    /// Same as [`Resolver::set_metrics`].
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Metrics(Some(sink));
+    }
+
+    /// Same as [`Resolver::config_version_id`].
+    pub fn config_version_id(&self) -> Option<i32> {
+        self.config_version_id
+    }
+
+    /// The resolution strategy of the resolver this was compiled from.
+    pub fn strategy(&self) -> &ResolutionStrategy {
+        &self.strategy
+    }
+
+    /// Returns the highest-precedence row matching `context` (keyed by attr name).
+    /// Under [`TieBreakPolicy::Error`] a tie resolves to `None`; see [`CompiledConfig::try_resolve`].
+    pub fn resolve(&self, context: &Context) -> Option<ResolvedConfig> {
+        self.try_resolve(context).ok().flatten()
+    }
+
+    /// Same as [`Resolver::try_resolve`].
+    pub fn try_resolve(&self, context: &Context) -> Result<Option<ResolvedConfig>> {
+        let started = Instant::now();
+        let outcome = self.resolve_untraced(context);
+        observe("compiled", self.config_version_id, &self.metrics, context, &outcome, started);
+        outcome
+    }
+
+    fn resolve_untraced(&self, context: &Context) -> Result<Option<ResolvedConfig>> {
+        let lookup = Lookup::new(&self.attr_name_to_id, &self.parents, context);
+        if let Some(weights) = &self.weights {
+            let scored = pick_scored(&self.rows, weights, &lookup, &self.tie_break)?;
+            return self.fallback.apply(&self.rows, scored.map(resolved_by_score), false);
+        }
+
+        for compiled in &self.ranks {
+            if !compiled.exact.iter().all(|id| lookup.values.contains_key(id)) {
+                continue;
+            }
+
+            let mut candidates: Vec<(usize, &CandidateRow)> = lookup
+                .keys(&compiled.exact)
+                .into_iter()
+                .filter_map(|(depth, key)| Some((depth, compiled.index.get(&key)?)))
+                .flat_map(|(depth, hits)| hits.iter().map(move |&i| (depth, &self.rows[i])))
+                .collect();
+            // a pattern can't beat a row matching the context values themselves
+            let exact_hit = candidates.iter().any(|(depth, _)| *depth == 0);
+            let scanned = compiled.ranged.iter().chain(if exact_hit { &[][..] } else { &compiled.patterned[..] });
+            for &i in scanned {
+                let row = &self.rows[i];
+                let depth: Option<usize> =
+                    compiled.exact.iter().map(|id| lookup.depth(*id, &row.match_values[id])).sum();
+                if let Some(depth) = depth {
+                    candidates.push((depth, row));
+                }
+            }
+            if let Some(Pick { row, .. }) = self.tie_break.pick(compiled.rank, candidates.into_iter())? {
+                let (match_id, params) = (row.match_id, row.params.clone());
+                return Ok(Some(ResolvedConfig { match_id, rank: compiled.rank, params }));
+            }
+        }
+
+        self.fallback.apply(&self.rows, None, true)
+    }
+
+    /// Resolves every context, in input order.
+    pub fn resolve_batch(&self, contexts: &[Context]) -> Vec<Option<ResolvedConfig>> {
+        contexts.iter().map(|context| self.resolve(context)).collect()
+    }
+
+    /// [`CompiledConfig::resolve_batch`] spread over the rayon thread pool; results keep input order.
+    #[cfg(feature = "parallel")]
+    pub fn resolve_batch_par(&self, contexts: &[Context]) -> Vec<Option<ResolvedConfig>> {
+        use rayon::prelude::*;
+
+        contexts.par_iter().map(|context| self.resolve(context)).collect()
+    }
+
+    /// Number of indexed (rank, row) entries.
+    pub fn index_len(&self) -> usize {
+        self.ranks.iter().map(|r| r.index.len()).sum()
+    }
+
+    /// The config in the versioned binary form of [`config_binary`](crate::config_binary),
+    /// to build once (in CI, say) and load at startup with [`CompiledConfig::from_bytes`]
+    /// instead of parsing and validating it again. The metrics sink is not kept.
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let (fallback_row, require_match) = (self.fallback.row, self.fallback.require_match);
+        let binary = BinaryConfig {
+            config_version_id: self.config_version_id,
+            ranks: self.ranks.iter().map(BinaryRank::from).collect(),
+            rows: self.rows.iter().map(BinaryRow::from).collect(),
+            attr_name_to_id: self.attr_name_to_id.iter().map(|(name, id)| (name.clone(), *id)).collect(),
+            parents: self
+                .parents
+                .iter()
+                .map(|(id, table)| {
+                    (*id, table.iter().map(|(child, parent)| (child.into(), parent.into())).collect())
+                })
+                .collect(),
+            tie_break: serde_json::to_string(&self.tie_break.policy).map_err(PrecedenceConfigError::InvalidJson)?,
+            weight_attr: self.tie_break.weight_attr,
+            created: self.tie_break.created.iter().map(|(id, at)| (*id, *at)).collect(),
+            strategy: serde_json::to_string(&self.strategy).map_err(PrecedenceConfigError::InvalidJson)?,
+            weights: self.weights.as_ref().map(|w| w.iter().map(|(id, weight)| (*id, *weight)).collect()),
+            fallback_row,
+            require_match,
+        };
+        crate::config_binary::encode(&binary)
+    }
+
+    /// Loads a config written by [`CompiledConfig::to_bytes`], index and all; bytes
+    /// of another format version fail with [`PrecedenceConfigError::BinaryFormatVersion`].
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let binary: BinaryConfig = crate::config_binary::decode(bytes)?;
+        let rows = binary.rows.into_iter().map(CandidateRow::try_from).collect::<Result<Vec<_>>>()?;
+        let ranks = binary.ranks.into_iter().map(CompiledRank::try_from).collect::<Result<Vec<_>>>()?;
+        let positions = ranks.iter().flat_map(|r| r.index.values().flatten().chain(&r.ranged).chain(&r.patterned));
+        if binary.fallback_row.into_iter().chain(positions.copied()).any(|i| i >= rows.len()) {
+            return Err(PrecedenceConfigError::InvalidFormat {
+                format: "binary config",
+                reason: "row position out of range".to_string(),
+            });
+        }
+        let mut parents = Parents::new();
+        for (id, entries) in binary.parents {
+            let table = parents.entry(id).or_default();
+            for (child, parent) in entries {
+                table.insert(child.into_key()?, parent.into_value()?);
+            }
+        }
+        Ok(CompiledConfig {
+            config_version_id: binary.config_version_id,
+            ranks,
+            rows,
+            attr_name_to_id: binary.attr_name_to_id.into_iter().collect(),
+            parents,
+            tie_break: TieBreaker {
+                policy: serde_json::from_str(&binary.tie_break).map_err(PrecedenceConfigError::InvalidJson)?,
+                weight_attr: binary.weight_attr,
+                created: binary.created.into_iter().collect(),
+            },
+            strategy: serde_json::from_str(&binary.strategy).map_err(PrecedenceConfigError::InvalidJson)?,
+            weights: binary.weights.map(|w| w.into_iter().collect()),
+            fallback: Fallback { row: binary.fallback_row, require_match: binary.require_match },
+            metrics: Metrics::default(),
+        })
+    }
+}
+
+/// The payload of [`CompiledConfig::to_bytes`]; maps as lists of pairs.
+#[cfg(feature = "binary")]
+#[derive(Serialize, Deserialize)]
+struct BinaryConfig {
+    config_version_id: Option<i32>,
+    ranks: Vec<BinaryRank>,
+    rows: Vec<BinaryRow>,
+    attr_name_to_id: Vec<(String, i32)>,
+    /// attr_id -> (child, parent)
+    parents: Vec<(i32, Vec<(BinaryValue, BinaryValue)>)>,
+    /// the policy and strategy are internally tagged, which postcard can't read, so as JSON
+    tie_break: String,
+    weight_attr: Option<i32>,
+    created: Vec<(i32, DateTime<Utc>)>,
+    strategy: String,
+    weights: Option<Vec<(i32, f64)>>,
+    fallback_row: Option<usize>,
+    require_match: bool,
+}
+
+#[cfg(feature = "binary")]
+#[derive(Serialize, Deserialize)]
+struct BinaryRank {
+    rank: i32,
+    exact: Vec<i32>,
+    index: Vec<(Vec<BinaryValue>, Vec<usize>)>,
+    ranged: Vec<usize>,
+    patterned: Vec<usize>,
+}
+
+#[cfg(feature = "binary")]
+impl From<&CompiledRank> for BinaryRank {
+    fn from(rank: &CompiledRank) -> Self {
+        BinaryRank {
+            rank: rank.rank,
+            exact: rank.exact.clone(),
+            index: rank
+                .index
+                .iter()
+                .map(|(key, rows)| (key.iter().map(BinaryValue::from).collect(), rows.clone()))
+                .collect(),
+            ranged: rank.ranged.clone(),
+            patterned: rank.patterned.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl TryFrom<BinaryRank> for CompiledRank {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(rank: BinaryRank) -> Result<Self> {
+        let mut index = HashMap::with_capacity(rank.index.len());
+        for (key, rows) in rank.index {
+            index.insert(key.into_iter().map(BinaryValue::into_key).collect::<Result<_>>()?, rows);
+        }
+        Ok(CompiledRank { rank: rank.rank, exact: rank.exact, index, ranged: rank.ranged, patterned: rank.patterned })
+    }
+}
+
+#[cfg(feature = "binary")]
+#[derive(Serialize, Deserialize)]
+struct BinaryRow {
+    match_id: i32,
+    match_values: Vec<(i32, BinaryValue)>,
+    mask: u64,
+    specificity: (usize, usize, usize),
+    /// (attr_id, role, value)
+    params: Vec<(i32, AttrRole, BinaryValue)>,
+}
+
+#[cfg(feature = "binary")]
+impl From<&CandidateRow> for BinaryRow {
+    fn from(row: &CandidateRow) -> Self {
+        let (regexes, patterns, Reverse(literals)) = row.specificity;
+        BinaryRow {
+            match_id: row.match_id,
+            match_values: row.match_values.iter().map(|(id, value)| (*id, BinaryValue::from(value))).collect(),
+            mask: row.mask.0,
+            specificity: (regexes, patterns, literals),
+            params: row.params.iter().map(|p| (p.attr_id, p.role, BinaryValue::from(&p.value))).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl TryFrom<BinaryRow> for CandidateRow {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(row: BinaryRow) -> Result<Self> {
+        let match_id = row.match_id;
+        let (regexes, patterns, literals) = row.specificity;
+        Ok(CandidateRow {
+            match_id,
+            match_values: row
+                .match_values
+                .into_iter()
+                .map(|(id, value)| Ok((id, value.into_value()?)))
+                .collect::<Result<_>>()?,
+            mask: RankMask(row.mask),
+            specificity: (regexes, patterns, Reverse(literals)),
+            params: row
+                .params
+                .into_iter()
+                .map(|(attr_id, role, value)| Ok(ConfigValue { match_id, attr_id, role, value: value.into_value()? }))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// `context` with its `null` values dropped, by attribute name: equal for contexts
+/// that resolve alike because they hold the same values.
+pub(crate) fn normalized_context(context: &Context) -> Vec<(String, ValueKey)> {
+    let mut normalized: Vec<(String, ValueKey)> = context
+        .iter()
+        .filter(|(_, value)| **value != TypedValue::Null)
+        .map(|(name, value)| (name.clone(), ValueKey::from(value)))
+        .collect();
+    normalized.sort_by(|a, b| a.0.cmp(&b.0));
+    normalized
+}
+
+/// Reports a finished lookup to the metrics sink, and with the `tracing` feature
+/// as one `debug` event (`warn` when it fails), with a hash of the normalized
+/// context to group repeated lookups by (stable within a process).
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn observe(
+    resolver: &'static str,
+    config_version_id: Option<i32>,
+    metrics: &Metrics,
+    context: &Context,
+    outcome: &Result<Option<ResolvedConfig>>,
+    started: Instant,
+) {
+    let elapsed = started.elapsed();
+    if let Some(sink) = &metrics.0 {
+        sink.resolution(&ResolutionEvent { config_version_id, outcome: ResolutionOutcome::of(outcome), elapsed });
+    }
+    #[cfg(feature = "tracing")]
+    trace_resolution(resolver, config_version_id, context, outcome, elapsed);
+}
+
+#[cfg(feature = "tracing")]
+fn trace_resolution(
+    resolver: &'static str,
+    config_version_id: Option<i32>,
+    context: &Context,
+    outcome: &Result<Option<ResolvedConfig>>,
+    elapsed: std::time::Duration,
+) {
+    use std::hash::{Hash, Hasher};
+
+    let elapsed_us = elapsed.as_micros() as u64;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    normalized_context(context).hash(&mut hasher);
+    let context_hash = format!("{:016x}", hasher.finish());
+    match outcome {
+        Ok(Some(resolved)) => tracing::debug!(
+            resolver,
+            config_version_id,
+            context_hash,
+            rank = resolved.rank,
+            match_id = resolved.match_id,
+            elapsed_us,
+            "resolved"
+        ),
+        Ok(None) => tracing::debug!(resolver, config_version_id, context_hash, elapsed_us, "no matching row"),
+        Err(e) => {
+            tracing::warn!(resolver, config_version_id, context_hash, elapsed_us, error = %e, "resolution failed")
+        }
+    }
+}
+
+/// Hashable stand-in for a `TypedValue`; equal keys iff the values compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ValueKey {
+    Int(i64),
+    /// f64 bits, with -0.0 folded into 0.0
+    Dec(u64),
+    #[cfg(feature = "rust_decimal")]
+    Decimal(rust_decimal::Decimal),
+    Str(String),
+    Enum(String),
+    Bool(bool),
+    /// the UTC instant of a `Dt` or a `DtTz`, which match each other as [`same_value`] has it
+    Dt(chrono::NaiveDateTime),
+    Date(chrono::NaiveDate),
+    Time(chrono::NaiveTime),
+    Duration(std::time::Duration),
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid),
+    List(Vec<ValueKey>),
+    OneOf(Vec<ValueKey>),
+    Pattern(StrPattern),
+    /// the regex source
+    #[cfg(feature = "regex")]
+    Regex(String),
+    /// (bound, inclusive) of the lower and upper end
+    Range(Option<(Box<ValueKey>, bool)>, Option<(Box<ValueKey>, bool)>),
+    Null,
+}
+
+impl From<&TypedValue> for ValueKey {
+    fn from(value: &TypedValue) -> Self {
+        match value {
+            TypedValue::Int(v) => ValueKey::Int(*v),
+            TypedValue::Dec(v) => ValueKey::Dec(if *v == 0.0 { 0.0f64.to_bits() } else { v.to_bits() }),
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(v) => ValueKey::Decimal(*v),
+            TypedValue::Str(v) => ValueKey::Str(v.clone()),
+            TypedValue::Enum(v) => ValueKey::Enum(v.clone()),
+            TypedValue::Bool(v) => ValueKey::Bool(*v),
+            TypedValue::Dt(v) => ValueKey::Dt(*v),
+            TypedValue::DtTz(v) => ValueKey::Dt(v.naive_utc()),
+            TypedValue::Date(v) => ValueKey::Date(*v),
+            TypedValue::Time(v) => ValueKey::Time(*v),
+            TypedValue::Duration(v) => ValueKey::Duration(*v),
+            #[cfg(feature = "uuid")]
+            TypedValue::Uuid(v) => ValueKey::Uuid(*v),
+            TypedValue::List(items) => ValueKey::List(items.iter().map(ValueKey::from).collect()),
+            TypedValue::OneOf(items) => ValueKey::OneOf(items.iter().map(ValueKey::from).collect()),
+            TypedValue::Pattern(pattern) => ValueKey::Pattern(pattern.clone()),
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(regex) => ValueKey::Regex(regex.as_str().to_string()),
+            TypedValue::Range(range) => ValueKey::Range(
+                range.lower().map(|b| (Box::new(ValueKey::from(&b.value)), b.inclusive)),
+                range.upper().map(|b| (Box::new(ValueKey::from(&b.value)), b.inclusive)),
+            ),
+            TypedValue::Null => ValueKey::Null,
+        }
+    }
+}
+
+/// A lookup context by attr_id, with the ancestors of its hierarchical values.
+struct Lookup<'a> {
+    values: HashMap<i32, &'a TypedValue>,
+    /// attr_id -> the context value's parent, grandparent, ..
+    ancestors: HashMap<i32, Vec<&'a TypedValue>>,
+}
+
+impl<'a> Lookup<'a> {
+    fn new(attr_name_to_id: &HashMap<String, i32>, parents: &'a Parents, context: &'a Context) -> Self {
+        let values: HashMap<i32, &TypedValue> = context
+            .iter()
+            .filter(|(_, value)| **value != TypedValue::Null)
+            .filter_map(|(name, value)| attr_name_to_id.get(name).map(|&id| (id, value)))
+            .collect();
+        let ancestors = values
+            .iter()
+            .filter_map(|(id, value)| {
+                let table = parents.get(id)?;
+                let mut chain = Vec::new();
+                let mut current = *value;
+                // the registry rejects cycles; the length check only bounds the walk
+                while let Some(parent) = table.get(&ValueKey::from(current)).filter(|_| chain.len() < table.len()) {
+                    chain.push(parent);
+                    current = parent;
+                }
+                Some((*id, chain))
+            })
+            .collect();
+        Self { values, ancestors }
+    }
+
+    /// The context value for `attr_id`, then its ancestors, nearest first.
+    fn chain(&self, attr_id: i32) -> impl Iterator<Item = &'a TypedValue> + '_ {
+        let ancestors = self.ancestors.get(&attr_id).into_iter().flatten().copied();
+        self.values.get(&attr_id).copied().into_iter().chain(ancestors)
+    }
+
+    /// How far up the context value's ancestry `want` matches: 0 for the value itself.
+    fn depth(&self, attr_id: i32, want: &TypedValue) -> Option<usize> {
+        let want = MatchValue::of(want);
+        self.chain(attr_id).position(|got| want.matches(got))
+    }
+
+    /// Index keys for `attr_ids` over every combination of the context values and
+    /// their ancestors, each with its total depth.
+    fn keys(&self, attr_ids: &[i32]) -> Vec<(usize, Vec<ValueKey>)> {
+        let mut keys = vec![(0, Vec::new())];
+        for &id in attr_ids {
+            keys = keys
+                .into_iter()
+                .flat_map(|(depth, key): (usize, Vec<ValueKey>)| {
+                    self.chain(id).enumerate().map(move |(up, value)| {
+                        (depth + up, key.iter().cloned().chain([ValueKey::from(value)]).collect())
+                    })
+                })
+                .collect();
+        }
+        keys
+    }
+}
+
+/// Why [`Resolver::resolve_explain`] picked (or failed to pick) a row.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    /// Ranks in evaluation order, up to and including the winning rank. Under
+    /// `WeightedScore`, one evaluation at [`SCORED_RANK`] whose exact attributes
+    /// are all those some row constrains.
+    pub ranks: Vec<RankEvaluation>,
+    pub winner: Option<ResolvedConfig>,
+    pub reason: String,
+    /// The policy that settled (or, for `Error`, refused to settle) ties.
+    pub tie_break: TieBreakPolicy,
+    pub strategy: ResolutionStrategy,
+}
+
+/// One rank of the precedence matrix applied to every config row.
+#[derive(Debug, Clone)]
+pub struct RankEvaluation {
+    pub rank: i32,
+    /// Attributes this rank requires to match exactly (`MatchType::Exact`).
+    pub exact_attrs: Vec<AttrRef>,
+    /// Exact attributes the context did not supply, so no row can match on them.
+    pub missing_from_context: Vec<AttrRef>,
+    pub rows: Vec<RowEvaluation>,
+}
+
+/// How a single config row fared against one rank.
+#[derive(Debug, Clone)]
+pub struct RowEvaluation {
+    pub match_id: i32,
+    pub is_match: bool,
+    /// Exact attributes whose row value equals (or, for a range, alternatives or a
+    /// pattern, contains) the context value.
+    pub matched: Vec<AttrRef>,
+    /// Exact attributes whose row value differs from (or is missing in) the context.
+    pub mismatched: Vec<AttrRef>,
+    /// Exact attributes the row leaves as a wildcard.
+    pub unconstrained: Vec<AttrRef>,
+    /// Attributes the rank wants as a wildcard but the row constrains.
+    pub not_wildcard: Vec<AttrRef>,
+    /// The row's score if it matches under `WeightedScore`; `None` under rank precedence.
+    pub score: Option<f64>,
+}
+
+#[derive(Default)]
+struct RowCheck {
+    matched: Vec<i32>,
+    mismatched: Vec<i32>,
+    unconstrained: Vec<i32>,
+    not_wildcard: Vec<i32>,
+}
+
+impl RowCheck {
+    fn is_match(&self) -> bool {
+        self.mismatched.is_empty() && self.unconstrained.is_empty() && self.not_wildcard.is_empty()
+    }
+}
+
+impl TieBreaker {
+    /// The winner among the rows matching at `rank`, each with the sum of how far
+    /// up the context's ancestry its values matched: the shallowest, then the
+    /// fewest regexes, the fewest patterns and the most characters fixed, with
+    /// the policy settling what is left.
+    fn pick<'r>(&self, rank: i32, rows: impl Iterator<Item = (usize, &'r CandidateRow)>) -> Result<Option<Pick<'r>>> {
+        let rows: Vec<(usize, &CandidateRow)> = rows.collect();
+        let Some(best) = rows.iter().map(|(depth, row)| (*depth, row.specificity)).min() else {
+            return Ok(None);
+        };
+        let mut tied: Vec<&CandidateRow> =
+            rows.into_iter().filter(|(depth, row)| (*depth, row.specificity) == best).map(|(_, row)| row).collect();
+        tied.sort_by_key(|row| row.match_id);
+        tied.dedup_by_key(|row| row.match_id);
+
+        let row = match &self.policy {
+            _ if tied.len() == 1 => tied[0],
+            TieBreakPolicy::Error => {
+                let match_ids = tied.iter().map(|row| row.match_id).collect();
+                return Err(PrecedenceConfigError::AmbiguousMatch { rank, match_ids });
+            }
+            TieBreakPolicy::FirstByMatchId => tied[0],
+            TieBreakPolicy::LatestCreated => highest(&tied, |row| self.created.get(&row.match_id)),
+            TieBreakPolicy::HighestWeight { .. } => highest(&tied, |row| self.weight(row)),
+        };
+        Ok(Some(Pick { row, tied: tied.len() }))
+    }
+
+    fn weight(&self, row: &CandidateRow) -> Option<f64> {
+        let param = row.params.iter().find(|p| Some(p.attr_id) == self.weight_attr)?;
+        match &param.value {
+            TypedValue::Int(v) => Some(*v as f64),
+            TypedValue::Dec(v) => Some(*v),
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(v),
+            _ => None,
+        }
+    }
+}
+
+impl Fallback {
+    /// `resolved`, else the default row (if `use_default`), else `None` or `NoMatchingRow`.
+    fn apply(
+        &self,
+        rows: &[CandidateRow],
+        resolved: Option<ResolvedConfig>,
+        use_default: bool,
+    ) -> Result<Option<ResolvedConfig>> {
+        let default = || {
+            let row = &rows[self.row.filter(|_| use_default)?];
+            Some(ResolvedConfig { match_id: row.match_id, rank: DEFAULT_RANK, params: row.params.clone() })
+        };
+        match resolved.or_else(default) {
+            None if self.require_match => Err(PrecedenceConfigError::NoMatchingRow),
+            resolved => Ok(resolved),
+        }
+    }
+}
+
+/// The winner [`pick_scored`] chose.
+struct Scored<'r> {
+    pick: Pick<'r>,
+    score: f64,
+    /// matching rows with the winner's score, the winner included
+    top: usize,
+}
+
+/// The highest-scoring of the rows whose every match value fits the context,
+/// see [`ResolutionStrategy::WeightedScore`].
+fn pick_scored<'r>(
+    rows: &'r [CandidateRow],
+    weights: &HashMap<i32, f64>,
+    lookup: &Lookup,
+    tie_break: &TieBreaker,
+) -> Result<Option<Scored<'r>>> {
+    let matching: Vec<(f64, usize, &CandidateRow)> = rows
+        .iter()
+        .filter_map(|row| {
+            let depth: usize = row.match_values.iter().map(|(id, want)| lookup.depth(*id, want)).sum::<Option<_>>()?;
+            Some((score(row, weights), depth, row))
+        })
+        .collect();
+    let Some(best) = matching.iter().map(|(score, ..)| *score).reduce(f64::max) else {
+        return Ok(None);
+    };
+    let top: Vec<(usize, &CandidateRow)> =
+        matching.into_iter().filter(|(score, ..)| *score == best).map(|(_, depth, row)| (depth, row)).collect();
+    let count = top.len();
+    Ok(tie_break.pick(SCORED_RANK, top.into_iter())?.map(|pick| Scored { pick, score: best, top: count }))
+}
+
+/// The sum of the weights of the attributes `row` constrains.
+fn score(row: &CandidateRow, weights: &HashMap<i32, f64>) -> f64 {
+    // fold from 0.0: an empty f64 `sum` is -0.0
+    row.match_values.keys().map(|id| weights.get(id).copied().unwrap_or(1.0)).fold(0.0, |total, w| total + w)
+}
+
+fn resolved_by_score(scored: Scored) -> ResolvedConfig {
+    let row = scored.pick.row;
+    ResolvedConfig { match_id: row.match_id, rank: SCORED_RANK, params: row.params.clone() }
+}
+
+/// The first of `rows` (in match_id order) with the highest key; rows without one lose.
+fn highest<'r, K: PartialOrd>(rows: &[&'r CandidateRow], key: impl Fn(&CandidateRow) -> Option<K>) -> &'r CandidateRow {
+    let mut best = (rows[0], key(rows[0]));
+    for &row in &rows[1..] {
+        let k = key(row);
+        if k.is_some() && (best.1.is_none() || k > best.1) {
+            best = (row, k);
+        }
+    }
+    best.0
+}
+
+impl CandidateRow {
+    /// Whether any match value is a pattern or a regex.
+    fn has_patterns(&self) -> bool {
+        self.specificity.0 + self.specificity.1 > 0
+    }
+}
+
+#[cfg(feature = "regex")]
+fn is_regex(value: &TypedValue) -> bool {
+    matches!(value, TypedValue::Regex(_))
+}
+
+#[cfg(not(feature = "regex"))]
+fn is_regex(_value: &TypedValue) -> bool {
+    false
+}
+
+/// The row's ancestry depth if it matches at `rule`'s rank.
+fn row_matches(row: &CandidateRow, rule: &RankRule, lookup: &Lookup) -> Option<usize> {
+    // same mask: the row constrains exactly the rank's exact attrs, so only the values remain to compare
+    if row.mask != rule.mask {
+        return None;
+    }
+    rule.exact.iter().map(|attr_id| lookup.depth(*attr_id, row.match_values.get(attr_id)?)).sum()
+}
+
+fn evaluate_row(row: &CandidateRow, exact: &BTreeSet<i32>, lookup: &Lookup) -> RowCheck {
+    let mut check = RowCheck::default();
+
+    for attr_id in exact {
+        match row.match_values.get(attr_id) {
+            None => check.unconstrained.push(*attr_id),
+            Some(want) if lookup.depth(*attr_id, want).is_some() => check.matched.push(*attr_id),
+            Some(_) => check.mismatched.push(*attr_id),
+        }
+    }
+    for attr_id in row.match_values.keys() {
+        if !exact.contains(attr_id) {
+            check.not_wildcard.push(*attr_id);
+        }
+    }
+
+    check
+}
+
+pub(crate) fn is_wildcard(value: &TypedValue) -> bool {
+    match value {
+        TypedValue::Null => true,
+        TypedValue::Str(s) => s == WILDCARD,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_envelope::validate_envelope;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::config_value::{DtParseOptions, DT_FORMAT};
+    use crate::test_fixtures::region_channel_registry;
+    use chrono::{DateTime, NaiveDateTime, TimeZone};
+    use serde_json::{json, Value};
+
+    /// Rank 1 matches region and channel, rank 2 region alone, rank 3 channel alone.
+    const MATRIX: &str = r#"[
+        { "rank": 1, "region": 1, "channel": 1 },
+        { "rank": 2, "region": 1, "channel": 0 },
+        { "rank": 3, "region": 0, "channel": 1 }
+    ]"#;
+
+    /// A resolver over `MATRIX` and rows given as `(match, limit)`, with match_ids from 1.
+    fn resolver(rows: &[(&[(&str, &str)], i64)]) -> Resolver {
+        let registry = region_channel_registry();
+        let mut values = Vec::new();
+        let id = |name: &str| registry.id_of(name).unwrap();
+        for (match_id, (match_part, limit)) in (1..).zip(rows) {
+            for (name, value) in match_part.iter() {
+                let value = TypedValue::Str(value.to_string());
+                values.push(ConfigValue { match_id, attr_id: id(name), role: AttrRole::Match, value });
+            }
+            let value = TypedValue::Int(*limit);
+            values.push(ConfigValue { match_id, attr_id: id("limit"), role: AttrRole::Param, value });
+        }
+        Resolver::new(&matrix_json_to_tall(MATRIX, 1, &registry).unwrap(), &values, &registry).unwrap()
+    }
+
+    /// `match_attrs` as (name, data_type) match attributes, then the `limit` param.
+    fn registry_with(match_attrs: &[(&str, &str)]) -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        for (name, data_type) in match_attrs {
+            registry.register(name, AttrRole::Match, data_type).unwrap();
+        }
+        registry.register("limit", AttrRole::Param, "int").unwrap();
+        registry
+    }
+
+    /// A resolver over `matrix` and envelope `rows`, each given as `(match, limit)`.
+    fn resolver_with(registry: &AttrRegistry, matrix: &str, rows: &[(Value, i64)]) -> Result<Resolver> {
+        let rows: Vec<Value> = rows
+            .iter()
+            .map(|(match_part, limit)| {
+                json!({ "match": match_part, "params": [{ "key": "limit", "type": "int", "value": limit }] })
+            })
+            .collect();
+        let envelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": rows,
+        }))
+        .unwrap();
+        let values = validate_envelope(&envelope, registry)?.values;
+        Resolver::new(&matrix_json_to_tall(matrix, 1, registry)?, &values, registry)
+    }
+
+    fn str_context(pairs: &[(&str, &str)]) -> HashMap<String, TypedValue> {
+        pairs.iter().map(|(name, value)| (name.to_string(), TypedValue::Str(value.to_string()))).collect()
+    }
+
+    /// (match_id, rank) of the winner.
+    fn winner(resolver: &Resolver, context: &HashMap<String, TypedValue>) -> Option<(i32, i32)> {
+        resolver.resolve(context).map(|r| (r.match_id, r.rank))
+    }
+
+    fn rows() -> Vec<(&'static [(&'static str, &'static str)], i64)> {
+        vec![
+            (&[("region", "eu"), ("channel", "web")], 1),
+            (&[("region", "eu")], 2),
+            (&[("region", "ALL"), ("channel", "web")], 3),
+        ]
+    }
+
+    #[test]
+    fn the_first_rank_with_a_matching_row_wins() {
+        let resolver = resolver(&rows());
+        assert_eq!(winner(&resolver, &str_context(&[("region", "eu"), ("channel", "web")])), Some((1, 1)));
+        assert_eq!(winner(&resolver, &str_context(&[("region", "eu"), ("channel", "app")])), Some((2, 2)));
+        assert_eq!(winner(&resolver, &str_context(&[("region", "us"), ("channel", "web")])), Some((3, 3)));
+    }
+
+    #[test]
+    fn absent_and_all_leave_an_attribute_unconstrained() {
+        let resolver = resolver(&rows());
+        // row 2 has no channel, so a context without one still finds it
+        assert_eq!(winner(&resolver, &str_context(&[("region", "eu")])), Some((2, 2)));
+        // row 3 leaves region as "ALL"
+        assert_eq!(winner(&resolver, &str_context(&[("channel", "web")])), Some((3, 3)));
+        let params = resolver.resolve(&str_context(&[("channel", "web")])).unwrap().params;
+        assert_eq!(params[0].value, TypedValue::Int(3));
+    }
+
+    #[test]
+    fn no_matching_row_is_none() {
+        let resolver = resolver(&rows());
+        assert_eq!(winner(&resolver, &str_context(&[("region", "us"), ("channel", "app")])), None);
+        assert_eq!(winner(&resolver, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_lowest_match_id() {
+        let resolver = resolver(&[(&[("region", "eu")], 1), (&[("region", "eu")], 2)]);
+        assert_eq!(winner(&resolver, &str_context(&[("region", "eu")])), Some((1, 2)));
+    }
+
+    #[test]
+    fn bad_rules_and_rows_are_rejected() {
+        let registry = region_channel_registry();
+        let rule = ConfigPrecedenceRule { config_version_id: 1, rank: 0, attr_id: 1, match_type: MatchType::Exact };
+        let err = Resolver::new(&[rule], &[], &registry).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidRank { rank: 0, .. }), "{err}");
+        let rules = matrix_json_to_tall(MATRIX, 1, &registry).unwrap();
+        let value = ConfigValue { match_id: 1, attr_id: 1, role: AttrRole::Match, value: TypedValue::Int(1) };
+        let err = Resolver::new(&rules, &[value.clone(), value], &registry).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateMatchAttr { match_id: 1, attr_id: 1 }), "{err}");
+    }
+
+    fn names(attrs: &[&str]) -> Vec<AttrRef> {
+        attrs.iter().map(|name| AttrRef::Name(name.to_string())).collect()
+    }
+
+    #[test]
+    fn explain_records_every_row_up_to_the_winning_rank() {
+        let resolver = resolver(&rows());
+        let explanation = resolver.resolve_explain(&str_context(&[("region", "eu"), ("channel", "app")]));
+        assert_eq!(explanation.winner.as_ref().map(|w| (w.match_id, w.rank)), Some((2, 2)));
+        assert_eq!(explanation.ranks.iter().map(|r| r.rank).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(explanation.reason, "rank 2 is the first rank with a matching row (match_id 2)");
+
+        let first = &explanation.ranks[0];
+        assert_eq!(first.exact_attrs, names(&["region", "channel"]));
+        assert!(first.missing_from_context.is_empty());
+        assert!(first.rows.iter().all(|row| !row.is_match));
+        let row1 = &first.rows[0];
+        assert_eq!((row1.matched.clone(), row1.mismatched.clone()), (names(&["region"]), names(&["channel"])));
+        assert_eq!(first.rows[1].unconstrained, names(&["channel"]));
+
+        let second = &explanation.ranks[1];
+        assert_eq!(second.exact_attrs, names(&["region"]));
+        assert_eq!(second.rows[0].not_wildcard, names(&["channel"]));
+        assert_eq!(second.rows.iter().map(|row| row.is_match).collect::<Vec<_>>(), vec![false, true, false]);
+    }
+
+    #[test]
+    fn explain_without_a_winner_lists_every_rank() {
+        let resolver = resolver(&rows());
+        let explanation = resolver.resolve_explain(&str_context(&[("channel", "app")]));
+        assert!(explanation.winner.is_none());
+        assert_eq!(explanation.ranks.len(), 3);
+        assert_eq!(explanation.ranks[0].missing_from_context, names(&["region"]));
+        assert_eq!(explanation.reason, "no row matched at any of the 3 ranks");
+    }
+
+    #[test]
+    fn explain_agrees_with_resolve() {
+        let resolver = resolver(&rows());
+        for context in [
+            str_context(&[("region", "eu"), ("channel", "web")]),
+            str_context(&[("region", "us"), ("channel", "web")]),
+            str_context(&[("region", "eu")]),
+            HashMap::new(),
+        ] {
+            let explained = resolver.resolve_explain(&context).winner.map(|w| (w.match_id, w.rank));
+            assert_eq!(explained, winner(&resolver, &context));
+        }
+    }
+
+    #[test]
+    fn a_null_context_value_is_a_missing_one() {
+        let resolver = resolver(&rows());
+        let mut context = str_context(&[("region", "eu")]);
+        context.insert("channel".to_string(), TypedValue::Null);
+        assert_eq!(winner(&resolver, &context), Some((2, 2)));
+        assert_eq!(winner(&resolver, &context), winner(&resolver, &str_context(&[("region", "eu")])));
+        assert_eq!(resolver.compile().resolve(&context).map(|r| r.match_id), Some(2));
+    }
+
+    fn batch_contexts() -> Vec<Context> {
+        ["web", "app", "pos"]
+            .iter()
+            .flat_map(|channel| ["eu", "us"].map(|region| str_context(&[("region", region), ("channel", channel)])))
+            .collect()
+    }
+
+    fn match_ids(results: Vec<Option<ResolvedConfig>>) -> Vec<Option<i32>> {
+        results.into_iter().map(|r| r.map(|r| r.match_id)).collect()
+    }
+
+    #[test]
+    fn batches_resolve_each_context_in_order() {
+        let resolver = resolver(&rows());
+        let contexts = batch_contexts();
+        let expected = vec![Some(1), Some(3), Some(2), None, Some(2), None];
+        assert_eq!(match_ids(resolver.resolve_batch(&contexts)), expected);
+        assert_eq!(match_ids(resolver.compile().resolve_batch(&contexts)), expected);
+        assert!(resolver.resolve_batch(&[]).is_empty());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_batches_keep_input_order() {
+        let resolver = resolver(&rows());
+        let contexts: Vec<Context> = batch_contexts().into_iter().cycle().take(600).collect();
+        assert_eq!(match_ids(resolver.resolve_batch_par(&contexts)), match_ids(resolver.resolve_batch(&contexts)));
+        let compiled = resolver.compile();
+        assert_eq!(match_ids(compiled.resolve_batch_par(&contexts)), match_ids(compiled.resolve_batch(&contexts)));
+    }
+
+    #[test]
+    fn compiled_configs_resolve_as_the_resolver_does() {
+        let resolver = resolver(&[
+            (&[("region", "eu"), ("channel", "web")], 1),
+            (&[("region", "eu")], 2),
+            (&[("region", "ALL"), ("channel", "web")], 3),
+            (&[("region", "us"), ("channel", "ALL")], 4),
+            (&[("region", "eu")], 5),
+            (&[("channel", "app")], 6),
+        ]);
+        let compiled = resolver.compile();
+        let mut matched = 0;
+        for region in [None, Some("eu"), Some("us"), Some("apac")] {
+            for channel in [None, Some("web"), Some("app"), Some("pos")] {
+                let mut context = Context::new();
+                context.extend(region.map(|r| ("region".to_string(), TypedValue::Str(r.to_string()))));
+                context.extend(channel.map(|c| ("channel".to_string(), TypedValue::Str(c.to_string()))));
+                let expected = winner(&resolver, &context);
+                assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), expected, "{:?}", context);
+                matched += usize::from(expected.is_some());
+            }
+        }
+        assert!(matched > 8, "only {} contexts matched", matched);
+    }
+
+    #[test]
+    fn dt_rows_match_contexts_at_the_same_instant_in_any_form() {
+        let registry = registry_with(&[("starts", "dt")]);
+        let matrix = r#"[{ "rank": 1, "starts": 1 }]"#;
+        let rows = [(json!({ "starts": "2025-08-22T00:00:00Z" }), 1)];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let naive = NaiveDateTime::parse_from_str("2025-08-22T00:00:00Z", DT_FORMAT).unwrap();
+        let paris = DateTime::parse_from_rfc3339("2025-08-22T02:00:00+02:00").unwrap();
+        let later = DateTime::parse_from_rfc3339("2025-08-22T02:00:00Z").unwrap();
+        let cases = [(TypedValue::Dt(naive), true), (TypedValue::DtTz(paris), true), (TypedValue::DtTz(later), false)];
+        for (value, matched) in cases {
+            let context: Context = [("starts".to_string(), value)].into();
+            assert_eq!(winner(&resolver, &context).is_some(), matched, "{:?}", context);
+            let compiled = resolver.compile().resolve(&context).map(|r| (r.match_id, r.rank));
+            assert_eq!(compiled, winner(&resolver, &context));
+        }
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_rows_match_contexts_in_any_case() {
+        let registry = registry_with(&[("tenant_id", "uuid")]);
+        let matrix = r#"[{ "rank": 1, "tenant_id": 1 }]"#;
+        let rows = [(json!({ "tenant_id": "67E55044-10B1-426F-9247-BB680E5FE0C8" }), 1)];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let tenant: uuid::Uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap();
+        let context: Context = [("tenant_id".to_string(), TypedValue::Uuid(tenant))].into_iter().collect();
+        assert_eq!(winner(&resolver, &context), Some((1, 1)));
+        assert_eq!(resolver.compile().resolve(&context).map(|r| r.match_id), Some(1));
+    }
+
+    #[test]
+    fn resolved_rows_carry_param_defaults() {
+        let mut registry = registry_with(&[("region", "str"), ("channel", "str")]);
+        registry.register("tier", AttrRole::Param, "str").unwrap();
+        registry.set_default("tier", Some(TypedValue::Str("basic".into()))).unwrap();
+        let rows = [(json!({ "region": "eu", "channel": "web" }), 1), (json!({ "region": "eu" }), 2)];
+        let resolver = resolver_with(&registry, MATRIX, &rows).unwrap();
+        let resolved = resolver.resolve(&str_context(&[("region", "eu")])).unwrap();
+        let params: Vec<(i32, &TypedValue)> = resolved.params.iter().map(|v| (v.attr_id, &v.value)).collect();
+        assert_eq!(params, vec![(3, &TypedValue::Int(2)), (4, &TypedValue::Str("basic".into()))]);
+    }
+
+    #[test]
+    fn range_rows_match_within_their_bounds() {
+        let registry = registry_with(&[("total", "dec"), ("starts", "dt")]);
+        let matrix = r#"[{ "rank": 1, "total": 1, "starts": 0 }, { "rank": 2, "total": 0, "starts": 1 }]"#;
+        let rows = [
+            (json!({ "total": { "lt": 100 } }), 1),
+            (json!({ "total": { "gte": 100, "lt": 500 } }), 2),
+            (json!({ "total": { "gte": 500 } }), 3),
+            (json!({ "starts": { "gte": "2025-01-01T00:00:00Z", "lt": "2026-01-01T00:00:00+01:00" } }), 4),
+        ];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let compiled = resolver.compile();
+        // dec context values as the attribute parses them, f64 or (with rust_decimal) exact
+        let dec = |raw: &str| parse_str_value(registry.get("total").unwrap(), raw).unwrap();
+        let cases = [
+            (dec("-1000000000"), Some(1)),
+            (dec("99.99"), Some(1)),
+            (TypedValue::Int(100), Some(2)),
+            (dec("499.5"), Some(2)),
+            (TypedValue::Int(500), Some(3)),
+            (dec("1000000000000"), Some(3)),
+        ];
+        for (total, expected) in cases {
+            let context: Context = [("total".to_string(), total)].into();
+            assert_eq!(winner(&resolver, &context).map(|(match_id, _)| match_id), expected, "{:?}", context);
+            assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), winner(&resolver, &context));
+        }
+        let starts = |raw: &str| -> Context {
+            [("starts".to_string(), DtParseOptions::default().parse(raw).unwrap())].into()
+        };
+        assert_eq!(winner(&resolver, &starts("2025-12-31T23:30:00Z")), None);
+        assert_eq!(winner(&resolver, &starts("2025-12-31T22:30:00Z")), Some((4, 2)));
+        assert_eq!(winner(&resolver, &starts("2025-01-01T01:00:00+01:00")), Some((4, 2)));
+        let compiled_winner = compiled.resolve(&starts("2025-06-01T00:00:00Z")).map(|r| (r.match_id, r.rank));
+        assert_eq!(compiled_winner, Some((4, 2)));
+    }
+
+    #[test]
+    fn one_of_rows_match_any_of_their_values() {
+        let registry = registry_with(&[("country", "str"), ("tier", "int")]);
+        let matrix = r#"[{ "rank": 1, "country": 1, "tier": 1 }, { "rank": 2, "country": 1, "tier": 0 }]"#;
+        let rows = [
+            (json!({ "country": ["de", "at", "ch"], "tier": [1, 2] }), 1),
+            (json!({ "country": ["de", "fr"] }), 2),
+            (json!({ "country": "de" }), 3),
+        ];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let compiled = resolver.compile();
+        let context = |country: &str, tier: Option<i64>| -> Context {
+            let mut context = str_context(&[("country", country)]);
+            context.extend(tier.map(|tier| ("tier".to_string(), TypedValue::Int(tier))));
+            context
+        };
+        let cases = [
+            (context("ch", Some(2)), Some((1, 1))),
+            (context("ch", Some(3)), None),
+            (context("fr", Some(1)), Some((2, 2))),
+            // a list and a single value tie at rank 2; the lowest match_id wins
+            (context("de", None), Some((2, 2))),
+            (context("at", None), None),
+            (context("it", Some(1)), None),
+        ];
+        for (context, expected) in cases {
+            assert_eq!(winner(&resolver, &context), expected, "{:?}", context);
+            assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), expected);
+        }
+        assert!(resolver_with(&registry, matrix, &[(json!({ "country": [] }), 1)]).is_err());
+    }
+
+    #[test]
+    fn exact_values_beat_patterns_and_longer_patterns_beat_shorter() {
+        let mut registry = registry_with(&[("sku", "str")]);
+        registry.set_patterns("sku", true).unwrap();
+        let matrix = r#"[{ "rank": 1, "sku": 1 }]"#;
+        let rows = [
+            (json!({ "sku": "*" }), 1),
+            (json!({ "sku": "EU-*" }), 2),
+            (json!({ "sku": "*-beta" }), 3),
+            (json!({ "sku": "EU-*-beta" }), 4),
+            (json!({ "sku": "EU-DE-beta" }), 5),
+        ];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let compiled = resolver.compile();
+        let cases = [("EU-DE-beta", 5), ("EU-FR-beta", 4), ("EU-FR", 2), ("US-beta", 3), ("US", 1)];
+        for (sku, expected) in cases {
+            let context = str_context(&[("sku", sku)]);
+            assert_eq!(winner(&resolver, &context), Some((expected, 1)), "{}", sku);
+            assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), Some((expected, 1)), "{}", sku);
+        }
+        // "EU-" and "-be" fix as many characters; the lowest match_id settles it
+        let rows = [(json!({ "sku": "*-be" }), 1), (json!({ "sku": "EU-*" }), 2)];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        assert_eq!(winner(&resolver, &str_context(&[("sku", "EU-be")])), Some((1, 1)));
+    }
+
+    #[test]
+    fn patterns_are_plain_text_without_attr_patterns() {
+        let registry = registry_with(&[("sku", "str")]);
+        let matrix = r#"[{ "rank": 1, "sku": 1 }]"#;
+        let resolver = resolver_with(&registry, matrix, &[(json!({ "sku": "EU-*" }), 1)]).unwrap();
+        assert_eq!(winner(&resolver, &str_context(&[("sku", "EU-DE")])), None);
+        assert_eq!(winner(&resolver, &str_context(&[("sku", "EU-*")])), Some((1, 1)));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_rows_lose_ties_to_patterns_and_exact_values() {
+        let mut registry = registry_with(&[("agent", "str")]);
+        registry.set_patterns("agent", true).unwrap();
+        let matrix = r#"[{ "rank": 1, "agent": 1 }]"#;
+        let rows = [
+            (json!({ "agent": { "pattern": "^Mozilla/[0-9]+" } }), 1),
+            (json!({ "agent": { "pattern": "Firefox" } }), 2),
+            (json!({ "agent": "Mozilla/5*" }), 3),
+            (json!({ "agent": "Mozilla/5.0 Firefox" }), 4),
+        ];
+        let resolver = resolver_with(&registry, matrix, &rows).unwrap();
+        let compiled = resolver.compile();
+        let cases = [
+            ("Mozilla/5.0 Firefox", Some(4)),
+            ("Mozilla/5.0 Chrome", Some(3)),
+            ("Mozilla/4.0", Some(1)),
+            ("Opera Firefox", Some(2)),
+            ("curl/8", None),
+        ];
+        for (agent, expected) in cases {
+            let context = str_context(&[("agent", agent)]);
+            assert_eq!(winner(&resolver, &context).map(|(match_id, _)| match_id), expected, "{}", agent);
+            assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), winner(&resolver, &context));
+        }
+        assert!(resolver_with(&registry, matrix, &[(json!({ "agent": { "pattern": "(" } }), 1)]).is_err());
+    }
+
+    #[test]
+    fn the_nearest_ancestor_wins_within_a_rank() {
+        let mut registry = registry_with(&[("region", "str"), ("channel", "str")]);
+        let parents = [("berlin", "de"), ("munich", "de"), ("de", "eu"), ("paris", "fr"), ("fr", "eu")];
+        registry.set_parents("region", parents.map(|(c, p)| (c.to_string(), p.to_string()))).unwrap();
+        let rows = [
+            (json!({ "region": "eu", "channel": "web" }), 1),
+            (json!({ "region": "eu" }), 2),
+            (json!({ "region": "de" }), 3),
+            (json!({ "region": "munich" }), 4),
+        ];
+        let resolver = resolver_with(&registry, MATRIX, &rows).unwrap();
+        let compiled = resolver.compile();
+        let cases = [
+            // an ancestor match at rank 1 still beats a direct one at rank 2
+            (str_context(&[("region", "munich"), ("channel", "web")]), Some((1, 1))),
+            (str_context(&[("region", "munich")]), Some((4, 2))),
+            (str_context(&[("region", "berlin")]), Some((3, 2))),
+            (str_context(&[("region", "de")]), Some((3, 2))),
+            (str_context(&[("region", "paris")]), Some((2, 2))),
+            (str_context(&[("region", "tokyo")]), None),
+        ];
+        for (context, expected) in cases {
+            assert_eq!(winner(&resolver, &context), expected, "{:?}", context);
+            assert_eq!(compiled.resolve(&context).map(|r| (r.match_id, r.rank)), winner(&resolver, &context));
+        }
+    }
+
+    type Outcome = std::result::Result<Option<(i32, i32)>, String>;
+
+    fn outcome(resolved: Result<Option<ResolvedConfig>>) -> Outcome {
+        resolved.map(|r| r.map(|r| (r.match_id, r.rank))).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn each_tie_break_policy_settles_ties_its_own_way() {
+        let rows = [
+            (json!({ "region": ["eu", "us"] }), 1),
+            (json!({ "region": "eu" }), 5),
+            (json!({ "region": ["eu"] }), 5),
+            (json!({ "region": "us" }), 9),
+        ];
+        let mut resolver = resolver_with(&region_channel_registry(), MATRIX, &rows).unwrap();
+        let at = |hour: u32| Utc.with_ymd_and_hms(2025, 8, 22, hour, 0, 0).unwrap();
+        resolver.set_row_created([(1, at(10)), (3, at(12)), (2, at(8))]);
+        let eu = str_context(&[("region", "eu")]);
+        let cases = [
+            (TieBreakPolicy::FirstByMatchId, Ok(Some((1, 2)))),
+            (TieBreakPolicy::LatestCreated, Ok(Some((3, 2)))),
+            // rows 2 and 3 tie on the weight too; the lowest match_id settles it
+            (TieBreakPolicy::HighestWeight { param: "limit".to_string() }, Ok(Some((2, 2)))),
+            (TieBreakPolicy::Error, Err(PrecedenceConfigError::AmbiguousMatch { rank: 2, match_ids: vec![1, 2, 3] })),
+        ];
+        for (policy, expected) in cases {
+            resolver.set_tie_break(policy.clone()).unwrap();
+            let expected = expected.map_err(|e| e.to_string());
+            assert_eq!(outcome(resolver.try_resolve(&eu)), expected, "{}", policy);
+            assert_eq!(outcome(resolver.compile().try_resolve(&eu)), expected, "{}", policy);
+            assert_eq!(resolver.resolve_explain(&eu).tie_break, policy);
+        }
+        // rows without a creation time lose to those with one
+        resolver.set_row_created([(4, at(1))]);
+        resolver.set_tie_break(TieBreakPolicy::LatestCreated).unwrap();
+        assert_eq!(winner(&resolver, &str_context(&[("region", "us")])), Some((4, 2)));
+        resolver.set_row_created([]);
+        assert_eq!(winner(&resolver, &eu), Some((1, 2)));
+    }
+
+    fn weighted(weights: &[(&str, f64)]) -> ResolutionStrategy {
+        ResolutionStrategy::WeightedScore { weights: weights.iter().map(|(name, w)| (name.to_string(), *w)).collect() }
+    }
+
+    #[test]
+    fn weighted_scores_ignore_ranks() {
+        let rows = [
+            (json!({ "channel": "web" }), 1),
+            (json!({ "region": "eu" }), 2),
+            (json!({ "region": "eu", "channel": "web" }), 3),
+            (json!({ "region": "us" }), 4),
+        ];
+        let mut resolver = resolver_with(&region_channel_registry(), MATRIX, &rows).unwrap();
+        resolver.set_strategy(weighted(&[("region", 3.0)])).unwrap();
+        let cases = [
+            (str_context(&[("region", "eu"), ("channel", "web")]), Some(3)),
+            (str_context(&[("region", "eu"), ("channel", "app")]), Some(2)),
+            (str_context(&[("region", "us"), ("channel", "web")]), Some(4)),
+            (str_context(&[("channel", "web")]), Some(1)),
+            (str_context(&[("region", "jp")]), None),
+        ];
+        for (context, expected) in &cases {
+            let expected = expected.map(|match_id| (match_id, SCORED_RANK));
+            assert_eq!(winner(&resolver, context), expected, "{:?}", context);
+            assert_eq!(outcome(resolver.compile().try_resolve(context)), Ok(expected));
+        }
+        // channel weighs 1 when left out of the weights
+        resolver.set_strategy(weighted(&[("region", 0.5)])).unwrap();
+        assert_eq!(winner(&resolver, &cases[2].0), Some((1, SCORED_RANK)));
+        let explanation = resolver.resolve_explain(&cases[2].0);
+        assert_eq!(explanation.ranks.len(), 1);
+        assert_eq!(explanation.ranks[0].rank, SCORED_RANK);
+    }
+
+    #[test]
+    fn bad_weights_leave_the_strategy_unchanged() {
+        let mut resolver = resolver(&rows());
+        let unknown = resolver.set_strategy(weighted(&[("planet", 1.0)]));
+        assert!(matches!(unknown, Err(PrecedenceConfigError::UnknownAttrKey { .. })));
+        let nan = resolver.set_strategy(weighted(&[("region", f64::NAN)]));
+        assert!(matches!(nan, Err(PrecedenceConfigError::InvalidAttrWeight { .. })));
+        assert_eq!(resolver.strategy(), &ResolutionStrategy::RankPrecedence);
+        assert_eq!(winner(&resolver, &str_context(&[("region", "eu"), ("channel", "web")])), Some((1, 1)));
+    }
+
+    #[test]
+    fn the_default_row_answers_when_nothing_else_matches() {
+        let mut rows = rows();
+        rows.push((&[("channel", "ALL")], 4));
+        let mut resolver = resolver(&rows);
+        assert_eq!(resolver.default_match_id(), Some(4));
+        let nowhere = str_context(&[("region", "us"), ("channel", "app")]);
+        for require_match in [false, true] {
+            resolver.set_require_match(require_match);
+            assert_eq!(winner(&resolver, &nowhere), Some((4, DEFAULT_RANK)));
+            assert_eq!(outcome(resolver.compile().try_resolve(&nowhere)), Ok(Some((4, DEFAULT_RANK))));
+            assert_eq!(winner(&resolver, &str_context(&[("region", "eu")])), Some((2, 2)));
+        }
+        let params = resolver.resolve(&Context::new()).unwrap().params;
+        assert_eq!(params[0].value, TypedValue::Int(4));
+    }
+
+    #[test]
+    fn a_version_has_at_most_one_default_row() {
+        let registry = registry_with(&[("region", "str"), ("channel", "str")]);
+        let rows = [(json!({}), 1), (json!({ "region": "eu" }), 2), (json!({ "channel": null }), 3)];
+        let err = resolver_with(&registry, MATRIX, &rows).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateDefaultRow { match_id: 1, other_match_id: 3 }));
+    }
+
+    #[test]
+    fn no_default_row_and_require_match_is_an_error() {
+        let mut resolver = resolver(&rows());
+        assert_eq!(resolver.default_match_id(), None);
+        resolver.set_require_match(true);
+        let nowhere = str_context(&[("region", "us"), ("channel", "app")]);
+        let expected = Err(PrecedenceConfigError::NoMatchingRow.to_string());
+        assert_eq!(outcome(resolver.try_resolve(&nowhere)), expected);
+        assert_eq!(outcome(resolver.compile().try_resolve(&nowhere)), expected);
+        resolver.set_require_match(false);
+        assert_eq!(outcome(resolver.try_resolve(&nowhere)), Ok(None));
+    }
+
+    /// Spans and events recorded by a subscriber installed for one test.
+    #[test]
+    fn attr_names_come_from_the_registry() {
+        let resolver = resolver(&[(&[("region", "eu")], 1)]);
+        assert_eq!(resolver.attr_name(1), Some("region"));
+        assert_eq!(resolver.attr_name(3), Some("limit"));
+        assert_eq!(resolver.attr_name(9), None);
+    }
+
+    #[cfg(feature = "tracing")]
+    mod traced {
+        use super::*;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Level, Metadata};
+
+        /// A span, or an event named by its message, with its fields as strings.
+        #[derive(Debug, Clone)]
+        struct Captured {
+            name: String,
+            level: Level,
+            /// for an event, the span it was emitted in
+            span: Option<String>,
+            fields: BTreeMap<String, String>,
+        }
+
+        #[derive(Default)]
+        struct Fields(BTreeMap<String, String>);
+
+        impl Visit for Fields {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name().to_string(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.insert(field.name().to_string(), format!("{value:?}"));
+            }
+        }
+
+        #[derive(Default, Clone)]
+        struct Capture {
+            /// span ids are indexes into this, plus one
+            spans: Arc<Mutex<Vec<Captured>>>,
+            events: Arc<Mutex<Vec<Captured>>>,
+            entered: Arc<Mutex<Vec<usize>>>,
+        }
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut fields = Fields::default();
+                span.record(&mut fields);
+                let metadata = span.metadata();
+                let mut spans = self.spans.lock().unwrap();
+                spans.push(Captured {
+                    name: metadata.name().to_string(),
+                    level: *metadata.level(),
+                    span: None,
+                    fields: fields.0,
+                });
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                let mut fields = Fields::default();
+                values.record(&mut fields);
+                self.spans.lock().unwrap()[span.into_u64() as usize - 1].fields.extend(fields.0);
+            }
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                let span = self.entered.lock().unwrap().last().map(|i| self.spans.lock().unwrap()[*i].name.clone());
+                self.events.lock().unwrap().push(Captured {
+                    name: fields.0.remove("message").unwrap_or_default(),
+                    level: *event.metadata().level(),
+                    span,
+                    fields: fields.0,
+                });
+            }
+
+            fn enter(&self, span: &Id) {
+                self.entered.lock().unwrap().push(span.into_u64() as usize - 1);
+            }
+
+            fn exit(&self, _span: &Id) {
+                self.entered.lock().unwrap().pop();
+            }
+        }
+
+        impl Capture {
+            /// Runs `f` with this subscriber as the thread's default.
+            fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+                tracing::subscriber::with_default(self.clone(), f)
+            }
+
+            fn spans(&self, name: &str) -> Vec<Captured> {
+                self.spans.lock().unwrap().iter().filter(|s| s.name == name).cloned().collect()
+            }
+
+            fn events(&self, name: &str) -> Vec<Captured> {
+                self.events.lock().unwrap().iter().filter(|e| e.name == name).cloned().collect()
+            }
+        }
+
+        fn field<'a>(captured: &'a Captured, name: &str) -> &'a str {
+            captured.fields.get(name).map(String::as_str).unwrap_or_else(|| panic!("no {name} in {captured:?}"))
+        }
+
+        #[test]
+        fn envelope_validation_is_a_span_recording_its_error() {
+            let capture = Capture::default();
+            let registry = region_channel_registry();
+            let rows = [(json!({ "region": "eu" }), 1), (json!({ "channel": "web" }), 2)];
+            capture.run(|| resolver_with(&registry, MATRIX, &rows)).unwrap();
+            let err = capture.run(|| resolver_with(&registry, MATRIX, &[(json!({ "planet": "mars" }), 1)]));
+            let err = err.unwrap_err().to_string();
+
+            let spans = capture.spans("validate_envelope");
+            assert_eq!(spans.len(), 2);
+            for (span, rows) in spans.iter().zip(["2", "1"]) {
+                assert_eq!(span.level, Level::DEBUG);
+                assert_eq!((field(span, "name"), field(span, "version"), field(span, "rows")), ("c", "1", rows));
+            }
+            // `err` logs the failure inside the span; the valid envelope logged nothing
+            let errors: Vec<_> =
+                capture.events.lock().unwrap().iter().filter(|e| e.level == Level::ERROR).cloned().collect();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].span.as_deref(), Some("validate_envelope"));
+            assert_eq!(field(&errors[0], "error"), err);
+            assert!(capture.spans("matrix_json_to_tall").iter().all(|s| field(s, "config_version_id") == "1"));
+        }
+
+        #[test]
+        fn every_lookup_is_an_event_with_its_outcome() {
+            let rows = [(json!({ "region": "eu" }), 1), (json!({ "channel": "web" }), 2)];
+            let mut resolver = resolver_with(&region_channel_registry(), MATRIX, &rows).unwrap();
+            let capture = Capture::default();
+            let compiled = capture.run(|| resolver.compile());
+            let eu = str_context(&[("region", "eu")]);
+            let nowhere = str_context(&[("region", "us")]);
+            capture.run(|| {
+                resolver.try_resolve(&eu).unwrap();
+                compiled.try_resolve(&eu).unwrap();
+                compiled.try_resolve(&nowhere).unwrap();
+            });
+            resolver.set_require_match(true);
+            let err = capture.run(|| resolver.try_resolve(&nowhere)).unwrap_err().to_string();
+
+            let compile = &capture.spans("compile")[0];
+            let counts = ["config_version_id", "ranks", "rows"].map(|name| field(compile, name));
+            assert_eq!(counts, ["1", "3", "2"]);
+            let index = &capture.events("compiled config")[0];
+            assert_eq!((index.span.as_deref(), index.level), (Some("compile"), Level::DEBUG));
+
+            let resolved = capture.events("resolved");
+            assert_eq!(resolved.iter().map(|e| field(e, "resolver")).collect::<Vec<_>>(), ["resolver", "compiled"]);
+            for event in &resolved {
+                assert_eq!(event.level, Level::DEBUG);
+                let winner = ["config_version_id", "rank", "match_id"].map(|name| field(event, name));
+                assert_eq!(winner, ["1", "2", "1"]);
+                assert!(event.fields.contains_key("elapsed_us"));
+            }
+            // the hash is of the normalized context, the same for both
+            assert_eq!(field(&resolved[0], "context_hash"), field(&resolved[1], "context_hash"));
+            assert_eq!(capture.events("no matching row").len(), 1);
+
+            let failed = &capture.events("resolution failed")[0];
+            assert_eq!((failed.level, field(failed, "error")), (Level::WARN, err.as_str()));
+        }
+    }
+}
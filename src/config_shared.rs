@@ -0,0 +1,244 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_envelope::validate_envelope;
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use crate::config_lint::validate_row_conflicts;
+use crate::config_precidence_rules::{convert_matrix_rows, ConversionOptions, MatrixRow};
+use crate::config_resolver::{CompiledConfig, Context, ResolvedConfig};
+use crate::config_types::ConfigEnvelope;
+use crate::config_value::AttrMeta;
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Everything a resolver needs in one file: the CONFIG_ATTR catalog, the
+/// precedence matrix and the config envelope.
+///
+/// ```json
+/// { "attrs": [..], "precedence_rank": [{ "rank": 1, "customer": 1 }, ..], "envelope": { "config": .., "rows": [..] } }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub attrs: Vec<AttrMeta>,
+    pub precedence_rank: Vec<MatrixRow>,
+    pub envelope: ConfigEnvelope,
+}
+
+impl ConfigBundle {
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| PrecedenceConfigError::InvalidFormat { format: "JSON", reason: e.to_string() })
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml)
+            .map_err(|e| PrecedenceConfigError::InvalidFormat { format: "YAML", reason: e.to_string() })
+    }
+
+    /// Reads a bundle, as YAML for `.yaml` / `.yml` files (`yaml` feature) and as JSON otherwise.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            PrecedenceConfigError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Self::from_yaml_str(&text),
+            _ => Self::from_json_str(&text),
+        }
+    }
+
+    /// Validates the whole bundle and compiles it: matrix attributes must all be
+    /// in the catalog, the envelope must pass [`validate_envelope`] and no two rows
+    /// may share a match tuple.
+    ///
+    /// A bundle has no CONFIG_VERSION row, so the compiled config's
+    /// `config_version_id` is the envelope's version number.
+    pub fn compile(self) -> Result<CompiledConfig> {
+        let registry = AttrRegistry::from_metas(self.attrs)?;
+        let options = ConversionOptions { strict_unknown_attrs: true };
+        let mut report = ValidationReport::fail_fast();
+        let version = self.envelope.config.version;
+        let rules = convert_matrix_rows(self.precedence_rank, version, &registry, &options, &mut report)?;
+        let validated = validate_envelope(&self.envelope, &registry)?;
+        validate_row_conflicts(&validated.values)?;
+        CompiledConfig::new(&rules, &validated.values, &registry)
+    }
+}
+
+/// A [`CompiledConfig`] that can be swapped while other threads resolve against it.
+///
+/// Readers get the config current at the moment they ask; a reload that fails
+/// validation leaves the current config in place. Share it across threads as an
+/// `Arc<SharedConfig>`.
+#[derive(Debug)]
+pub struct SharedConfig {
+    current: ArcSwap<CompiledConfig>,
+    /// bumped after every swap
+    generation: AtomicU64,
+}
+
+impl SharedConfig {
+    pub fn new(config: CompiledConfig) -> Self {
+        Self { current: ArcSwap::from_pointee(config), generation: AtomicU64::new(0) }
+    }
+
+    /// Compiles `path` (see [`ConfigBundle::from_file`]) as the initial config.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(Self::new(ConfigBundle::from_file(path)?.compile()?))
+    }
+
+    /// The current config; holding it keeps that version alive across reloads.
+    pub fn load(&self) -> Arc<CompiledConfig> {
+        self.current.load_full()
+    }
+
+    /// Resolves against the current config.
+    pub fn resolve(&self, context: &Context) -> Option<ResolvedConfig> {
+        self.current.load().resolve(context)
+    }
+
+    /// Replaces the current config unconditionally.
+    pub fn store(&self, config: CompiledConfig) {
+        self.current.store(Arc::new(config));
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// How many times the config has been swapped. It is bumped after the new
+    /// config is in place, so a reader that reads the generation before loading
+    /// never pairs a generation with an older config.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Runs `load` and swaps in its config only if it succeeds.
+    pub fn reload(&self, load: impl FnOnce() -> Result<CompiledConfig>) -> Result<()> {
+        self.store(load()?);
+        Ok(())
+    }
+
+    /// Re-reads and re-validates `path`; on error the current config stays.
+    pub fn reload_file(&self, path: &Path) -> Result<()> {
+        self.reload(|| ConfigBundle::from_file(path)?.compile())
+    }
+}
+
+/// Keeps a [`SharedConfig::watch`] running; dropping it stops the watch.
+#[cfg(feature = "watch")]
+pub struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+#[cfg(feature = "watch")]
+impl SharedConfig {
+    /// Reloads `path` with [`SharedConfig::reload_file`] every time it is written
+    /// or replaced, handing each outcome to `on_reload` (e.g. to log failures).
+    ///
+    /// The file's directory is watched rather than the file itself, so editors
+    /// and deploy tools that replace the file by renaming over it are still seen.
+    pub fn watch(
+        self: &Arc<Self>,
+        path: &Path,
+        on_reload: impl Fn(Result<()>) + Send + 'static,
+    ) -> Result<ConfigWatcher> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let path = path.to_path_buf();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        };
+        let shared = Arc::clone(self);
+        let file_name = path.file_name().map(|name| name.to_os_string());
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let changed = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.iter().any(|p| p.file_name().map(|name| name.to_os_string()) == file_name);
+                if changed {
+                    on_reload(shared.reload_file(&path));
+                }
+            }
+            Err(err) => on_reload(Err(PrecedenceConfigError::Watch(err))),
+        })
+        .map_err(PrecedenceConfigError::Watch)?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(PrecedenceConfigError::Watch)?;
+        Ok(ConfigWatcher { _watcher: watcher })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_value::TypedValue;
+    use crate::test_fixtures::{region_context, region_registry};
+    use serde_json::{json, Value};
+
+    /// A bundle keyed on `region` whose one row for `eu` sets `limit`.
+    fn bundle(limit: Value) -> Value {
+        let registry = region_registry();
+        let attrs: Vec<&AttrMeta> = registry.iter().collect();
+        json!({
+            "attrs": attrs,
+            "precedence_rank": [{ "rank": 1, "region": 1 }],
+            "envelope": {
+                "config": { "name": "c", "version": 1, "version_name": "v1" },
+                "rows": [{
+                    "match": { "region": "eu" },
+                    "params": [{ "key": "limit", "type": "int", "value": limit }],
+                }],
+            },
+        })
+    }
+
+    fn compile(bundle: &Value) -> Result<CompiledConfig> {
+        ConfigBundle::from_json_str(&bundle.to_string())?.compile()
+    }
+
+    fn limit(shared: &SharedConfig) -> Option<TypedValue> {
+        shared.resolve(&region_context("eu")).map(|r| r.params[0].value.clone())
+    }
+
+    #[test]
+    fn a_bundle_compiles_only_when_it_all_validates() {
+        let mut fourth = bundle(json!(5));
+        fourth["envelope"]["config"]["version"] = json!(4);
+        assert_eq!(compile(&fourth).unwrap().config_version_id(), Some(4));
+        let mut unknown_column = bundle(json!(5));
+        unknown_column["precedence_rank"][0]["planet"] = json!(1);
+        assert!(compile(&unknown_column).is_err());
+        assert!(compile(&bundle(json!("five"))).is_err());
+        let mut conflicting = bundle(json!(5));
+        let row = conflicting["envelope"]["rows"][0].clone();
+        conflicting["envelope"]["rows"].as_array_mut().unwrap().push(row);
+        assert!(matches!(compile(&conflicting), Err(PrecedenceConfigError::ConflictingRows { .. })));
+        assert!(matches!(ConfigBundle::from_json_str("{"), Err(PrecedenceConfigError::InvalidFormat { .. })));
+    }
+
+    #[test]
+    fn a_failed_reload_keeps_the_current_config() {
+        let shared = SharedConfig::new(compile(&bundle(json!(5))).unwrap());
+        let before = shared.load();
+        assert!(shared.reload(|| compile(&bundle(json!("five")))).is_err());
+        assert_eq!((shared.generation(), limit(&shared)), (0, Some(TypedValue::Int(5))));
+        shared.reload(|| compile(&bundle(json!(6)))).unwrap();
+        assert_eq!((shared.generation(), limit(&shared)), (1, Some(TypedValue::Int(6))));
+        // a config loaded before the swap stays usable
+        assert_eq!(before.resolve(&region_context("eu")).unwrap().params[0].value, TypedValue::Int(5));
+    }
+
+    #[test]
+    fn reload_file_rereads_the_bundle() {
+        let path = std::env::temp_dir().join(format!("precedence-config-shared-{}.json", std::process::id()));
+        std::fs::write(&path, bundle(json!(5)).to_string()).unwrap();
+        let shared = SharedConfig::from_file(&path).unwrap();
+        std::fs::write(&path, bundle(json!(8)).to_string()).unwrap();
+        shared.reload_file(&path).unwrap();
+        std::fs::write(&path, "not json").unwrap();
+        assert!(shared.reload_file(&path).is_err());
+        assert_eq!(limit(&shared), Some(TypedValue::Int(8)));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(shared.reload_file(&path), Err(PrecedenceConfigError::Io(_))));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{Result, ValidationReport};
+use crate::config_lint::{lint_precedence, LintFinding};
+use crate::config_precidence_rules::{
+    convert_matrix_rows, move_rank, ConfigPrecedenceRule, ConversionOptions, MatrixRow, RenumberMap,
+};
+use crate::config_resolver::{CompiledConfig, Context, ResolvedConfig};
+use crate::config_value::{ConfigValue, TypedValue};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How a batch of sample contexts resolved against one config version, see [`simulate`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    /// One entry per sample, in input order; `None` when no row matched.
+    pub results: Vec<Option<ResolvedConfig>>,
+    /// rank -> samples won at that rank, for every rank in the rules (0 if never exercised).
+    pub rank_hits: BTreeMap<i32, usize>,
+    /// match_id -> samples resolved to that row, for every row (0 if never hit).
+    pub row_hits: BTreeMap<i32, usize>,
+    /// Indexes of the samples no row matched.
+    pub unmatched: Vec<usize>,
+}
+
+impl SimulationReport {
+    /// Ranks no sample was won at, ascending.
+    pub fn unused_ranks(&self) -> Vec<i32> {
+        self.rank_hits.iter().filter(|(_, hits)| **hits == 0).map(|(&rank, _)| rank).collect()
+    }
+
+    /// Rows no sample resolved to, by ascending match_id.
+    pub fn unused_rows(&self) -> Vec<i32> {
+        self.row_hits.iter().filter(|(_, hits)| **hits == 0).map(|(&match_id, _)| match_id).collect()
+    }
+}
+
+/// Resolves every sample against one config version's rules and values and tallies
+/// which row and rank each one landed on.
+///
+/// A row can be dead for the samples without being dead in general; see
+/// [`find_dead_rows`](crate::config_lint::find_dead_rows) for the latter.
+pub fn simulate(
+    rules: &[ConfigPrecedenceRule],
+    values: &[ConfigValue],
+    registry: &AttrRegistry,
+    samples: &[Context],
+) -> Result<SimulationReport> {
+    let compiled = CompiledConfig::new(rules, values, registry)?;
+    let results = compiled.resolve_batch(samples);
+
+    let mut rank_hits: BTreeMap<i32, usize> = rules.iter().map(|r| (r.rank, 0)).collect();
+    let mut row_hits: BTreeMap<i32, usize> = values.iter().map(|v| (v.match_id, 0)).collect();
+    let mut unmatched = Vec::new();
+    for (index, result) in results.iter().enumerate() {
+        match result {
+            Some(resolved) => {
+                *rank_hits.entry(resolved.rank).or_default() += 1;
+                *row_hits.entry(resolved.match_id).or_default() += 1;
+            }
+            None => unmatched.push(index),
+        }
+    }
+
+    Ok(SimulationReport { results, rank_hits, row_hits, unmatched })
+}
+
+/// A sample that resolves differently under two config versions, see [`compare_resolutions`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolutionChange {
+    /// Index of the sample in the input.
+    pub sample: usize,
+    pub old: Option<ResolvedConfig>,
+    pub new: Option<ResolvedConfig>,
+    /// attr_ids of the params that were added, removed or given a different value, ascending.
+    pub changed_params: Vec<i32>,
+}
+
+/// Resolves every sample against `old` and `new` and returns the ones whose winning
+/// row (`match_id`) or resolved param values differ, in sample order.
+///
+/// A sample that keeps its row and params but is now won at another rank is not a
+/// change. Rows are told apart by `match_id` alone, so if the new version renumbers
+/// its rows every sample landing on a renumbered row is reported.
+pub fn compare_resolutions(old: &CompiledConfig, new: &CompiledConfig, samples: &[Context]) -> Vec<ResolutionChange> {
+    old.resolve_batch(samples)
+        .into_iter()
+        .zip(new.resolve_batch(samples))
+        .enumerate()
+        .filter_map(|(sample, (old, new))| {
+            let changed_params = changed_params(old.as_ref(), new.as_ref());
+            let same_row = old.as_ref().map(|r| r.match_id) == new.as_ref().map(|r| r.match_id);
+            (!same_row || !changed_params.is_empty()).then_some(ResolutionChange { sample, old, new, changed_params })
+        })
+        .collect()
+}
+
+/// What [`move_rank`] would do to a matrix, see [`move_rank_dry_run`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RankMovePreview {
+    /// old rank -> new rank of every row
+    pub renumbered: RenumberMap,
+    /// The matrix as the move leaves it.
+    pub matrix: Vec<MatrixRow>,
+    /// [`lint_precedence`] findings on the moved matrix, such as a rank it now shadows.
+    pub findings: Vec<LintFinding>,
+    /// Samples whose winning row or params differ after the move, see [`compare_resolutions`].
+    pub changes: Vec<ResolutionChange>,
+}
+
+/// Runs [`move_rank`] on a copy of `matrix` and resolves every sample against one
+/// config version's `values` under the matrix before and after the move; `matrix`
+/// itself is left as is. Matrix columns missing from `registry` are skipped, as
+/// [`matrix_json_to_tall`](crate::config_precidence_rules::matrix_json_to_tall) does.
+pub fn move_rank_dry_run(
+    matrix: &[MatrixRow],
+    from_rank: i32,
+    to_rank: i32,
+    values: &[ConfigValue],
+    registry: &AttrRegistry,
+    samples: &[Context],
+) -> Result<RankMovePreview> {
+    let mut moved = matrix.to_vec();
+    let renumbered = move_rank(&mut moved, from_rank, to_rank)?;
+
+    let options = ConversionOptions::default();
+    let tall = |matrix: &[MatrixRow]| {
+        convert_matrix_rows(matrix.to_vec(), 0, registry, &options, &mut ValidationReport::fail_fast())
+    };
+    let (old_rules, new_rules) = (tall(matrix)?, tall(&moved)?);
+    let old = CompiledConfig::new(&old_rules, values, registry)?;
+    let new = CompiledConfig::new(&new_rules, values, registry)?;
+
+    Ok(RankMovePreview {
+        renumbered,
+        findings: lint_precedence(&new_rules),
+        changes: compare_resolutions(&old, &new, samples),
+        matrix: moved,
+    })
+}
+
+fn changed_params(old: Option<&ResolvedConfig>, new: Option<&ResolvedConfig>) -> Vec<i32> {
+    fn by_attr(resolved: Option<&ResolvedConfig>) -> BTreeMap<i32, &TypedValue> {
+        resolved.into_iter().flat_map(|r| &r.params).map(|v| (v.attr_id, &v.value)).collect()
+    }
+    let (old, new) = (by_attr(old), by_attr(new));
+
+    let attr_ids: BTreeSet<i32> = old.keys().chain(new.keys()).copied().collect();
+    attr_ids.into_iter().filter(|attr_id| old.get(attr_id) != new.get(attr_id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_envelope::envelope_to_config_values;
+    use crate::config_error::PrecedenceConfigError;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use crate::config_value::TypedValue;
+    use crate::test_fixtures::region_channel_registry;
+    use serde_json::{json, Value};
+
+    /// Rank 1 matches region and channel, rank 2 region alone, rank 3 channel alone.
+    const MATRIX: &str = r#"[
+        { "rank": 1, "region": 1, "channel": 1 },
+        { "rank": 2, "region": 1, "channel": 0 },
+        { "rank": 3, "region": 0, "channel": 1 }
+    ]"#;
+
+    /// Config values of envelope rows given as `(match, limit)`; match_ids count from 1.
+    fn values(registry: &AttrRegistry, rows: &[(Value, i64)]) -> Vec<ConfigValue> {
+        let rows: Vec<Value> = rows
+            .iter()
+            .map(|(match_part, limit)| {
+                json!({ "match": match_part, "params": [{ "key": "limit", "type": "int", "value": limit }] })
+            })
+            .collect();
+        let envelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": rows,
+        }))
+        .unwrap();
+        envelope_to_config_values(&envelope, registry).unwrap()
+    }
+
+    fn rows() -> Vec<(Value, i64)> {
+        vec![
+            (json!({ "region": "eu", "channel": "web" }), 1),
+            (json!({ "region": "eu" }), 2),
+            (json!({ "channel": "web" }), 3),
+            (json!({ "region": "us", "channel": "app" }), 4),
+        ]
+    }
+
+    fn context(region: &str, channel: &str) -> Context {
+        [("region", region), ("channel", channel)]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), TypedValue::Str(value.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn simulate_tallies_ranks_rows_and_misses() {
+        let registry = region_channel_registry();
+        let rules = matrix_json_to_tall(MATRIX, 1, &registry).unwrap();
+        let samples = [context("eu", "web"), context("eu", "app"), context("fr", "web"), context("fr", "pos")];
+        let report = simulate(&rules, &values(&registry, &rows()), &registry, &samples).unwrap();
+        let winners: Vec<Option<(i32, i32)>> =
+            report.results.iter().map(|r| r.as_ref().map(|r| (r.match_id, r.rank))).collect();
+        assert_eq!(winners, [Some((1, 1)), Some((2, 2)), Some((3, 3)), None]);
+        assert_eq!(report.rank_hits, BTreeMap::from([(1, 1), (2, 1), (3, 1)]));
+        assert_eq!(report.unmatched, [3]);
+        assert_eq!(report.unused_rows(), [4]);
+        assert!(report.unused_ranks().is_empty());
+    }
+
+    #[test]
+    fn ranks_no_sample_reaches_are_unused() {
+        let registry = region_channel_registry();
+        let rules = matrix_json_to_tall(MATRIX, 1, &registry).unwrap();
+        let report = simulate(&rules, &values(&registry, &rows()), &registry, &[context("eu", "web")]).unwrap();
+        assert_eq!(report.unused_ranks(), [2, 3]);
+        assert_eq!(report.unused_rows(), [2, 3, 4]);
+        assert_eq!(report.row_hits[&1], 1);
+    }
+
+    fn compiled(registry: &AttrRegistry, matrix: &str, rows: &[(Value, i64)]) -> CompiledConfig {
+        let rules = matrix_json_to_tall(matrix, 1, registry).unwrap();
+        CompiledConfig::new(&rules, &values(registry, rows), registry).unwrap()
+    }
+
+    #[test]
+    fn changed_rows_and_params_are_reported_in_sample_order() {
+        let registry = region_channel_registry();
+        let old = compiled(&registry, MATRIX, &rows());
+        let mut new_rows = rows();
+        new_rows[1].1 = 20;
+        new_rows.remove(2);
+        let new = compiled(&registry, MATRIX, &new_rows);
+        let samples = [context("eu", "web"), context("fr", "web"), context("eu", "app"), context("fr", "pos")];
+        let changes = compare_resolutions(&old, &new, &samples);
+        let match_id = |r: &Option<ResolvedConfig>| r.as_ref().map(|r| r.match_id);
+        let moves: Vec<_> = changes.iter().map(|c| (c.sample, match_id(&c.old), match_id(&c.new))).collect();
+        assert_eq!(moves, [(1, Some(3), None), (2, Some(2), Some(2))]);
+        assert!(changes.iter().all(|c| c.changed_params == [3]));
+    }
+
+    #[test]
+    fn a_row_won_at_another_rank_is_not_a_change() {
+        let registry = region_channel_registry();
+        let rows = &rows()[..1];
+        let old = compiled(&registry, MATRIX, rows);
+        let shifted = r#"[{ "rank": 1, "region": 0, "channel": 0 }, { "rank": 2, "region": 1, "channel": 1 }]"#;
+        let new = compiled(&registry, shifted, rows);
+        let samples = [context("eu", "web")];
+        assert_eq!(new.resolve(&samples[0]).map(|r| r.rank), Some(2));
+        assert!(compare_resolutions(&old, &new, &samples).is_empty());
+    }
+
+    #[test]
+    fn a_rank_move_dry_run_reports_the_resolutions_it_changes() {
+        let registry = region_channel_registry();
+        let matrix: Vec<MatrixRow> = serde_json::from_str(MATRIX).unwrap();
+        let values = values(&registry, &rows());
+        let samples = [context("eu", "web"), context("eu", "app"), context("fr", "web"), context("us", "app")];
+        // channel alone now comes first, ahead of region and channel
+        let preview = move_rank_dry_run(&matrix, 3, 1, &values, &registry, &samples).unwrap();
+        assert_eq!(preview.renumbered, RenumberMap::from([(1, 2), (2, 3), (3, 1)]));
+        assert_eq!((preview.matrix[0].get("channel"), preview.matrix[0].get("region")), (Some(1), Some(0)));
+        assert!(preview.findings.is_empty());
+        let match_id = |r: &Option<ResolvedConfig>| r.as_ref().map(|r| r.match_id);
+        let moves: Vec<_> = preview.changes.iter().map(|c| (c.sample, match_id(&c.old), match_id(&c.new))).collect();
+        assert_eq!(moves, [(0, Some(1), Some(3))]);
+
+        let err = move_rank_dry_run(&matrix, 4, 1, &values, &registry, &samples).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidRankMove { from_rank: 4, .. }), "{err}");
+    }
+}
@@ -0,0 +1,467 @@
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::ConfigPrecedenceRule;
+use crate::config_resolver::WILDCARD;
+use crate::config_types::ParamType;
+use crate::config_value::{
+    is_pattern_for, is_regex_object, parse_one_of_value, parse_pattern_value, parse_range_value, parse_regex_value,
+    parse_str_value, AttrMeta, AttrRole, ConfigValue, TypedValue,
+};
+use chrono::NaiveDateTime;
+use std::collections::BTreeSet;
+
+/// The typed columns of one CONFIG_VALUE row (see README: Config Value Table); at
+/// most one is set, none for a `null` value.
+///
+/// `VAL_DEC` travels as text so decimals keep every digit whichever Rust type
+/// holds them; bind it as `$n::numeric` and select it as `val_dec::text`. Types
+/// without a column of their own (`date`, `time`, `duration`, `enum`, `uuid`,
+/// lists) are stored in `VAL_STR` in their text form, as are the `"ALL"` wildcard
+/// and range and alternative match values (as their envelope JSON). A `str` or
+/// `enum` value starting with `[`, `{` or `\` is stored behind a `\`, so it does
+/// not read back as one of those.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValueColumns {
+    pub val_int: Option<i64>,
+    pub val_dec: Option<String>,
+    pub val_str: Option<String>,
+    pub val_bool: Option<bool>,
+    /// UTC; a datetime with an offset is stored as its UTC instant.
+    pub val_dt: Option<NaiveDateTime>,
+}
+
+impl ValueColumns {
+    pub fn from_typed(value: &TypedValue) -> Self {
+        let mut columns = Self::default();
+        match value {
+            TypedValue::Int(v) => columns.val_int = Some(*v),
+            TypedValue::Dec(_) => columns.val_dec = Some(value.to_string()),
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(_) => columns.val_dec = Some(value.to_string()),
+            TypedValue::Bool(v) => columns.val_bool = Some(*v),
+            TypedValue::Dt(v) => columns.val_dt = Some(*v),
+            TypedValue::DtTz(v) => columns.val_dt = Some(v.naive_utc()),
+            TypedValue::Null => {}
+            TypedValue::Str(v) | TypedValue::Enum(v) if v.starts_with(['[', '{', STR_ESCAPE]) => {
+                columns.val_str = Some(format!("{STR_ESCAPE}{v}"))
+            }
+            other => columns.val_str = Some(other.to_string()),
+        }
+        columns
+    }
+
+    /// Reads the value back by `meta.data_type`. Match values may hold the `"ALL"`
+    /// wildcard in `VAL_STR` whatever their type, a range object unless they are
+    /// `str` or `enum` (which take a regex object instead), and an array of
+    /// alternatives unless they have a list type. A match value holding a `*` reads
+    /// back as a pattern when the attribute takes patterns. A `str` or `enum` value
+    /// behind a `\` is that string, whatever it holds.
+    pub fn to_typed(&self, meta: &AttrMeta) -> Result<TypedValue> {
+        let ty: ParamType = meta.data_type.parse().map_err(|_| PrecedenceConfigError::UnsupportedDataType {
+            key: meta.attr_name.to_string(),
+            data_type: meta.data_type.clone(),
+        })?;
+        if *self == Self::default() {
+            return Ok(TypedValue::Null);
+        }
+        if meta.role == AttrRole::Match && self.val_str.as_deref() == Some(WILDCARD) {
+            return Ok(TypedValue::Str(WILDCARD.to_string()));
+        }
+        if matches!(ty, ParamType::Str | ParamType::Enum)
+            && let Some(raw) = self.val_str.as_deref().and_then(|raw| raw.strip_prefix(STR_ESCAPE))
+        {
+            return parse_str_value(meta, raw);
+        }
+        if meta.role == AttrRole::Match
+            && let Some(raw) = self.val_str.as_deref()
+        {
+            let json = || serde_json::from_str::<serde_json::Value>(raw).ok();
+            if raw.starts_with('{')
+                && let Some(json) = json()
+            {
+                if !matches!(ty, ParamType::Str | ParamType::Enum) {
+                    return parse_range_value(meta, &json);
+                }
+                if is_regex_object(&json) {
+                    return parse_regex_value(meta, &json);
+                }
+            }
+            if raw.starts_with('[')
+                && !matches!(ty, ParamType::List(_))
+                && let Some(json) = json().filter(serde_json::Value::is_array)
+            {
+                return parse_one_of_value(meta, &json);
+            }
+            if is_pattern_for(meta, raw) {
+                return parse_pattern_value(meta, raw);
+            }
+        }
+
+        let value = match ty {
+            ParamType::Int => self.val_int.map(TypedValue::Int),
+            ParamType::Dec => self.val_dec.as_deref().map(|raw| parse_str_value(meta, raw)).transpose()?,
+            ParamType::Bool => self.val_bool.map(TypedValue::Bool),
+            ParamType::Dt => self.val_dt.map(TypedValue::Dt),
+            _ => self.val_str.as_deref().map(|raw| parse_str_value(meta, raw)).transpose()?,
+        };
+        value.ok_or_else(|| PrecedenceConfigError::InvalidValue {
+            key: meta.attr_name.to_string(),
+            data_type: meta.data_type.clone(),
+            value: format!("{:?}", self),
+            reason: "value is not in the column for this data type".to_string(),
+        })
+    }
+}
+
+/// Leads a `VAL_STR` text that is a plain `str` or `enum` value (see [`ValueColumns`]).
+const STR_ESCAPE: char = '\\';
+
+/// Target of [`export_sql`] and [`export_values_sql`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    /// Multi-row `INSERT`s into the lowercase tables used by `config_postgres`.
+    Postgres,
+    /// `COPY ... FROM stdin` blocks in Postgres' text format, for `psql`.
+    PostgresCopy,
+    /// Multi-row `INSERT`s into the `dbo.` tables from the README.
+    SqlServer,
+}
+
+/// Rows per `INSERT` statement; SQL Server accepts at most 1000.
+const INSERT_BATCH_ROWS: usize = 1000;
+
+const DT_SQL_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+enum Cell {
+    Int(i64),
+    /// numeric literal in plain decimal form, or `NaN`, `inf` or `-inf`
+    Num(String),
+    Str(String),
+    Bool(bool),
+    Dt(NaiveDateTime),
+    Null,
+}
+
+impl Cell {
+    fn from_option<T>(value: Option<T>, cell: impl FnOnce(T) -> Cell) -> Cell {
+        value.map_or(Cell::Null, cell)
+    }
+
+    fn literal(&self, dialect: SqlDialect) -> String {
+        match (self, dialect) {
+            (Cell::Int(v), _) => v.to_string(),
+            (Cell::Num(v), _) if v.parse::<f64>().is_ok_and(|v| !v.is_finite()) => format!("'{}'", v),
+            (Cell::Num(v), _) => v.clone(),
+            (Cell::Str(v), SqlDialect::SqlServer) => format!("N'{}'", v.replace('\'', "''")),
+            (Cell::Str(v), _) => format!("'{}'", v.replace('\'', "''")),
+            (Cell::Bool(v), SqlDialect::SqlServer) => if *v { "1" } else { "0" }.to_string(),
+            (Cell::Bool(v), _) => if *v { "TRUE" } else { "FALSE" }.to_string(),
+            (Cell::Dt(v), _) => format!("'{}'", dt_text(v, dialect)),
+            (Cell::Null, _) => "NULL".to_string(),
+        }
+    }
+
+    fn copy_field(&self) -> String {
+        match self {
+            Cell::Int(v) => v.to_string(),
+            Cell::Num(v) => v.clone(),
+            Cell::Str(v) => v
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+            Cell::Bool(v) => if *v { "t" } else { "f" }.to_string(),
+            Cell::Dt(v) => dt_text(v, SqlDialect::PostgresCopy),
+            Cell::Null => "\\N".to_string(),
+        }
+    }
+}
+
+/// `v` at the dialect's precision: microseconds for Postgres' `timestamp`, 100 ns
+/// ticks for SQL Server's `datetime2`. Finer digits are cut off.
+fn dt_text(v: &NaiveDateTime, dialect: SqlDialect) -> String {
+    let nanos = v.and_utc().timestamp_subsec_nanos();
+    match dialect {
+        SqlDialect::SqlServer => format!("{}.{:07}", v.format(DT_SQL_FORMAT), nanos / 100),
+        _ => format!("{}.{:06}", v.format(DT_SQL_FORMAT), nanos / 1000),
+    }
+}
+
+/// Reviewable SQL that loads `tall` into CONFIG_PRECEDENCE_RULE: batched INSERTs,
+/// or a COPY block for [`SqlDialect::PostgresCopy`]. Empty input gives an empty string.
+pub fn export_sql(tall: &[ConfigPrecedenceRule], dialect: SqlDialect) -> String {
+    let rows: Vec<Vec<Cell>> = tall
+        .iter()
+        .map(|r| {
+            vec![
+                Cell::Int(r.config_version_id.into()),
+                Cell::Int(r.rank.into()),
+                Cell::Int(r.attr_id.into()),
+                Cell::Int(u8::from(r.match_type).into()),
+            ]
+        })
+        .collect();
+
+    let mut out = String::new();
+    let columns = ["config_version_id", "rank", "attr_id", "match_type"];
+    write_table(&mut out, dialect, "config_precedence_rule", &columns, &rows);
+    out
+}
+
+/// Like [`export_sql`] for tall config values of one version: a CONFIG_ROW per
+/// distinct match_id (used as the `config_row_id`), then the CONFIG_VALUE rows
+/// with each value split by [`ValueColumns`]. For SQL Server the explicit row ids
+/// are wrapped in `SET IDENTITY_INSERT`.
+///
+/// Postgres takes a `NaN` or infinite `dec` as a quoted `numeric`; SQL Server has
+/// no such numbers, and exporting one for it is an error.
+pub fn export_values_sql(config_version_id: i32, values: &[ConfigValue], dialect: SqlDialect) -> Result<String> {
+    let match_ids: BTreeSet<i32> = values.iter().map(|v| v.match_id).collect();
+    let row_rows: Vec<Vec<Cell>> = match_ids
+        .iter()
+        .map(|&match_id| vec![Cell::Int(match_id.into()), Cell::Int(config_version_id.into())])
+        .collect();
+    let value_rows: Vec<Vec<Cell>> = values
+        .iter()
+        .map(|v| {
+            if dialect == SqlDialect::SqlServer
+                && let TypedValue::Dec(dec) = v.value
+                && !dec.is_finite()
+            {
+                return Err(PrecedenceConfigError::InvalidFormat {
+                    format: "SQL Server value",
+                    reason: format!("dec {} of match_id {} attr_id {} is not finite", dec, v.match_id, v.attr_id),
+                });
+            }
+            let columns = ValueColumns::from_typed(&v.value);
+            Ok(vec![
+                Cell::Int(v.match_id.into()),
+                Cell::Int(v.attr_id.into()),
+                Cell::from_option(columns.val_int, Cell::Int),
+                Cell::from_option(columns.val_dec, Cell::Num),
+                Cell::from_option(columns.val_str, Cell::Str),
+                Cell::from_option(columns.val_bool, Cell::Bool),
+                Cell::from_option(columns.val_dt, Cell::Dt),
+            ])
+        })
+        .collect::<Result<_>>()?;
+
+    let mut out = String::new();
+    if row_rows.is_empty() {
+        return Ok(out);
+    }
+    let identity_insert = dialect == SqlDialect::SqlServer;
+    if identity_insert {
+        out.push_str("SET IDENTITY_INSERT dbo.CONFIG_ROW ON;\n");
+    }
+    write_table(&mut out, dialect, "config_row", &["config_row_id", "config_version_id"], &row_rows);
+    if identity_insert {
+        out.push_str("SET IDENTITY_INSERT dbo.CONFIG_ROW OFF;\n");
+    }
+    write_table(
+        &mut out,
+        dialect,
+        "config_value",
+        &["config_row_id", "attr_id", "val_int", "val_dec", "val_str", "val_bool", "val_dt"],
+        &value_rows,
+    );
+    Ok(out)
+}
+
+fn write_table(out: &mut String, dialect: SqlDialect, table: &str, columns: &[&str], rows: &[Vec<Cell>]) {
+    if rows.is_empty() {
+        return;
+    }
+    let (table, columns) = match dialect {
+        SqlDialect::SqlServer => (
+            format!("dbo.{}", table.to_uppercase()),
+            columns.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>().join(", "),
+        ),
+        _ => (table.to_string(), columns.join(", ")),
+    };
+
+    if dialect == SqlDialect::PostgresCopy {
+        out.push_str(&format!("COPY {} ({}) FROM stdin;\n", table, columns));
+        for row in rows {
+            out.push_str(&row.iter().map(Cell::copy_field).collect::<Vec<_>>().join("\t"));
+            out.push('\n');
+        }
+        out.push_str("\\.\n");
+        return;
+    }
+
+    for batch in rows.chunks(INSERT_BATCH_ROWS) {
+        out.push_str(&format!("INSERT INTO {} ({}) VALUES\n", table, columns));
+        for (i, row) in batch.iter().enumerate() {
+            let literals: Vec<String> = row.iter().map(|cell| cell.literal(dialect)).collect();
+            let end = if i + 1 == batch.len() { ";" } else { "," };
+            out.push_str(&format!("  ({}){}\n", literals.join(", "), end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_attr::AttrRegistry;
+    use chrono::{NaiveDate, TimeZone, Timelike};
+
+    fn meta(role: AttrRole, data_type: &str) -> AttrMeta {
+        let mut registry = AttrRegistry::new();
+        let attr_id = registry.register("attr", role, data_type).unwrap();
+        registry.get_by_id(attr_id).unwrap().clone()
+    }
+
+    fn round_trip(data_type: &str, value: TypedValue) -> (ValueColumns, TypedValue) {
+        let columns = ValueColumns::from_typed(&value);
+        let back = columns.to_typed(&meta(AttrRole::Param, data_type)).unwrap();
+        (columns, back)
+    }
+
+    #[test]
+    fn each_type_lands_in_its_column_and_reads_back() {
+        let dt = NaiveDate::from_ymd_opt(2025, 8, 22).unwrap().and_hms_opt(6, 30, 0).unwrap();
+        let (columns, back) = round_trip("int", TypedValue::Int(-4));
+        assert_eq!((columns.val_int, back), (Some(-4), TypedValue::Int(-4)));
+        let (columns, _) = round_trip("bool", TypedValue::Bool(true));
+        assert_eq!(columns, ValueColumns { val_bool: Some(true), ..Default::default() });
+        let (columns, back) = round_trip("dt", TypedValue::Dt(dt));
+        assert_eq!((columns.val_dt, back), (Some(dt), TypedValue::Dt(dt)));
+        let (columns, back) = round_trip("date", TypedValue::Date(dt.date()));
+        assert_eq!((columns.val_str.as_deref(), back), (Some("2025-08-22"), TypedValue::Date(dt.date())));
+        let (columns, back) = round_trip("str", TypedValue::Null);
+        assert_eq!((columns, back), (ValueColumns::default(), TypedValue::Null));
+    }
+
+    #[test]
+    fn decimals_travel_as_text() {
+        let columns = ValueColumns { val_dec: Some("12.50".to_string()), ..Default::default() };
+        let value = columns.to_typed(&meta(AttrRole::Param, "dec")).unwrap();
+        assert_eq!(ValueColumns::from_typed(&value).val_dec.unwrap().parse::<f64>().unwrap(), 12.5);
+    }
+
+    #[test]
+    fn an_offset_datetime_is_stored_as_utc() {
+        let local = chrono::FixedOffset::east_opt(2 * 3600).unwrap().with_ymd_and_hms(2025, 8, 22, 2, 0, 0).unwrap();
+        let columns = ValueColumns::from_typed(&TypedValue::DtTz(local));
+        assert_eq!(columns.val_dt, Some(NaiveDate::from_ymd_opt(2025, 8, 22).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn a_value_in_the_wrong_column_is_invalid() {
+        let columns = ValueColumns { val_str: Some("5".to_string()), ..Default::default() };
+        assert!(matches!(
+            columns.to_typed(&meta(AttrRole::Param, "int")),
+            Err(PrecedenceConfigError::InvalidValue { ref reason, .. }) if reason.contains("column")
+        ));
+        let mut bad = meta(AttrRole::Param, "int");
+        bad.data_type = "money".to_string();
+        assert!(matches!(columns.to_typed(&bad), Err(PrecedenceConfigError::UnsupportedDataType { .. })));
+    }
+
+    #[test]
+    fn the_wildcard_reads_back_for_any_match_type() {
+        let columns = ValueColumns::from_typed(&TypedValue::Str(WILDCARD.to_string()));
+        let value = columns.to_typed(&meta(AttrRole::Match, "int")).unwrap();
+        assert_eq!(value, TypedValue::Str(WILDCARD.to_string()));
+        assert!(columns.to_typed(&meta(AttrRole::Param, "int")).is_err());
+    }
+
+    #[test]
+    fn strings_that_read_like_json_round_trip() {
+        for raw in [r#"["a"]"#, r#"{"pattern":"^a"}"#, r"\x", "plain"] {
+            let value = TypedValue::Str(raw.to_string());
+            let columns = ValueColumns::from_typed(&value);
+            assert!(columns.val_str.as_deref().unwrap().ends_with(raw));
+            for role in [AttrRole::Match, AttrRole::Param] {
+                assert_eq!(columns.to_typed(&meta(role, "str")).unwrap(), value, "{raw}");
+            }
+        }
+        assert_eq!(ValueColumns::from_typed(&TypedValue::Str("plain".into())).val_str.as_deref(), Some("plain"));
+        let alternatives = ValueColumns { val_str: Some(r#"["a"]"#.to_string()), ..Default::default() };
+        assert!(matches!(alternatives.to_typed(&meta(AttrRole::Match, "str")).unwrap(), TypedValue::OneOf(_)));
+    }
+
+    fn rules() -> Vec<ConfigPrecedenceRule> {
+        use crate::config_precidence_rules::MatchType;
+        [(1, MatchType::Exact), (2, MatchType::Ignore)]
+            .into_iter()
+            .map(|(attr_id, match_type)| ConfigPrecedenceRule { config_version_id: 7, rank: 1, attr_id, match_type })
+            .collect()
+    }
+
+    fn values() -> Vec<ConfigValue> {
+        vec![
+            ConfigValue { match_id: 3, attr_id: 1, role: AttrRole::Match, value: TypedValue::Str("O'Hare".into()) },
+            ConfigValue { match_id: 3, attr_id: 2, role: AttrRole::Param, value: TypedValue::Bool(true) },
+            ConfigValue { match_id: 4, attr_id: 2, role: AttrRole::Param, value: TypedValue::Null },
+        ]
+    }
+
+    #[test]
+    fn rules_export_as_inserts_or_copy() {
+        assert_eq!(
+            export_sql(&rules(), SqlDialect::Postgres),
+            concat!(
+                "INSERT INTO config_precedence_rule (config_version_id, rank, attr_id, match_type) VALUES\n",
+                "  (7, 1, 1, 1),\n  (7, 1, 2, 0);\n",
+            )
+        );
+        assert_eq!(
+            export_sql(&rules(), SqlDialect::PostgresCopy),
+            concat!(
+                "COPY config_precedence_rule (config_version_id, rank, attr_id, match_type) FROM stdin;\n",
+                "7\t1\t1\t1\n7\t1\t2\t0\n\\.\n",
+            )
+        );
+        assert!(export_sql(&[], SqlDialect::SqlServer).is_empty());
+    }
+
+    #[test]
+    fn values_export_with_their_rows_per_dialect() {
+        let postgres = export_values_sql(7, &values(), SqlDialect::Postgres).unwrap();
+        assert!(postgres.starts_with(concat!(
+            "INSERT INTO config_row (config_row_id, config_version_id) VALUES\n",
+            "  (3, 7),\n  (4, 7);\n",
+        )));
+        assert!(postgres.contains("  (3, 1, NULL, NULL, 'O''Hare', NULL, NULL),\n"));
+        assert!(postgres.contains("  (3, 2, NULL, NULL, NULL, TRUE, NULL),\n"));
+        let sql_server = export_values_sql(7, &values(), SqlDialect::SqlServer).unwrap();
+        assert!(sql_server.starts_with("SET IDENTITY_INSERT dbo.CONFIG_ROW ON;\nINSERT INTO dbo.CONFIG_ROW ("));
+        assert!(sql_server.contains("N'O''Hare', NULL, NULL"));
+        assert!(sql_server.contains("NULL, 1, NULL"));
+        let copy = export_values_sql(7, &values(), SqlDialect::PostgresCopy).unwrap();
+        assert!(copy.contains("\n4\t2\t\\N\t\\N\t\\N\t\\N\t\\N\n"));
+        assert!(export_values_sql(7, &[], SqlDialect::Postgres).unwrap().is_empty());
+    }
+
+    #[test]
+    fn non_finite_decimals_are_quoted_for_postgres_and_refused_for_sql_server() {
+        let nan = [ConfigValue { match_id: 1, attr_id: 2, role: AttrRole::Param, value: TypedValue::Dec(f64::NAN) }];
+        assert!(export_values_sql(7, &nan, SqlDialect::Postgres).unwrap().contains("  (1, 2, NULL, 'NaN', NULL,"));
+        assert!(export_values_sql(7, &nan, SqlDialect::PostgresCopy).unwrap().contains("\n1\t2\t\\N\tNaN\t"));
+        let err = export_values_sql(7, &nan, SqlDialect::SqlServer).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidFormat { format: "SQL Server value", .. }), "{err}");
+        let inf = [ConfigValue { value: TypedValue::Dec(f64::NEG_INFINITY), ..nan[0].clone() }];
+        assert!(export_values_sql(7, &inf, SqlDialect::Postgres).unwrap().contains("'-inf'"));
+    }
+
+    #[test]
+    fn datetimes_carry_the_dialects_precision() {
+        let dt = NaiveDate::from_ymd_opt(2025, 8, 22).unwrap().and_hms_nano_opt(6, 30, 0, 123_456_789).unwrap();
+        let value = [ConfigValue { match_id: 1, attr_id: 2, role: AttrRole::Param, value: TypedValue::Dt(dt) }];
+        let sql = |dialect| export_values_sql(7, &value, dialect).unwrap();
+        assert!(sql(SqlDialect::Postgres).contains("'2025-08-22T06:30:00.123456'"));
+        assert!(sql(SqlDialect::PostgresCopy).contains("\t2025-08-22T06:30:00.123456\n"));
+        assert!(sql(SqlDialect::SqlServer).contains("'2025-08-22T06:30:00.1234567'"));
+        let whole = [ConfigValue { value: TypedValue::Dt(dt.with_nanosecond(0).unwrap()), ..value[0].clone() }];
+        assert!(export_values_sql(7, &whole, SqlDialect::SqlServer).unwrap().contains("'2025-08-22T06:30:00.0000000'"));
+    }
+
+    #[test]
+    fn inserts_are_batched() {
+        let tall: Vec<ConfigPrecedenceRule> = (1..=INSERT_BATCH_ROWS as i32 + 1)
+            .map(|rank| ConfigPrecedenceRule { rank, ..rules()[0].clone() })
+            .collect();
+        assert_eq!(export_sql(&tall, SqlDialect::SqlServer).matches("INSERT INTO").count(), 2);
+    }
+}
@@ -0,0 +1,446 @@
+use crate::config_error::{PrecedenceConfigError, Result};
+use crate::config_precidence_rules::ConfigPrecedenceRule;
+use crate::config_types::{AuditInfo, ConfigStatus};
+use crate::config_value::{AttrMeta, ConfigValue};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// One config version as a storage backend keeps it: the CONFIG, CONFIG_VERSION,
+/// CONFIG_PRECEDENCE_RULE and CONFIG_VALUE rows that belong to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionRecord {
+    pub name: String,
+    pub config_id: i32,
+    pub config_version_id: i32,
+    pub version: i32,
+    pub version_name: String,
+    #[serde(default)]
+    pub status: ConfigStatus,
+    #[serde(default)]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub valid_to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub based_on: Option<i32>,
+    #[serde(default)]
+    pub audit: Option<AuditInfo>,
+    #[serde(default)]
+    pub revision: u64,
+    pub rules: Vec<ConfigPrecedenceRule>,
+    pub values: Vec<ConfigValue>,
+    /// match_id -> audit of the row's last change
+    #[serde(default)]
+    pub row_audits: BTreeMap<i32, AuditInfo>,
+}
+
+/// Where a [`ConfigStore`](crate::config_store::ConfigStore) persists its attributes
+/// and versions, see `ConfigStore::load` and `ConfigStore::save`.
+///
+/// Backends only move records; validation stays in the store, so a database or
+/// object-store backend needs no knowledge of ranks or typed values.
+pub trait ConfigStorage {
+    /// The CONFIG_ATTR catalog, in any order.
+    fn load_attrs(&self) -> Result<Vec<AttrMeta>>;
+
+    /// Replaces the whole catalog.
+    fn save_attrs(&mut self, attrs: &[AttrMeta]) -> Result<()>;
+
+    /// Names of the configs with at least one stored version, sorted.
+    fn list_configs(&self) -> Result<Vec<String>>;
+
+    /// Version numbers stored for `name`, ascending; empty for an unknown config.
+    fn list_versions(&self, name: &str) -> Result<Vec<i32>>;
+
+    fn load_version(&self, name: &str, version: i32) -> Result<Option<VersionRecord>>;
+
+    /// Inserts the version, or replaces the stored one with the same name and version.
+    fn save_version(&mut self, record: &VersionRecord) -> Result<()>;
+
+    /// Returns whether the version was stored.
+    fn delete_version(&mut self, name: &str, version: i32) -> Result<bool>;
+}
+
+/// [`ConfigStorage`] held in process memory; also the layout [`JsonFileStorage`] writes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryStorage {
+    attrs: Vec<AttrMeta>,
+    /// (name, version) -> record; a list ordered by name then version on disk
+    #[serde(with = "version_list")]
+    versions: BTreeMap<(String, i32), VersionRecord>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ConfigStorage for MemoryStorage {
+    fn load_attrs(&self) -> Result<Vec<AttrMeta>> {
+        Ok(self.attrs.clone())
+    }
+
+    fn save_attrs(&mut self, attrs: &[AttrMeta]) -> Result<()> {
+        self.attrs = attrs.to_vec();
+        Ok(())
+    }
+
+    fn list_configs(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.versions.keys().map(|(name, _)| name.clone()).collect();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn list_versions(&self, name: &str) -> Result<Vec<i32>> {
+        Ok(self.versions.keys().filter(|(n, _)| n == name).map(|(_, version)| *version).collect())
+    }
+
+    fn load_version(&self, name: &str, version: i32) -> Result<Option<VersionRecord>> {
+        Ok(self.versions.get(&(name.to_string(), version)).cloned())
+    }
+
+    fn save_version(&mut self, record: &VersionRecord) -> Result<()> {
+        self.versions.insert((record.name.clone(), record.version), record.clone());
+        Ok(())
+    }
+
+    fn delete_version(&mut self, name: &str, version: i32) -> Result<bool> {
+        Ok(self.versions.remove(&(name.to_string(), version)).is_some())
+    }
+}
+
+mod version_list {
+    use super::VersionRecord;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        versions: &BTreeMap<(String, i32), VersionRecord>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(versions.values())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<(String, i32), VersionRecord>, D::Error> {
+        let records = Vec::<VersionRecord>::deserialize(deserializer)?;
+        Ok(records.into_iter().map(|r| ((r.name.clone(), r.version), r)).collect())
+    }
+}
+
+/// [`ConfigStorage`] kept in one JSON file (`{ "attrs": [..], "versions": [..] }`).
+///
+/// Every call reads the file; every write replaces it through a temporary file in
+/// the same directory, so readers never see a half-written store. A missing file
+/// reads as empty.
+#[derive(Debug, Clone)]
+pub struct JsonFileStorage {
+    path: PathBuf,
+}
+
+impl JsonFileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<MemoryStorage> {
+        self.parse(std::fs::read_to_string(&self.path))
+    }
+
+    fn write(&self, storage: &MemoryStorage) -> Result<()> {
+        let tmp = self.tmp_path();
+        std::fs::write(&tmp, render(storage)?).map_err(|e| self.io_error(e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| self.io_error(e))
+    }
+
+    fn parse(&self, read: std::io::Result<String>) -> Result<MemoryStorage> {
+        let json = match read {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(MemoryStorage::new()),
+            Err(e) => return Err(self.io_error(e)),
+        };
+        serde_json::from_str(&json).map_err(|e| PrecedenceConfigError::InvalidFormat {
+            format: "JSON",
+            reason: format!("{}: {}", self.path.display(), e),
+        })
+    }
+
+    fn tmp_path(&self) -> PathBuf {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        tmp.into()
+    }
+
+    fn update<T>(&self, apply: impl FnOnce(&mut MemoryStorage) -> Result<T>) -> Result<T> {
+        let mut storage = self.read()?;
+        let out = apply(&mut storage)?;
+        self.write(&storage)?;
+        Ok(out)
+    }
+
+    fn io_error(&self, e: std::io::Error) -> PrecedenceConfigError {
+        PrecedenceConfigError::Io(std::io::Error::new(e.kind(), format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+fn render(storage: &MemoryStorage) -> Result<String> {
+    serde_json::to_string_pretty(storage).map_err(PrecedenceConfigError::InvalidJson)
+}
+
+impl ConfigStorage for JsonFileStorage {
+    fn load_attrs(&self) -> Result<Vec<AttrMeta>> {
+        ConfigStorage::load_attrs(&self.read()?)
+    }
+
+    fn save_attrs(&mut self, attrs: &[AttrMeta]) -> Result<()> {
+        self.update(|storage| ConfigStorage::save_attrs(storage, attrs))
+    }
+
+    fn list_configs(&self) -> Result<Vec<String>> {
+        ConfigStorage::list_configs(&self.read()?)
+    }
+
+    fn list_versions(&self, name: &str) -> Result<Vec<i32>> {
+        ConfigStorage::list_versions(&self.read()?, name)
+    }
+
+    fn load_version(&self, name: &str, version: i32) -> Result<Option<VersionRecord>> {
+        ConfigStorage::load_version(&self.read()?, name, version)
+    }
+
+    fn save_version(&mut self, record: &VersionRecord) -> Result<()> {
+        self.update(|storage| ConfigStorage::save_version(storage, record))
+    }
+
+    fn delete_version(&mut self, name: &str, version: i32) -> Result<bool> {
+        self.update(|storage| ConfigStorage::delete_version(storage, name, version))
+    }
+}
+
+/// Async counterpart of [`ConfigStorage`] (`tokio` feature), for backends whose
+/// I/O should not block the runtime. Same contract, method for method.
+#[cfg(feature = "tokio")]
+pub trait AsyncConfigStorage: Send + Sync {
+    fn load_attrs(&self) -> impl Future<Output = Result<Vec<AttrMeta>>> + Send;
+
+    fn save_attrs(&mut self, attrs: &[AttrMeta]) -> impl Future<Output = Result<()>> + Send;
+
+    fn list_configs(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    fn list_versions(&self, name: &str) -> impl Future<Output = Result<Vec<i32>>> + Send;
+
+    fn load_version(&self, name: &str, version: i32) -> impl Future<Output = Result<Option<VersionRecord>>> + Send;
+
+    fn save_version(&mut self, record: &VersionRecord) -> impl Future<Output = Result<()>> + Send;
+
+    fn delete_version(&mut self, name: &str, version: i32) -> impl Future<Output = Result<bool>> + Send;
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncConfigStorage for MemoryStorage {
+    async fn load_attrs(&self) -> Result<Vec<AttrMeta>> {
+        ConfigStorage::load_attrs(self)
+    }
+
+    async fn save_attrs(&mut self, attrs: &[AttrMeta]) -> Result<()> {
+        ConfigStorage::save_attrs(self, attrs)
+    }
+
+    async fn list_configs(&self) -> Result<Vec<String>> {
+        ConfigStorage::list_configs(self)
+    }
+
+    async fn list_versions(&self, name: &str) -> Result<Vec<i32>> {
+        ConfigStorage::list_versions(self, name)
+    }
+
+    async fn load_version(&self, name: &str, version: i32) -> Result<Option<VersionRecord>> {
+        ConfigStorage::load_version(self, name, version)
+    }
+
+    async fn save_version(&mut self, record: &VersionRecord) -> Result<()> {
+        ConfigStorage::save_version(self, record)
+    }
+
+    async fn delete_version(&mut self, name: &str, version: i32) -> Result<bool> {
+        ConfigStorage::delete_version(self, name, version)
+    }
+}
+
+/// Reads and writes the file through `tokio::fs`; same layout and atomic replace as the sync impl.
+#[cfg(feature = "tokio")]
+impl JsonFileStorage {
+    async fn read_async(&self) -> Result<MemoryStorage> {
+        self.parse(tokio::fs::read_to_string(&self.path).await)
+    }
+
+    async fn update_async<T>(&self, apply: impl FnOnce(&mut MemoryStorage) -> Result<T>) -> Result<T> {
+        let mut storage = self.read_async().await?;
+        let out = apply(&mut storage)?;
+        let tmp = self.tmp_path();
+        tokio::fs::write(&tmp, render(&storage)?).await.map_err(|e| self.io_error(e))?;
+        tokio::fs::rename(&tmp, &self.path).await.map_err(|e| self.io_error(e))?;
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncConfigStorage for JsonFileStorage {
+    async fn load_attrs(&self) -> Result<Vec<AttrMeta>> {
+        ConfigStorage::load_attrs(&self.read_async().await?)
+    }
+
+    async fn save_attrs(&mut self, attrs: &[AttrMeta]) -> Result<()> {
+        self.update_async(|storage| ConfigStorage::save_attrs(storage, attrs)).await
+    }
+
+    async fn list_configs(&self) -> Result<Vec<String>> {
+        ConfigStorage::list_configs(&self.read_async().await?)
+    }
+
+    async fn list_versions(&self, name: &str) -> Result<Vec<i32>> {
+        ConfigStorage::list_versions(&self.read_async().await?, name)
+    }
+
+    async fn load_version(&self, name: &str, version: i32) -> Result<Option<VersionRecord>> {
+        ConfigStorage::load_version(&self.read_async().await?, name, version)
+    }
+
+    async fn save_version(&mut self, record: &VersionRecord) -> Result<()> {
+        self.update_async(|storage| ConfigStorage::save_version(storage, record)).await
+    }
+
+    async fn delete_version(&mut self, name: &str, version: i32) -> Result<bool> {
+        self.update_async(|storage| ConfigStorage::delete_version(storage, name, version)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_resolver::Resolver;
+    use crate::config_store::{ConfigStore, StoreOptions};
+    use crate::test_fixtures::{eu_store, region_context};
+    use serde_json::json;
+
+    fn record(name: &str, version: i32) -> VersionRecord {
+        serde_json::from_value(json!({
+            "name": name,
+            "config_id": 1,
+            "config_version_id": version,
+            "version": version,
+            "version_name": format!("v{version}"),
+            "rules": [],
+            "values": [],
+        }))
+        .unwrap()
+    }
+
+    /// A fresh path named after `test`, with nothing at it.
+    fn store_path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("precedence-config-storage-{}-{}.json", test, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn exercise(storage: &mut impl ConfigStorage) {
+        assert!(storage.list_configs().unwrap().is_empty());
+        for (name, version) in [("b", 2), ("a", 1), ("b", 1)] {
+            storage.save_version(&record(name, version)).unwrap();
+        }
+        let mut renamed = record("b", 2);
+        renamed.version_name = "second".to_string();
+        storage.save_version(&renamed).unwrap();
+        assert_eq!(storage.list_configs().unwrap(), ["a", "b"]);
+        assert_eq!(storage.list_versions("b").unwrap(), [1, 2]);
+        assert!(storage.list_versions("c").unwrap().is_empty());
+        assert_eq!(storage.load_version("b", 2).unwrap().unwrap().version_name, "second");
+        assert!(storage.load_version("a", 2).unwrap().is_none());
+        assert!(storage.delete_version("b", 1).unwrap());
+        assert!(!storage.delete_version("b", 1).unwrap());
+        assert_eq!(storage.list_versions("b").unwrap(), [2]);
+    }
+
+    #[test]
+    fn memory_storage_keeps_versions_by_name_and_number() {
+        exercise(&mut MemoryStorage::new());
+    }
+
+    #[test]
+    fn json_file_storage_persists_across_instances() {
+        let path = store_path("persist");
+        exercise(&mut JsonFileStorage::new(&path));
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        assert!(!Path::new(&tmp).exists());
+        let reopened = JsonFileStorage::new(&path);
+        assert_eq!(ConfigStorage::list_configs(&reopened).unwrap(), ["a", "b"]);
+        assert_eq!(ConfigStorage::load_version(&reopened, "b", 2).unwrap().unwrap().version_name, "second");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_json_file_that_does_not_parse_is_an_invalid_format() {
+        let path = store_path("invalid");
+        std::fs::write(&path, "{ not json").unwrap();
+        let err = ConfigStorage::load_attrs(&JsonFileStorage::new(&path)).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidFormat { format: "JSON", .. }));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_store_round_trips_through_storage() {
+        let store = eu_store(StoreOptions::default());
+        let mut storage = MemoryStorage::new();
+        store.save(&mut storage).unwrap();
+        let loaded = ConfigStore::load(&storage).unwrap();
+        assert_eq!(loaded.registry().id_of("region"), store.registry().id_of("region"));
+        assert_eq!(format!("{:?}", loaded.get_version("c", 1)), format!("{:?}", store.get_version("c", 1)));
+    }
+
+    #[test]
+    fn a_resolver_loads_one_version_from_storage() {
+        let mut storage = MemoryStorage::new();
+        eu_store(StoreOptions::default()).save(&mut storage).unwrap();
+        let resolver = Resolver::from_storage(&storage, "c", 1).unwrap();
+        assert_eq!(resolver.resolve(&region_context("eu")).map(|r| r.match_id), Some(1));
+        assert!(matches!(
+            Resolver::from_storage(&storage, "c", 2),
+            Err(PrecedenceConfigError::UnknownConfigVersion { version: 2, .. })
+        ));
+        let mut refreshed = ConfigStore::load(&storage).unwrap();
+        assert_eq!(refreshed.load_version(&storage, "c", 1).unwrap().version_name(), "v1");
+    }
+
+    /// Polls `future` to completion; the [`MemoryStorage`] futures never wait on I/O.
+    #[cfg(feature = "tokio")]
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_storage_matches_the_sync_calls() {
+        let store = eu_store(StoreOptions::default());
+        let mut storage = MemoryStorage::new();
+        block_on(store.save_async(&mut storage)).unwrap();
+        assert_eq!(ConfigStorage::list_versions(&storage, "c").unwrap(), [1]);
+        let mut loaded = block_on(ConfigStore::load_async(&storage)).unwrap();
+        assert!(block_on(loaded.load_version_async(&storage, "c", 2)).is_err());
+        let resolver = block_on(Resolver::from_storage_async(&storage, "c", 1)).unwrap();
+        assert_eq!(resolver.resolve(&region_context("eu")).map(|r| r.match_id), Some(1));
+    }
+}
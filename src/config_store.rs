@@ -0,0 +1,1503 @@
+use crate::config_attr::AttrRegistry;
+use crate::config_changelog::{Change, ChangeLog};
+use crate::config_diff::{diff_envelopes, ConfigDiff};
+use crate::config_envelope::{config_values_to_envelope_with_audits, validate_envelope};
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use crate::config_lint::validate_row_conflicts;
+use crate::config_metrics::{Metrics, MetricsSink};
+use crate::config_overlay::ConfigOverlay;
+use crate::config_patch::{apply_patch, apply_precedence_patch, ConfigPatch, PatchOp};
+use crate::config_precidence_rules::ConfigPrecedenceRule;
+use crate::config_resolver::{
+    is_wildcard, CompiledConfig, Context, ResolutionStrategy, ResolvedConfig, Resolver, TieBreakPolicy,
+};
+#[cfg(feature = "tokio")]
+use crate::config_storage::AsyncConfigStorage;
+use crate::config_storage::{ConfigStorage, VersionRecord};
+use crate::config_types::{check_validity, is_effective_at, AuditInfo, ConfigEnvelope, ConfigMeta, ConfigStatus};
+use crate::config_value::{AttrMeta, AttrRole, ConfigValue, TypedValue};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// One CONFIG_VERSION: its precedence rules and tall CONFIG_VALUE rows.
+///
+/// Only a [`ConfigStore`] builds or changes these, so the contents always passed
+/// the store's checks against its attribute catalog.
+#[derive(Debug, Clone)]
+pub struct ConfigVersion {
+    config_version_id: i32,
+    version: i32,
+    version_name: String,
+    status: ConfigStatus,
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+    based_on: Option<i32>,
+    audit: Option<AuditInfo>,
+    /// bumped by every change to the version, see [`ConfigStore::update_version`]
+    revision: u64,
+    rules: Vec<ConfigPrecedenceRule>,
+    values: Vec<ConfigValue>,
+    /// match_id -> audit of the row's last change
+    row_audits: BTreeMap<i32, AuditInfo>,
+}
+
+impl ConfigVersion {
+    pub fn config_version_id(&self) -> i32 {
+        self.config_version_id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn version_name(&self) -> &str {
+        &self.version_name
+    }
+
+    pub fn status(&self) -> ConfigStatus {
+        self.status
+    }
+
+    pub fn valid_from(&self) -> Option<DateTime<Utc>> {
+        self.valid_from
+    }
+
+    pub fn valid_to(&self) -> Option<DateTime<Utc>> {
+        self.valid_to
+    }
+
+    /// Whether `as_of` falls in `[valid_from, valid_to)`.
+    pub fn is_effective_at(&self, as_of: DateTime<Utc>) -> bool {
+        is_effective_at(self.valid_from, self.valid_to, as_of)
+    }
+
+    /// Version number this one was materialized from, see [`ConfigStore::insert_overlay`].
+    pub fn based_on(&self) -> Option<i32> {
+        self.based_on
+    }
+
+    /// 0 when created, plus one per change since.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Who created the version, when and why.
+    pub fn audit(&self) -> Option<&AuditInfo> {
+        self.audit.as_ref()
+    }
+
+    /// Who last changed the row with this match_id, when and why.
+    pub fn row_audit(&self, match_id: i32) -> Option<&AuditInfo> {
+        self.row_audits.get(&match_id)
+    }
+
+    pub fn row_audits(&self) -> &BTreeMap<i32, AuditInfo> {
+        &self.row_audits
+    }
+
+    /// The version's metadata as it appears in an envelope.
+    pub fn meta(&self, name: &str) -> ConfigMeta {
+        ConfigMeta {
+            name: name.to_string(),
+            version: self.version,
+            version_name: self.version_name.clone(),
+            status: self.status,
+            valid_from: self.valid_from,
+            valid_to: self.valid_to,
+            audit: self.audit.clone(),
+        }
+    }
+
+    /// Tall precedence rules, each carrying this version's `config_version_id`.
+    pub fn rules(&self) -> &[ConfigPrecedenceRule] {
+        &self.rules
+    }
+
+    pub fn values(&self) -> &[ConfigValue] {
+        &self.values
+    }
+
+    fn uses_attr(&self, attr_id: i32) -> bool {
+        self.rules.iter().any(|r| r.attr_id == attr_id) || self.values.iter().any(|v| v.attr_id == attr_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StoredConfig {
+    config_id: i32,
+    /// version number -> version
+    versions: BTreeMap<i32, ConfigVersion>,
+}
+
+/// In-memory CONFIG / CONFIG_VERSION / CONFIG_ATTR tables.
+///
+/// Configs are keyed by name and their versions by version number; ids are
+/// allocated like `IDENTITY(1,1)`. Every mutation is checked before it is applied,
+/// so a failed call leaves the store unchanged:
+///
+/// - rules need rank >= 1, a known match attribute and no repeated (rank, attr);
+/// - values need a known attribute with the same role, param values within the
+///   attribute's constraints, one value per match attribute per row, and no two
+///   rows with the same match tuple (see [`validate_row_conflicts`]);
+/// - an attribute can only be removed once no version refers to it.
+///
+/// Versions move through [`ConfigStatus`] draft -> review -> published -> archived
+/// (see [`ConfigStore::publish`] and its neighbours); only drafts can be edited.
+/// A version may also carry a validity window, which [`ConfigStore::resolve_at`]
+/// and [`ConfigStore::schedule`] use to pick the version in force at an instant.
+#[derive(Debug, Clone)]
+pub struct ConfigStore {
+    registry: AttrRegistry,
+    options: StoreOptions,
+    configs: BTreeMap<String, StoredConfig>,
+    next_config_id: i32,
+    next_config_version_id: i32,
+    /// recorded as `created_by` / `reason` on changes, see [`ConfigStore::set_actor`]
+    actor: Option<String>,
+    reason: Option<String>,
+    changelog: ChangeLog,
+    snapshots: BTreeMap<u64, ConfigSnapshot>,
+    next_snapshot_id: u64,
+    tie_break: TieBreakPolicy,
+    strategy: ResolutionStrategy,
+    require_match: bool,
+    metrics: Metrics,
+}
+
+/// A copy of one version's rules, values and metadata, taken by
+/// [`ConfigStore::snapshot`] and put back by [`ConfigStore::rollback_to`].
+#[derive(Debug, Clone)]
+pub struct ConfigSnapshot {
+    snapshot_id: u64,
+    name: String,
+    config_id: i32,
+    taken_at: DateTime<Utc>,
+    version: ConfigVersion,
+}
+
+impl ConfigSnapshot {
+    pub fn snapshot_id(&self) -> u64 {
+        self.snapshot_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn taken_at(&self) -> DateTime<Utc> {
+        self.taken_at
+    }
+
+    /// The version as it was when the snapshot was taken.
+    pub fn version(&self) -> &ConfigVersion {
+        &self.version
+    }
+}
+
+/// Options for [`ConfigStore::with_options`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StoreOptions {
+    /// [`ConfigStore::resolver`] refuses versions that are not published, and only
+    /// published versions take effect in [`ConfigStore::resolve_at`].
+    pub strict: bool,
+}
+
+impl Default for ConfigStore {
+    fn default() -> Self {
+        Self::new(AttrRegistry::new())
+    }
+}
+
+impl ConfigStore {
+    /// An empty store over an existing attribute catalog.
+    pub fn new(registry: AttrRegistry) -> Self {
+        Self::with_options(registry, StoreOptions::default())
+    }
+
+    pub fn with_options(registry: AttrRegistry, options: StoreOptions) -> Self {
+        Self {
+            registry,
+            options,
+            configs: BTreeMap::new(),
+            next_config_id: 1,
+            next_config_version_id: 1,
+            actor: None,
+            reason: None,
+            changelog: ChangeLog::new(),
+            snapshots: BTreeMap::new(),
+            next_snapshot_id: 1,
+            tie_break: TieBreakPolicy::default(),
+            strategy: ResolutionStrategy::default(),
+            require_match: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn options(&self) -> StoreOptions {
+        self.options
+    }
+
+    /// The tie-break policy of the resolvers this store builds. Rows' audit
+    /// `created_at` feeds [`TieBreakPolicy::LatestCreated`].
+    pub fn set_tie_break(&mut self, policy: TieBreakPolicy) {
+        self.tie_break = policy;
+    }
+
+    pub fn tie_break(&self) -> &TieBreakPolicy {
+        &self.tie_break
+    }
+
+    /// The resolution strategy of the resolvers this store builds; its weights
+    /// are checked against the catalog when a resolver is built.
+    pub fn set_strategy(&mut self, strategy: ResolutionStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn strategy(&self) -> &ResolutionStrategy {
+        &self.strategy
+    }
+
+    /// Makes the resolvers this store builds fail with `NoMatchingRow` when no
+    /// row matches, see [`Resolver::set_require_match`].
+    pub fn set_require_match(&mut self, require_match: bool) {
+        self.require_match = require_match;
+    }
+
+    pub fn require_match(&self) -> bool {
+        self.require_match
+    }
+
+    /// Reports the lookups of every resolver this store builds to `sink`, see
+    /// [`Resolver::set_metrics`].
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics = Metrics(Some(sink));
+    }
+
+    /// Who is making the following changes and why. New versions and changed
+    /// rows are stamped with these and the current time; `None` clears them.
+    pub fn set_actor(&mut self, created_by: Option<&str>, reason: Option<&str>) {
+        self.actor = created_by.map(str::to_string);
+        self.reason = reason.map(str::to_string);
+    }
+
+    fn stamp(&self) -> AuditInfo {
+        AuditInfo { created_by: self.actor.clone(), created_at: Some(Utc::now()), reason: self.reason.clone() }
+    }
+
+    /// Every change made to the configs of this store since it was built or loaded.
+    pub fn changelog(&self) -> &ChangeLog {
+        &self.changelog
+    }
+
+    fn log(&mut self, name: &str, version: Option<i32>, change: Change) {
+        let stamp = self.stamp();
+        self.changelog.record(name, version, stamp, change);
+    }
+
+    /// Reads the catalog and every stored version from `storage`, running the same
+    /// checks as the mutating methods; ids are kept as stored.
+    pub fn load(storage: &impl ConfigStorage) -> Result<Self> {
+        let mut store = Self::new(AttrRegistry::from_metas(storage.load_attrs()?)?);
+        for name in storage.list_configs()? {
+            for version in storage.list_versions(&name)? {
+                let record = storage
+                    .load_version(&name, version)?
+                    .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.clone(), version })?;
+                store.restore_version(record)?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Writes the catalog and every version to `storage`, and deletes stored versions
+    /// this store no longer has. Storage is per version, so a config without any
+    /// versions is not written.
+    pub fn save(&self, storage: &mut impl ConfigStorage) -> Result<()> {
+        storage.save_attrs(&self.registry.iter().cloned().collect::<Vec<_>>())?;
+        for record in self.records() {
+            storage.save_version(&record)?;
+        }
+        for name in storage.list_configs()? {
+            for version in storage.list_versions(&name)? {
+                if self.get_version(&name, version).is_none() {
+                    storage.delete_version(&name, version)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// [`ConfigStore::load`] over an [`AsyncConfigStorage`].
+    #[cfg(feature = "tokio")]
+    pub async fn load_async(storage: &impl AsyncConfigStorage) -> Result<Self> {
+        let mut store = Self::new(AttrRegistry::from_metas(storage.load_attrs().await?)?);
+        for name in storage.list_configs().await? {
+            for version in storage.list_versions(&name).await? {
+                let record = storage
+                    .load_version(&name, version)
+                    .await?
+                    .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.clone(), version })?;
+                store.restore_version(record)?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// [`ConfigStore::save`] over an [`AsyncConfigStorage`].
+    #[cfg(feature = "tokio")]
+    pub async fn save_async(&self, storage: &mut impl AsyncConfigStorage) -> Result<()> {
+        storage.save_attrs(&self.registry.iter().cloned().collect::<Vec<_>>()).await?;
+        for record in self.records() {
+            storage.save_version(&record).await?;
+        }
+        for name in storage.list_configs().await? {
+            for version in storage.list_versions(&name).await? {
+                if self.get_version(&name, version).is_none() {
+                    storage.delete_version(&name, version).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches one version from `storage` into this store, replacing the copy it
+    /// already holds (a refresh). The version's attributes must already be in the
+    /// catalog; if the fetched version fails the checks the old copy is kept.
+    pub fn load_version(&mut self, storage: &impl ConfigStorage, name: &str, version: i32) -> Result<&ConfigVersion> {
+        let record = storage
+            .load_version(name, version)?
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        self.replace_version(record)?;
+        self.log(name, Some(version), Change::VersionReloaded);
+        Ok(self.get_version(name, version).expect("version restored above"))
+    }
+
+    /// [`ConfigStore::load_version`] over an [`AsyncConfigStorage`].
+    #[cfg(feature = "tokio")]
+    pub async fn load_version_async(
+        &mut self,
+        storage: &impl AsyncConfigStorage,
+        name: &str,
+        version: i32,
+    ) -> Result<&ConfigVersion> {
+        let record = storage
+            .load_version(name, version)
+            .await?
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        self.replace_version(record)?;
+        self.log(name, Some(version), Change::VersionReloaded);
+        Ok(self.get_version(name, version).expect("version restored above"))
+    }
+
+    pub fn registry(&self) -> &AttrRegistry {
+        &self.registry
+    }
+
+    /// See [`AttrRegistry::register`].
+    pub fn register_attr(&mut self, attr_name: &str, role: AttrRole, data_type: &str) -> Result<i32> {
+        self.registry.register(attr_name, role, data_type)
+    }
+
+    /// See [`AttrRegistry::insert`].
+    pub fn insert_attr(&mut self, meta: AttrMeta) -> Result<()> {
+        self.registry.insert(meta)
+    }
+
+    /// See [`AttrRegistry::set_default`].
+    pub fn set_attr_default(&mut self, attr_name: &str, default: Option<TypedValue>) -> Result<()> {
+        self.registry.set_default(attr_name, default)
+    }
+
+    /// See [`AttrRegistry::rename`]; stored rules and values refer to the attr_id
+    /// and keep it.
+    pub fn rename_attr(&mut self, old: &str, new: &str) -> Result<()> {
+        self.registry.rename(old, new)
+    }
+
+    /// Removes an attribute that no rule or value of any version uses.
+    pub fn remove_attr(&mut self, attr_name: &str) -> Result<AttrMeta> {
+        let attr_id = self
+            .registry
+            .id_of(attr_name)
+            .ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: attr_name.to_string() })?;
+        for (name, config) in &self.configs {
+            if let Some(version) = config.versions.values().find(|v| v.uses_attr(attr_id)) {
+                return Err(PrecedenceConfigError::AttrInUse {
+                    attr_name: attr_name.to_string(),
+                    name: name.clone(),
+                    version: version.version,
+                });
+            }
+        }
+        Ok(self.registry.remove(attr_name).expect("attr_id looked up above"))
+    }
+
+    /// Adds an empty config, returning its config_id.
+    pub fn create_config(&mut self, name: &str) -> Result<i32> {
+        if self.configs.contains_key(name) {
+            return Err(PrecedenceConfigError::DuplicateConfig { name: name.to_string() });
+        }
+        let config_id = self.next_config_id;
+        self.next_config_id += 1;
+        self.configs.insert(name.to_string(), StoredConfig { config_id, versions: BTreeMap::new() });
+        self.log(name, None, Change::ConfigCreated);
+        Ok(config_id)
+    }
+
+    pub fn config_id(&self, name: &str) -> Option<i32> {
+        self.configs.get(name).map(|c| c.config_id)
+    }
+
+    /// Config names, sorted.
+    pub fn config_names(&self) -> impl Iterator<Item = &str> {
+        self.configs.keys().map(String::as_str)
+    }
+
+    /// Removes a config along with all of its versions.
+    pub fn remove_config(&mut self, name: &str) -> Result<()> {
+        self.configs.remove(name).ok_or_else(|| PrecedenceConfigError::UnknownConfig { name: name.to_string() })?;
+        self.log(name, None, Change::ConfigRemoved);
+        Ok(())
+    }
+
+    /// Adds a draft version to an existing config, returning its config_version_id.
+    /// `rules` are stamped with that id.
+    pub fn create_version(
+        &mut self,
+        name: &str,
+        version: i32,
+        version_name: &str,
+        rules: Vec<ConfigPrecedenceRule>,
+        values: Vec<ConfigValue>,
+    ) -> Result<i32> {
+        let meta = ConfigMeta {
+            name: name.to_string(),
+            version,
+            version_name: version_name.to_string(),
+            status: ConfigStatus::Draft,
+            valid_from: None,
+            valid_to: None,
+            audit: None,
+        };
+        let row_audits = stamp_rows(&values, &BTreeMap::new(), &self.stamp());
+        let config_version_id = self.add_version(meta, rules, values, row_audits)?;
+        self.log(name, Some(version), Change::VersionCreated);
+        Ok(config_version_id)
+    }
+
+    fn add_version(
+        &mut self,
+        meta: ConfigMeta,
+        mut rules: Vec<ConfigPrecedenceRule>,
+        values: Vec<ConfigValue>,
+        row_audits: BTreeMap<i32, AuditInfo>,
+    ) -> Result<i32> {
+        let ConfigMeta { name, version, version_name, status, valid_from, valid_to, audit } = meta;
+        let config = self
+            .configs
+            .get(&name)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfig { name: name.clone() })?;
+        if config.versions.contains_key(&version) {
+            return Err(PrecedenceConfigError::DuplicateConfigVersion { name, version });
+        }
+        check_validity(&name, version, valid_from, valid_to)?;
+        check_rules(&rules, &self.registry)?;
+        check_values(&values, &self.registry)?;
+
+        let config_version_id = self.next_config_version_id;
+        self.next_config_version_id += 1;
+        for rule in &mut rules {
+            rule.config_version_id = config_version_id;
+        }
+        let stored = ConfigVersion {
+            config_version_id,
+            version,
+            version_name,
+            status,
+            valid_from,
+            valid_to,
+            based_on: None,
+            audit: Some(audit.unwrap_or_default().or(&self.stamp())),
+            revision: 0,
+            rules,
+            values,
+            row_audits,
+        };
+        self.configs.get_mut(&name).expect("config looked up above").versions.insert(version, stored);
+        Ok(config_version_id)
+    }
+
+    /// Validates `envelope` against the catalog and stores its rows as a new version
+    /// under `envelope.config`, creating the config if needed. The version keeps the
+    /// envelope's status, validity window and audit fields, so an exported published
+    /// version imports as published; audit fields the envelope leaves out are
+    /// stamped from [`ConfigStore::set_actor`].
+    pub fn insert_envelope(&mut self, envelope: &ConfigEnvelope, rules: Vec<ConfigPrecedenceRule>) -> Result<i32> {
+        let config_version_id = self.import_envelope(envelope, rules)?;
+        self.log(&envelope.config.name, Some(envelope.config.version), Change::EnvelopeInserted);
+        Ok(config_version_id)
+    }
+
+    fn import_envelope(&mut self, envelope: &ConfigEnvelope, rules: Vec<ConfigPrecedenceRule>) -> Result<i32> {
+        let validated = validate_envelope(envelope, &self.registry)?;
+        let meta = validated.config;
+        if let Some(config) = self.configs.get(&meta.name)
+            && config.versions.contains_key(&meta.version)
+        {
+            return Err(PrecedenceConfigError::DuplicateConfigVersion { name: meta.name, version: meta.version });
+        }
+        check_rules(&rules, &self.registry)?;
+        check_values(&validated.values, &self.registry)?;
+
+        if !self.configs.contains_key(&meta.name) {
+            self.create_config(&meta.name)?;
+        }
+        let row_audits = stamp_rows(&validated.values, &validated.row_audits, &self.stamp());
+        self.add_version(meta, rules, validated.values, row_audits)
+    }
+
+    /// Materializes `overlay` against the stored version it is based on and stores
+    /// the result as a new version, as [`ConfigStore::insert_envelope`] would.
+    /// `rules` default to the base version's rules. The new version records
+    /// `based_on`, so [`ConfigStore::diff_from_base`] can show its deltas later.
+    pub fn insert_overlay(&mut self, overlay: &ConfigOverlay, rules: Option<Vec<ConfigPrecedenceRule>>) -> Result<i32> {
+        let (name, based_on) = (&overlay.config.name, overlay.based_on);
+        let base = self.envelope(name, based_on)?;
+        let merged = overlay.materialize(&base)?;
+        let rules = match rules {
+            Some(rules) => rules,
+            None => self.get_version(name, based_on).expect("base exported above").rules.clone(),
+        };
+        let config_version_id = self.import_envelope(&merged, rules)?;
+        self.version_mut(name, overlay.config.version)?.based_on = Some(based_on);
+        self.log(name, Some(overlay.config.version), Change::OverlayInserted { based_on });
+        Ok(config_version_id)
+    }
+
+    /// A stored version as an envelope (see [`config_values_to_envelope_with_audits`]),
+    /// carrying the version's and its rows' audit fields.
+    pub fn envelope(&self, name: &str, version: i32) -> Result<ConfigEnvelope> {
+        let stored = self
+            .get_version(name, version)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        let meta = stored.meta(name);
+        Ok(config_values_to_envelope_with_audits(&stored.values, &meta, &stored.row_audits, &self.registry))
+    }
+
+    /// The row changes of a version created by [`ConfigStore::insert_overlay`]
+    /// relative to its base as the base stands now.
+    pub fn diff_from_base(&self, name: &str, version: i32) -> Result<ConfigDiff> {
+        let stored = self
+            .get_version(name, version)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        let based_on = stored
+            .based_on
+            .ok_or_else(|| PrecedenceConfigError::NoBaseVersion { name: name.to_string(), version })?;
+        Ok(diff_envelopes(&self.envelope(name, based_on)?, &self.envelope(name, version)?))
+    }
+
+    pub fn get_version(&self, name: &str, version: i32) -> Option<&ConfigVersion> {
+        self.configs.get(name).and_then(|c| c.versions.get(&version))
+    }
+
+    /// A config's versions by ascending version number; empty for an unknown config.
+    pub fn versions(&self, name: &str) -> impl DoubleEndedIterator<Item = &ConfigVersion> {
+        self.configs.get(name).into_iter().flat_map(|c| c.versions.values())
+    }
+
+    /// The version with the highest version number.
+    pub fn latest_version(&self, name: &str) -> Option<&ConfigVersion> {
+        self.configs.get(name).and_then(|c| c.versions.values().next_back())
+    }
+
+    /// Replaces a draft's precedence rules.
+    pub fn set_rules(&mut self, name: &str, version: i32, mut rules: Vec<ConfigPrecedenceRule>) -> Result<()> {
+        check_rules(&rules, &self.registry)?;
+        let stored = self.draft_mut(name, version)?;
+        for rule in &mut rules {
+            rule.config_version_id = stored.config_version_id;
+        }
+        stored.rules = rules;
+        stored.revision += 1;
+        self.log(name, Some(version), Change::RulesSet);
+        Ok(())
+    }
+
+    /// Replaces a draft's config values. Rows (by match_id) that are new or whose
+    /// values changed are stamped from [`ConfigStore::set_actor`]; the others keep
+    /// their audit fields.
+    pub fn set_values(&mut self, name: &str, version: i32, values: Vec<ConfigValue>) -> Result<()> {
+        check_values(&values, &self.registry)?;
+        let stamp = self.stamp();
+        let stored = self.draft_mut(name, version)?;
+        let (old, new) = (rows_by_match_id(&stored.values), rows_by_match_id(&values));
+        let (mut added, mut changed) = (Vec::new(), Vec::new());
+        let mut row_audits = BTreeMap::new();
+        for (match_id, row) in &new {
+            let unchanged = old.get(match_id) == Some(row);
+            match old.get(match_id) {
+                None => added.push(*match_id),
+                Some(_) if !unchanged => changed.push(*match_id),
+                Some(_) => {}
+            }
+            let audit = match stored.row_audits.get(match_id) {
+                Some(audit) if unchanged => audit.clone(),
+                _ => stamp.clone(),
+            };
+            row_audits.insert(*match_id, audit);
+        }
+        let removed = old.keys().filter(|match_id| !new.contains_key(match_id)).copied().collect();
+        stored.values = values;
+        stored.row_audits = row_audits;
+        stored.revision += 1;
+        self.log(name, Some(version), Change::ValuesSet { added, changed, removed });
+        Ok(())
+    }
+
+    /// Renames a draft.
+    pub fn set_version_name(&mut self, name: &str, version: i32, version_name: &str) -> Result<()> {
+        let stored = self.draft_mut(name, version)?;
+        let from = std::mem::replace(&mut stored.version_name, version_name.to_string());
+        stored.revision += 1;
+        self.log(name, Some(version), Change::VersionRenamed { from, to: version_name.to_string() });
+        Ok(())
+    }
+
+    /// Sets a draft's validity window; `None` leaves that end open.
+    pub fn set_validity(
+        &mut self,
+        name: &str,
+        version: i32,
+        valid_from: Option<DateTime<Utc>>,
+        valid_to: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        check_validity(name, version, valid_from, valid_to)?;
+        let stored = self.draft_mut(name, version)?;
+        stored.valid_from = valid_from;
+        stored.valid_to = valid_to;
+        stored.revision += 1;
+        self.log(name, Some(version), Change::ValiditySet { valid_from, valid_to });
+        Ok(())
+    }
+
+    /// Applies `patch` to a draft: its row and metadata operations to the version's
+    /// envelope (see [`apply_patch`]) and its precedence operations to the rules
+    /// (see [`apply_precedence_patch`]), then checks the result as
+    /// [`ConfigStore::insert_envelope`] would. `SetConfig` may rename the version
+    /// or move its validity window but not change its name, number or status.
+    /// Rows the patch changes or adds are stamped from [`ConfigStore::set_actor`].
+    pub fn apply_patch(&mut self, name: &str, version: i32, patch: &ConfigPatch) -> Result<()> {
+        self.draft_mut(name, version)?;
+        let old = self.envelope(name, version)?;
+        let mut patched = old.clone();
+        apply_patch(&mut patched, patch)?;
+        let mut rules = self.get_version(name, version).expect("draft looked up above").rules.clone();
+        apply_precedence_patch(&mut rules, patch)?;
+        let (before, after) = (&old.config, &patched.config);
+        if after.name != before.name || after.version != before.version || after.status != before.status {
+            let index = patch.ops.iter().rposition(|op| matches!(op, PatchOp::SetConfig { .. })).unwrap_or_default();
+            return Err(PrecedenceConfigError::InvalidPatchOp {
+                index,
+                reason: "a patch cannot change the config name, version number or status".to_string(),
+            });
+        }
+
+        let stamp = self.stamp();
+        for change in diff_envelopes(&old, &patched).changed_rows {
+            patched.rows[change.new_index].audit = Some(stamp.clone());
+        }
+        let validated = validate_envelope(&patched, &self.registry)?;
+        check_rules(&rules, &self.registry)?;
+        check_values(&validated.values, &self.registry)?;
+
+        let row_audits = stamp_rows(&validated.values, &validated.row_audits, &stamp);
+        let meta = validated.config;
+        let stored = self.version_mut(name, version)?;
+        for rule in &mut rules {
+            rule.config_version_id = stored.config_version_id;
+        }
+        stored.version_name = meta.version_name;
+        stored.valid_from = meta.valid_from;
+        stored.valid_to = meta.valid_to;
+        stored.audit = meta.audit;
+        stored.rules = rules;
+        stored.values = validated.values;
+        stored.row_audits = row_audits;
+        stored.revision += 1;
+        self.log(name, Some(version), Change::PatchApplied { patch: patch.clone() });
+        Ok(())
+    }
+
+    /// [`ConfigStore::apply_patch`] on the version with this config_version_id, as
+    /// long as nobody changed it since the caller read `expected_revision`;
+    /// otherwise fails with [`PrecedenceConfigError::RevisionConflict`] and leaves
+    /// the version alone. Returns the new revision.
+    pub fn update_version(
+        &mut self,
+        config_version_id: i32,
+        expected_revision: u64,
+        patch: &ConfigPatch,
+    ) -> Result<u64> {
+        let (name, _, stored) =
+            self.locate(config_version_id).ok_or(PrecedenceConfigError::UnknownConfigVersionId { config_version_id })?;
+        let (name, version) = (name.to_string(), stored.version);
+        if stored.revision != expected_revision {
+            return Err(PrecedenceConfigError::RevisionConflict {
+                name,
+                version,
+                expected: expected_revision,
+                found: stored.revision,
+            });
+        }
+        self.apply_patch(&name, version, patch)?;
+        Ok(self.get_version(&name, version).expect("version patched above").revision)
+    }
+
+    /// Draft -> review; the version can no longer be edited.
+    pub fn submit_for_review(&mut self, name: &str, version: i32) -> Result<()> {
+        self.transition(name, version, ConfigStatus::Draft, ConfigStatus::Review)
+    }
+
+    /// Review -> published.
+    pub fn publish(&mut self, name: &str, version: i32) -> Result<()> {
+        self.transition(name, version, ConfigStatus::Review, ConfigStatus::Published)
+    }
+
+    /// Published -> archived.
+    pub fn archive(&mut self, name: &str, version: i32) -> Result<()> {
+        self.transition(name, version, ConfigStatus::Published, ConfigStatus::Archived)
+    }
+
+    /// Review -> draft, e.g. when a reviewer asks for changes.
+    pub fn revert_to_draft(&mut self, name: &str, version: i32) -> Result<()> {
+        self.transition(name, version, ConfigStatus::Review, ConfigStatus::Draft)
+    }
+
+    /// Removes one version, returning it.
+    pub fn remove_version(&mut self, name: &str, version: i32) -> Result<ConfigVersion> {
+        let removed = self
+            .configs
+            .get_mut(name)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfig { name: name.to_string() })?
+            .versions
+            .remove(&version)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        self.log(name, Some(version), Change::VersionRemoved);
+        Ok(removed)
+    }
+
+    /// Copies the version with this config_version_id, status included, returning
+    /// the snapshot's id. Snapshots live in memory with the store.
+    pub fn snapshot(&mut self, config_version_id: i32) -> Result<u64> {
+        let (name, config_id, stored) =
+            self.locate(config_version_id).ok_or(PrecedenceConfigError::UnknownConfigVersionId { config_version_id })?;
+        let (name, stored) = (name.to_string(), stored.clone());
+        let snapshot_id = self.next_snapshot_id;
+        self.next_snapshot_id += 1;
+        let snapshot = ConfigSnapshot { snapshot_id, name, config_id, taken_at: Utc::now(), version: stored };
+        self.snapshots.insert(snapshot_id, snapshot);
+        Ok(snapshot_id)
+    }
+
+    /// Puts a snapshot's version back in one step, replacing whatever the config
+    /// holds under that version number now (or restoring it if it was removed),
+    /// e.g. to undo a bad publish. The snapshot is checked against the current
+    /// attribute catalog first; on error nothing changes. The snapshot is kept, and
+    /// the restored version's revision moves past the one it replaces.
+    pub fn rollback_to(&mut self, snapshot_id: u64) -> Result<()> {
+        let snapshot = self.snapshots.get(&snapshot_id).ok_or(PrecedenceConfigError::UnknownSnapshot { snapshot_id })?;
+        check_rules(&snapshot.version.rules, &self.registry)?;
+        check_values(&snapshot.version.values, &self.registry)?;
+
+        let (name, version) = (snapshot.name.clone(), snapshot.version.version);
+        let mut stored = snapshot.version.clone();
+        let config_id = snapshot.config_id;
+        let versions = &mut self
+            .configs
+            .entry(name.clone())
+            .or_insert_with(|| StoredConfig { config_id, versions: BTreeMap::new() })
+            .versions;
+        let replaced = versions.get(&version).map_or(0, |v| v.revision);
+        stored.revision = stored.revision.max(replaced) + 1;
+        versions.insert(version, stored);
+        self.log(&name, Some(version), Change::RolledBack { snapshot_id });
+        Ok(())
+    }
+
+    pub fn get_snapshot(&self, snapshot_id: u64) -> Option<&ConfigSnapshot> {
+        self.snapshots.get(&snapshot_id)
+    }
+
+    /// Snapshots, oldest first.
+    pub fn snapshots(&self) -> impl Iterator<Item = &ConfigSnapshot> {
+        self.snapshots.values()
+    }
+
+    pub fn remove_snapshot(&mut self, snapshot_id: u64) -> Result<ConfigSnapshot> {
+        self.snapshots.remove(&snapshot_id).ok_or(PrecedenceConfigError::UnknownSnapshot { snapshot_id })
+    }
+
+    /// A [`Resolver`] over one stored version, with the store's tie-break policy;
+    /// with [`StoreOptions::strict`] the version must be published.
+    pub fn resolver(&self, name: &str, version: i32) -> Result<Resolver> {
+        let stored = self
+            .get_version(name, version)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        if self.options.strict && stored.status != ConfigStatus::Published {
+            return Err(PrecedenceConfigError::UnpublishedVersion {
+                name: name.to_string(),
+                version,
+                status: stored.status,
+            });
+        }
+        self.build_resolver(stored)
+    }
+
+    fn build_resolver(&self, stored: &ConfigVersion) -> Result<Resolver> {
+        let mut resolver = Resolver::new(&stored.rules, &stored.values, &self.registry)?;
+        resolver.set_tie_break(self.tie_break.clone())?;
+        resolver.set_strategy(self.strategy.clone())?;
+        resolver.set_require_match(self.require_match);
+        if let Some(sink) = &self.metrics.0 {
+            resolver.set_metrics(Arc::clone(sink));
+        }
+        resolver.set_row_created(stored.row_audits.iter().filter_map(|(&match_id, a)| Some((match_id, a.created_at?))));
+        Ok(resolver)
+    }
+
+    /// The version in force at `as_of`: the highest version number whose window
+    /// contains `as_of`, among published versions in strict mode and among all
+    /// but archived ones otherwise.
+    pub fn effective_version(&self, name: &str, as_of: DateTime<Utc>) -> Option<&ConfigVersion> {
+        self.versions(name).rev().find(|v| self.takes_effect(v) && v.is_effective_at(as_of))
+    }
+
+    /// Resolves `context` against [`ConfigStore::effective_version`] at `as_of`;
+    /// `None` when no version is in force or no row matches.
+    pub fn resolve_at(&self, name: &str, context: &Context, as_of: DateTime<Utc>) -> Result<Option<ResolvedConfig>> {
+        if !self.configs.contains_key(name) {
+            return Err(PrecedenceConfigError::UnknownConfig { name: name.to_string() });
+        }
+        match self.effective_version(name, as_of) {
+            Some(stored) => self.resolver(name, stored.version)?.try_resolve(context),
+            None => Ok(None),
+        }
+    }
+
+    /// Compiles every version of `name` that can take effect (see
+    /// [`ConfigStore::effective_version`]), so lookups at any instant need no store.
+    pub fn schedule(&self, name: &str) -> Result<ScheduledConfig> {
+        if !self.configs.contains_key(name) {
+            return Err(PrecedenceConfigError::UnknownConfig { name: name.to_string() });
+        }
+        let versions = self
+            .versions(name)
+            .rev()
+            .filter(|v| self.takes_effect(v))
+            .map(|v| {
+                Ok(ScheduledVersion {
+                    version: v.version,
+                    valid_from: v.valid_from,
+                    valid_to: v.valid_to,
+                    config: self.build_resolver(v)?.compile(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(ScheduledConfig { versions })
+    }
+
+    /// (name, config_id, version) of the version with this config_version_id.
+    fn locate(&self, config_version_id: i32) -> Option<(&str, i32, &ConfigVersion)> {
+        self.configs.iter().find_map(|(name, config)| {
+            let stored = config.versions.values().find(|v| v.config_version_id == config_version_id)?;
+            Some((name.as_str(), config.config_id, stored))
+        })
+    }
+
+    fn takes_effect(&self, stored: &ConfigVersion) -> bool {
+        match stored.status {
+            ConfigStatus::Published => true,
+            ConfigStatus::Archived => false,
+            ConfigStatus::Draft | ConfigStatus::Review => !self.options.strict,
+        }
+    }
+
+    fn records(&self) -> impl Iterator<Item = VersionRecord> + '_ {
+        self.configs.iter().flat_map(|(name, config)| {
+            config.versions.values().map(move |stored| VersionRecord {
+                name: name.clone(),
+                config_id: config.config_id,
+                config_version_id: stored.config_version_id,
+                version: stored.version,
+                version_name: stored.version_name.clone(),
+                status: stored.status,
+                valid_from: stored.valid_from,
+                valid_to: stored.valid_to,
+                based_on: stored.based_on,
+                audit: stored.audit.clone(),
+                revision: stored.revision,
+                rules: stored.rules.clone(),
+                values: stored.values.clone(),
+                row_audits: stored.row_audits.clone(),
+            })
+        })
+    }
+
+    /// [`ConfigStore::restore_version`], dropping any copy of the version held
+    /// already; the old copy is put back if the record fails its checks.
+    fn replace_version(&mut self, record: VersionRecord) -> Result<()> {
+        let (name, version) = (record.name.clone(), record.version);
+        let old = self.configs.get_mut(&name).and_then(|c| c.versions.remove(&version));
+        let outcome = self.restore_version(record);
+        if outcome.is_err()
+            && let Some(old) = old
+        {
+            self.configs.get_mut(&name).expect("config held the old version").versions.insert(version, old);
+        }
+        outcome
+    }
+
+    fn restore_version(&mut self, record: VersionRecord) -> Result<()> {
+        let VersionRecord {
+            name,
+            config_id,
+            config_version_id,
+            version,
+            version_name,
+            status,
+            valid_from,
+            valid_to,
+            based_on,
+            audit,
+            revision,
+            mut rules,
+            values,
+            row_audits,
+        } = record;
+        check_validity(&name, version, valid_from, valid_to)?;
+        check_rules(&rules, &self.registry)?;
+        check_values(&values, &self.registry)?;
+
+        let config = self
+            .configs
+            .entry(name.clone())
+            .or_insert_with(|| StoredConfig { config_id, versions: BTreeMap::new() });
+        if config.versions.contains_key(&version) {
+            return Err(PrecedenceConfigError::DuplicateConfigVersion { name, version });
+        }
+        for rule in &mut rules {
+            rule.config_version_id = config_version_id;
+        }
+        let stored = ConfigVersion {
+            config_version_id,
+            version,
+            version_name,
+            status,
+            valid_from,
+            valid_to,
+            based_on,
+            audit,
+            revision,
+            rules,
+            values,
+            row_audits,
+        };
+        config.versions.insert(version, stored);
+        self.next_config_id = self.next_config_id.max(config_id + 1);
+        self.next_config_version_id = self.next_config_version_id.max(config_version_id + 1);
+        Ok(())
+    }
+
+    fn transition(&mut self, name: &str, version: i32, from: ConfigStatus, to: ConfigStatus) -> Result<()> {
+        let stored = self.version_mut(name, version)?;
+        if stored.status != from {
+            return Err(PrecedenceConfigError::InvalidStatusTransition {
+                name: name.to_string(),
+                version,
+                from: stored.status,
+                to,
+            });
+        }
+        stored.status = to;
+        stored.revision += 1;
+        self.log(name, Some(version), Change::StatusChanged { from, to });
+        Ok(())
+    }
+
+    fn draft_mut(&mut self, name: &str, version: i32) -> Result<&mut ConfigVersion> {
+        let stored = self.version_mut(name, version)?;
+        if !stored.status.is_draft() {
+            return Err(PrecedenceConfigError::VersionNotEditable {
+                name: name.to_string(),
+                version,
+                status: stored.status,
+            });
+        }
+        Ok(stored)
+    }
+
+    fn version_mut(&mut self, name: &str, version: i32) -> Result<&mut ConfigVersion> {
+        self.configs
+            .get_mut(name)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfig { name: name.to_string() })?
+            .versions
+            .get_mut(&version)
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })
+    }
+}
+
+/// The versions of one config compiled with their validity windows, from
+/// [`ConfigStore::schedule`]. Scheduled changes take effect as the clock passes
+/// their `valid_from`, without a reload.
+#[derive(Debug, Clone)]
+pub struct ScheduledConfig {
+    /// by descending version number
+    versions: Vec<ScheduledVersion>,
+}
+
+#[derive(Debug, Clone)]
+struct ScheduledVersion {
+    version: i32,
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+    config: CompiledConfig,
+}
+
+impl ScheduledConfig {
+    /// Version number in force at `as_of`: the highest one whose window contains it.
+    pub fn version_at(&self, as_of: DateTime<Utc>) -> Option<i32> {
+        self.compiled_at(as_of).map(|v| v.version)
+    }
+
+    /// Resolves `context` against the version in force at `as_of`.
+    pub fn resolve_at(&self, context: &Context, as_of: DateTime<Utc>) -> Option<ResolvedConfig> {
+        self.compiled_at(as_of)?.config.resolve(context)
+    }
+
+    /// [`ScheduledConfig::resolve_at`] the current time.
+    pub fn resolve(&self, context: &Context) -> Option<ResolvedConfig> {
+        self.resolve_at(context, Utc::now())
+    }
+
+    fn compiled_at(&self, as_of: DateTime<Utc>) -> Option<&ScheduledVersion> {
+        self.versions.iter().find(|v| is_effective_at(v.valid_from, v.valid_to, as_of))
+    }
+}
+
+/// Every row of `values` with its audit from `given`, unset fields taken from `stamp`.
+fn stamp_rows(
+    values: &[ConfigValue],
+    given: &BTreeMap<i32, AuditInfo>,
+    stamp: &AuditInfo,
+) -> BTreeMap<i32, AuditInfo> {
+    values
+        .iter()
+        .map(|v| (v.match_id, given.get(&v.match_id).cloned().unwrap_or_default().or(stamp)))
+        .collect()
+}
+
+/// (attr_id, role, value) by match_id, ordered by attr_id, for spotting changed rows.
+fn rows_by_match_id(values: &[ConfigValue]) -> BTreeMap<i32, Vec<(i32, AttrRole, &TypedValue)>> {
+    let mut rows: BTreeMap<i32, Vec<(i32, AttrRole, &TypedValue)>> = BTreeMap::new();
+    for v in values {
+        rows.entry(v.match_id).or_default().push((v.attr_id, v.role, &v.value));
+    }
+    for row in rows.values_mut() {
+        row.sort_by_key(|(attr_id, _, _)| *attr_id);
+    }
+    rows
+}
+
+fn check_rules(rules: &[ConfigPrecedenceRule], registry: &AttrRegistry) -> Result<()> {
+    let mut seen: BTreeSet<(i32, i32)> = BTreeSet::new();
+    for (index, r) in rules.iter().enumerate() {
+        if r.rank <= 0 {
+            return Err(PrecedenceConfigError::InvalidRank { index, rank: r.rank });
+        }
+        let meta = registry
+            .get_by_id(r.attr_id)
+            .ok_or(PrecedenceConfigError::UnknownRuleAttr { rank: r.rank, attr: AttrRef::Id(r.attr_id) })?;
+        if meta.role != AttrRole::Match {
+            return Err(PrecedenceConfigError::WrongRole {
+                key: meta.attr_name.to_string(),
+                expected: AttrRole::Match,
+                found: meta.role,
+            });
+        }
+        if !seen.insert((r.rank, r.attr_id)) {
+            return Err(PrecedenceConfigError::DuplicateRankAttr {
+                rank: r.rank,
+                attr_id: r.attr_id,
+                attr_name: meta.attr_name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn check_values(values: &[ConfigValue], registry: &AttrRegistry) -> Result<()> {
+    let mut seen_match: BTreeSet<(i32, i32)> = BTreeSet::new();
+    for v in values {
+        let meta = registry
+            .get_by_id(v.attr_id)
+            .ok_or(PrecedenceConfigError::UnknownValueAttr { match_id: v.match_id, attr_id: v.attr_id })?;
+        if meta.role != v.role {
+            return Err(PrecedenceConfigError::WrongRole {
+                key: meta.attr_name.to_string(),
+                expected: v.role,
+                found: meta.role,
+            });
+        }
+        match v.role {
+            AttrRole::Match => {
+                if !is_wildcard(&v.value) && !seen_match.insert((v.match_id, v.attr_id)) {
+                    return Err(PrecedenceConfigError::DuplicateMatchAttr { match_id: v.match_id, attr_id: v.attr_id });
+                }
+            }
+            AttrRole::Param => meta.check_constraints(&v.value)?,
+        }
+    }
+    validate_row_conflicts(values)
+}
+
+impl Resolver {
+    /// Loads the catalog and one version from `storage`, checks them as
+    /// [`ConfigStore::load`] would, and builds a resolver over that version.
+    pub fn from_storage(storage: &impl ConfigStorage, name: &str, version: i32) -> Result<Self> {
+        let mut store = ConfigStore::new(AttrRegistry::from_metas(storage.load_attrs()?)?);
+        let record = storage
+            .load_version(name, version)?
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        store.restore_version(record)?;
+        store.resolver(name, version)
+    }
+
+    /// [`Resolver::from_storage`] over an [`AsyncConfigStorage`].
+    #[cfg(feature = "tokio")]
+    pub async fn from_storage_async(storage: &impl AsyncConfigStorage, name: &str, version: i32) -> Result<Self> {
+        let mut store = ConfigStore::new(AttrRegistry::from_metas(storage.load_attrs().await?)?);
+        let record = storage
+            .load_version(name, version)
+            .await?
+            .ok_or_else(|| PrecedenceConfigError::UnknownConfigVersion { name: name.to_string(), version })?;
+        store.restore_version(record)?;
+        store.resolver(name, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::MatchType;
+    use crate::test_fixtures::{eu_store, region_context};
+    use serde_json::json;
+
+    /// A store holding `c` v1: one row keyed on `region`, ranked by rank 1.
+    fn store() -> ConfigStore {
+        eu_store(StoreOptions::default())
+    }
+
+    /// What a failed call must leave as it was.
+    fn state(store: &ConfigStore) -> String {
+        format!("{:?} {}", store.get_version("c", 1), store.changelog().len())
+    }
+
+    #[test]
+    fn rejected_rules_leave_the_store_unchanged() {
+        let mut store = store();
+        let before = state(&store);
+        let limit = store.registry().id_of("limit").unwrap();
+        let region = store.registry().id_of("region").unwrap();
+        let bad = [
+            ConfigPrecedenceRule { config_version_id: 0, rank: 0, attr_id: region, match_type: MatchType::Exact },
+            ConfigPrecedenceRule { config_version_id: 0, rank: 1, attr_id: limit, match_type: MatchType::Exact },
+            ConfigPrecedenceRule { config_version_id: 0, rank: 1, attr_id: 99, match_type: MatchType::Exact },
+        ];
+        for rule in bad {
+            assert!(store.set_rules("c", 1, vec![rule]).is_err());
+            assert_eq!(state(&store), before);
+        }
+    }
+
+    #[test]
+    fn rejected_values_leave_the_store_unchanged() {
+        let mut store = store();
+        let before = state(&store);
+        let region = store.registry().id_of("region").unwrap();
+        let row = |value: &str| ConfigValue {
+            match_id: 1,
+            attr_id: region,
+            role: AttrRole::Match,
+            value: TypedValue::Str(value.to_string()),
+        };
+        let err = store.set_values("c", 1, vec![row("eu"), row("us")]).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::DuplicateMatchAttr { match_id: 1, .. }));
+        assert_eq!(state(&store), before);
+    }
+
+    #[test]
+    fn rejected_rollback_leaves_the_store_unchanged() {
+        let mut store = store();
+        let config_version_id = store.get_version("c", 1).unwrap().config_version_id();
+        let snapshot_id = store.snapshot(config_version_id).unwrap();
+        store.remove_version("c", 1).unwrap();
+        store.remove_attr("region").unwrap();
+        let before = state(&store);
+        assert!(store.rollback_to(snapshot_id).is_err());
+        assert_eq!(state(&store), before);
+        assert!(store.get_version("c", 1).is_none());
+    }
+
+    #[test]
+    fn rejected_update_leaves_the_store_unchanged() {
+        let mut store = store();
+        let config_version_id = store.get_version("c", 1).unwrap().config_version_id();
+        let before = state(&store);
+        let region = store.registry().id_of("region").unwrap();
+
+        let err = store.update_version(config_version_id, 7, &ConfigPatch::default()).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::RevisionConflict { expected: 7, found: 0, .. }));
+        assert_eq!(state(&store), before);
+
+        let patch: ConfigPatch = serde_json::from_value(json!({
+            "ops": [{ "op": "set_precedence", "rank": 0, "attr_id": region, "match_type": 1 }],
+        }))
+        .unwrap();
+        assert!(store.update_version(config_version_id, 0, &patch).is_err());
+        assert_eq!(state(&store), before);
+    }
+
+    fn status(store: &ConfigStore) -> ConfigStatus {
+        store.get_version("c", 1).unwrap().status()
+    }
+
+    #[test]
+    fn versions_move_through_the_lifecycle_in_order() {
+        let mut store = store();
+        assert!(matches!(
+            store.publish("c", 1),
+            Err(PrecedenceConfigError::InvalidStatusTransition {
+                from: ConfigStatus::Draft,
+                to: ConfigStatus::Published,
+                ..
+            })
+        ));
+        store.submit_for_review("c", 1).unwrap();
+        store.revert_to_draft("c", 1).unwrap();
+        store.submit_for_review("c", 1).unwrap();
+        store.publish("c", 1).unwrap();
+        assert!(store.revert_to_draft("c", 1).is_err());
+        store.archive("c", 1).unwrap();
+        assert_eq!(status(&store), ConfigStatus::Archived);
+        assert_eq!(store.get_version("c", 1).unwrap().revision(), 5);
+    }
+
+    #[test]
+    fn only_drafts_can_be_edited() {
+        let mut store = store();
+        store.set_version_name("c", 1, "first").unwrap();
+        store.submit_for_review("c", 1).unwrap();
+        let before = state(&store);
+        assert!(matches!(
+            store.set_version_name("c", 1, "second"),
+            Err(PrecedenceConfigError::VersionNotEditable { status: ConfigStatus::Review, .. })
+        ));
+        assert!(store.set_rules("c", 1, store.get_version("c", 1).unwrap().rules().to_vec()).is_err());
+        assert_eq!(state(&store), before);
+        assert_eq!(store.get_version("c", 1).unwrap().version_name(), "first");
+    }
+
+    #[test]
+    fn strict_stores_only_resolve_published_versions() {
+        let lenient = store();
+        assert!(lenient.resolver("c", 1).is_ok());
+        let mut store = eu_store(StoreOptions { strict: true });
+        assert!(matches!(
+            store.resolver("c", 1),
+            Err(PrecedenceConfigError::UnpublishedVersion { status: ConfigStatus::Draft, .. })
+        ));
+        store.submit_for_review("c", 1).unwrap();
+        store.publish("c", 1).unwrap();
+        assert!(store.resolver("c", 1).is_ok());
+    }
+
+    /// Adds `c` `version` with `limit` on the same row and rules as v1; `window`
+    /// holds the version's `valid_from` / `valid_to`.
+    fn add_version(store: &mut ConfigStore, version: i32, limit: i64, window: serde_json::Value) {
+        let mut config = json!({ "name": "c", "version": version, "version_name": format!("v{version}") });
+        config.as_object_mut().unwrap().extend(window.as_object().unwrap().clone());
+        let envelope: ConfigEnvelope = serde_json::from_value(json!({
+            "config": config,
+            "rows": [{ "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": limit }] }],
+        }))
+        .unwrap();
+        let rules = store.get_version("c", 1).unwrap().rules().to_vec();
+        store.insert_envelope(&envelope, rules).unwrap();
+    }
+
+    fn at(day: u32) -> DateTime<Utc> {
+        chrono::TimeZone::with_ymd_and_hms(&Utc, 2025, 9, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn the_latest_version_in_its_window_is_in_force() {
+        let mut store = store();
+        let window = json!({ "valid_from": "2025-09-10T00:00:00Z", "valid_to": "2025-09-20T00:00:00Z" });
+        add_version(&mut store, 2, 7, window);
+        let in_force = |as_of| store.effective_version("c", as_of).map(ConfigVersion::version);
+        assert_eq!((in_force(at(9)), in_force(at(10)), in_force(at(20))), (Some(1), Some(2), Some(1)));
+        let eu = region_context("eu");
+        let limit = store.resolve_at("c", &eu, at(15)).unwrap().unwrap().params[0].value.clone();
+        assert_eq!(limit, TypedValue::Int(7));
+        assert!(matches!(store.resolve_at("d", &eu, at(15)), Err(PrecedenceConfigError::UnknownConfig { .. })));
+
+        let schedule = store.schedule("c").unwrap();
+        assert_eq!((schedule.version_at(at(12)), schedule.version_at(at(25))), (Some(2), Some(1)));
+        assert_eq!(schedule.resolve_at(&eu, at(12)).unwrap().params[0].value, TypedValue::Int(7));
+    }
+
+    #[test]
+    fn archived_versions_never_take_effect() {
+        let mut store = store();
+        add_version(&mut store, 2, 7, json!({ "status": "published" }));
+        assert_eq!(store.effective_version("c", at(1)).map(ConfigVersion::version), Some(2));
+        store.archive("c", 2).unwrap();
+        assert_eq!(store.effective_version("c", at(1)).map(ConfigVersion::version), Some(1));
+        assert_eq!(store.schedule("c").unwrap().version_at(at(1)), Some(1));
+    }
+
+    #[test]
+    fn a_window_must_end_after_it_starts() {
+        let mut store = store();
+        let err = store.set_validity("c", 1, Some(at(5)), Some(at(5))).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidValidityWindow { version: 1, .. }));
+        store.set_validity("c", 1, Some(at(5)), None).unwrap();
+        assert_eq!(store.get_version("c", 1).unwrap().valid_from(), Some(at(5)));
+        assert!(store.effective_version("c", at(4)).is_none());
+        assert_eq!(store.resolve_at("c", &region_context("eu"), at(4)).unwrap().map(|r| r.match_id), None);
+
+        let envelope: ConfigEnvelope = serde_json::from_value(json!({
+            "config": {
+                "name": "c",
+                "version": 2,
+                "version_name": "v2",
+                "valid_from": "2025-09-05T00:00:00Z",
+                "valid_to": "2025-09-01T00:00:00Z",
+            },
+            "rows": [],
+        }))
+        .unwrap();
+        let rules = store.get_version("c", 1).unwrap().rules().to_vec();
+        assert!(matches!(
+            store.insert_envelope(&envelope, rules),
+            Err(PrecedenceConfigError::InvalidValidityWindow { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn an_overlay_is_stored_as_a_version_based_on_its_base() {
+        let mut store = store();
+        let overlay: ConfigOverlay = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 2, "version_name": "v2" },
+            "based_on": 1,
+            "rows": [{ "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": 9 }] }],
+        }))
+        .unwrap();
+        store.insert_overlay(&overlay, None).unwrap();
+        let stored = store.get_version("c", 2).unwrap();
+        assert_eq!((stored.based_on(), stored.rules().len()), (Some(1), 1));
+        assert_eq!(store.diff_from_base("c", 2).unwrap().changed_rows.len(), 1);
+        assert!(store.insert_overlay(&overlay, None).is_err());
+    }
+
+    #[test]
+    fn versions_and_rows_are_stamped_with_the_actor() {
+        let mut store = ConfigStore::new(store().registry().clone());
+        store.set_actor(Some("alice"), Some("T-1"));
+        let envelope: ConfigEnvelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": [
+                { "match": { "region": "eu" }, "params": [], "audit": { "created_by": "bob" } },
+                { "match": { "region": "us" }, "params": [] },
+            ],
+        }))
+        .unwrap();
+        store.insert_envelope(&envelope, Vec::new()).unwrap();
+        let by = |store: &ConfigStore, match_id| {
+            let audit = store.get_version("c", 1).unwrap().row_audit(match_id).unwrap().clone();
+            (audit.created_by.unwrap(), audit.reason.unwrap())
+        };
+        let version = store.get_version("c", 1).unwrap().audit().unwrap();
+        assert_eq!((version.created_by.as_deref(), version.created_at.is_some()), (Some("alice"), true));
+        assert_eq!(by(&store, 1), ("bob".to_string(), "T-1".to_string()));
+        assert_eq!(by(&store, 2), ("alice".to_string(), "T-1".to_string()));
+        let exported = store.envelope("c", 1).unwrap();
+        assert_eq!(exported.rows[0].audit.as_ref().unwrap().created_by.as_deref(), Some("bob"));
+
+        store.set_actor(Some("carol"), Some("T-2"));
+        let patch: ConfigPatch = serde_json::from_value(json!({ "ops": [{
+            "op": "set_param", "match": { "region": "us" }, "key": "limit", "value": { "type": "int", "value": 3 },
+        }] }))
+        .unwrap();
+        store.apply_patch("c", 1, &patch).unwrap();
+        assert_eq!(by(&store, 1), ("bob".to_string(), "T-1".to_string()));
+        assert_eq!(by(&store, 2), ("carol".to_string(), "T-2".to_string()));
+    }
+
+    #[test]
+    fn rollback_restores_a_snapshot_and_moves_the_revision_on() {
+        let mut store = store();
+        let config_version_id = store.get_version("c", 1).unwrap().config_version_id();
+        let snapshot_id = store.snapshot(config_version_id).unwrap();
+        let limit = |store: &ConfigStore| store.envelope("c", 1).unwrap().rows[0].params[0].value.clone();
+        store.set_version_name("c", 1, "renamed").unwrap();
+        store.submit_for_review("c", 1).unwrap();
+        store.publish("c", 1).unwrap();
+        let published = store.get_version("c", 1).unwrap().revision();
+
+        store.rollback_to(snapshot_id).unwrap();
+        let restored = store.get_version("c", 1).unwrap();
+        assert_eq!((restored.version_name(), restored.status()), ("v1", ConfigStatus::Draft));
+        assert_eq!(restored.revision(), published + 1);
+        assert_eq!(limit(&store), json!(5));
+        assert_eq!(store.changelog().entries().last().unwrap().change, Change::RolledBack { snapshot_id });
+
+        store.remove_version("c", 1).unwrap();
+        store.rollback_to(snapshot_id).unwrap();
+        assert!(store.get_version("c", 1).is_some());
+        let snapshot = store.get_snapshot(snapshot_id).unwrap();
+        assert_eq!((snapshot.name(), snapshot.version().version_name()), ("c", "v1"));
+        assert_eq!(store.snapshots().count(), 1);
+        store.remove_snapshot(snapshot_id).unwrap();
+        assert!(matches!(store.rollback_to(snapshot_id), Err(PrecedenceConfigError::UnknownSnapshot { .. })));
+        assert!(matches!(store.snapshot(99), Err(PrecedenceConfigError::UnknownConfigVersionId { .. })));
+    }
+
+    #[test]
+    fn updates_apply_only_at_the_expected_revision() {
+        let mut store = store();
+        let config_version_id = store.get_version("c", 1).unwrap().config_version_id();
+        let rename = |to: &str| -> ConfigPatch {
+            serde_json::from_value(json!({ "ops": [{
+                "op": "set_config", "config": { "name": "c", "version": 1, "version_name": to },
+            }] }))
+            .unwrap()
+        };
+        let revision = store.update_version(config_version_id, 0, &rename("first")).unwrap();
+        assert_eq!(revision, 1);
+        // a second writer that read revision 0 loses
+        let err = store.update_version(config_version_id, 0, &rename("second")).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::RevisionConflict { expected: 0, found: 1, .. }));
+        assert_eq!(store.update_version(config_version_id, 1, &rename("second")).unwrap(), 2);
+        assert_eq!(store.get_version("c", 1).unwrap().version_name(), "second");
+        assert!(matches!(
+            store.update_version(99, 0, &rename("x")),
+            Err(PrecedenceConfigError::UnknownConfigVersionId { config_version_id: 99 })
+        ));
+
+        let mut storage = crate::config_storage::MemoryStorage::new();
+        store.save(&mut storage).unwrap();
+        assert_eq!(ConfigStore::load(&storage).unwrap().get_version("c", 1).unwrap().revision(), 2);
+    }
+}
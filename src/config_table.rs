@@ -0,0 +1,292 @@
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use crate::config_resolver::{Context, ResolvedConfig, Resolver, ValueKey};
+use crate::config_value::{parse_str_value, AttrMeta, TypedValue};
+use std::collections::HashMap;
+
+/// Most cells [`Resolver::materialize_table`] will precompute.
+pub const MAX_TABLE_CELLS: usize = 1 << 20;
+
+/// Every lookup a [`Resolver`] can answer over small attribute domains, resolved
+/// ahead of time by [`Resolver::materialize_table`].
+///
+/// A lookup maps each attribute's context value to its position in the
+/// attribute's domain and reads one cell, without touching a row. Context
+/// values are matched as exactly as the resolver compares them; `null` and
+/// absent values are not supplied, and attributes no row constrains are ignored.
+#[derive(Debug, Clone)]
+pub struct LookupTable {
+    /// in attr_id order; the first has the largest stride
+    attrs: Vec<TableAttr>,
+    /// per combination, an index into `outcomes`
+    cells: Vec<u32>,
+    outcomes: Vec<Outcome>,
+}
+
+#[derive(Debug, Clone)]
+struct TableAttr {
+    name: String,
+    /// value -> digit; digit 0 is "not supplied"
+    digits: HashMap<ValueKey, usize>,
+    stride: usize,
+}
+
+/// What the resolver answered for a combination, errors included.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum OutcomeKey {
+    Row { match_id: i32, rank: i32 },
+    None,
+    Ambiguous { rank: i32, match_ids: Vec<i32> },
+    NoMatch,
+}
+
+#[derive(Debug, Clone)]
+enum Outcome {
+    Resolved(Option<ResolvedConfig>),
+    Ambiguous { rank: i32, match_ids: Vec<i32> },
+    NoMatch,
+}
+
+impl Resolver {
+    /// Precomputes the answer for every combination of the match attributes' values.
+    ///
+    /// Every attribute a row constrains must have a small enumerable domain: a
+    /// `bool`, an `enum`, an attribute with `allowed` values, or an `int` bounded
+    /// by `min` and `max` (`UnenumerableDomain` otherwise). Each attribute adds
+    /// "not supplied" to its domain, and the product of the domain sizes may not
+    /// pass [`MAX_TABLE_CELLS`] (`TableTooLarge`). The resolver's strategy,
+    /// tie-break and default row all apply, as the cells are filled by
+    /// [`Resolver::try_resolve`].
+    pub fn materialize_table(&self) -> Result<LookupTable> {
+        let mut domains = Vec::new();
+        for (&attr_id, domain) in self.domains() {
+            let name = self.attr_name(attr_id);
+            let attr = || name.map_or(AttrRef::Id(attr_id), |name| AttrRef::Name(name.to_string()));
+            let (Some(name), Some(values)) = (name, domain) else {
+                return Err(PrecedenceConfigError::UnenumerableDomain { attr: attr() });
+            };
+            domains.push((name.to_string(), values));
+        }
+        let cells: u128 = domains.iter().map(|(_, values)| values.len() as u128 + 1).product();
+        if cells > MAX_TABLE_CELLS as u128 {
+            return Err(PrecedenceConfigError::TableTooLarge { cells, max_cells: MAX_TABLE_CELLS });
+        }
+
+        let mut attrs: Vec<TableAttr> = Vec::with_capacity(domains.len());
+        let mut stride = cells as usize;
+        for (name, values) in &domains {
+            stride /= values.len() + 1;
+            let digits = values.iter().enumerate().map(|(i, value)| (ValueKey::from(value), i + 1)).collect();
+            attrs.push(TableAttr { name: name.clone(), digits, stride });
+        }
+
+        let mut table = LookupTable { attrs, cells: Vec::with_capacity(cells as usize), outcomes: Vec::new() };
+        let mut seen: HashMap<OutcomeKey, u32> = HashMap::new();
+        let mut context = Context::new();
+        for cell in 0..cells as usize {
+            context.clear();
+            for ((name, values), attr) in domains.iter().zip(&table.attrs) {
+                let digit = cell / attr.stride % (values.len() + 1);
+                if digit > 0 {
+                    context.insert(name.clone(), values[digit - 1].clone());
+                }
+            }
+            let (key, outcome) = match self.try_resolve(&context) {
+                Ok(Some(resolved)) => {
+                    let key = OutcomeKey::Row { match_id: resolved.match_id, rank: resolved.rank };
+                    (key, Outcome::Resolved(Some(resolved)))
+                }
+                Ok(None) => (OutcomeKey::None, Outcome::Resolved(None)),
+                Err(PrecedenceConfigError::AmbiguousMatch { rank, match_ids }) => (
+                    OutcomeKey::Ambiguous { rank, match_ids: match_ids.clone() },
+                    Outcome::Ambiguous { rank, match_ids },
+                ),
+                Err(PrecedenceConfigError::NoMatchingRow) => (OutcomeKey::NoMatch, Outcome::NoMatch),
+                Err(e) => return Err(e),
+            };
+            let index = *seen.entry(key).or_insert_with(|| {
+                table.outcomes.push(outcome);
+                table.outcomes.len() as u32 - 1
+            });
+            table.cells.push(index);
+        }
+        Ok(table)
+    }
+}
+
+impl LookupTable {
+    /// The precomputed [`Resolver::resolve`]; `None` also for a context value
+    /// outside the table's domains (see [`LookupTable::try_resolve`]).
+    pub fn resolve(&self, context: &Context) -> Option<&ResolvedConfig> {
+        self.try_resolve(context).ok().flatten()
+    }
+
+    /// The precomputed [`Resolver::try_resolve`], failing with `OutOfTableDomain`
+    /// for a context value the table has no cell for.
+    pub fn try_resolve(&self, context: &Context) -> Result<Option<&ResolvedConfig>> {
+        let mut cell = 0;
+        for attr in &self.attrs {
+            let digit = match context.get(&attr.name) {
+                None | Some(TypedValue::Null) => 0,
+                Some(value) => *attr.digits.get(&ValueKey::from(value)).ok_or_else(|| {
+                    PrecedenceConfigError::OutOfTableDomain { attr_name: attr.name.clone(), value: value.to_string() }
+                })?,
+            };
+            cell += digit * attr.stride;
+        }
+        match &self.outcomes[self.cells[cell] as usize] {
+            Outcome::Resolved(resolved) => Ok(resolved.as_ref()),
+            Outcome::Ambiguous { rank, match_ids } => {
+                Err(PrecedenceConfigError::AmbiguousMatch { rank: *rank, match_ids: match_ids.clone() })
+            }
+            Outcome::NoMatch => Err(PrecedenceConfigError::NoMatchingRow),
+        }
+    }
+
+    /// Names of the attributes the table is keyed by, in attr_id order.
+    pub fn attr_names(&self) -> impl Iterator<Item = &str> {
+        self.attrs.iter().map(|attr| attr.name.as_str())
+    }
+
+    /// Number of precomputed combinations.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Number of distinct answers among the cells.
+    pub fn outcome_count(&self) -> usize {
+        self.outcomes.len()
+    }
+}
+
+/// Every value of `meta`, if it is a match attribute with a small enumerable domain.
+pub(crate) fn domain_of(meta: &AttrMeta) -> Option<Vec<TypedValue>> {
+    if let Some(allowed) = meta.constraints.as_ref().and_then(|c| c.allowed.as_ref()) {
+        return allowed.iter().map(|raw| parse_str_value(meta, raw).ok()).collect();
+    }
+    match meta.data_type.as_str() {
+        "bool" => Some(vec![TypedValue::Bool(false), TypedValue::Bool(true)]),
+        "enum" if !meta.enum_values.is_empty() => {
+            Some(meta.enum_values.iter().map(|v| TypedValue::Enum(v.clone())).collect())
+        }
+        "int" => {
+            let constraints = meta.constraints.as_ref()?;
+            let (min, max) = (constraints.min?.ceil(), constraints.max?.floor());
+            if !(min.is_finite() && max.is_finite()) || max - min >= MAX_TABLE_CELLS as f64 {
+                return None;
+            }
+            Some((min as i64..=max as i64).map(TypedValue::Int).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_attr::AttrRegistry;
+    use crate::config_envelope::envelope_to_config_values;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use serde_json::{json, Value};
+
+    /// `region` (enum eu/us) and `vip` (bool) match attributes, and `limit`.
+    fn registry(region: Value) -> AttrRegistry {
+        let metas = json!([
+            region,
+            { "attr_id": 2, "attr_name": "vip", "data_type": "bool", "role": "match" },
+            { "attr_id": 3, "attr_name": "limit", "data_type": "int", "role": "param" },
+        ]);
+        AttrRegistry::from_metas(serde_json::from_value::<Vec<AttrMeta>>(metas).unwrap()).unwrap()
+    }
+
+    fn enum_region() -> Value {
+        json!({
+            "attr_id": 1, "attr_name": "region", "data_type": "enum", "role": "match",
+            "enum_values": ["eu", "us"],
+        })
+    }
+
+    /// Rank 1 matches region and vip, rank 2 region alone.
+    fn resolver(registry: &AttrRegistry, rows: &[(Value, i64)]) -> Resolver {
+        let matrix = r#"[{ "rank": 1, "region": 1, "vip": 1 }, { "rank": 2, "region": 1, "vip": 0 }]"#;
+        let rows: Vec<Value> = rows
+            .iter()
+            .map(|(match_part, limit)| {
+                json!({ "match": match_part, "params": [{ "key": "limit", "type": "int", "value": limit }] })
+            })
+            .collect();
+        let envelope = serde_json::from_value(json!({
+            "config": { "name": "c", "version": 1, "version_name": "v1" },
+            "rows": rows,
+        }))
+        .unwrap();
+        let values = envelope_to_config_values(&envelope, registry).unwrap();
+        Resolver::new(&matrix_json_to_tall(matrix, 1, registry).unwrap(), &values, registry).unwrap()
+    }
+
+    fn rows() -> Vec<(Value, i64)> {
+        vec![(json!({ "region": "eu", "vip": true }), 1), (json!({ "region": "eu", "vip": null }), 2)]
+    }
+
+    fn context(pairs: &[(&str, TypedValue)]) -> Context {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+    }
+
+    #[test]
+    fn every_cell_answers_as_the_resolver_does() {
+        let registry = registry(enum_region());
+        let resolver = resolver(&registry, &rows());
+        let table = resolver.materialize_table().unwrap();
+        assert_eq!(table.attr_names().collect::<Vec<_>>(), ["region", "vip"]);
+        // (eu, us, not supplied) x (false, true, not supplied); answers rank 1, rank 2 or none
+        assert_eq!((table.len(), table.outcome_count()), (9, 3));
+        let regions = [None, Some(TypedValue::Enum("eu".into())), Some(TypedValue::Enum("us".into()))];
+        let vips = [None, Some(TypedValue::Bool(false)), Some(TypedValue::Bool(true)), Some(TypedValue::Null)];
+        for region in &regions {
+            for vip in &vips {
+                let pairs = [("region", region), ("vip", vip)];
+                let context: Context = pairs
+                    .into_iter()
+                    .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+                    .collect();
+                let expected = resolver.try_resolve(&context).unwrap().map(|r| (r.match_id, r.rank));
+                assert_eq!(table.try_resolve(&context).unwrap().map(|r| (r.match_id, r.rank)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn values_outside_the_domains_are_rejected() {
+        let registry = registry(enum_region());
+        let table = resolver(&registry, &rows()).materialize_table().unwrap();
+        let jp = context(&[("region", TypedValue::Enum("jp".into()))]);
+        assert!(matches!(
+            table.try_resolve(&jp),
+            Err(PrecedenceConfigError::OutOfTableDomain { attr_name, .. }) if attr_name == "region"
+        ));
+        assert!(table.resolve(&jp).is_none());
+    }
+
+    #[test]
+    fn only_small_enumerable_domains_are_materialized() {
+        let free_text = json!({ "attr_id": 1, "attr_name": "region", "data_type": "str", "role": "match" });
+        let rows = [(json!({ "region": "eu", "vip": true }), 1)];
+        assert!(matches!(
+            resolver(&registry(free_text), &rows).materialize_table(),
+            Err(PrecedenceConfigError::UnenumerableDomain { attr: AttrRef::Name(name) }) if name == "region"
+        ));
+
+        let wide = json!({
+            "attr_id": 1, "attr_name": "region", "data_type": "int", "role": "match",
+            "constraints": { "min": 0.0, "max": 999_999.0 },
+        });
+        let rows = [(json!({ "region": 7, "vip": true }), 1)];
+        assert!(matches!(
+            resolver(&registry(wide), &rows).materialize_table(),
+            Err(PrecedenceConfigError::TableTooLarge { cells: 3_000_003, max_cells: MAX_TABLE_CELLS })
+        ));
+    }
+}
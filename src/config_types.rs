@@ -1,48 +1,243 @@
+use crate::config_attr::AttrName;
+use crate::config_error::{PrecedenceConfigError, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Layout version written into new envelopes. Documents of older layouts are
+/// upgraded by [`migrate_envelope`](crate::config_migration::migrate_envelope).
+pub const ENVELOPE_SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ConfigEnvelope {
+    /// Layout of the document; when missing (as in version 2 documents, which
+    /// differ in nothing else) it reads as [`ENVELOPE_SCHEMA_VERSION`].
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub config: ConfigMeta,
     pub rows: Vec<ConfigRow>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+fn current_schema_version() -> u32 {
+    ENVELOPE_SCHEMA_VERSION
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ConfigMeta {
     pub name: String,
     pub version: i32,
     pub version_name: String,
+    /// Lifecycle state; left out of the serialized form while `draft`.
+    #[serde(default, skip_serializing_if = "ConfigStatus::is_draft")]
+    pub status: ConfigStatus,
+    /// First instant the version applies (inclusive); unset means since forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// Instant the version stops applying (exclusive); unset means open-ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<DateTime<Utc>>,
+    /// Who created the version, when and why.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditInfo>,
+}
+
+/// Who made a change, when and why; attached to versions and rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Change reason, e.g. a ticket reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditInfo {
+    pub fn is_empty(&self) -> bool {
+        self.created_by.is_none() && self.created_at.is_none() && self.reason.is_none()
+    }
+
+    /// Fills the fields `self` leaves unset from `other`.
+    pub fn or(self, other: &AuditInfo) -> AuditInfo {
+        AuditInfo {
+            created_by: self.created_by.or_else(|| other.created_by.clone()),
+            created_at: self.created_at.or(other.created_at),
+            reason: self.reason.or_else(|| other.reason.clone()),
+        }
+    }
+}
+
+impl ConfigMeta {
+    /// Whether `as_of` falls in `[valid_from, valid_to)`.
+    pub fn is_effective_at(&self, as_of: DateTime<Utc>) -> bool {
+        is_effective_at(self.valid_from, self.valid_to, as_of)
+    }
+}
+
+pub(crate) fn is_effective_at(
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+    as_of: DateTime<Utc>,
+) -> bool {
+    valid_from.is_none_or(|from| from <= as_of) && valid_to.is_none_or(|to| as_of < to)
+}
+
+/// Rejects a window that ends at or before it starts.
+pub(crate) fn check_validity(
+    name: &str,
+    version: i32,
+    valid_from: Option<DateTime<Utc>>,
+    valid_to: Option<DateTime<Utc>>,
+) -> Result<()> {
+    match (valid_from, valid_to) {
+        (Some(from), Some(to)) if to <= from => Err(PrecedenceConfigError::InvalidValidityWindow {
+            name: name.to_string(),
+            version,
+            valid_from: from,
+            valid_to: to,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Where a config version is in its lifecycle: `draft` -> `review` -> `published` -> `archived`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigStatus {
+    /// Being edited; the only state whose rules and values can change.
+    #[default]
+    Draft,
+    /// Frozen for sign-off; goes on to `published` or back to `draft`.
+    Review,
+    /// Live.
+    Published,
+    /// Retired; kept for audit.
+    Archived,
+}
+
+impl ConfigStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigStatus::Draft => "draft",
+            ConfigStatus::Review => "review",
+            ConfigStatus::Published => "published",
+            ConfigStatus::Archived => "archived",
+        }
+    }
+
+    pub fn is_draft(&self) -> bool {
+        *self == ConfigStatus::Draft
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl fmt::Display for ConfigStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ConfigRow {
     #[serde(flatten)]
     pub match_part: MatchPart,
     pub params: Vec<Param>,
+    /// Who last changed the row, when and why.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<AuditInfo>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct MatchPart {
     // All dynamic match attributes live here
     #[serde(rename = "match")]
-    pub attrs: HashMap<String, serde_json::Value>, // allow null/number/string
+    pub attrs: HashMap<AttrName, serde_json::Value>, // allow null/number/string
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Param {
-    pub key: String,
+    pub key: AttrName,
     #[serde(rename = "type")]
     pub ty: ParamType,
     pub value: serde_json::Value, // validated downstream based on ty
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
+/// CONFIG_ATTR `DATA_TYPE`, (de)serialized by its spelling: `int`, `dec`, `str`,
+/// `bool`, `dt`, or `list<T>` for a list of `T` (e.g. `list<str>`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum ParamType {
     Int,
     Dec,
     Str,
     Bool,
     Dt,
+    Date,
+    Time,
+    Duration,
+    /// Closed set of strings listed in `AttrMeta::enum_values`.
+    Enum,
+    /// Requires the `uuid` feature.
+    #[cfg(feature = "uuid")]
+    Uuid,
+    List(Box<ParamType>),
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::Int => f.write_str("int"),
+            ParamType::Dec => f.write_str("dec"),
+            ParamType::Str => f.write_str("str"),
+            ParamType::Bool => f.write_str("bool"),
+            ParamType::Dt => f.write_str("dt"),
+            ParamType::Date => f.write_str("date"),
+            ParamType::Time => f.write_str("time"),
+            ParamType::Duration => f.write_str("duration"),
+            ParamType::Enum => f.write_str("enum"),
+            #[cfg(feature = "uuid")]
+            ParamType::Uuid => f.write_str("uuid"),
+            ParamType::List(elem) => write!(f, "list<{}>", elem),
+        }
+    }
+}
+
+impl FromStr for ParamType {
+    type Err = PrecedenceConfigError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "int" => Ok(ParamType::Int),
+            "dec" => Ok(ParamType::Dec),
+            "str" => Ok(ParamType::Str),
+            "bool" => Ok(ParamType::Bool),
+            "dt" => Ok(ParamType::Dt),
+            "date" => Ok(ParamType::Date),
+            "time" => Ok(ParamType::Time),
+            "duration" => Ok(ParamType::Duration),
+            "enum" => Ok(ParamType::Enum),
+            #[cfg(feature = "uuid")]
+            "uuid" => Ok(ParamType::Uuid),
+            other => match other.strip_prefix("list<").and_then(|rest| rest.strip_suffix('>')) {
+                Some(elem) => Ok(ParamType::List(Box::new(elem.parse()?))),
+                None => Err(PrecedenceConfigError::InvalidDataType(other.to_string())),
+            },
+        }
+    }
 }
 
+impl TryFrom<String> for ParamType {
+    type Error = PrecedenceConfigError;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<ParamType> for String {
+    fn from(value: ParamType) -> Self {
+        value.to_string()
+    }
+}
@@ -0,0 +1,190 @@
+//! Incremental checks on tall precedence rules, for ingestion pipelines that
+//! stream rules in and can't hold them all for
+//! [`validate_ranks`](crate::config_precidence_rules::validate_ranks).
+
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{AttrRef, PrecedenceConfigError, Result};
+use crate::config_precidence_rules::{check_rank_set, ConfigPrecedenceRule, MatchType, RankScheme};
+use crate::config_value::AttrRole;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+
+/// Checks tall rules one at a time, keeping only the (rank, attr_id) pairs and
+/// ranks seen so far.
+///
+/// [`push_row`](Validator::push_row) checks what a single rule can show: a
+/// positive rank, an attr_id of a match attribute in the registry, and no
+/// (rank, attr_id) pair given twice. [`finish`](Validator::finish) checks the
+/// ranks as a whole like [`validate_ranks`]. A rejected rule is not recorded,
+/// so the caller may log it and keep pushing.
+///
+/// [`validate_ranks`]: crate::config_precidence_rules::validate_ranks
+#[derive(Debug, Clone)]
+pub struct Validator<'a> {
+    registry: &'a AttrRegistry,
+    attr_count: usize,
+    scheme: RankScheme,
+    index: usize,
+    seen: HashSet<(i32, i32)>,
+    ranks: BTreeSet<i32>,
+    exact: usize,
+}
+
+/// What a [`Validator`] accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationSummary {
+    pub rules: usize,
+    pub ranks: usize,
+    /// distinct attr_ids
+    pub attrs: usize,
+    /// rules with `MatchType::Exact`
+    pub exact: usize,
+}
+
+impl<'a> Validator<'a> {
+    /// Validator for the rank scheme `scheme` over `attr_count` attributes.
+    pub fn new(registry: &'a AttrRegistry, attr_count: usize, scheme: RankScheme) -> Self {
+        Self {
+            registry,
+            attr_count,
+            scheme,
+            index: 0,
+            seen: HashSet::new(),
+            ranks: BTreeSet::new(),
+            exact: 0,
+        }
+    }
+
+    /// Validator for [`RankScheme::Triangular`], the ranks `validate_ranks_contiguous_and_triangular` expects.
+    pub fn triangular(registry: &'a AttrRegistry, attr_count: usize) -> Self {
+        Self::new(registry, attr_count, RankScheme::Triangular)
+    }
+
+    /// Checks the next rule; its index in errors counts every rule pushed.
+    pub fn push_row(&mut self, rule: &ConfigPrecedenceRule) -> Result<()> {
+        let index = self.index;
+        self.index += 1;
+
+        if rule.rank <= 0 {
+            return Err(PrecedenceConfigError::InvalidRank { index, rank: rule.rank });
+        }
+        let meta = self
+            .registry
+            .get_by_id(rule.attr_id)
+            .ok_or(PrecedenceConfigError::UnknownRuleAttr { rank: rule.rank, attr: AttrRef::Id(rule.attr_id) })?;
+        if meta.role != AttrRole::Match {
+            return Err(PrecedenceConfigError::WrongRole {
+                key: meta.attr_name.to_string(),
+                expected: AttrRole::Match,
+                found: meta.role,
+            });
+        }
+        if !self.seen.insert((rule.rank, rule.attr_id)) {
+            return Err(PrecedenceConfigError::DuplicateRankAttr {
+                rank: rule.rank,
+                attr_id: rule.attr_id,
+                attr_name: meta.attr_name.to_string(),
+            });
+        }
+
+        self.ranks.insert(rule.rank);
+        if rule.match_type == MatchType::Exact {
+            self.exact += 1;
+        }
+        Ok(())
+    }
+
+    /// Number of rules accepted so far.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Checks the ranks of every accepted rule: at least one rule, and ranks
+    /// exactly 1..=N for the N the scheme expects.
+    pub fn finish(self) -> Result<ValidationSummary> {
+        if self.seen.is_empty() {
+            return Err(PrecedenceConfigError::NoPrecedenceRules);
+        }
+        check_rank_set(&self.ranks, self.attr_count, &self.scheme)?;
+        let attrs: HashSet<i32> = self.seen.iter().map(|&(_, attr_id)| attr_id).collect();
+        Ok(ValidationSummary { rules: self.seen.len(), ranks: self.ranks.len(), attrs: attrs.len(), exact: self.exact })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::{generate_triangular_matrix, matrix_json_to_tall};
+    use crate::test_fixtures::region_channel_registry;
+
+    fn rule(rank: i32, attr_id: i32, match_type: MatchType) -> ConfigPrecedenceRule {
+        ConfigPrecedenceRule { config_version_id: 1, rank, attr_id, match_type }
+    }
+
+    #[test]
+    fn a_streamed_triangular_table_validates() {
+        let registry = region_channel_registry();
+        let matrix = serde_json::to_string(&generate_triangular_matrix(&["region", "channel"])).unwrap();
+        let mut validator = Validator::triangular(&registry, 2);
+        assert!(validator.is_empty());
+        for rule in matrix_json_to_tall(&matrix, 1, &registry).unwrap() {
+            validator.push_row(&rule).unwrap();
+        }
+        assert_eq!(validator.len(), 6);
+        assert_eq!(validator.finish().unwrap(), ValidationSummary { rules: 6, ranks: 3, attrs: 2, exact: 4 });
+    }
+
+    #[test]
+    fn rejected_rules_are_not_recorded() {
+        let registry = region_channel_registry();
+        let mut validator = Validator::triangular(&registry, 1);
+        assert!(matches!(
+            validator.push_row(&rule(0, 1, MatchType::Exact)),
+            Err(PrecedenceConfigError::InvalidRank { index: 0, rank: 0 })
+        ));
+        assert!(matches!(
+            validator.push_row(&rule(1, 9, MatchType::Exact)),
+            Err(PrecedenceConfigError::UnknownRuleAttr { rank: 1, attr: AttrRef::Id(9) })
+        ));
+        assert!(matches!(
+            validator.push_row(&rule(1, 3, MatchType::Exact)),
+            Err(PrecedenceConfigError::WrongRole { found: AttrRole::Param, .. })
+        ));
+        validator.push_row(&rule(1, 1, MatchType::Exact)).unwrap();
+        assert!(matches!(
+            validator.push_row(&rule(1, 1, MatchType::Ignore)),
+            Err(PrecedenceConfigError::DuplicateRankAttr { rank: 1, attr_id: 1, .. })
+        ));
+        let summary = validator.finish().unwrap();
+        assert_eq!((summary.rules, summary.exact), (1, 1));
+    }
+
+    #[test]
+    fn finish_checks_the_ranks_as_a_whole() {
+        let registry = region_channel_registry();
+        assert!(matches!(
+            Validator::triangular(&registry, 2).finish(),
+            Err(PrecedenceConfigError::NoPrecedenceRules)
+        ));
+
+        let mut validator = Validator::triangular(&registry, 2);
+        validator.push_row(&rule(1, 1, MatchType::Exact)).unwrap();
+        assert!(matches!(
+            validator.finish(),
+            Err(PrecedenceConfigError::NonTriangularRankCount { found: 1, expected: 3, attr_count: 2 })
+        ));
+
+        let mut validator = Validator::triangular(&registry, 2);
+        for rank in [1, 2, 4] {
+            validator.push_row(&rule(rank, 1, MatchType::Exact)).unwrap();
+        }
+        assert!(matches!(
+            validator.finish(),
+            Err(PrecedenceConfigError::NonContiguousRank { rank: 4, expected: 3 })
+        ));
+    }
+}
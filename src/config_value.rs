@@ -1,40 +1,740 @@
-use chrono::NaiveDateTime;
-use anyhow::{bail, Result};
-use std::collections::HashMap;
-use serde::Deserialize;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat};
+use crate::config_attr::{AttrName, AttrRegistry};
+use crate::config_types::{Param, ParamType};
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use serde::de::{self, Deserializer};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigValue {
     pub match_id: i32,
     pub attr_id: i32,
-    pub role: String, // "match" or "param"
+    pub role: AttrRole,
     pub value: TypedValue,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypedValue {
     Int(i64),
     Dec(f64),
+    /// Exact `dec` value; with the `rust_decimal` feature `dec` attributes parse into this instead of `Dec`.
+    #[cfg(feature = "rust_decimal")]
+    Decimal(rust_decimal::Decimal),
     Str(String),
+    /// `enum` attribute value, one of the attribute's `enum_values`.
+    Enum(String),
     Bool(bool),
+    /// UTC datetime without an offset in its text form (`2025-08-22T00:00:00Z`).
     Dt(NaiveDateTime),
+    /// Datetime that carried an explicit offset (`2025-08-22T02:00:00+02:00`);
+    /// compares by instant, so the same moment at different offsets is equal.
+    DtTz(DateTime<FixedOffset>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    /// Non-negative span; text form is ISO-8601 (`PT30M`) or humantime-style (`30m`, `1h30m`).
+    Duration(Duration),
+    /// `uuid` attribute (with the `uuid` feature); any accepted spelling compares equal.
+    #[cfg(feature = "uuid")]
+    Uuid(uuid::Uuid),
+    List(Vec<TypedValue>),
+    /// Match values only: any value in the range matches, see [`ValueRange`].
+    Range(Box<ValueRange>),
+    /// Match values only: any of these values matches, see [`parse_one_of_value`].
+    OneOf(Vec<TypedValue>),
+    /// Match values only: any `str` / `enum` value fitting the pattern matches, see [`StrPattern`].
+    Pattern(StrPattern),
+    /// Match values only: any `str` / `enum` value the regex matches, see [`RegexMatch`].
+    #[cfg(feature = "regex")]
+    Regex(RegexMatch),
+    /// No value. As a match value the attribute is left unconstrained (like `"ALL"`);
+    /// in a context it counts as not supplied.
+    Null,
+}
+
+impl TypedValue {
+    /// Tag used in the serialized form: the `DATA_TYPE` spelling, or `list` / `null`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TypedValue::Int(_) => "int",
+            TypedValue::Dec(_) => "dec",
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(_) => "dec",
+            TypedValue::Str(_) => "str",
+            TypedValue::Enum(_) => "enum",
+            TypedValue::Bool(_) => "bool",
+            TypedValue::Dt(_) | TypedValue::DtTz(_) => "dt",
+            TypedValue::Date(_) => "date",
+            TypedValue::Time(_) => "time",
+            TypedValue::Duration(_) => "duration",
+            #[cfg(feature = "uuid")]
+            TypedValue::Uuid(_) => "uuid",
+            TypedValue::List(_) => "list",
+            TypedValue::Range(_) => "range",
+            TypedValue::OneOf(_) => "one_of",
+            TypedValue::Pattern(_) => "pattern",
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(_) => "regex",
+            TypedValue::Null => "null",
+        }
+    }
+
+    /// The untagged envelope form read back by [`parse_json_value`]: ints and
+    /// bools as JSON scalars, lists and alternatives as arrays, ranges as
+    /// `{ "gte": .., "lt": .. }` objects (see [`parse_range_value`]), `Null` as `null`, everything else
+    /// (decimals included, so `Decimal` keeps its scale) as its text form.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        use serde_json::Value;
+
+        match self {
+            TypedValue::Int(v) => Value::from(*v),
+            TypedValue::Bool(v) => Value::Bool(*v),
+            TypedValue::List(items) | TypedValue::OneOf(items) => {
+                Value::Array(items.iter().map(TypedValue::to_json_value).collect())
+            }
+            TypedValue::Range(range) => range.to_json_value(),
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(regex) => serde_json::json!({ "pattern": regex.as_str() }),
+            TypedValue::Null => Value::Null,
+            other => Value::String(other.to_string()),
+        }
+    }
+}
+
+/// Serialized as `{ "type": "dec", "value": "0.125" }`. Decimals and datetimes are
+/// written as strings (`DT_FORMAT`) so they round-trip exactly; lists hold tagged
+/// elements. With the `rust_decimal` feature a `dec` tag reads back as `Decimal`.
+impl Serialize for TypedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut tagged = serializer.serialize_struct("TypedValue", 2)?;
+        tagged.serialize_field("type", self.type_name())?;
+        match self {
+            TypedValue::Int(v) => tagged.serialize_field("value", v)?,
+            TypedValue::Dec(v) => tagged.serialize_field("value", &v.to_string())?,
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(v) => tagged.serialize_field("value", &v.to_string())?,
+            TypedValue::Str(v) | TypedValue::Enum(v) => tagged.serialize_field("value", v)?,
+            TypedValue::Bool(v) => tagged.serialize_field("value", v)?,
+            TypedValue::Dt(v) => tagged.serialize_field("value", &v.format(DT_FORMAT).to_string())?,
+            TypedValue::DtTz(v) => tagged.serialize_field("value", &format_dt_tz(v))?,
+            TypedValue::Date(v) => tagged.serialize_field("value", &v.format(DATE_FORMAT).to_string())?,
+            TypedValue::Time(v) => tagged.serialize_field("value", &v.format(TIME_FORMAT).to_string())?,
+            TypedValue::Duration(v) => tagged.serialize_field("value", &format_duration(*v))?,
+            #[cfg(feature = "uuid")]
+            TypedValue::Uuid(v) => tagged.serialize_field("value", &v.to_string())?,
+            TypedValue::List(items) | TypedValue::OneOf(items) => tagged.serialize_field("value", items)?,
+            TypedValue::Range(range) => tagged.serialize_field("value", range)?,
+            TypedValue::Pattern(pattern) => tagged.serialize_field("value", &pattern.to_string())?,
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(regex) => tagged.serialize_field("value", regex.as_str())?,
+            TypedValue::Null => tagged.serialize_field("value", &())?,
+        }
+        tagged.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TypedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Tagged {
+            #[serde(rename = "type")]
+            ty: String,
+            #[serde(default)]
+            value: serde_json::Value,
+        }
+
+        let tagged = Tagged::deserialize(deserializer)?;
+        match tagged.ty.as_str() {
+            "null" => Ok(TypedValue::Null),
+            "list" => Vec::<TypedValue>::deserialize(tagged.value)
+                .map(TypedValue::List)
+                .map_err(de::Error::custom),
+            "one_of" => Vec::<TypedValue>::deserialize(tagged.value)
+                .map(TypedValue::OneOf)
+                .map_err(de::Error::custom),
+            "range" => ValueRange::deserialize(tagged.value)
+                .map(|range| TypedValue::Range(Box::new(range)))
+                .map_err(de::Error::custom),
+            "pattern" => {
+                let text = String::deserialize(tagged.value).map_err(de::Error::custom)?;
+                StrPattern::parse(&text).map(TypedValue::Pattern).ok_or_else(|| de::Error::custom(PATTERN_REASON))
+            }
+            #[cfg(feature = "regex")]
+            "regex" => {
+                let text = String::deserialize(tagged.value).map_err(de::Error::custom)?;
+                RegexMatch::new(&text).map(TypedValue::Regex).map_err(de::Error::custom)
+            }
+            other => {
+                let ty: ParamType = other.parse().map_err(de::Error::custom)?;
+                parse_json_typed("value", &ty, &tagged.value, &SERDE_DT_OPTIONS).map_err(de::Error::custom)
+            }
+        }
+    }
+}
+
+/// DATETIME2 text form used by params (`2025-08-22T00:00:00Z`, optional fractional seconds).
+pub const DT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
+
+/// Text form of `date` values (`2025-12-24`).
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Text form of `time` values (`17:30:00`, optional fractional seconds); `17:30` is also accepted.
+pub const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+/// One accepted spelling of a `dt` value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DtFormat {
+    /// Any RFC 3339 timestamp, e.g. `2025-08-22T00:00:00.5+02:00`; parses to `DtTz`.
+    Rfc3339,
+    /// chrono format with an offset (`%z`, `%:z`, ...); parses to `DtTz`.
+    WithOffset(String),
+    /// chrono format without an offset, read as UTC; parses to `Dt`.
+    Naive(String),
+}
+
+/// Which datetime spellings `dt` values may use, tried in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtParseOptions {
+    pub formats: Vec<DtFormat>,
+}
+
+/// Any RFC 3339 timestamp, `...Z` included, as a `DtTz`; then [`DT_FORMAT`] as a `Dt`.
+impl Default for DtParseOptions {
+    fn default() -> Self {
+        Self { formats: vec![DtFormat::Rfc3339, DtFormat::Naive(DT_FORMAT.to_string())] }
+    }
+}
+
+static DEFAULT_DT_OPTIONS: LazyLock<DtParseOptions> = LazyLock::new(DtParseOptions::default);
+
+/// [`DT_FORMAT`] first, so a serialized `Dt` (`...Z`) reads back as `Dt` and a `DtTz` (`+00:00`) as `DtTz`.
+static SERDE_DT_OPTIONS: LazyLock<DtParseOptions> =
+    LazyLock::new(|| DtParseOptions { formats: vec![DtFormat::Naive(DT_FORMAT.to_string()), DtFormat::Rfc3339] });
+
+impl DtParseOptions {
+    /// Parses `raw` with the first format that accepts it.
+    pub fn parse(&self, raw: &str) -> Option<TypedValue> {
+        self.formats.iter().find_map(|format| match format {
+            DtFormat::Rfc3339 => DateTime::parse_from_rfc3339(raw).ok().map(TypedValue::DtTz),
+            DtFormat::WithOffset(f) => DateTime::parse_from_str(raw, f).ok().map(TypedValue::DtTz),
+            DtFormat::Naive(f) => NaiveDateTime::parse_from_str(raw, f).ok().map(TypedValue::Dt),
+        })
+    }
+}
+
+/// RFC 3339 with the offset spelled out (`+00:00`, never `Z`) so it reads back as `DtTz`.
+fn format_dt_tz(v: &DateTime<FixedOffset>) -> String {
+    v.to_rfc3339_opts(SecondsFormat::AutoSi, false)
+}
+
+/// Equality as matching has it: a `Dt` (read as UTC) and a `DtTz` are equal when
+/// they are the same instant, any other values when they are `==`.
+pub(crate) fn same_value(a: &TypedValue, b: &TypedValue) -> bool {
+    match (a, b) {
+        (TypedValue::Dt(naive), TypedValue::DtTz(offset)) | (TypedValue::DtTz(offset), TypedValue::Dt(naive)) => {
+            *naive == offset.naive_utc()
+        }
+        _ => a == b,
+    }
+}
+
+/// Renders the raw form accepted by [`parse_str_value`], so values round-trip
+/// (decimals keep their scale, e.g. `0.150`). Lists are joined with
+/// [`LIST_DELIMITER`], ranges and alternatives render as their envelope JSON and
+/// `Null` as `null`.
+impl fmt::Display for TypedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedValue::Int(v) => write!(f, "{}", v),
+            TypedValue::Dec(v) => write!(f, "{}", v),
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(v) => write!(f, "{}", v),
+            TypedValue::Str(v) | TypedValue::Enum(v) => f.write_str(v),
+            TypedValue::Bool(v) => write!(f, "{}", v),
+            TypedValue::Dt(v) => write!(f, "{}", v.format(DT_FORMAT)),
+            TypedValue::DtTz(v) => f.write_str(&format_dt_tz(v)),
+            TypedValue::Date(v) => write!(f, "{}", v.format(DATE_FORMAT)),
+            TypedValue::Time(v) => write!(f, "{}", v.format(TIME_FORMAT)),
+            TypedValue::Duration(v) => f.write_str(&format_duration(*v)),
+            #[cfg(feature = "uuid")]
+            TypedValue::Uuid(v) => write!(f, "{}", v),
+            TypedValue::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{}", LIST_DELIMITER)?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            }
+            TypedValue::Range(_) | TypedValue::OneOf(_) => write!(f, "{}", self.to_json_value()),
+            TypedValue::Pattern(pattern) => write!(f, "{}", pattern),
+            #[cfg(feature = "regex")]
+            TypedValue::Regex(_) => write!(f, "{}", self.to_json_value()),
+            TypedValue::Null => f.write_str("null"),
+        }
+    }
+}
+
+/// A span of `int`, `dec`, `dt`, `date`, `time` or `duration` values that a match
+/// attribute accepts instead of a single value, written `{ "gte": 100, "lt": 500 }`
+/// in envelopes (`gt` / `gte` for the lower end, `lt` / `lte` for the upper).
+/// Either end may be left open, but not both, and the range may not be empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RangeRepr", into = "RangeRepr")]
+pub struct ValueRange {
+    lower: Option<RangeBound>,
+    upper: Option<RangeBound>,
 }
 
+/// One end of a [`ValueRange`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeBound {
+    pub value: TypedValue,
+    pub inclusive: bool,
+}
+
+impl RangeBound {
+    pub fn inclusive(value: TypedValue) -> Self {
+        Self { value, inclusive: true }
+    }
+
+    pub fn exclusive(value: TypedValue) -> Self {
+        Self { value, inclusive: false }
+    }
+}
+
+impl ValueRange {
+    /// Fails with [`PrecedenceConfigError::InvalidRange`] when both ends are open,
+    /// a bound is not an orderable value, or no value fits between the bounds.
+    pub fn new(lower: Option<RangeBound>, upper: Option<RangeBound>) -> Result<Self> {
+        Self::build(lower, upper).map_err(|reason| PrecedenceConfigError::InvalidRange { reason: reason.to_string() })
+    }
+
+    fn build(lower: Option<RangeBound>, upper: Option<RangeBound>) -> std::result::Result<Self, &'static str> {
+        if lower.is_none() && upper.is_none() {
+            return Err("a range needs at least one of gt, gte, lt, lte");
+        }
+        for bound in lower.iter().chain(&upper) {
+            if compare_ordered(&bound.value, &bound.value) != Some(Ordering::Equal) {
+                return Err("range bounds must be int, dec, dt, date, time or duration values");
+            }
+        }
+        if let (Some(lower), Some(upper)) = (&lower, &upper) {
+            match compare_ordered(&lower.value, &upper.value) {
+                Some(Ordering::Less) => {}
+                Some(Ordering::Equal) if lower.inclusive && upper.inclusive => {}
+                Some(_) => return Err("the range is empty"),
+                None => return Err("range bounds have different types"),
+            }
+        }
+        Ok(Self { lower, upper })
+    }
+
+    pub fn lower(&self) -> Option<&RangeBound> {
+        self.lower.as_ref()
+    }
+
+    pub fn upper(&self) -> Option<&RangeBound> {
+        self.upper.as_ref()
+    }
+
+    /// Whether `value` lies within the range. Values are compared within their
+    /// kind (`int` and `dec` together, `dt` by UTC instant); anything else,
+    /// `Null` included, is outside.
+    pub fn contains(&self, value: &TypedValue) -> bool {
+        let above = self.lower.as_ref().is_none_or(|bound| match compare_ordered(value, &bound.value) {
+            Some(Ordering::Greater) => true,
+            Some(Ordering::Equal) => bound.inclusive,
+            _ => false,
+        });
+        let below = self.upper.as_ref().is_none_or(|bound| match compare_ordered(value, &bound.value) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => bound.inclusive,
+            _ => false,
+        });
+        above && below
+    }
+
+    /// The envelope form, bounds in their untagged JSON form.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        let RangeRepr { gt, gte, lt, lte } = self.clone().into();
+        let ends = [("gt", gt), ("gte", gte), ("lt", lt), ("lte", lte)];
+        serde_json::Value::Object(
+            ends.into_iter().filter_map(|(key, bound)| Some((key.to_string(), bound?.to_json_value()))).collect(),
+        )
+    }
+}
+
+/// Serialized form of a [`ValueRange`], bounds as tagged values.
+#[derive(Serialize, Deserialize)]
+struct RangeRepr {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gt: Option<TypedValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gte: Option<TypedValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lt: Option<TypedValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lte: Option<TypedValue>,
+}
+
+impl TryFrom<RangeRepr> for ValueRange {
+    type Error = &'static str;
+
+    fn try_from(repr: RangeRepr) -> std::result::Result<Self, Self::Error> {
+        let end = |exclusive: Option<TypedValue>, inclusive: Option<TypedValue>| match (exclusive, inclusive) {
+            (Some(_), Some(_)) => Err("a range end cannot be both exclusive and inclusive"),
+            (Some(value), None) => Ok(Some(RangeBound::exclusive(value))),
+            (None, Some(value)) => Ok(Some(RangeBound::inclusive(value))),
+            (None, None) => Ok(None),
+        };
+        Self::build(end(repr.gt, repr.gte)?, end(repr.lt, repr.lte)?)
+    }
+}
+
+impl From<ValueRange> for RangeRepr {
+    fn from(range: ValueRange) -> Self {
+        let split = |bound: Option<RangeBound>| match bound {
+            Some(RangeBound { value, inclusive: true }) => (None, Some(value)),
+            Some(RangeBound { value, inclusive: false }) => (Some(value), None),
+            None => (None, None),
+        };
+        let ((gt, gte), (lt, lte)) = (split(range.lower), split(range.upper));
+        Self { gt, gte, lt, lte }
+    }
+}
+
+/// Character that stands for any run of characters in a [`StrPattern`].
+pub const PATTERN_WILDCARD: char = '*';
+
+const PATTERN_REASON: &str = "a pattern holds a single '*'";
+
+/// A `str` / `enum` match value with one [`PATTERN_WILDCARD`], written as text in
+/// envelopes: `"*"` matches any value, `"EU-*"` values starting with `EU-`,
+/// `"*-beta"` values ending with `-beta`, and `"EU-*-beta"` both. Only parsed for
+/// attributes with `AttrMeta::patterns` set.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StrPattern {
+    prefix: String,
+    suffix: String,
+}
+
+impl StrPattern {
+    /// `None` unless `text` holds exactly one `*`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (prefix, suffix) = text.split_once(PATTERN_WILDCARD)?;
+        if suffix.contains(PATTERN_WILDCARD) {
+            return None;
+        }
+        Some(Self { prefix: prefix.to_string(), suffix: suffix.to_string() })
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn suffix(&self) -> &str {
+        &self.suffix
+    }
+
+    /// Characters the pattern fixes; the more, the more specific the pattern.
+    pub fn literal_len(&self) -> usize {
+        self.prefix.chars().count() + self.suffix.chars().count()
+    }
+
+    /// Whether `text` starts with the prefix and ends with the suffix, without the two overlapping.
+    pub fn matches(&self, text: &str) -> bool {
+        text.len() >= self.prefix.len() + self.suffix.len()
+            && text.starts_with(&self.prefix)
+            && text.ends_with(&self.suffix)
+    }
+}
+
+impl fmt::Display for StrPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.prefix, PATTERN_WILDCARD, self.suffix)
+    }
+}
+
+/// A match value accepting the `str` / `enum` values its regex finds a match in,
+/// written `{ "pattern": "^abc[0-9]+$" }` in envelopes (anchor the regex to match
+/// the whole value). Needs the `regex` feature. The regex is compiled once, when
+/// the value is parsed, so resolvers built from it only run it.
+#[cfg(feature = "regex")]
 #[derive(Debug, Clone)]
+pub struct RegexMatch {
+    regex: regex::Regex,
+}
+
+#[cfg(feature = "regex")]
+impl RegexMatch {
+    pub fn new(pattern: &str) -> std::result::Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(|regex| Self { regex })
+    }
+
+    /// The regex as written.
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+/// Regexes compare by their source text.
+#[cfg(feature = "regex")]
+impl PartialEq for RegexMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// Order of two values of the same kind, with `int` / `dec` and the two `dt`
+/// forms comparable with each other; `None` for anything else.
+fn compare_ordered(a: &TypedValue, b: &TypedValue) -> Option<Ordering> {
+    match (a, b) {
+        (TypedValue::Int(a), TypedValue::Int(b)) => Some(a.cmp(b)),
+        (TypedValue::Dec(a), TypedValue::Dec(b)) => a.partial_cmp(b),
+        (TypedValue::Int(a), TypedValue::Dec(b)) => (*a as f64).partial_cmp(b),
+        (TypedValue::Dec(a), TypedValue::Int(b)) => a.partial_cmp(&(*b as f64)),
+        #[cfg(feature = "rust_decimal")]
+        (TypedValue::Decimal(a), TypedValue::Decimal(b)) => Some(a.cmp(b)),
+        #[cfg(feature = "rust_decimal")]
+        (TypedValue::Int(a), TypedValue::Decimal(b)) => Some(rust_decimal::Decimal::from(*a).cmp(b)),
+        #[cfg(feature = "rust_decimal")]
+        (TypedValue::Decimal(a), TypedValue::Int(b)) => Some(a.cmp(&rust_decimal::Decimal::from(*b))),
+        (TypedValue::Dt(a), TypedValue::Dt(b)) => Some(a.cmp(b)),
+        (TypedValue::DtTz(a), TypedValue::DtTz(b)) => Some(a.cmp(b)),
+        (TypedValue::Dt(a), TypedValue::DtTz(b)) => Some(a.cmp(&b.naive_utc())),
+        (TypedValue::DtTz(a), TypedValue::Dt(b)) => Some(a.naive_utc().cmp(b)),
+        (TypedValue::Date(a), TypedValue::Date(b)) => Some(a.cmp(b)),
+        (TypedValue::Time(a), TypedValue::Time(b)) => Some(a.cmp(b)),
+        (TypedValue::Duration(a), TypedValue::Duration(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttrMeta {
     pub attr_id: i32,
-    pub attr_name: String,
-    pub data_type: String, // "int", "dec", "str", "bool", "dt"
-    pub role: String,      // "match" or "param"
+    pub attr_name: AttrName,
+    /// "int", "dec", "str", "bool", "dt", "date", "time", "duration", "enum" or
+    /// "list<T>" (see `ParamType`).
+    pub data_type: String,
+    pub role: AttrRole,
+    /// Param value used when a row omits the attribute (filled in by
+    /// `parse_config_values` and the resolver). Ignored for match attributes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<TypedValue>,
+    /// Allowed values of an `enum` (or `list<enum>`) attribute, matched case-sensitively.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enum_values: Vec<String>,
+    /// Checked on param values by `parse_config_values` and `validate_envelope`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<ValueConstraints>,
+    /// Lets a `str` / `enum` match attribute take `*` patterns (see [`StrPattern`]);
+    /// otherwise a `*` is an ordinary character.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub patterns: bool,
+    /// Parent of each value of a hierarchical match attribute, in text form
+    /// (`"Berlin" -> "DE"`): a context value also matches rows keyed at its
+    /// ancestors. See `AttrRegistry::set_parents`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parents: BTreeMap<String, String>,
+    /// Set when the attribute is on its way out: configs using it still load, with
+    /// warnings. See `AttrRegistry::set_deprecated`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// Other names the attribute is known by in input (`"ctry"` for `"country"`);
+    /// matrix columns, param keys and envelope keys may use them. See
+    /// `AttrRegistry::set_aliases`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// Why and in favour of what an attribute is deprecated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Attribute to use instead, which [`migrate_deprecated`](crate::config_deprecation::migrate_deprecated)
+    /// rewrites the old name to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl AttrMeta {
+    /// Fails with [`PrecedenceConfigError::HierarchyCycle`] if following `parents`
+    /// up from some value comes back around.
+    pub(crate) fn check_parents(&self) -> Result<()> {
+        'values: for value in self.parents.keys() {
+            let mut current = value;
+            for _ in 0..=self.parents.len() {
+                match self.parents.get(current) {
+                    Some(parent) => current = parent,
+                    None => continue 'values,
+                }
+            }
+            return Err(PrecedenceConfigError::HierarchyCycle {
+                attr_name: self.attr_name.to_string(),
+                value: value.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks `value` against this attribute's constraints, if any.
+    pub fn check_constraints(&self, value: &TypedValue) -> Result<()> {
+        match &self.constraints {
+            Some(constraints) => constraints.check(&self.attr_name, value),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Limits on an attribute's values. `null` passes every check; list values are
+/// checked element by element.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValueConstraints {
+    /// Inclusive lower bound for `int` / `dec` values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Inclusive upper bound for `int` / `dec` values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Maximum `str` length in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    /// Regex a `str` value must match in full; needs the `regex` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Allowed values, compared against the value's text form (its `Display`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<String>>,
+    #[cfg(feature = "regex")]
+    #[serde(skip)]
+    compiled_pattern: std::sync::OnceLock<std::result::Result<regex::Regex, String>>,
+}
+
+impl ValueConstraints {
+    pub fn check(&self, key: &str, value: &TypedValue) -> Result<()> {
+        let violation = |reason: String| PrecedenceConfigError::ConstraintViolation {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason,
+        };
+
+        let number = match value {
+            TypedValue::Null => return Ok(()),
+            TypedValue::List(items) => return items.iter().try_for_each(|item| self.check(key, item)),
+            TypedValue::Int(v) => Some(*v as f64),
+            TypedValue::Dec(v) => Some(*v),
+            #[cfg(feature = "rust_decimal")]
+            TypedValue::Decimal(v) => rust_decimal::prelude::ToPrimitive::to_f64(v),
+            _ => None,
+        };
+        if let Some(n) = number {
+            if let Some(min) = self.min.filter(|min| n < *min) {
+                return Err(violation(format!("below the minimum {}", min)));
+            }
+            if let Some(max) = self.max.filter(|max| n > *max) {
+                return Err(violation(format!("above the maximum {}", max)));
+            }
+        }
+
+        if let TypedValue::Str(s) | TypedValue::Enum(s) = value {
+            if let Some(max_len) = self.max_len.filter(|max_len| s.chars().count() > *max_len) {
+                return Err(violation(format!("longer than {} characters", max_len)));
+            }
+            if let Some(pattern) = &self.pattern
+                && !self.matches_pattern(pattern, s).map_err(violation)?
+            {
+                return Err(violation(format!("does not match /{}/", pattern)));
+            }
+        }
+
+        if let Some(allowed) = &self.allowed {
+            let text = value.to_string();
+            if !allowed.contains(&text) {
+                return Err(violation(format!("not one of [{}]", allowed.join(", "))));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "regex")]
+    fn matches_pattern(&self, pattern: &str, s: &str) -> std::result::Result<bool, String> {
+        let compiled = self
+            .compiled_pattern
+            .get_or_init(|| regex::Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| e.to_string()));
+        match compiled {
+            Ok(regex) => Ok(regex.is_match(s)),
+            Err(e) => Err(format!("invalid pattern /{}/: {}", pattern, e)),
+        }
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn matches_pattern(&self, pattern: &str, _s: &str) -> std::result::Result<bool, String> {
+        Err(format!("pattern /{}/ needs the `regex` feature", pattern))
+    }
+}
+
+/// ATTR_ROLE: whether an attribute is a match key ("Column name") or an output param ("Config Value").
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttrRole {
+    Match,
+    Param,
+}
+
+impl AttrRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttrRole::Match => "match",
+            AttrRole::Param => "param",
+        }
+    }
+}
+
+impl fmt::Display for AttrRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AttrRole {
+    type Err = PrecedenceConfigError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "match" => Ok(AttrRole::Match),
+            "param" => Ok(AttrRole::Param),
+            other => Err(PrecedenceConfigError::InvalidRole(other.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RawParam {
     pub key: String,
     pub type_: String, // "int", "dec", etc.
-    pub value: String,
+    pub value: Option<String>, // None => TypedValue::Null
 }
 
+/// Parses one config row's params into typed `ConfigValue`s. A failure is
+/// returned as `InvalidParam` (index and key into `raw_params`) wrapping the cause,
+/// e.g. `InvalidValue` with the expected type and the offending raw value.
+/// Param attributes with an `AttrMeta::default` that the row omits are appended
+/// afterwards, in attr_id order.
 /// ```JSON
 /// {
 ///     "match_id": 123,
@@ -47,47 +747,1032 @@ pub struct RawParam {
 pub fn parse_config_values(
     match_id: i32,
     raw_params: &[RawParam],
-    attr_lookup: &HashMap<String, AttrMeta>,
+    registry: &AttrRegistry,
+) -> Result<Vec<ConfigValue>> {
+    parse_config_values_with_options(match_id, raw_params, registry, &ParseOptions::default())
+}
+
+/// Same as [`parse_config_values`], with [`ParseOptions`].
+pub fn parse_config_values_with_options(
+    match_id: i32,
+    raw_params: &[RawParam],
+    registry: &AttrRegistry,
+    options: &ParseOptions,
+) -> Result<Vec<ConfigValue>> {
+    let mut report = ValidationReport::fail_fast();
+    collect_config_values(match_id, raw_params, registry, options, &mut report)
+}
+
+/// Same as [`parse_config_values_with_options`], but returns every bad param instead of just the first.
+/// Each error is an `InvalidParam` carrying the param's index and key.
+pub fn parse_config_values_report(
+    match_id: i32,
+    raw_params: &[RawParam],
+    registry: &AttrRegistry,
+    options: &ParseOptions,
+) -> std::result::Result<Vec<ConfigValue>, ValidationReport> {
+    let mut report = ValidationReport::collect_all();
+    let outcome = collect_config_values(match_id, raw_params, registry, options, &mut report);
+    report.finish(outcome)
+}
+
+/// Knobs for [`parse_config_values_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub duplicate_keys: DuplicateKeyPolicy,
+    pub dt: DtParseOptions,
+}
+
+/// What to do when the same param key appears more than once in a row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Strict: reject the repeated key with `DuplicateParamKey`.
+    #[default]
+    Error,
+    /// Keep the first occurrence and drop the rest.
+    FirstWins,
+    /// Keep the last occurrence (in the position of the first).
+    LastWins,
+}
+
+fn collect_config_values(
+    match_id: i32,
+    raw_params: &[RawParam],
+    registry: &AttrRegistry,
+    options: &ParseOptions,
+    report: &mut ValidationReport,
 ) -> Result<Vec<ConfigValue>> {
-    let mut out = Vec::new();
+    let mut out: Vec<ConfigValue> = Vec::new();
+    // attr_id -> (index in raw_params, position in out); an alias repeats its attribute
+    let mut seen: HashMap<i32, (usize, usize)> = HashMap::new();
 
-    for param in raw_params {
-        let Some(meta) = attr_lookup.get(&param.key) else {
-            bail!("Unknown attribute key: {}", param.key);
+    for (index, param) in raw_params.iter().enumerate() {
+        let invalid = |source: PrecedenceConfigError| PrecedenceConfigError::InvalidParam {
+            index,
+            key: param.key.clone(),
+            source: Box::new(source),
         };
 
-        if meta.role != "param" {
-            bail!("Attribute '{}' is not a param (role = {})", param.key, meta.role);
-        }
+        let (meta, value) = match parse_param(param, registry, &options.dt) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                report.push(invalid(err))?;
+                continue;
+            }
+        };
+        let value = ConfigValue {
+            match_id,
+            attr_id: meta.attr_id,
+            role: meta.role,
+            value,
+        };
 
-        let value = match meta.data_type.as_str() {
-            "int" => {
-                let v = param.value.parse::<i64>()?;
-                TypedValue::Int(v)
+        match seen.get(&meta.attr_id) {
+            None => {
+                seen.insert(meta.attr_id, (index, out.len()));
+                out.push(value);
             }
-            "dec" => {
-                let v = param.value.parse::<f64>()?;
-                TypedValue::Dec(v)
+            Some(&(first_index, position)) => match options.duplicate_keys {
+                DuplicateKeyPolicy::Error => report.push(invalid(PrecedenceConfigError::DuplicateParamKey {
+                    key: param.key.clone(),
+                    first_index,
+                }))?,
+                DuplicateKeyPolicy::FirstWins => {}
+                DuplicateKeyPolicy::LastWins => out[position] = value,
+            },
+        }
+    }
+
+    let defaults = missing_defaults(match_id, &out, registry);
+    out.extend(defaults);
+    Ok(out)
+}
+
+/// Default values for the param attributes `present` lacks, in attr_id order.
+pub(crate) fn missing_defaults(match_id: i32, present: &[ConfigValue], registry: &AttrRegistry) -> Vec<ConfigValue> {
+    registry
+        .with_role(AttrRole::Param)
+        .filter(|meta| !present.iter().any(|v| v.attr_id == meta.attr_id))
+        .filter_map(|meta| {
+            meta.default.as_ref().map(|value| ConfigValue {
+                match_id,
+                attr_id: meta.attr_id,
+                role: AttrRole::Param,
+                value: value.clone(),
+            })
+        })
+        .collect()
+}
+
+fn parse_param<'a>(
+    param: &RawParam,
+    registry: &'a AttrRegistry,
+    dt: &DtParseOptions,
+) -> Result<(&'a AttrMeta, TypedValue)> {
+    let Some(meta) = registry.get(&param.key) else {
+        return Err(PrecedenceConfigError::UnknownAttrKey { key: param.key.clone() });
+    };
+
+    if meta.role != AttrRole::Param {
+        return Err(PrecedenceConfigError::WrongRole {
+            key: param.key.clone(),
+            expected: AttrRole::Param,
+            found: meta.role,
+        });
+    }
+
+    let value = match &param.value {
+        Some(raw) => parse_str_value_with_options(meta, raw, dt)?,
+        None => TypedValue::Null,
+    };
+    meta.check_constraints(&value)?;
+
+    Ok((meta, value))
+}
+
+/// Separator for list values given as a single string (`"US,CA,MX"`).
+pub const LIST_DELIMITER: char = ',';
+
+/// Parses a raw (string) value according to the attribute's `data_type`.
+/// List types split the string on [`LIST_DELIMITER`].
+pub fn parse_str_value(meta: &AttrMeta, raw: &str) -> Result<TypedValue> {
+    parse_str_value_with_options(meta, raw, &DEFAULT_DT_OPTIONS)
+}
+
+/// Same as [`parse_str_value`], accepting the datetime spellings in `dt`.
+pub fn parse_str_value_with_options(meta: &AttrMeta, raw: &str, dt: &DtParseOptions) -> Result<TypedValue> {
+    let value = parse_str_typed(&meta.attr_name, &data_type_of(meta)?, raw, dt)?;
+    check_enum(meta, &value)?;
+    Ok(value)
+}
+
+/// Parses a JSON value according to the attribute's `data_type`.
+/// Strings go through [`parse_str_value`]; numbers and booleans must fit the type
+/// directly; arrays fill list types; `null` becomes `TypedValue::Null` whatever the type.
+pub fn parse_json_value(meta: &AttrMeta, value: &serde_json::Value) -> Result<TypedValue> {
+    parse_json_value_with_options(meta, value, &DEFAULT_DT_OPTIONS)
+}
+
+/// Same as [`parse_json_value`], accepting the datetime spellings in `dt`.
+pub fn parse_json_value_with_options(
+    meta: &AttrMeta,
+    value: &serde_json::Value,
+    dt: &DtParseOptions,
+) -> Result<TypedValue> {
+    let typed = parse_json_typed(&meta.attr_name, &data_type_of(meta)?, value, dt)?;
+    check_enum(meta, &typed)?;
+    Ok(typed)
+}
+
+/// Parses a match attribute's range object (`{ "gte": 100, "lt": 500 }`) into
+/// `TypedValue::Range`, each bound by the attribute's `data_type`, which must be
+/// `int`, `dec`, `dt`, `date`, `time` or `duration`.
+pub fn parse_range_value(meta: &AttrMeta, value: &serde_json::Value) -> Result<TypedValue> {
+    let ty = data_type_of(meta)?;
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidValue {
+        key: meta.attr_name.to_string(),
+        data_type: meta.data_type.clone(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    let orderable = matches!(
+        ty,
+        ParamType::Int | ParamType::Dec | ParamType::Dt | ParamType::Date | ParamType::Time | ParamType::Duration
+    );
+    if !orderable {
+        return Err(invalid("ranges need an int, dec, dt, date, time or duration attribute"));
+    }
+    let serde_json::Value::Object(ends) = value else {
+        return Err(invalid("a range is an object with gt, gte, lt and/or lte"));
+    };
+
+    let mut repr = RangeRepr { gt: None, gte: None, lt: None, lte: None };
+    for (key, bound) in ends {
+        let slot = match key.as_str() {
+            "gt" => &mut repr.gt,
+            "gte" => &mut repr.gte,
+            "lt" => &mut repr.lt,
+            "lte" => &mut repr.lte,
+            _ => return Err(invalid(&format!("unknown range key '{}'", key))),
+        };
+        if bound.is_null() {
+            return Err(invalid(&format!("range key '{}' has no value", key)));
+        }
+        *slot = Some(parse_json_typed(&meta.attr_name, &ty, bound, &DEFAULT_DT_OPTIONS)?);
+    }
+    let range = ValueRange::try_from(repr).map_err(invalid)?;
+    Ok(TypedValue::Range(Box::new(range)))
+}
+
+/// Parses a match value holding a `*` into `TypedValue::Pattern`. The attribute
+/// must be `str` or `enum` with `AttrMeta::patterns` set, and the text must hold
+/// exactly one `*`; enum patterns are not checked against `enum_values`.
+pub fn parse_pattern_value(meta: &AttrMeta, value: &str) -> Result<TypedValue> {
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidValue {
+        key: meta.attr_name.to_string(),
+        data_type: meta.data_type.clone(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    if !meta.patterns {
+        return Err(invalid("patterns are not enabled for this attribute"));
+    }
+    if !matches!(data_type_of(meta)?, ParamType::Str | ParamType::Enum) {
+        return Err(invalid("patterns need a str or enum attribute"));
+    }
+    StrPattern::parse(value).map(TypedValue::Pattern).ok_or_else(|| invalid(PATTERN_REASON))
+}
+
+/// Parses a match attribute's regex object (`{ "pattern": "^abc[0-9]+$" }`) into
+/// `TypedValue::Regex`. The attribute must be `str` or `enum`; without the `regex`
+/// feature every regex is rejected.
+pub fn parse_regex_value(meta: &AttrMeta, value: &serde_json::Value) -> Result<TypedValue> {
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidValue {
+        key: meta.attr_name.to_string(),
+        data_type: meta.data_type.clone(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    if !matches!(data_type_of(meta)?, ParamType::Str | ParamType::Enum) {
+        return Err(invalid("regex match values need a str or enum attribute"));
+    }
+    let pattern = match value {
+        serde_json::Value::Object(fields) if fields.len() == 1 => fields.get("pattern").and_then(|p| p.as_str()),
+        _ => None,
+    };
+    let pattern = pattern.ok_or_else(|| invalid("a regex is an object with a single pattern string"))?;
+    regex_value(pattern).map_err(|reason| invalid(&reason))
+}
+
+#[cfg(feature = "regex")]
+fn regex_value(pattern: &str) -> std::result::Result<TypedValue, String> {
+    RegexMatch::new(pattern).map(TypedValue::Regex).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "regex"))]
+fn regex_value(_pattern: &str) -> std::result::Result<TypedValue, String> {
+    Err("regex match values need the `regex` feature".to_string())
+}
+
+/// Whether a match value object is a regex (`{ "pattern": .. }`) rather than a range.
+pub(crate) fn is_regex_object(value: &serde_json::Value) -> bool {
+    value.get("pattern").is_some()
+}
+
+/// Whether `value` is meant as a pattern for `meta`: the attribute takes patterns
+/// and the text holds a `*`.
+pub(crate) fn is_pattern_for(meta: &AttrMeta, value: &str) -> bool {
+    meta.patterns && value.contains(PATTERN_WILDCARD)
+}
+
+/// Parses a match attribute's JSON array (`["US", "CA"]`) into `TypedValue::OneOf`,
+/// each element by the attribute's `data_type`. The attribute must not be a list
+/// type (where an array is the value itself), and the array must hold at least
+/// one value and no `null`, `"ALL"`, arrays or objects.
+pub fn parse_one_of_value(meta: &AttrMeta, value: &serde_json::Value) -> Result<TypedValue> {
+    let ty = data_type_of(meta)?;
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidValue {
+        key: meta.attr_name.to_string(),
+        data_type: meta.data_type.clone(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    if matches!(ty, ParamType::List(_)) {
+        return Err(invalid("an array is the value itself for a list attribute, not a set of alternatives"));
+    }
+    let serde_json::Value::Array(items) = value else {
+        return Err(invalid("alternatives are a JSON array"));
+    };
+    if items.is_empty() {
+        return Err(invalid("the array lists no values"));
+    }
+    let items = items
+        .iter()
+        .map(|item| match item {
+            serde_json::Value::Null | serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+                Err(invalid("alternatives must be single values"))
             }
-            "str" => TypedValue::Str(param.value.clone()),
-            "bool" => {
-                let v = param.value.parse::<bool>()?;
-                TypedValue::Bool(v)
+            item if item.as_str() == Some(crate::config_resolver::WILDCARD) => {
+                Err(invalid("alternatives cannot include the wildcard"))
             }
-            "dt" => {
-                let v = NaiveDateTime::parse_from_str(&param.value, "%Y-%m-%dT%H:%M:%SZ")?;
-                TypedValue::Dt(v)
+            item => parse_json_value(meta, item),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(TypedValue::OneOf(items))
+}
+
+/// Rejects `Enum` values (including list elements) missing from `meta.enum_values`.
+fn check_enum(meta: &AttrMeta, value: &TypedValue) -> Result<()> {
+    match value {
+        TypedValue::Enum(v) if !meta.enum_values.contains(v) => Err(PrecedenceConfigError::InvalidValue {
+            key: meta.attr_name.to_string(),
+            data_type: meta.data_type.clone(),
+            value: v.clone(),
+            reason: if meta.enum_values.is_empty() {
+                "the attribute declares no enum_values".to_string()
+            } else {
+                format!("expected one of [{}]", meta.enum_values.join(", "))
+            },
+        }),
+        TypedValue::List(items) | TypedValue::OneOf(items) => items.iter().try_for_each(|item| check_enum(meta, item)),
+        _ => Ok(()),
+    }
+}
+
+/// Parses an envelope param's JSON value by the type it declares (no catalog lookup).
+pub fn parse_param_value(param: &Param) -> Result<TypedValue> {
+    parse_json_typed(&param.key, &param.ty, &param.value, &DEFAULT_DT_OPTIONS)
+}
+
+pub(crate) fn data_type_of(meta: &AttrMeta) -> Result<ParamType> {
+    meta.data_type.parse().map_err(|_| PrecedenceConfigError::UnsupportedDataType {
+        key: meta.attr_name.to_string(),
+        data_type: meta.data_type.clone(),
+    })
+}
+
+fn parse_str_typed(key: &str, ty: &ParamType, raw: &str, dt: &DtParseOptions) -> Result<TypedValue> {
+    let invalid = |reason: String| PrecedenceConfigError::InvalidValue {
+        key: key.to_string(),
+        data_type: ty.to_string(),
+        value: raw.to_string(),
+        reason,
+    };
+
+    let value = match ty {
+        ParamType::Int => {
+            let v = raw.parse::<i64>().map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Int(v)
+        }
+        #[cfg(not(feature = "rust_decimal"))]
+        ParamType::Dec => {
+            let v = raw.parse::<f64>().map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Dec(v)
+        }
+        #[cfg(feature = "rust_decimal")]
+        ParamType::Dec => {
+            let v = parse_decimal(raw).map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Decimal(v)
+        }
+        ParamType::Str => TypedValue::Str(raw.to_string()),
+        ParamType::Enum => TypedValue::Enum(raw.to_string()),
+        ParamType::Bool => {
+            let v = raw.parse::<bool>().map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Bool(v)
+        }
+        ParamType::Dt => dt
+            .parse(raw)
+            .ok_or_else(|| invalid("does not match any accepted datetime format".to_string()))?,
+        ParamType::Date => {
+            let v = NaiveDate::parse_from_str(raw, DATE_FORMAT).map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Date(v)
+        }
+        ParamType::Time => {
+            let v = NaiveTime::parse_from_str(raw, TIME_FORMAT)
+                .or_else(|e| NaiveTime::parse_from_str(raw, "%H:%M").map_err(|_| e))
+                .map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Time(v)
+        }
+        ParamType::Duration => TypedValue::Duration(parse_duration(raw).map_err(invalid)?),
+        #[cfg(feature = "uuid")]
+        ParamType::Uuid => {
+            let v = uuid::Uuid::parse_str(raw).map_err(|e| invalid(e.to_string()))?;
+            TypedValue::Uuid(v)
+        }
+        ParamType::List(elem) => {
+            if raw.trim().is_empty() {
+                return Ok(TypedValue::List(Vec::new()));
             }
-            _ => bail!("Unsupported data type: {}", meta.data_type),
+            let items = raw
+                .split(LIST_DELIMITER)
+                .map(|item| parse_str_typed(key, elem, item.trim(), dt))
+                .collect::<Result<Vec<_>>>()?;
+            TypedValue::List(items)
+        }
+    };
+
+    Ok(value)
+}
+
+fn parse_json_typed(key: &str, ty: &ParamType, value: &serde_json::Value, dt: &DtParseOptions) -> Result<TypedValue> {
+    use serde_json::Value;
+
+    let invalid = |reason: &str| PrecedenceConfigError::InvalidValue {
+        key: key.to_string(),
+        data_type: ty.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+
+    match (ty, value) {
+        (_, Value::Null) => Ok(TypedValue::Null),
+        (_, Value::String(raw)) => parse_str_typed(key, ty, raw, dt),
+        (ParamType::Int, Value::Number(n)) => {
+            n.as_i64().map(TypedValue::Int).ok_or_else(|| invalid("not a 64-bit integer"))
+        }
+        #[cfg(not(feature = "rust_decimal"))]
+        (ParamType::Dec, Value::Number(n)) => n.as_f64().map(TypedValue::Dec).ok_or_else(|| invalid("not a decimal")),
+        #[cfg(feature = "rust_decimal")]
+        (ParamType::Dec, Value::Number(n)) => parse_decimal(&n.to_string())
+            .map(TypedValue::Decimal)
+            .map_err(|_| invalid("not a decimal")),
+        (ParamType::Bool, Value::Bool(b)) => Ok(TypedValue::Bool(*b)),
+        (ParamType::List(elem), Value::Array(items)) => items
+            .iter()
+            .map(|item| parse_json_typed(key, elem, item, dt))
+            .collect::<Result<Vec<_>>>()
+            .map(TypedValue::List),
+        _ => Err(invalid("unexpected JSON type")),
+    }
+}
+
+/// Parses ISO-8601 `P[nW][nD][T[nH][nM][n[.f]S]]` or humantime-style `1h 30m`
+/// (units `w`, `d`, `h`, `m`, `s`, `ms`, `us`, `ns`). Years and months are
+/// rejected since they have no fixed length.
+fn parse_duration(raw: &str) -> std::result::Result<Duration, String> {
+    if let Some(iso) = raw.strip_prefix('P') {
+        parse_iso_duration(iso)
+    } else {
+        parse_human_duration(raw)
+    }
+}
+
+fn parse_iso_duration(iso: &str) -> std::result::Result<Duration, String> {
+    let (date_part, time_part) = match iso.split_once('T') {
+        Some((_, "")) => return Err("missing time components after 'T'".to_string()),
+        Some((date, time)) => (date, Some(time)),
+        None => (iso, None),
+    };
+    if date_part.is_empty() && time_part.is_none() {
+        return Err("no duration components".to_string());
+    }
+
+    let mut total = Duration::ZERO;
+    let date_units: &[(char, u64)] = &[('W', 604_800), ('D', 86_400)];
+    let time_units: &[(char, u64)] = &[('H', 3_600), ('M', 60), ('S', 1)];
+    for (part, units) in [(date_part, date_units), (time_part.unwrap_or(""), time_units)] {
+        let mut rest = part;
+        let mut next_unit = 0;
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .ok_or_else(|| format!("number '{}' has no unit", rest))?;
+            let (number, tail) = rest.split_at(end);
+            let unit = tail.chars().next().unwrap_or_default();
+            let Some(position) = units[next_unit..].iter().position(|(u, _)| *u == unit) else {
+                return Err(match unit {
+                    'Y' | 'M' if units[0].0 == 'W' => "years and months have no fixed length".to_string(),
+                    _ => format!("unexpected or out-of-order unit '{}'", unit),
+                });
+            };
+            let seconds_per_unit = units[next_unit + position].1;
+            next_unit += position + 1;
+
+            let span = if unit == 'S' {
+                parse_seconds(number)?
+            } else {
+                let n: u64 = number.parse().map_err(|_| format!("'{}' is not a whole number", number))?;
+                Duration::from_secs(n.checked_mul(seconds_per_unit).ok_or("duration overflows")?)
+            };
+            total = total.checked_add(span).ok_or("duration overflows")?;
+            rest = &tail[unit.len_utf8()..];
+        }
+    }
+
+    Ok(total)
+}
+
+/// `12` or `12.345`, exact to the nanosecond (further digits are truncated).
+fn parse_seconds(number: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("'{}' is not a number of seconds", number);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let secs: u64 = whole.parse().map_err(|_| invalid())?;
+    let nanos = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+    Ok(Duration::new(secs, nanos.parse().map_err(|_| invalid())?))
+}
+
+fn parse_human_duration(raw: &str) -> std::result::Result<Duration, String> {
+    let mut rest = raw.trim();
+    if rest.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number at '{}'", rest));
+        }
+        let n: u64 = rest[..digits].parse().map_err(|_| "duration overflows".to_string())?;
+        let tail = &rest[digits..];
+        let unit_len = tail.find(|c: char| c.is_ascii_digit() || c.is_whitespace()).unwrap_or(tail.len());
+
+        let span = match &tail[..unit_len] {
+            "w" => n.checked_mul(604_800).map(Duration::from_secs),
+            "d" => n.checked_mul(86_400).map(Duration::from_secs),
+            "h" => n.checked_mul(3_600).map(Duration::from_secs),
+            "m" => n.checked_mul(60).map(Duration::from_secs),
+            "s" => Some(Duration::from_secs(n)),
+            "ms" => Some(Duration::from_millis(n)),
+            "us" => Some(Duration::from_micros(n)),
+            "ns" => Some(Duration::from_nanos(n)),
+            "" => return Err(format!("number '{}' has no unit", n)),
+            other => return Err(format!("unknown unit '{}'", other)),
         };
+        total = span.and_then(|span| total.checked_add(span)).ok_or("duration overflows")?;
+        rest = tail[unit_len..].trim_start();
+    }
 
-        out.push(ConfigValue {
-            match_id,
-            attr_id: meta.attr_id,
-            role: meta.role.clone(),
-            value,
+    Ok(total)
+}
+
+/// ISO-8601 form read back by [`parse_duration`]: `P2DT1H30M`, `PT0.5S`, `PT0S`.
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86_400, secs % 86_400 / 3_600, secs % 3_600 / 60, secs % 60);
+
+    let mut out = String::from("P");
+    if days > 0 {
+        out.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || d.subsec_nanos() > 0 || days == 0 {
+        out.push('T');
+        if hours > 0 {
+            out.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 {
+            out.push_str(&format!("{}M", minutes));
+        }
+        if d.subsec_nanos() > 0 {
+            let fraction = format!("{:09}", d.subsec_nanos());
+            out.push_str(&format!("{}.{}S", seconds, fraction.trim_end_matches('0')));
+        } else if seconds > 0 || out == "PT" {
+            out.push_str(&format!("{}S", seconds));
+        }
+    }
+    out
+}
+
+/// Accepts plain (`0.125`) and scientific (`1.25e-1`) notation.
+#[cfg(feature = "rust_decimal")]
+fn parse_decimal(raw: &str) -> std::result::Result<rust_decimal::Decimal, rust_decimal::Error> {
+    raw.parse::<rust_decimal::Decimal>()
+        .or_else(|_| rust_decimal::Decimal::from_scientific(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(key: &str, type_: &str, value: Option<&str>) -> RawParam {
+        RawParam { key: key.to_string(), type_: type_.to_string(), value: value.map(str::to_string) }
+    }
+
+    fn param_registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("limit", AttrRole::Param, "int").unwrap();
+        registry.register("rate", AttrRole::Param, "dec").unwrap();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry
+    }
+
+    #[test]
+    fn the_report_variant_collects_every_bad_param() {
+        let params = [
+            raw("limit", "int", Some("ten")),
+            raw("rate", "dec", Some("0.5")),
+            raw("planet", "str", Some("mars")),
+            raw("region", "str", Some("eu")),
+        ];
+        let report = parse_config_values_report(1, &params, &param_registry(), &ParseOptions::default()).unwrap_err();
+        let indexes: Vec<usize> = report
+            .errors
+            .iter()
+            .map(|e| match e {
+                PrecedenceConfigError::InvalidParam { index, .. } => *index,
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(indexes, [0, 2, 3]);
+        let err = parse_config_values(1, &params, &param_registry()).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidParam { index: 0, .. }));
+        let values = parse_config_values_report(1, &params[1..2], &param_registry(), &ParseOptions::default()).unwrap();
+        assert_eq!(values[0].value.to_string(), "0.5");
+    }
+
+    #[test]
+    fn attr_role_parses_and_serializes_lowercase() {
+        for role in [AttrRole::Match, AttrRole::Param] {
+            assert_eq!(role.as_str().parse::<AttrRole>().unwrap(), role);
+            assert_eq!(serde_json::to_value(role).unwrap(), serde_json::json!(role.to_string()));
+        }
+        assert!(matches!("Match".parse::<AttrRole>(), Err(PrecedenceConfigError::InvalidRole(ref s)) if s == "Match"));
+        assert!(serde_json::from_str::<AttrRole>("\"output\"").is_err());
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn dec_values_are_exact_decimals() {
+        use rust_decimal::Decimal;
+        let registry = param_registry();
+        let rate = registry.get("rate").unwrap();
+        let dec = |text: &str| TypedValue::Decimal(text.parse::<Decimal>().unwrap());
+        assert_eq!(parse_str_value(rate, "0.125").unwrap(), dec("0.125"));
+        assert_eq!(parse_str_value(rate, "1e-2").unwrap(), dec("0.01"));
+        assert_eq!(parse_json_value(rate, &serde_json::json!(0.1)).unwrap(), dec("0.1"));
+        let values = parse_config_values(1, &[raw("rate", "dec", Some("0.1"))], &registry).unwrap();
+        assert_eq!(values[0].value, dec("0.1"));
+        // the scale survives a Display round trip
+        assert_eq!(parse_str_value(rate, &dec("1.50").to_string()).unwrap().to_string(), "1.50");
+        let json = serde_json::to_string(&dec("1.50")).unwrap();
+        assert_eq!(json, r#"{"type":"dec","value":"1.50"}"#);
+        let back: TypedValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_string(), "1.50");
+    }
+
+    #[cfg(not(feature = "rust_decimal"))]
+    #[test]
+    fn dec_values_are_floats_without_rust_decimal() {
+        let registry = param_registry();
+        let values = parse_config_values(1, &[raw("rate", "dec", Some("0.125"))], &registry).unwrap();
+        assert_eq!(values[0].value, TypedValue::Dec(0.125));
+    }
+
+    #[test]
+    fn null_parses_from_a_missing_value_and_json_null() {
+        let registry = param_registry();
+        let values = parse_config_values(1, &[raw("limit", "int", None)], &registry).unwrap();
+        assert_eq!(values[0].value, TypedValue::Null);
+        let region = registry.get("region").unwrap();
+        assert_eq!(parse_json_value(region, &serde_json::Value::Null).unwrap(), TypedValue::Null);
+        assert_eq!(TypedValue::Null.to_string(), "null");
+        let json = serde_json::to_string(&TypedValue::Null).unwrap();
+        assert_eq!(json, r#"{"type":"null","value":null}"#);
+        assert_eq!(serde_json::from_str::<TypedValue>(r#"{"type":"null"}"#).unwrap(), TypedValue::Null);
+    }
+
+    #[test]
+    fn lists_parse_from_arrays_and_delimited_text() {
+        let mut registry = AttrRegistry::new();
+        registry.register("countries", AttrRole::Param, "list<str>").unwrap();
+        registry.register("caps", AttrRole::Param, "list<int>").unwrap();
+        let countries = registry.get("countries").unwrap();
+        let strs = |items: &[&str]| TypedValue::List(items.iter().map(|s| TypedValue::Str(s.to_string())).collect());
+        assert_eq!(parse_str_value(countries, "US, CA,MX").unwrap(), strs(&["US", "CA", "MX"]));
+        assert_eq!(parse_json_value(countries, &serde_json::json!(["US", "CA"])).unwrap(), strs(&["US", "CA"]));
+        assert_eq!(parse_str_value(countries, " ").unwrap(), TypedValue::List(Vec::new()));
+
+        let caps = registry.get("caps").unwrap();
+        let ints = TypedValue::List(vec![TypedValue::Int(1), TypedValue::Int(2)]);
+        assert_eq!(parse_str_value(caps, "1,2").unwrap(), ints);
+        let err = parse_str_value(caps, "1,two").unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidValue { ref value, .. } if value == "two"), "{err}");
+        assert_eq!("list<int>".parse::<ParamType>().unwrap().to_string(), "list<int>");
+        assert!("list<list<str>>".parse::<ParamType>().is_ok());
+    }
+
+    #[test]
+    fn tagged_serde_round_trips_every_variant() {
+        let naive = NaiveDateTime::parse_from_str("2025-08-22T09:30:00.5Z", DT_FORMAT).unwrap();
+        let offset = DateTime::parse_from_rfc3339("2025-08-22T09:30:00+00:00").unwrap();
+        let range = ValueRange::new(
+            Some(RangeBound::inclusive(TypedValue::Int(1))),
+            Some(RangeBound::exclusive(TypedValue::Int(9))),
+        );
+        let values = vec![
+            TypedValue::Int(-3),
+            TypedValue::Str("eu".into()),
+            TypedValue::Enum("gold".into()),
+            TypedValue::Bool(true),
+            TypedValue::Dt(naive),
+            TypedValue::DtTz(offset),
+            TypedValue::Date(NaiveDate::from_ymd_opt(2025, 12, 24).unwrap()),
+            TypedValue::Time(NaiveTime::from_hms_opt(17, 30, 0).unwrap()),
+            TypedValue::Duration(Duration::from_secs(90)),
+            TypedValue::List(vec![TypedValue::Int(1), TypedValue::Null]),
+            TypedValue::OneOf(vec![TypedValue::Str("a".into()), TypedValue::Str("b".into())]),
+            TypedValue::Range(Box::new(range.unwrap())),
+            TypedValue::Pattern(StrPattern::parse("eu-*").unwrap()),
+            TypedValue::Null,
+        ];
+        #[cfg(not(feature = "rust_decimal"))]
+        let values = [values, vec![TypedValue::Dec(0.125)]].concat();
+        for value in values {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<TypedValue>(&json).unwrap(), value, "{json}");
+        }
+        assert_eq!(
+            serde_json::to_value(TypedValue::Dt(naive)).unwrap(),
+            serde_json::json!({ "type": "dt", "value": "2025-08-22T09:30:00.500Z" })
+        );
+    }
+
+    #[test]
+    fn config_values_round_trip() {
+        let value = ConfigValue { match_id: 4, attr_id: 2, role: AttrRole::Param, value: TypedValue::Int(7) };
+        let json = serde_json::to_value(&value).unwrap();
+        let expected = serde_json::json!({
+            "match_id": 4, "attr_id": 2, "role": "param", "value": { "type": "int", "value": 7 },
         });
+        assert_eq!(json, expected);
+        let back: ConfigValue = serde_json::from_value(json).unwrap();
+        assert_eq!((back.match_id, back.attr_id, back.role, back.value), (4, 2, AttrRole::Param, TypedValue::Int(7)));
+        assert!(serde_json::from_str::<TypedValue>(r#"{"type":"int","value":"x"}"#).is_err());
+        assert!(serde_json::from_str::<TypedValue>(r#"{"type":"planet","value":1}"#).is_err());
     }
 
-    Ok(out)
+    #[test]
+    fn a_bad_param_names_its_index_key_type_and_value() {
+        let params = [raw("region", "str", Some("eu")), raw("limit", "int", Some("ten"))];
+        let err = parse_config_values(1, &params[1..], &param_registry()).unwrap_err();
+        let PrecedenceConfigError::InvalidParam { index, key, source } = &err else {
+            panic!("expected InvalidParam, got {err:?}");
+        };
+        assert_eq!((*index, key.as_str()), (0, "limit"));
+        let PrecedenceConfigError::InvalidValue { data_type, value, .. } = source.as_ref() else {
+            panic!("expected InvalidValue, got {source:?}");
+        };
+        assert_eq!((data_type.as_str(), value.as_str()), ("int", "ten"));
+        assert!(err.to_string().starts_with("params[0] ('limit'): Invalid int value 'ten'"), "{err}");
+
+        // a match attribute among the params is a role error at its own index
+        let err = parse_config_values(1, &params, &param_registry()).unwrap_err();
+        let PrecedenceConfigError::InvalidParam { index: 0, source, .. } = err else {
+            panic!("expected InvalidParam at 0, got {err:?}");
+        };
+        assert!(matches!(*source, PrecedenceConfigError::WrongRole { .. }), "{source}");
+    }
+
+    #[test]
+    fn duplicate_keys_follow_the_policy() {
+        let params = [
+            raw("limit", "int", Some("1")),
+            raw("rate", "dec", Some("0.5")),
+            raw("limit", "int", Some("2")),
+        ];
+        let parse = |duplicate_keys| {
+            let options = ParseOptions { duplicate_keys, ..ParseOptions::default() };
+            parse_config_values_with_options(1, &params, &param_registry(), &options)
+        };
+        let err = parse(DuplicateKeyPolicy::Error).unwrap_err();
+        let PrecedenceConfigError::InvalidParam { index: 2, source, .. } = err else {
+            panic!("expected InvalidParam at 2, got {err:?}");
+        };
+        assert!(matches!(*source, PrecedenceConfigError::DuplicateParamKey { first_index: 0, .. }), "{source}");
+
+        let limits = |values: Vec<ConfigValue>| -> Vec<(i32, TypedValue)> {
+            values.into_iter().filter(|v| v.attr_id == 1).map(|v| (v.attr_id, v.value)).collect()
+        };
+        let first = parse(DuplicateKeyPolicy::FirstWins).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(limits(first), vec![(1, TypedValue::Int(1))]);
+        let last = parse(DuplicateKeyPolicy::LastWins).unwrap();
+        assert_eq!(last[0].attr_id, 1, "the last value keeps the first position");
+        assert_eq!(limits(last), vec![(1, TypedValue::Int(2))]);
+    }
+
+    #[test]
+    fn default_dt_options_read_rfc3339_first() {
+        let options = DtParseOptions::default();
+        assert!(matches!(options.parse("2025-08-22T00:00:00Z"), Some(TypedValue::DtTz(_))));
+        assert!(matches!(options.parse("2025-08-22T02:00:00+02:00"), Some(TypedValue::DtTz(_))));
+        assert_eq!(options.parse("2025-08-22T00:00:00Z"), options.parse("2025-08-22T02:00:00+02:00"));
+        assert_eq!(options.parse("22/08/2025"), None);
+
+        let naive = DtParseOptions { formats: vec![DtFormat::Naive("%d/%m/%Y %H:%M".to_string())] };
+        assert!(matches!(naive.parse("22/08/2025 09:30"), Some(TypedValue::Dt(_))));
+        assert_eq!(naive.parse("2025-08-22T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn dt_and_dt_tz_are_the_same_value_at_the_same_instant() {
+        let naive = NaiveDateTime::parse_from_str("2025-08-22T00:00:00Z", DT_FORMAT).unwrap();
+        let utc = TypedValue::Dt(naive);
+        let paris = TypedValue::DtTz(DateTime::parse_from_rfc3339("2025-08-22T02:00:00+02:00").unwrap());
+        let later = TypedValue::DtTz(DateTime::parse_from_rfc3339("2025-08-22T02:00:00Z").unwrap());
+        assert!(same_value(&utc, &paris) && same_value(&paris, &utc));
+        assert!(!same_value(&utc, &later));
+        assert!(!same_value(&TypedValue::Int(1), &TypedValue::Str("1".into())));
+    }
+
+    fn typed_registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        for (name, data_type) in [("blackout_date", "date"), ("cutoff_time", "time"), ("ttl", "duration")] {
+            registry.register(name, AttrRole::Param, data_type).unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn dates_and_times_parse_on_their_own() {
+        let registry = typed_registry();
+        let (date, time) = (registry.get("blackout_date").unwrap(), registry.get("cutoff_time").unwrap());
+        let christmas_eve = TypedValue::Date(NaiveDate::from_ymd_opt(2025, 12, 24).unwrap());
+        assert_eq!(parse_str_value(date, "2025-12-24").unwrap(), christmas_eve);
+        assert!(parse_str_value(date, "2025-02-30").is_err());
+        assert!(parse_str_value(date, "2025-12-24T00:00:00Z").is_err());
+
+        let half_past_five = TypedValue::Time(NaiveTime::from_hms_opt(17, 30, 0).unwrap());
+        assert_eq!(parse_str_value(time, "17:30").unwrap(), half_past_five);
+        assert_eq!(parse_str_value(time, "17:30:00").unwrap(), half_past_five);
+        let fractional = parse_str_value(time, "17:30:00.250").unwrap();
+        assert_eq!(fractional, TypedValue::Time(NaiveTime::from_hms_milli_opt(17, 30, 0, 250).unwrap()));
+        assert!(parse_str_value(time, "25:00").is_err());
+        assert_eq!(fractional.to_string(), "17:30:00.250");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuids_parse_whatever_the_case() {
+        let mut registry = AttrRegistry::new();
+        registry.register("tenant_id", AttrRole::Match, "uuid").unwrap();
+        let tenant = registry.get("tenant_id").unwrap();
+        let lower = parse_str_value(tenant, "67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        assert_eq!(parse_str_value(tenant, "67E55044-10B1-426F-9247-BB680E5FE0C8").unwrap(), lower);
+        assert!(matches!(lower, TypedValue::Uuid(_)));
+        assert!(parse_str_value(tenant, "67e55044").is_err());
+        let json = serde_json::to_string(&lower).unwrap();
+        assert_eq!(json, r#"{"type":"uuid","value":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#);
+        assert_eq!(serde_json::from_str::<TypedValue>(&json).unwrap(), lower);
+    }
+
+    #[test]
+    fn durations_parse_iso_and_human_forms() {
+        let registry = typed_registry();
+        let ttl = registry.get("ttl").unwrap();
+        let duration = |raw: &str| match parse_str_value(ttl, raw) {
+            Ok(TypedValue::Duration(d)) => Ok(d),
+            Ok(other) => panic!("expected a duration, got {other:?}"),
+            Err(e) => Err(e),
+        };
+        assert_eq!(duration("PT30M").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(duration("P1DT1H").unwrap(), Duration::from_secs(90_000));
+        assert_eq!(duration("PT0.5S").unwrap(), Duration::from_millis(500));
+        assert_eq!(duration("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(duration("1h 30m 250ms").unwrap(), Duration::from_millis(5_400_250));
+        for bad in ["P1M", "PT", "P", "30", "30x", "PT1S1M", ""] {
+            assert!(duration(bad).is_err(), "{bad}");
+        }
+        for d in [Duration::ZERO, Duration::from_millis(500), Duration::from_secs(90_061)] {
+            assert_eq!(duration(&TypedValue::Duration(d).to_string()).unwrap(), d);
+        }
+        assert_eq!(TypedValue::Duration(Duration::from_secs(5_400)).to_string(), "PT1H30M");
+    }
+
+    #[test]
+    fn omitted_params_take_the_attribute_default() {
+        let mut registry = param_registry();
+        registry.set_default("limit", Some(TypedValue::Int(10))).unwrap();
+        let values = parse_config_values(3, &[raw("rate", "dec", Some("0.5"))], &registry).unwrap();
+        let limit = values.iter().find(|v| v.attr_id == 1).unwrap();
+        assert_eq!((limit.match_id, &limit.value), (3, &TypedValue::Int(10)));
+        assert_eq!(values.len(), 2);
+
+        // a given value, null included, wins over the default
+        for (given, expected) in [(Some("4"), TypedValue::Int(4)), (None, TypedValue::Null)] {
+            let values = parse_config_values(1, &[raw("limit", "int", given)], &registry).unwrap();
+            assert_eq!(values.iter().map(|v| &v.value).collect::<Vec<_>>(), vec![&expected]);
+        }
+        registry.set_default("limit", None).unwrap();
+        assert!(parse_config_values(1, &[], &registry).unwrap().is_empty());
+        assert!(registry.set_default("planet", None).is_err());
+    }
+
+    fn constrained_registry() -> AttrRegistry {
+        let metas = serde_json::json!([
+            { "attr_id": 1, "attr_name": "discount_pct", "data_type": "dec", "role": "param",
+              "constraints": { "min": 0.0, "max": 1.0 } },
+            { "attr_id": 2, "attr_name": "code", "data_type": "str", "role": "param",
+              "constraints": { "max_len": 4, "allowed": ["A1", "B2", "TOOLONG"] } },
+            { "attr_id": 3, "attr_name": "caps", "data_type": "list<int>", "role": "param",
+              "constraints": { "max": 10.0 } },
+            { "attr_id": 4, "attr_name": "sku", "data_type": "str", "role": "param",
+              "constraints": { "pattern": "[A-Z]{3}-[0-9]+" } },
+        ]);
+        AttrRegistry::from_metas(serde_json::from_value::<Vec<AttrMeta>>(metas).unwrap()).unwrap()
+    }
+
+    /// The `reason` of the constraint violation parsing one param fails with.
+    fn violation(key: &str, type_: &str, value: &str) -> String {
+        let err = parse_config_values(1, &[raw(key, type_, Some(value))], &constrained_registry()).unwrap_err();
+        match err {
+            PrecedenceConfigError::InvalidParam { source, .. } => match *source {
+                PrecedenceConfigError::ConstraintViolation { reason, .. } => reason,
+                other => panic!("expected a constraint violation, got {other:?}"),
+            },
+            other => panic!("expected InvalidParam, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn values_are_checked_against_their_constraints() {
+        let registry = constrained_registry();
+        let ok = [
+            raw("discount_pct", "dec", Some("0.125")),
+            raw("code", "str", Some("B2")),
+            raw("caps", "list<int>", Some("1,10")),
+        ];
+        assert_eq!(parse_config_values(1, &ok, &registry).unwrap().len(), 3);
+        assert_eq!(violation("discount_pct", "dec", "12.5"), "above the maximum 1");
+        assert_eq!(violation("discount_pct", "dec", "-0.1"), "below the minimum 0");
+        assert_eq!(violation("code", "str", "TOOLONG"), "longer than 4 characters");
+        assert_eq!(violation("code", "str", "C3"), "not one of [A1, B2, TOOLONG]");
+        assert_eq!(violation("caps", "list<int>", "1,11"), "above the maximum 10");
+        // null passes
+        assert!(parse_config_values(1, &[raw("discount_pct", "dec", None)], &registry).is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn str_patterns_match_in_full() {
+        let registry = constrained_registry();
+        assert!(parse_config_values(1, &[raw("sku", "str", Some("ABC-12"))], &registry).is_ok());
+        assert_eq!(violation("sku", "str", "xABC-12"), "does not match /[A-Z]{3}-[0-9]+/");
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn str_patterns_need_the_regex_feature() {
+        assert_eq!(violation("sku", "str", "ABC-12"), "pattern /[A-Z]{3}-[0-9]+/ needs the `regex` feature");
+    }
+
+    #[test]
+    fn enum_params_need_a_declared_value() {
+        let mut registry = AttrRegistry::new();
+        registry.register("tier", AttrRole::Param, "enum").unwrap();
+        let err = parse_config_values(1, &[raw("tier", "enum", Some("gold"))], &registry).unwrap_err();
+        assert!(err.to_string().ends_with("the attribute declares no enum_values"), "{err}");
+
+        let mut meta = registry.remove("tier").unwrap();
+        meta.enum_values = vec!["gold".into(), "silver".into()];
+        registry.insert(meta).unwrap();
+        let values = parse_config_values(1, &[raw("tier", "enum", Some("gold"))], &registry).unwrap();
+        assert_eq!(values[0].value, TypedValue::Enum("gold".into()));
+        assert!(parse_config_values(1, &[raw("tier", "enum", Some("Gold"))], &registry).is_err());
+    }
+
+    fn dt(raw: &str) -> TypedValue {
+        DtParseOptions::default().parse(raw).unwrap()
+    }
+
+    #[test]
+    fn ranges_honour_open_and_closed_bounds() {
+        let at_least = ValueRange::new(Some(RangeBound::inclusive(TypedValue::Int(100))), None).unwrap();
+        assert!(at_least.contains(&TypedValue::Int(100)) && at_least.contains(&TypedValue::Int(i64::MAX)));
+        assert!(!at_least.contains(&TypedValue::Int(99)));
+        let below = ValueRange::new(None, Some(RangeBound::exclusive(TypedValue::Int(500)))).unwrap();
+        assert!(below.contains(&TypedValue::Int(i64::MIN)) && !below.contains(&TypedValue::Int(500)));
+        let point = ValueRange::new(
+            Some(RangeBound::inclusive(TypedValue::Int(7))),
+            Some(RangeBound::inclusive(TypedValue::Int(7))),
+        )
+        .unwrap();
+        assert!(point.contains(&TypedValue::Int(7)) && !point.contains(&TypedValue::Int(8)));
+        assert!(!below.contains(&TypedValue::Null) && !below.contains(&TypedValue::Str("1".into())));
+    }
+
+    #[test]
+    fn ranges_compare_int_with_dec_and_dt_by_instant() {
+        let range = ValueRange::new(
+            Some(RangeBound::exclusive(TypedValue::Int(10))),
+            Some(RangeBound::inclusive(TypedValue::Dec(20.5))),
+        )
+        .unwrap();
+        assert!(range.contains(&TypedValue::Dec(10.5)) && range.contains(&TypedValue::Int(20)));
+        assert!(!range.contains(&TypedValue::Dec(10.0)) && !range.contains(&TypedValue::Int(21)));
+
+        let naive = TypedValue::Dt(NaiveDateTime::parse_from_str("2025-08-22T00:00:00Z", DT_FORMAT).unwrap());
+        let next_day = RangeBound::exclusive(dt("2025-08-23T00:00:00Z"));
+        let range = ValueRange::new(Some(RangeBound::inclusive(naive)), Some(next_day)).unwrap();
+        assert!(range.contains(&dt("2025-08-22T02:00:00+02:00")));
+        assert!(!range.contains(&dt("2025-08-22T01:59:59+02:00")));
+        assert!(!range.contains(&dt("2025-08-23T02:00:00+02:00")));
+    }
+
+    #[test]
+    fn ranges_must_be_bounded_ordered_and_non_empty() {
+        let bound = |value: i64| Some(RangeBound::exclusive(TypedValue::Int(value)));
+        assert!(ValueRange::new(None, None).is_err());
+        assert!(ValueRange::new(bound(5), bound(5)).is_err());
+        assert!(ValueRange::new(bound(6), bound(5)).is_err());
+        assert!(ValueRange::new(Some(RangeBound::inclusive(TypedValue::Str("a".into()))), None).is_err());
+        assert!(ValueRange::new(bound(1), Some(RangeBound::inclusive(dt("2025-08-22T00:00:00Z")))).is_err());
+    }
+
+    #[test]
+    fn patterns_fix_a_prefix_and_a_suffix() {
+        let pattern = |text: &str| StrPattern::parse(text).unwrap();
+        assert!(pattern("*").matches("") && pattern("*").matches("anything"));
+        assert!(pattern("EU-*").matches("EU-DE") && !pattern("EU-*").matches("US-EU-"));
+        assert!(pattern("*-beta").matches("app-beta") && !pattern("*-beta").matches("beta"));
+        assert!(pattern("ab*ba").matches("abba") && !pattern("ab*ba").matches("aba"));
+        assert_eq!(pattern("EU-*-beta").literal_len(), 8);
+        assert_eq!(StrPattern::parse("EU"), None);
+        assert_eq!(StrPattern::parse("EU-*-*"), None);
+        assert_eq!(pattern("EU-*").to_string(), "EU-*");
+    }
 }
@@ -0,0 +1,556 @@
+//! Excel workbooks (`xlsx` feature) holding a precedence matrix and config rows,
+//! for configs whose owners edit them in Excel.
+//!
+//! The matrix lives on the [`MATRIX_SHEET`] sheet: a header row with `rank` and
+//! one column per attribute, then one row per rank with 1 (exact), 0 (ignore)
+//! or blank. Every other sheet is one config, named after it:
+//!
+//! | version | 3 | version_name | eu | status | draft |
+//! |---------|---|--------------|----|--------|-------|
+//! |         |   |              |    |        |       |
+//! | region  | channel | fee | enabled |
+//! | EU      | web     | 2.5 | true    |
+//!
+//! The first row holds `key, value` pairs of the config metadata (`version` and
+//! `version_name`, optionally `status`, `valid_from` and `valid_to`), the second
+//! is blank, the third names the columns and every row after that is an
+//! envelope row. Columns are catalog attributes, match or param by their role.
+//! A blank cell leaves the match attribute a wildcard or the param unset.
+//!
+//! Cells are read by the attribute's `data_type`, not by how Excel typed them,
+//! so a `str` column of `00123` zip codes or an `int` column Excel holds as
+//! `5.0` reads back as written. Lists are comma-separated text; ranges and
+//! alternatives are JSON text.
+
+use crate::config_attr::AttrRegistry;
+use crate::config_error::{PrecedenceConfigError, Result, ValidationReport};
+use crate::config_precidence_rules::{
+    convert_matrix_rows, tall_to_matrix_rows_with_options, ConfigPrecedenceRule, ConversionOptions, MatrixRow,
+};
+use crate::config_types::{
+    ConfigEnvelope, ConfigMeta, ConfigRow, ConfigStatus, MatchPart, Param, ParamType, ENVELOPE_SCHEMA_VERSION,
+};
+use crate::config_value::{
+    parse_json_value, parse_param_value, AttrMeta, AttrRole, TypedValue, DATE_FORMAT, DT_FORMAT, LIST_DELIMITER,
+    TIME_FORMAT,
+};
+use calamine::{Data, DataType, Reader};
+use chrono::{DateTime, Utc};
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+
+/// Name of the sheet holding the precedence matrix.
+pub const MATRIX_SHEET: &str = "precedence";
+
+/// The precedence rules and configs of one workbook.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XlsxWorkbook {
+    /// Empty when the workbook has no [`MATRIX_SHEET`].
+    pub rules: Vec<ConfigPrecedenceRule>,
+    /// In sheet order.
+    pub envelopes: Vec<ConfigEnvelope>,
+}
+
+/// Reads a workbook laid out as described in the [module docs](self).
+///
+/// The matrix goes through the same conversion as `matrix_json_to_tall`. Each
+/// cell must parse as its attribute's `data_type` (match cells may also hold
+/// wildcards and other match-only values); whole envelopes are left to
+/// `validate_envelope`.
+pub fn read_xlsx<R: io::Read + io::Seek>(
+    reader: R,
+    config_version_id: i32,
+    registry: &AttrRegistry,
+    options: &ConversionOptions,
+) -> Result<XlsxWorkbook> {
+    let mut workbook: calamine::Xlsx<R> = calamine::open_workbook_from_rs(reader).map_err(xlsx_error)?;
+    let mut out = XlsxWorkbook::default();
+    for name in workbook.sheet_names() {
+        let range = workbook.worksheet_range(&name).map_err(xlsx_error)?;
+        let sheet = Sheet { name: &name, range: &range };
+        if name == MATRIX_SHEET {
+            let mut report = ValidationReport::fail_fast();
+            out.rules = convert_matrix_rows(sheet.matrix_rows()?, config_version_id, registry, options, &mut report)?;
+        } else {
+            out.envelopes.push(sheet.envelope(registry)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Writes `workbook` as [`read_xlsx`] reads it: the rules as a matrix with
+/// columns in name order, then one sheet per envelope. Columns follow attr_id
+/// order, match attributes first; dates, datetimes and times are written as
+/// Excel dates, strings always as text.
+///
+/// Every envelope needs its own config name, which must be a valid sheet name,
+/// and every column must be in the catalog.
+pub fn write_xlsx(workbook: &XlsxWorkbook, registry: &AttrRegistry, options: &ConversionOptions) -> Result<Vec<u8>> {
+    let mut out = Workbook::new();
+    if !workbook.rules.is_empty() {
+        let rows = tall_to_matrix_rows_with_options(&workbook.rules, registry, options)?;
+        write_matrix(out.add_worksheet(), &rows).map_err(xlsx_error)?;
+    }
+    let mut names = BTreeSet::new();
+    for envelope in &workbook.envelopes {
+        let name = &envelope.config.name;
+        if name == MATRIX_SHEET || !names.insert(name) {
+            return Err(PrecedenceConfigError::InvalidXlsx {
+                sheet: name.clone(),
+                row: 0,
+                reason: "a config name can only be used by one sheet".to_string(),
+            });
+        }
+        write_envelope(out.add_worksheet(), envelope, registry)?;
+    }
+    out.save_to_buffer().map_err(xlsx_error)
+}
+
+struct Sheet<'a> {
+    name: &'a str,
+    range: &'a calamine::Range<Data>,
+}
+
+impl Sheet<'_> {
+    /// The cell at 0-based `(row, col)` as text, `None` if blank.
+    fn text(&self, row: u32, col: u32, ty: Option<&ParamType>) -> Result<Option<String>> {
+        let Some(cell) = self.range.get_value((row, col)) else {
+            return Ok(None);
+        };
+        let text = match cell {
+            Data::Empty => return Ok(None),
+            Data::String(s) if s.trim().is_empty() => return Ok(None),
+            Data::String(s) => s.trim().to_string(),
+            Data::Int(i) => i.to_string(),
+            // Excel keeps every number as a float; integral ones read back without `.0`
+            Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+            Data::Float(f) => f.to_string(),
+            Data::Bool(b) => b.to_string(),
+            Data::DateTime(_) => {
+                let at = cell.as_datetime().ok_or_else(|| self.error(row, "date outside the supported range"))?;
+                match ty {
+                    Some(ParamType::Date) => at.format(DATE_FORMAT).to_string(),
+                    Some(ParamType::Time) => at.format(TIME_FORMAT).to_string(),
+                    _ if at.time() == chrono::NaiveTime::MIN && ty != Some(&ParamType::Dt) => {
+                        at.format(DATE_FORMAT).to_string()
+                    }
+                    _ => at.format(DT_FORMAT).to_string(),
+                }
+            }
+            Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+            Data::Error(e) => return Err(self.error(row, &format!("cell {} holds the error {}", col + 1, e))),
+        };
+        Ok(Some(text))
+    }
+
+    fn rows(&self) -> u32 {
+        self.range.end().map_or(0, |(row, _)| row + 1)
+    }
+
+    fn cols(&self) -> u32 {
+        self.range.end().map_or(0, |(_, col)| col + 1)
+    }
+
+    /// The non-blank cells of the 0-based `row` as `(col, text)`.
+    fn row_texts(&self, row: u32) -> Result<Vec<(u32, String)>> {
+        let mut texts = Vec::new();
+        for col in 0..self.cols() {
+            if let Some(text) = self.text(row, col, None)? {
+                texts.push((col, text));
+            }
+        }
+        Ok(texts)
+    }
+
+    /// `row` is 0-based; the error names it 1-based, as Excel does.
+    fn error(&self, row: u32, reason: &str) -> PrecedenceConfigError {
+        let (sheet, reason) = (self.name.to_string(), reason.to_string());
+        PrecedenceConfigError::InvalidXlsx { sheet, row: row as usize + 1, reason }
+    }
+
+    fn matrix_rows(&self) -> Result<Vec<MatrixRow>> {
+        let header = self.row_texts(0)?;
+        let Some(rank_col) = header.iter().find(|(_, name)| name == "rank").map(|(col, _)| *col) else {
+            return Err(self.error(0, "missing `rank` column"));
+        };
+        let mut rows = Vec::new();
+        for row in 1..self.rows() {
+            let cells = self.row_texts(row)?;
+            if cells.is_empty() {
+                continue;
+            }
+            let mut rank = None;
+            let mut attrs = Vec::new();
+            for (col, text) in cells {
+                let Some((_, name)) = header.iter().find(|(c, _)| *c == col) else {
+                    return Err(self.error(row, &format!("cell {} has no column name", col + 1)));
+                };
+                if col == rank_col {
+                    rank = Some(text.parse::<i32>().map_err(|e| self.error(row, &format!("rank '{}': {}", text, e)))?);
+                } else {
+                    let match_type = text
+                        .parse::<u8>()
+                        .map_err(|e| self.error(row, &format!("column '{}' value '{}': {}", name, text, e)))?;
+                    attrs.push((name.into(), match_type));
+                }
+            }
+            let rank = rank.ok_or_else(|| self.error(row, "no rank"))?;
+            rows.push(MatrixRow { rank, attrs });
+        }
+        Ok(rows)
+    }
+
+    fn envelope(&self, registry: &AttrRegistry) -> Result<ConfigEnvelope> {
+        let config = self.config_meta()?;
+
+        let mut columns: Vec<(u32, &AttrMeta, ParamType)> = Vec::new();
+        for (col, name) in self.row_texts(2)? {
+            let meta = registry.get(&name).ok_or_else(|| self.error(2, &format!("unknown attribute '{}'", name)))?;
+            let ty = meta.data_type.parse().map_err(|_| {
+                PrecedenceConfigError::UnsupportedDataType { key: name.clone(), data_type: meta.data_type.clone() }
+            })?;
+            if columns.iter().any(|(_, m, _)| m.attr_id == meta.attr_id) {
+                return Err(self.error(2, &format!("duplicate column '{}'", name)));
+            }
+            columns.push((col, meta, ty));
+        }
+
+        let mut rows = Vec::new();
+        for row in 3..self.rows() {
+            let mut attrs = HashMap::new();
+            let mut params = Vec::new();
+            let mut blank = true;
+            for (col, meta, ty) in &columns {
+                let Some(text) = self.text(row, *col, Some(ty))? else {
+                    continue;
+                };
+                blank = false;
+                let value = cell_json(meta, &text).map_err(|e| self.error(row, &e.to_string()))?;
+                match meta.role {
+                    AttrRole::Match => {
+                        attrs.insert(meta.attr_name.clone(), value);
+                    }
+                    AttrRole::Param => params.push(Param { key: meta.attr_name.clone(), ty: ty.clone(), value }),
+                }
+            }
+            if !blank {
+                rows.push(ConfigRow { match_part: MatchPart { attrs }, params, audit: None });
+            }
+        }
+        Ok(ConfigEnvelope { schema_version: ENVELOPE_SCHEMA_VERSION, config, rows })
+    }
+
+    fn config_meta(&self) -> Result<ConfigMeta> {
+        let cells = self.row_texts(0)?;
+        let mut fields: HashMap<String, String> = HashMap::new();
+        for pair in cells.chunks(2) {
+            let [(key_col, key), (value_col, value)] = pair else {
+                return Err(self.error(0, &format!("metadata key '{}' has no value", pair[0].1)));
+            };
+            if *value_col != key_col + 1 {
+                return Err(self.error(0, &format!("metadata key '{}' has no value next to it", key)));
+            }
+            fields.insert(key.clone(), value.clone());
+        }
+
+        let required = |key: &str| fields.get(key).ok_or_else(|| self.error(0, &format!("missing `{}`", key)));
+        let version = required("version")?;
+        let version = version.parse().map_err(|e| self.error(0, &format!("version '{}': {}", version, e)))?;
+        let version_name = required("version_name")?.clone();
+        let status = match fields.get("status") {
+            Some(status) => serde_json::from_value::<ConfigStatus>(serde_json::Value::String(status.clone()))
+                .map_err(|_| self.error(0, &format!("unknown status '{}'", status)))?,
+            None => ConfigStatus::default(),
+        };
+        let instant = |key: &str| -> Result<Option<DateTime<Utc>>> {
+            let Some(raw) = fields.get(key) else {
+                return Ok(None);
+            };
+            let at = DateTime::parse_from_rfc3339(raw)
+                .map(|at| at.with_timezone(&Utc))
+                .or_else(|_| chrono::NaiveDateTime::parse_from_str(raw, DT_FORMAT).map(|at| at.and_utc()))
+                .map_err(|e| self.error(0, &format!("{} '{}': {}", key, raw, e)))?;
+            Ok(Some(at))
+        };
+        Ok(ConfigMeta {
+            name: self.name.to_string(),
+            version,
+            version_name,
+            status,
+            valid_from: instant("valid_from")?,
+            valid_to: instant("valid_to")?,
+            audit: None,
+        })
+    }
+}
+
+/// The JSON an envelope holds for the cell `text` of `meta`'s column: typed
+/// values in their canonical form. Match cells that don't parse as a plain
+/// value (wildcards, patterns) stay text for `validate_envelope` to judge;
+/// `{` or `[` starts a JSON range or list of alternatives.
+fn cell_json(meta: &AttrMeta, text: &str) -> Result<serde_json::Value> {
+    let raw = serde_json::Value::String(text.to_string());
+    if meta.role == AttrRole::Match {
+        if text.starts_with(['{', '[']) {
+            return serde_json::from_str(text).map_err(PrecedenceConfigError::InvalidJson);
+        }
+        return Ok(parse_json_value(meta, &raw).map_or(raw, |value| value.to_json_value()));
+    }
+    Ok(parse_json_value(meta, &raw)?.to_json_value())
+}
+
+fn write_matrix(sheet: &mut Worksheet, rows: &[MatrixRow]) -> std::result::Result<(), XlsxError> {
+    sheet.set_name(MATRIX_SHEET)?;
+    let names: BTreeSet<&str> = rows.iter().flat_map(|r| r.attrs.iter().map(|(name, _)| name.as_str())).collect();
+    sheet.write_string(0, 0, "rank")?;
+    for (col, name) in names.iter().enumerate() {
+        sheet.write_string(0, col as u16 + 1, *name)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let line = i as u32 + 1;
+        sheet.write_number(line, 0, row.rank)?;
+        for (col, name) in names.iter().enumerate() {
+            if let Some(match_type) = row.get(name) {
+                sheet.write_number(line, col as u16 + 1, match_type)?;
+            }
+        }
+    }
+    sheet.set_freeze_panes(1, 1)?;
+    Ok(())
+}
+
+fn write_envelope(sheet: &mut Worksheet, envelope: &ConfigEnvelope, registry: &AttrRegistry) -> Result<()> {
+    let config = &envelope.config;
+    let invalid = |reason: String| PrecedenceConfigError::InvalidXlsx { sheet: config.name.clone(), row: 0, reason };
+    sheet.set_name(&config.name).map_err(|e| invalid(e.to_string()))?;
+
+    let mut metas: Vec<&AttrMeta> = Vec::new();
+    for row in &envelope.rows {
+        let keys = row.match_part.attrs.keys().chain(row.params.iter().map(|p| &p.key));
+        for key in keys {
+            let meta = registry.get(key).ok_or_else(|| PrecedenceConfigError::UnknownAttrKey { key: key.to_string() })?;
+            if !metas.iter().any(|m| m.attr_id == meta.attr_id) {
+                metas.push(meta);
+            }
+        }
+    }
+    metas.sort_by_key(|m| (m.role != AttrRole::Match, m.attr_id));
+
+    let formats = CellFormats::new();
+    (|| -> std::result::Result<(), XlsxError> {
+        let mut col = 0;
+        let mut pair = |sheet: &mut Worksheet, key: &str, value: &str| -> std::result::Result<(), XlsxError> {
+            sheet.write_string(0, col, key)?;
+            sheet.write_string(0, col + 1, value)?;
+            col += 2;
+            Ok(())
+        };
+        pair(sheet, "version", &config.version.to_string())?;
+        pair(sheet, "version_name", &config.version_name)?;
+        if !config.status.is_draft() {
+            pair(sheet, "status", config.status.as_str())?;
+        }
+        for (key, at) in [("valid_from", config.valid_from), ("valid_to", config.valid_to)] {
+            if let Some(at) = at {
+                pair(sheet, key, &at.to_rfc3339())?;
+            }
+        }
+
+        for (col, meta) in metas.iter().enumerate() {
+            sheet.write_string(2, col as u16, meta.attr_name.as_str())?;
+        }
+        for (i, row) in envelope.rows.iter().enumerate() {
+            let line = i as u32 + 3;
+            for (col, meta) in metas.iter().enumerate() {
+                let col = col as u16;
+                match meta.role {
+                    AttrRole::Match => {
+                        if let Some(value) = row.match_part.attrs.get(&meta.attr_name) {
+                            write_json(sheet, line, col, value)?;
+                        }
+                    }
+                    AttrRole::Param => {
+                        if let Some(param) = row.params.iter().find(|p| p.key == meta.attr_name) {
+                            match parse_param_value(param) {
+                                Ok(value) => write_typed(sheet, line, col, &value, &formats)?,
+                                Err(_) => write_json(sheet, line, col, &param.value)?,
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        sheet.set_freeze_panes(3, 0)?;
+        Ok(())
+    })()
+    .map_err(|e| invalid(e.to_string()))
+}
+
+struct CellFormats {
+    date: Format,
+    dt: Format,
+    time: Format,
+}
+
+impl CellFormats {
+    fn new() -> Self {
+        Self {
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            dt: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+            time: Format::new().set_num_format("hh:mm:ss"),
+        }
+    }
+}
+
+/// Numbers beyond this lose digits as Excel floats, so they are written as text.
+const MAX_EXACT_INT: i64 = 1 << 53;
+
+fn write_typed(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &TypedValue,
+    formats: &CellFormats,
+) -> std::result::Result<(), XlsxError> {
+    match value {
+        TypedValue::Int(v) if v.abs() <= MAX_EXACT_INT => sheet.write_number(row, col, *v as f64)?,
+        TypedValue::Dec(v) => sheet.write_number(row, col, *v)?,
+        TypedValue::Bool(v) => sheet.write_boolean(row, col, *v)?,
+        TypedValue::Date(v) => sheet.write_datetime_with_format(row, col, v, &formats.date)?,
+        TypedValue::Dt(v) => sheet.write_datetime_with_format(row, col, v, &formats.dt)?,
+        TypedValue::Time(v) => sheet.write_datetime_with_format(row, col, v, &formats.time)?,
+        TypedValue::Null => sheet,
+        TypedValue::List(items) => {
+            let items: Vec<String> = items.iter().map(TypedValue::to_string).collect();
+            sheet.write_string(row, col, items.join(&LIST_DELIMITER.to_string()))?
+        }
+        other => sheet.write_string(row, col, other.to_string())?,
+    };
+    Ok(())
+}
+
+fn write_json(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &serde_json::Value,
+) -> std::result::Result<(), XlsxError> {
+    match value {
+        serde_json::Value::Null => sheet,
+        serde_json::Value::String(s) => sheet.write_string(row, col, s)?,
+        serde_json::Value::Bool(b) => sheet.write_boolean(row, col, *b)?,
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) if i.abs() <= MAX_EXACT_INT => sheet.write_number(row, col, i as f64)?,
+            Some(_) => sheet.write_string(row, col, n.to_string())?,
+            None => sheet.write_number(row, col, n.as_f64().unwrap_or_default())?,
+        },
+        other => sheet.write_string(row, col, other.to_string())?,
+    };
+    Ok(())
+}
+
+fn xlsx_error(e: impl std::fmt::Display) -> PrecedenceConfigError {
+    PrecedenceConfigError::InvalidFormat { format: "xlsx", reason: e.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_precidence_rules::matrix_json_to_tall;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    fn registry() -> AttrRegistry {
+        let mut registry = AttrRegistry::new();
+        registry.register("region", AttrRole::Match, "str").unwrap();
+        registry.register("zip", AttrRole::Match, "str").unwrap();
+        registry.register("limit", AttrRole::Param, "int").unwrap();
+        registry.register("starts", AttrRole::Param, "date").unwrap();
+        registry.register("enabled", AttrRole::Param, "bool").unwrap();
+        registry
+    }
+
+    fn envelope(name: &str) -> ConfigEnvelope {
+        serde_json::from_value(json!({
+            "config": { "name": name, "version": 3, "version_name": "eu", "status": "published" },
+            "rows": [
+                {
+                    "match": { "region": "eu", "zip": "00123" },
+                    "params": [
+                        { "key": "limit", "type": "int", "value": 5 },
+                        { "key": "starts", "type": "date", "value": "2026-03-01" },
+                        { "key": "enabled", "type": "bool", "value": true },
+                    ],
+                },
+                { "match": { "region": "ALL" }, "params": [{ "key": "limit", "type": "int", "value": 1 }] },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn read(bytes: Vec<u8>) -> Result<XlsxWorkbook> {
+        read_xlsx(Cursor::new(bytes), 1, &registry(), &ConversionOptions::default())
+    }
+
+    #[test]
+    fn workbooks_round_trip() {
+        let registry = registry();
+        let matrix = r#"[{ "rank": 1, "region": 1, "zip": 1 }, { "rank": 2, "region": 1 }]"#;
+        let rules = matrix_json_to_tall(matrix, 1, &registry).unwrap();
+        let workbook = XlsxWorkbook { rules, envelopes: vec![envelope("fees"), envelope("limits")] };
+        let bytes = write_xlsx(&workbook, &registry, &ConversionOptions::default()).unwrap();
+        let mut read_back = read(bytes).unwrap();
+        read_back.rules.sort_by_key(|rule| (rule.rank, rule.attr_id));
+        assert_eq!(read_back, workbook);
+    }
+
+    #[test]
+    fn cells_are_read_by_the_attribute_type() {
+        let mut out = Workbook::new();
+        let sheet = out.add_worksheet().set_name("fees").unwrap();
+        sheet.write_string(0, 0, "version").unwrap();
+        sheet.write_number(0, 1, 2).unwrap();
+        sheet.write_string(0, 2, "version_name").unwrap();
+        sheet.write_string(0, 3, "v2").unwrap();
+        for (col, name) in ["zip", "limit", "enabled"].iter().enumerate() {
+            sheet.write_string(2, col as u16, *name).unwrap();
+        }
+        // a zip code typed as a number, an int held as a float and a bool typed as text
+        sheet.write_number(3, 0, 123).unwrap();
+        sheet.write_number(3, 1, 5.0).unwrap();
+        sheet.write_string(3, 2, "true").unwrap();
+        let envelopes = read(out.save_to_buffer().unwrap()).unwrap().envelopes;
+        let row = &envelopes[0].rows[0];
+        assert_eq!(row.match_part.attrs["zip"], json!("123"));
+        let params: Vec<_> = row.params.iter().map(|p| (p.key.as_str(), p.value.clone())).collect();
+        assert_eq!(params, [("limit", json!(5)), ("enabled", json!(true))]);
+        assert_eq!((envelopes[0].config.version, envelopes[0].config.status), (2, ConfigStatus::Draft));
+    }
+
+    #[test]
+    fn errors_name_the_sheet_and_row() {
+        let mut out = Workbook::new();
+        let sheet = out.add_worksheet().set_name(MATRIX_SHEET).unwrap();
+        sheet.write_string(0, 0, "region").unwrap();
+        let err = read(out.save_to_buffer().unwrap()).unwrap_err();
+        assert!(
+            matches!(&err, PrecedenceConfigError::InvalidXlsx { sheet, row: 1, .. } if sheet == MATRIX_SHEET),
+            "{err}"
+        );
+
+        let mut out = Workbook::new();
+        let sheet = out.add_worksheet().set_name("fees").unwrap();
+        sheet.write_string(0, 0, "version").unwrap();
+        sheet.write_number(0, 1, 2).unwrap();
+        sheet.write_string(0, 2, "version_name").unwrap();
+        sheet.write_string(0, 3, "v2").unwrap();
+        sheet.write_string(2, 0, "planet").unwrap();
+        let err = read(out.save_to_buffer().unwrap()).unwrap_err();
+        assert!(matches!(&err, PrecedenceConfigError::InvalidXlsx { row: 3, .. }), "{err}");
+
+        let twice = XlsxWorkbook { rules: Vec::new(), envelopes: vec![envelope("fees"), envelope("fees")] };
+        let err = write_xlsx(&twice, &registry(), &ConversionOptions::default()).unwrap_err();
+        assert!(matches!(err, PrecedenceConfigError::InvalidXlsx { row: 0, .. }), "{err}");
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(feature = "test-support")]
+pub mod config_arbitrary;
+#[cfg(feature = "arrow")]
+pub mod config_arrow;
+pub mod config_attr;
+#[cfg(feature = "binary")]
+pub mod config_binary;
+pub mod config_cache;
+pub mod config_canonical;
+pub mod config_changelog;
+#[cfg(feature = "diesel")]
+pub mod config_diesel;
+pub mod config_deprecation;
+pub mod config_diff;
+pub mod config_envelope;
+pub mod config_error;
+pub mod config_findings;
+pub mod config_formats;
+pub mod config_generator;
+pub mod config_json_schema;
+pub mod config_lint;
+pub mod config_merge;
+pub mod config_metrics;
+pub mod config_migration;
+pub mod config_overlay;
+pub mod config_params;
+pub mod config_patch;
+#[cfg(feature = "polars")]
+pub mod config_polars;
+#[cfg(feature = "sqlx-postgres")]
+pub mod config_postgres;
+pub mod config_precidence_rules;
+pub mod config_rename;
+pub mod config_resolver;
+pub mod config_shared;
+pub mod config_simulation;
+pub mod config_sql;
+pub mod config_storage;
+pub mod config_store;
+pub mod config_table;
+pub mod config_types;
+pub mod config_validator;
+pub mod config_value;
+#[cfg(feature = "xlsx")]
+pub mod config_xlsx;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+
+#[cfg(feature = "derive")]
+pub use precedence_config_derive::FromResolvedConfig;
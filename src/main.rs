@@ -1,7 +0,0 @@
-mod config_precidence_rules;
-mod config_types;
-mod config_value;
-
-fn main() {
-    println!("Hello, world!");
-}
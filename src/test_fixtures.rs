@@ -0,0 +1,61 @@
+//! Fixtures shared by the unit tests: a catalog keyed on `region` (and `channel`
+//! where a test needs two match attributes) with an int `limit` param, and config
+//! `c` v1 over it.
+
+use crate::config_attr::AttrRegistry;
+use crate::config_envelope::validate_envelope;
+use crate::config_precidence_rules::{matrix_json_to_tall, ConfigPrecedenceRule, MatchType};
+use crate::config_resolver::{CompiledConfig, Context};
+use crate::config_store::{ConfigStore, StoreOptions};
+use crate::config_types::ConfigEnvelope;
+use crate::config_value::{AttrRole, TypedValue};
+use serde_json::json;
+
+/// `region` (match, attr_id 1) and `limit` (param, attr_id 2).
+pub(crate) fn region_registry() -> AttrRegistry {
+    let mut registry = AttrRegistry::new();
+    registry.register("region", AttrRole::Match, "str").unwrap();
+    registry.register("limit", AttrRole::Param, "int").unwrap();
+    registry
+}
+
+/// `region` and `channel` (match, attr_ids 1 and 2) and `limit` (param, attr_id 3).
+pub(crate) fn region_channel_registry() -> AttrRegistry {
+    let mut registry = AttrRegistry::new();
+    registry.register("region", AttrRole::Match, "str").unwrap();
+    registry.register("channel", AttrRole::Match, "str").unwrap();
+    registry.register("limit", AttrRole::Param, "int").unwrap();
+    registry
+}
+
+/// Config `c` v1 with one row, for region `eu`, setting `limit`.
+pub(crate) fn eu_envelope(limit: i64) -> ConfigEnvelope {
+    serde_json::from_value(json!({
+        "config": { "name": "c", "version": 1, "version_name": "v1" },
+        "rows": [{ "match": { "region": "eu" }, "params": [{ "key": "limit", "type": "int", "value": limit }] }],
+    }))
+    .unwrap()
+}
+
+/// A lookup context holding only `region`.
+pub(crate) fn region_context(region: &str) -> Context {
+    Context::from([("region".to_string(), TypedValue::Str(region.to_string()))])
+}
+
+/// [`eu_envelope`] over [`region_registry`], ranked by rank 1 on `region`.
+pub(crate) fn eu_compiled(limit: i64) -> CompiledConfig {
+    let registry = region_registry();
+    let values = validate_envelope(&eu_envelope(limit), &registry).unwrap().values;
+    let rules = matrix_json_to_tall(r#"[{ "rank": 1, "region": 1 }]"#, 1, &registry).unwrap();
+    CompiledConfig::new(&rules, &values, &registry).unwrap()
+}
+
+/// A store over [`region_registry`] holding `eu_envelope(5)`, ranked by rank 1 on `region`.
+pub(crate) fn eu_store(options: StoreOptions) -> ConfigStore {
+    let registry = region_registry();
+    let region = registry.id_of("region").unwrap();
+    let rule = ConfigPrecedenceRule { config_version_id: 0, rank: 1, attr_id: region, match_type: MatchType::Exact };
+    let mut store = ConfigStore::with_options(registry, options);
+    store.insert_envelope(&eu_envelope(5), vec![rule]).unwrap();
+    store
+}